@@ -0,0 +1,2 @@
+-- Timestamps are TEXT on PostgreSQL from the start, kept for version parity with SQLite.
+SELECT 1;
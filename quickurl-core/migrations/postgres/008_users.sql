@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    email TEXT UNIQUE NOT NULL,
+    password_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+-- Links created through an API key or the admin key have no owner
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS user_id TEXT REFERENCES users(id) ON DELETE CASCADE;
+ALTER TABLE archived_urls ADD COLUMN IF NOT EXISTS user_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_urls_user_id ON urls(user_id);
+
+-- Dedupe is scoped to the owner, so two users shortening the same URL get their own links
+DROP INDEX IF EXISTS idx_urls_normalized_url;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_urls_owner_normalized_url ON urls(COALESCE(user_id, ''), normalized_url);
@@ -0,0 +1,12 @@
+-- Every create, update, delete and restore of a link. No foreign key, entries outlive purged links
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    url_id TEXT NOT NULL,
+    token TEXT NOT NULL,
+    action TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    changes TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_url_id ON audit_log(url_id);
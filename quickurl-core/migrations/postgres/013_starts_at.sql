@@ -0,0 +1,2 @@
+-- Scheduled links answer 404 until starts_at, NULL means live from creation
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS starts_at TEXT;
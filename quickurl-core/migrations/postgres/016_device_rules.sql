@@ -0,0 +1,3 @@
+-- Rules can match on device as well as country, either condition may be left out
+ALTER TABLE redirect_rules ALTER COLUMN country DROP NOT NULL;
+ALTER TABLE redirect_rules ADD COLUMN IF NOT EXISTS device TEXT;
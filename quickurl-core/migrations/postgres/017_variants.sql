@@ -0,0 +1,13 @@
+-- A/B split destinations, picked by weight when no targeting rule matches
+CREATE TABLE IF NOT EXISTS url_variants (
+    url_id TEXT NOT NULL REFERENCES urls(id) ON DELETE CASCADE,
+    position BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    destination TEXT NOT NULL,
+    weight BIGINT NOT NULL,
+    PRIMARY KEY (url_id, position)
+);
+
+-- 1 keeps each visitor (by IP hash) on the same variant
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS sticky_variants BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE clicks ADD COLUMN IF NOT EXISTS variant TEXT;
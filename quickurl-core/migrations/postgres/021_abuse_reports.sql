@@ -0,0 +1,17 @@
+-- Anonymous abuse reports, open until a moderator resolves them with an action
+CREATE TABLE IF NOT EXISTS abuse_reports (
+    id TEXT PRIMARY KEY,
+    url_id TEXT NOT NULL REFERENCES urls(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    details TEXT,
+    -- IP hash, one open report per visitor and link
+    reporter TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    resolved_at TEXT,
+    resolution TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_abuse_reports_url_id ON abuse_reports(url_id);
+
+-- "abuse" (410) or "legal" (451) once a moderator disabled the link
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS takedown TEXT;
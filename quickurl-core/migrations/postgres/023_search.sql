@@ -0,0 +1,7 @@
+-- Full-text index over titles and destinations for GET /urls/search. Punctuation in URLs is
+-- turned into spaces first so their host and path words are indexed one by one. Queries must
+-- repeat this expression exactly (search::DOCUMENT) for the index to be used.
+CREATE INDEX IF NOT EXISTS idx_urls_search ON urls USING GIN ((
+    setweight(to_tsvector('simple', COALESCE(title, '')), 'A')
+    || setweight(to_tsvector('simple', regexp_replace(original_url, '[^[:alnum:]]+', ' ', 'g')), 'B')
+));
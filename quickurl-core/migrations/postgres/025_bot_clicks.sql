@@ -0,0 +1,2 @@
+-- Redirects served to crawlers and link previews, kept out of click_count and the clicks table
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS bot_clicks BIGINT NOT NULL DEFAULT 0;
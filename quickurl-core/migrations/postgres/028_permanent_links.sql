@@ -0,0 +1,2 @@
+-- expires_at may be NULL for links that never expire
+ALTER TABLE urls ALTER COLUMN expires_at DROP NOT NULL;
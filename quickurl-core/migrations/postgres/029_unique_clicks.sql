@@ -0,0 +1,9 @@
+-- Visits counted once per visitor (IP and user agent, with a daily salt) and dedupe window
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS unique_clicks BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE clicks ADD COLUMN IF NOT EXISTS visitor_hash TEXT;
+CREATE INDEX IF NOT EXISTS idx_clicks_url_id_visitor_hash ON clicks(url_id, visitor_hash);
+
+-- Older clicks have no visitor hash, one visit per address and day is the closest estimate
+UPDATE urls SET unique_clicks = (
+    SELECT COUNT(DISTINCT ip_hash || substr(clicked_at, 1, 10)) FROM clicks WHERE clicks.url_id = urls.id
+);
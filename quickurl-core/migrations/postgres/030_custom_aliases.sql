@@ -0,0 +1,3 @@
+-- Links whose token was chosen by the creator, counted against max_custom_aliases_per_user.
+-- Earlier links cannot be told apart and count as generated.
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS custom_alias BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,20 @@
+-- Links with an org_id belong to the organization instead of a user, its members share them
+CREATE TABLE IF NOT EXISTS organizations (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+-- role is "admin", "editor" or "viewer"
+CREATE TABLE IF NOT EXISTS organization_members (
+    org_id TEXT NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (org_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_organization_members_user_id ON organization_members(user_id);
+
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS org_id TEXT REFERENCES organizations(id);
+CREATE INDEX IF NOT EXISTS idx_urls_org_id ON urls(org_id);
@@ -0,0 +1,5 @@
+-- Accounts signed in through the OIDC provider, matched by its subject claim. Accounts it created
+-- have an empty password_hash, which never verifies.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS oidc_issuer TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS oidc_subject TEXT;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_oidc_subject ON users(oidc_issuer, oidc_subject);
@@ -0,0 +1,2 @@
+-- Query parameters of the short URL are appended to the destination on redirect
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS forward_query BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,9 @@
+-- Extra tokens that redirect to an existing link and count towards its statistics. A token is
+-- either a link's own or an alias, the application checks both tables before taking one.
+CREATE TABLE IF NOT EXISTS link_aliases (
+    token TEXT PRIMARY KEY,
+    url_id TEXT NOT NULL REFERENCES urls(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_link_aliases_url_id ON link_aliases(url_id);
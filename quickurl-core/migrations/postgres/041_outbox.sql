@@ -0,0 +1,13 @@
+-- Events written in the transaction of the change they announce, so they exist exactly when
+-- the change does. A dispatcher claims an entry by moving next_attempt_at past the time its
+-- attempt can take, and deletes it once delivered or out of attempts.
+CREATE TABLE IF NOT EXISTS outbox (
+    id TEXT PRIMARY KEY,
+    topic TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts BIGINT NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL,
+    next_attempt_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_outbox_due ON outbox(topic, next_attempt_at);
@@ -0,0 +1,2 @@
+-- A moderator asked for visitors to be warned before they are sent on
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS warned BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- Filled in from the destination's OpenGraph tags, for link previews and oEmbed
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS description TEXT;
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS image_url TEXT;
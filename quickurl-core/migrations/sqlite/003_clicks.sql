@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS clicks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    url_id TEXT NOT NULL REFERENCES urls(id) ON DELETE CASCADE,
+    clicked_at DATETIME NOT NULL,
+    referrer TEXT,
+    user_agent TEXT,
+    ip_hash TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_clicks_url_id_clicked_at ON clicks(url_id, clicked_at);
@@ -0,0 +1,30 @@
+-- Timestamps become plain TEXT in a fixed-width RFC 3339 format so the same queries
+-- work through sqlx::Any on SQLite and PostgreSQL. Indexed columns cannot be dropped,
+-- so the indexes are recreated at the end.
+DROP INDEX IF EXISTS idx_urls_created_at;
+DROP INDEX IF EXISTS idx_urls_expires_at;
+DROP INDEX IF EXISTS idx_clicks_url_id_clicked_at;
+
+ALTER TABLE urls ADD COLUMN created_at_text TEXT NOT NULL DEFAULT '';
+ALTER TABLE urls ADD COLUMN expires_at_text TEXT NOT NULL DEFAULT '';
+UPDATE urls SET
+    created_at_text = strftime('%Y-%m-%dT%H:%M:%f', created_at) || '000Z',
+    expires_at_text = strftime('%Y-%m-%dT%H:%M:%f', expires_at) || '000Z';
+ALTER TABLE urls DROP COLUMN created_at;
+ALTER TABLE urls DROP COLUMN expires_at;
+ALTER TABLE urls RENAME COLUMN created_at_text TO created_at;
+ALTER TABLE urls RENAME COLUMN expires_at_text TO expires_at;
+
+ALTER TABLE api_keys ADD COLUMN created_at_text TEXT NOT NULL DEFAULT '';
+UPDATE api_keys SET created_at_text = strftime('%Y-%m-%dT%H:%M:%f', created_at) || '000Z';
+ALTER TABLE api_keys DROP COLUMN created_at;
+ALTER TABLE api_keys RENAME COLUMN created_at_text TO created_at;
+
+ALTER TABLE clicks ADD COLUMN clicked_at_text TEXT NOT NULL DEFAULT '';
+UPDATE clicks SET clicked_at_text = strftime('%Y-%m-%dT%H:%M:%f', clicked_at) || '000Z';
+ALTER TABLE clicks DROP COLUMN clicked_at;
+ALTER TABLE clicks RENAME COLUMN clicked_at_text TO clicked_at;
+
+CREATE INDEX IF NOT EXISTS idx_urls_created_at ON urls(created_at);
+CREATE INDEX IF NOT EXISTS idx_urls_expires_at ON urls(expires_at);
+CREATE INDEX IF NOT EXISTS idx_clicks_url_id_clicked_at ON clicks(url_id, clicked_at);
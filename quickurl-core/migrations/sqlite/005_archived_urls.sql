@@ -0,0 +1,13 @@
+-- Expired links moved out of urls by the cleanup job when cleanup_mode = "archive"
+CREATE TABLE IF NOT EXISTS archived_urls (
+    id TEXT PRIMARY KEY,
+    token TEXT NOT NULL,
+    original_url TEXT NOT NULL,
+    title TEXT,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    click_count INTEGER DEFAULT 0,
+    archived_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_archived_urls_token ON archived_urls(token);
@@ -0,0 +1,4 @@
+-- Canonical destination, only set for links created with dedupe so repeated requests share one token
+ALTER TABLE urls ADD COLUMN normalized_url TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_urls_normalized_url ON urls(normalized_url);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS domains (
+    id TEXT PRIMARY KEY,
+    hostname TEXT UNIQUE NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+-- Links on a custom domain only resolve through that Host, NULL means the default domain
+ALTER TABLE urls ADD COLUMN domain TEXT REFERENCES domains(hostname);
+ALTER TABLE archived_urls ADD COLUMN domain TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_urls_domain ON urls(domain);
+
+DROP INDEX IF EXISTS idx_urls_owner_normalized_url;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_urls_owner_domain_normalized_url
+    ON urls(COALESCE(user_id, ''), COALESCE(domain, ''), normalized_url);
@@ -0,0 +1,4 @@
+-- Deleted links keep their row (and token) until an admin purges them, so they can be restored
+ALTER TABLE urls ADD COLUMN deleted_at TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_urls_deleted_at ON urls(deleted_at);
@@ -0,0 +1,3 @@
+-- Filled from the local GeoIP database when one is configured, ISO country code and English city name
+ALTER TABLE clicks ADD COLUMN country TEXT;
+ALTER TABLE clicks ADD COLUMN city TEXT;
@@ -0,0 +1,8 @@
+-- Per-visitor destination overrides, evaluated in position order before the link's own URL
+CREATE TABLE IF NOT EXISTS redirect_rules (
+    url_id TEXT NOT NULL REFERENCES urls(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    country TEXT NOT NULL,
+    destination TEXT NOT NULL,
+    PRIMARY KEY (url_id, position)
+);
@@ -0,0 +1,16 @@
+-- Rules can match on device as well as country, either condition may be left out.
+-- SQLite cannot drop NOT NULL in place, so the table is rebuilt.
+CREATE TABLE redirect_rules_new (
+    url_id TEXT NOT NULL REFERENCES urls(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    country TEXT,
+    device TEXT,
+    destination TEXT NOT NULL,
+    PRIMARY KEY (url_id, position)
+);
+
+INSERT INTO redirect_rules_new (url_id, position, country, destination)
+SELECT url_id, position, country, destination FROM redirect_rules;
+
+DROP TABLE redirect_rules;
+ALTER TABLE redirect_rules_new RENAME TO redirect_rules;
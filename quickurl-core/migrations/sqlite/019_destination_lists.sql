@@ -0,0 +1,8 @@
+-- Destination hosts links may not point to ("block") or, once any are listed, must point to ("allow").
+-- An entry covers the host and all of its subdomains.
+CREATE TABLE IF NOT EXISTS destination_lists (
+    hostname TEXT PRIMARY KEY,
+    list TEXT NOT NULL,
+    reason TEXT,
+    created_at TEXT NOT NULL
+);
@@ -0,0 +1,3 @@
+-- Set when a Safe Browsing check lists the destination, cleared again once a rescan finds it clean
+ALTER TABLE urls ADD COLUMN flagged_at TEXT;
+ALTER TABLE urls ADD COLUMN flag_reason TEXT;
@@ -0,0 +1,6 @@
+-- Snowflake worker ids leased by running instances, a lease nobody renews can be taken over
+CREATE TABLE IF NOT EXISTS token_workers (
+    worker_id INTEGER PRIMARY KEY,
+    holder TEXT NOT NULL,
+    expires_at TEXT NOT NULL
+);
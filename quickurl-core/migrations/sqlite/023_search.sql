@@ -0,0 +1,19 @@
+-- Full-text index over titles and destinations for GET /urls/search, kept in sync by triggers.
+-- It refers to urls by rowid; should a VACUUM ever renumber them, run
+-- INSERT INTO urls_fts(urls_fts) VALUES ('rebuild');
+CREATE VIRTUAL TABLE IF NOT EXISTS urls_fts USING fts5(title, original_url, content = 'urls', prefix = '2 3');
+
+CREATE TRIGGER IF NOT EXISTS urls_fts_insert AFTER INSERT ON urls BEGIN
+    INSERT INTO urls_fts (rowid, title, original_url) VALUES (new.rowid, new.title, new.original_url);
+END;
+
+CREATE TRIGGER IF NOT EXISTS urls_fts_delete AFTER DELETE ON urls BEGIN
+    INSERT INTO urls_fts (urls_fts, rowid, title, original_url) VALUES ('delete', old.rowid, old.title, old.original_url);
+END;
+
+CREATE TRIGGER IF NOT EXISTS urls_fts_update AFTER UPDATE OF title, original_url ON urls BEGIN
+    INSERT INTO urls_fts (urls_fts, rowid, title, original_url) VALUES ('delete', old.rowid, old.title, old.original_url);
+    INSERT INTO urls_fts (rowid, title, original_url) VALUES (new.rowid, new.title, new.original_url);
+END;
+
+INSERT INTO urls_fts (urls_fts) VALUES ('rebuild');
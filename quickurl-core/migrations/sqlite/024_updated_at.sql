@@ -0,0 +1,3 @@
+-- Moves whenever a field shown in GET /urls/:token changes, the ETag is derived from it
+ALTER TABLE urls ADD COLUMN updated_at TEXT;
+UPDATE urls SET updated_at = created_at;
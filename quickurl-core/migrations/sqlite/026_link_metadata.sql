@@ -0,0 +1,3 @@
+-- Free-form notes and a JSON object integrations attach to links, metadata is stored as its text
+ALTER TABLE urls ADD COLUMN notes TEXT;
+ALTER TABLE urls ADD COLUMN metadata TEXT;
@@ -0,0 +1,4 @@
+-- Result of the last destination check: "ok", the HTTP status of an error answer or the failure
+ALTER TABLE urls ADD COLUMN last_checked_at TEXT;
+ALTER TABLE urls ADD COLUMN health_status TEXT;
+CREATE INDEX IF NOT EXISTS idx_urls_health_status ON urls (health_status);
@@ -0,0 +1,11 @@
+-- expires_at may be NULL for links that never expire. SQLite cannot drop NOT NULL in place, so
+-- the column is copied as in 004; rowids survive, the search index is rebuilt to be sure.
+DROP INDEX IF EXISTS idx_urls_expires_at;
+
+ALTER TABLE urls ADD COLUMN expires_at_nullable TEXT;
+UPDATE urls SET expires_at_nullable = expires_at;
+ALTER TABLE urls DROP COLUMN expires_at;
+ALTER TABLE urls RENAME COLUMN expires_at_nullable TO expires_at;
+
+CREATE INDEX IF NOT EXISTS idx_urls_expires_at ON urls(expires_at);
+INSERT INTO urls_fts (urls_fts) VALUES ('rebuild');
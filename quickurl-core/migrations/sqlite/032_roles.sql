@@ -0,0 +1,3 @@
+-- "admin", "editor" or "read_only". Existing keys and accounts keep what they could do so far.
+ALTER TABLE api_keys ADD COLUMN role TEXT NOT NULL DEFAULT 'editor';
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'editor';
@@ -0,0 +1,7 @@
+-- scopes is space separated (links:read, links:write, stats:read, admin), NULL allows all the
+-- role does. A rotated key's old hash keeps working until previous_expires_at.
+ALTER TABLE api_keys ADD COLUMN scopes TEXT;
+ALTER TABLE api_keys ADD COLUMN previous_key_hash TEXT;
+ALTER TABLE api_keys ADD COLUMN previous_expires_at TEXT;
+ALTER TABLE api_keys ADD COLUMN last_used_at TEXT;
+CREATE INDEX IF NOT EXISTS idx_api_keys_previous_key_hash ON api_keys(previous_key_hash);
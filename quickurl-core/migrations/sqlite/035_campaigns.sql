@@ -0,0 +1,15 @@
+-- Groups links for roll-up statistics. user_id is the account that created the campaign,
+-- NULL for campaigns created with an API key or the admin key.
+CREATE TABLE IF NOT EXISTS campaigns (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT,
+    user_id TEXT REFERENCES users(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_campaigns_user_id ON campaigns(user_id);
+
+-- Deleting a campaign keeps its links
+ALTER TABLE urls ADD COLUMN campaign_id TEXT REFERENCES campaigns(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_urls_campaign_id ON urls(campaign_id);
@@ -0,0 +1,11 @@
+-- Clicks per link and UTC day, rolled up from the click events so daily series outlive
+-- click_retention_days. Days are only added once they are over and never rewritten.
+CREATE TABLE IF NOT EXISTS click_stats_daily (
+    url_id TEXT NOT NULL REFERENCES urls(id) ON DELETE CASCADE,
+    date TEXT NOT NULL,
+    clicks INTEGER NOT NULL,
+    unique_visitors INTEGER NOT NULL,
+    PRIMARY KEY (url_id, date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_click_stats_daily_date ON click_stats_daily(date);
@@ -0,0 +1,2 @@
+-- Query parameters of the short URL are appended to the destination on redirect
+ALTER TABLE urls ADD COLUMN forward_query INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,12 @@
+-- Recurring background jobs shared by all instances. next_run_at is advanced by the instance
+-- that claims a run, locked_until keeps the others away until it is done or presumed dead.
+CREATE TABLE IF NOT EXISTS jobs (
+    name TEXT PRIMARY KEY,
+    next_run_at TEXT,
+    locked_by TEXT,
+    locked_until TEXT,
+    last_run_at TEXT,
+    last_status TEXT,
+    last_error TEXT,
+    last_duration_ms INTEGER
+);
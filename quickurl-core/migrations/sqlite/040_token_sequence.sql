@@ -0,0 +1,7 @@
+-- Counter behind sequential tokens, instances take blocks of values from it
+CREATE TABLE IF NOT EXISTS token_sequence (
+    id INTEGER PRIMARY KEY,
+    next_value INTEGER NOT NULL
+);
+
+INSERT INTO token_sequence (id, next_value) VALUES (1, 0) ON CONFLICT (id) DO NOTHING;
@@ -0,0 +1,2 @@
+-- Owners and moderators switch a link off without deleting it, it keeps its token and stats
+ALTER TABLE urls ADD COLUMN is_active INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,2 @@
+-- Unknown tokens on the domain redirect here instead of answering 404
+ALTER TABLE domains ADD COLUMN fallback_url TEXT;
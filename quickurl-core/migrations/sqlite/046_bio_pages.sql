@@ -0,0 +1,25 @@
+-- Link-in-bio pages, served at /@slug. user_id is the account that created the page, NULL for
+-- pages created with an API key or the admin key.
+CREATE TABLE IF NOT EXISTS bio_pages (
+    id TEXT PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    title TEXT NOT NULL,
+    description TEXT,
+    user_id TEXT REFERENCES users(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_bio_pages_user_id ON bio_pages(user_id);
+
+-- The page's links in order, a purged link drops off its pages
+CREATE TABLE IF NOT EXISTS bio_page_links (
+    page_id TEXT NOT NULL REFERENCES bio_pages(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    url_id TEXT NOT NULL REFERENCES urls(id) ON DELETE CASCADE,
+    title TEXT,
+    icon TEXT,
+    PRIMARY KEY (page_id, position)
+);
+
+CREATE INDEX IF NOT EXISTS idx_bio_page_links_url_id ON bio_page_links(url_id);
@@ -0,0 +1,2 @@
+-- Placeholders like {page} in the destination are filled from the short URL's path and query
+ALTER TABLE urls ADD COLUMN template INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- Destinations are stored normalized, this is the URL as it was submitted
+ALTER TABLE urls ADD COLUMN raw_url TEXT;
@@ -0,0 +1,2 @@
+-- The schedule next_run_at was worked out from, a changed one in the config starts over from now
+ALTER TABLE jobs ADD COLUMN schedule TEXT;
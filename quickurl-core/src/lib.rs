@@ -0,0 +1,16 @@
+// Links, their storage and token generation without the HTTP server, for services that shorten
+// and resolve links in the same database directly. The server builds on the same modules.
+pub mod profanity;
+pub mod reserved;
+pub mod resolver;
+pub mod scaling;
+pub mod sequence;
+pub mod signed;
+pub mod snowflake;
+pub mod storage;
+pub mod token;
+pub mod validation;
+
+pub use resolver::{Link, Resolver};
+pub use signed::{SignedLink, Signer};
+pub use storage::{Backend, DatabaseOptions};
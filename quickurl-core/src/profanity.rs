@@ -0,0 +1,64 @@
+// Generated tokens are random letters and digits and now and then spell something nobody wants on a
+// printed flyer. Tokens containing one of these are drawn again; custom aliases are the owner's
+// choice and are not checked.
+const BUILTIN_WORDS: &[&str] = &[
+    "anal", "anus", "arse", "bitch", "boob", "butt", "clit", "cock", "coon", "crap", "cunt", "dick",
+    "dildo", "dyke", "fag", "fuck", "gook", "homo", "jizz", "kike", "nazi", "nigg", "paki", "penis",
+    "piss", "poop", "porn", "pussy", "rape", "retard", "scum", "sex", "shit", "slut", "spic", "tits",
+    "twat", "wank", "whore",
+];
+
+// Digits read as the letters they are used for, so f4ck counts as fuck
+fn unleet(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' => 'i',
+        '3' => 'e',
+        '4' => 'a',
+        '5' => 's',
+        '7' => 't',
+        '8' => 'b',
+        c => c.to_ascii_lowercase(),
+    }
+}
+
+// Substrings generated tokens may not contain, compared case-insensitively
+#[derive(Clone, Default)]
+pub struct WordFilter {
+    words: Vec<String>,
+}
+
+impl WordFilter {
+    pub fn new(builtin: bool, extra_words: &[String]) -> Self {
+        let builtin = BUILTIN_WORDS.iter().filter(|_| builtin).map(|word| word.to_string());
+        let words = builtin
+            .chain(extra_words.iter().map(|word| word.trim().chars().map(unleet).collect()))
+            .filter(|word| !word.is_empty())
+            .collect();
+        Self { words }
+    }
+
+    pub fn matches(&self, token: &str) -> bool {
+        if self.words.is_empty() {
+            return false;
+        }
+        let token: String = token.chars().map(unleet).collect();
+        self.words.iter().any(|word| token.contains(word.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_filter() {
+        let filter = WordFilter::new(true, &["Acme".into()]);
+
+        assert!(filter.matches("xFuCkx"));
+        assert!(filter.matches("s3xy12"));
+        assert!(filter.matches("4cm3ab"));
+        assert!(!filter.matches("abc123"));
+        assert!(!WordFilter::new(false, &[]).matches("shit12"));
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+
+// First path segments the service uses or may use for its own routes. These are
+// always reserved, config can only add to them.
+const SYSTEM_WORDS: &[&str] = &[
+    "admin", "api", "assets", "auth", "campaigns", "docs", "domains", "events", "favicon",
+    "graphql", "health", "healthz", "keys", "login", "logout", "metrics", "openapi", "orgs", "p",
+    "pages", "readyz", "register", "report", "robots", "shorten", "static", "stats", "status",
+    "urls", "users", "v1", "v2", "webhooks",
+];
+
+// Tokens starting with these are kept free for system namespaces
+const SYSTEM_PREFIXES: &[&str] = &["_"];
+
+// Tokens that can never be handed out, compared case-insensitively
+#[derive(Clone)]
+pub struct ReservedTokens {
+    words: HashSet<String>,
+    prefixes: Vec<String>,
+}
+
+impl ReservedTokens {
+    pub fn new(extra_words: &[String], extra_prefixes: &[String]) -> Self {
+        let words = SYSTEM_WORDS
+            .iter()
+            .map(|word| word.to_string())
+            .chain(extra_words.iter().map(|word| word.trim().to_lowercase()))
+            .filter(|word| !word.is_empty())
+            .collect();
+        let prefixes = SYSTEM_PREFIXES
+            .iter()
+            .map(|prefix| prefix.to_string())
+            .chain(extra_prefixes.iter().map(|prefix| prefix.trim().to_lowercase()))
+            .filter(|prefix| !prefix.is_empty())
+            .collect();
+        Self { words, prefixes }
+    }
+
+    pub fn contains(&self, token: &str) -> bool {
+        let token = token.to_lowercase();
+        self.words.contains(&token) || self.prefixes.iter().any(|prefix| token.starts_with(prefix.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserved_tokens() {
+        let reserved = ReservedTokens::new(&["Pricing".into()], &["sys-".into()]);
+
+        assert!(reserved.contains("health"));
+        assert!(reserved.contains("readyz"));
+        assert!(reserved.contains("URLS"));
+        assert!(reserved.contains("pricing"));
+        assert!(reserved.contains("_internal"));
+        assert!(reserved.contains("sys-status"));
+        assert!(!reserved.contains("healthy"));
+        assert!(!reserved.contains("abc123"));
+    }
+}
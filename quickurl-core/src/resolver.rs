@@ -0,0 +1,371 @@
+use chrono::{DateTime, Utc};
+use sqlx::any::AnyRow;
+use sqlx::{AnyPool, Row};
+use std::fmt;
+use uuid::Uuid;
+
+use crate::profanity::WordFilter;
+use crate::reserved::ReservedTokens;
+use crate::storage;
+use crate::token::TokenGenerator;
+use crate::validation;
+
+// Generated tokens that collided before the next attempt gets one character longer
+const ATTEMPTS_PER_LENGTH: usize = 3;
+const MAX_URL_LENGTH: usize = 2048;
+
+// A link as stored, without the server's targeting rules, variants and metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub id: String,
+    pub token: String,
+    pub original_url: String,
+    pub created_at: DateTime<Utc>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_clicks: Option<i64>,
+    pub click_count: i64,
+    // Custom domain the link belongs to, None for the default one
+    pub domain: Option<String>,
+    // Listed by Safe Browsing or marked by a moderator, whether it still redirects is up to the caller
+    pub flagged: bool,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidUrl(String),
+    // Unknown tokens and scheduled links that are not live yet
+    NotFound,
+    Deleted,
+    Expired,
+    // Taken down after an abuse report
+    Blocked,
+    // Switched off by its owner or a moderator
+    Disabled,
+    LimitReached,
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidUrl(reason) => f.write_str(reason),
+            Error::NotFound => f.write_str("link not found"),
+            Error::Deleted => f.write_str("link was deleted"),
+            Error::Expired => f.write_str("link has expired"),
+            Error::Blocked => f.write_str("link was taken down"),
+            Error::Disabled => f.write_str("link is disabled"),
+            Error::LimitReached => f.write_str("link has reached its click limit"),
+            Error::Database(e) => write!(f, "database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(e: sqlx::Error) -> Self {
+        Error::Database(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    // Not live yet, looks like an unknown token
+    Pending,
+    Live,
+    Expired,
+}
+
+pub fn schedule(starts_at: Option<DateTime<Utc>>, expires_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Schedule {
+    if starts_at.is_some_and(|starts_at| now < starts_at) {
+        Schedule::Pending
+    } else if expires_at.is_some_and(|expires_at| now > expires_at) {
+        Schedule::Expired
+    } else {
+        Schedule::Live
+    }
+}
+
+// The lowercase spelling a case-insensitive token is stored under, None when it already is
+pub fn folded(token: &str) -> Option<String> {
+    token.bytes().any(|b| b.is_ascii_uppercase()).then(|| token.to_ascii_lowercase())
+}
+
+// Token of the link an alias points to
+pub async fn canonical_token(db: &AnyPool, alias: &str) -> Result<Option<String>, sqlx::Error> {
+    let row = sqlx::query("SELECT u.token FROM link_aliases a JOIN urls u ON u.id = a.url_id WHERE a.token = $1")
+        .bind(alias)
+        .fetch_optional(db)
+        .await?;
+    Ok(row.map(|row| row.get("token")))
+}
+
+// Shortens and resolves links in the server's database without going through its HTTP API.
+// Custom domains, targeting rules, A/B splits and click stats stay with the server; a click
+// recorded here only counts towards click_count and the link's click limit.
+#[derive(Clone)]
+pub struct Resolver {
+    db: AnyPool,
+    tokens: TokenGenerator,
+    reserved: ReservedTokens,
+    words: WordFilter,
+    case_insensitive: bool,
+    allow_private: bool,
+    schemes: Vec<String>,
+}
+
+impl Resolver {
+    pub fn new(db: AnyPool) -> Self {
+        Self {
+            db,
+            tokens: TokenGenerator::new(),
+            reserved: ReservedTokens::new(&[], &[]),
+            words: WordFilter::new(true, &[]),
+            case_insensitive: false,
+            allow_private: false,
+            schemes: Vec::new(),
+        }
+    }
+
+    pub fn tokens(mut self, tokens: TokenGenerator) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    pub fn reserved(mut self, reserved: ReservedTokens) -> Self {
+        self.reserved = reserved;
+        self
+    }
+
+    pub fn word_filter(mut self, words: WordFilter) -> Self {
+        self.words = words;
+        self
+    }
+
+    // Matches the server's case_insensitive_tokens, the token generator should then be lowercase too
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    pub fn allow_private_destinations(mut self, allow: bool) -> Self {
+        self.allow_private = allow;
+        self
+    }
+
+    // The server's allowed_schemes, destinations besides http and https
+    pub fn allowed_schemes(mut self, schemes: Vec<String>) -> Self {
+        self.schemes = schemes;
+        self
+    }
+
+    // Validates the destination like the server does and stores it under a new random token
+    pub async fn shorten(&self, url: &str) -> Result<Link, Error> {
+        let url = validation::validate_destination_with_schemes(url, MAX_URL_LENGTH, self.allow_private, &self.schemes)
+            .map_err(Error::InvalidUrl)?;
+        let now = storage::now();
+        let mut attempt = 0;
+        loop {
+            let token = self.tokens.extended(attempt / ATTEMPTS_PER_LENGTH).generate();
+            attempt += 1;
+            let unusable = self.reserved.contains(&token) || self.words.matches(&token);
+            if unusable || canonical_token(&self.db, &token).await?.is_some() {
+                continue;
+            }
+            let link = Link {
+                id: Uuid::new_v4().to_string(),
+                token,
+                original_url: url.to_string(),
+                created_at: now,
+                starts_at: None,
+                expires_at: None,
+                max_clicks: None,
+                click_count: 0,
+                domain: None,
+                flagged: false,
+            };
+            let inserted = storage::retry_busy(|| {
+                sqlx::query(
+                    r#"
+                    INSERT INTO urls (id, token, original_url, created_at, updated_at, click_count)
+                    VALUES ($1, $2, $3, $4, $4, 0)
+                    ON CONFLICT (token) DO NOTHING
+                    "#,
+                )
+                .bind(&link.id)
+                .bind(&link.token)
+                .bind(&link.original_url)
+                .bind(storage::ts(now))
+                .execute(&self.db)
+            })
+            .await?;
+            if inserted.rows_affected() == 1 {
+                return Ok(link);
+            }
+        }
+    }
+
+    // The live link behind a token, its alias or, for case-insensitive tokens, another spelling
+    pub async fn resolve(&self, token: &str) -> Result<Link, Error> {
+        let folded = if self.case_insensitive { folded(token) } else { None };
+        for spelling in std::iter::once(token.to_string()).chain(folded) {
+            let mut link = self.load(&spelling).await?;
+            if link.is_none() {
+                if let Some(canonical) = canonical_token(&self.db, &spelling).await? {
+                    link = self.load(&canonical).await?;
+                }
+            }
+            if let Some(link) = link {
+                return match schedule(link.starts_at, link.expires_at, Utc::now()) {
+                    Schedule::Pending => Err(Error::NotFound),
+                    Schedule::Expired => Err(Error::Expired),
+                    Schedule::Live => Ok(link),
+                };
+            }
+        }
+        Err(Error::NotFound)
+    }
+
+    // Resolves the token and counts a click, refusing it once a limited link has none left
+    pub async fn click(&self, token: &str) -> Result<Link, Error> {
+        let mut link = self.resolve(token).await?;
+        let counted = storage::retry_busy(|| {
+            sqlx::query(
+                r#"
+                UPDATE urls SET click_count = click_count + 1
+                WHERE id = $1 AND deleted_at IS NULL AND (max_clicks IS NULL OR click_count < max_clicks)
+                "#,
+            )
+            .bind(&link.id)
+            .execute(&self.db)
+        })
+        .await?;
+        if counted.rows_affected() == 0 {
+            return Err(Error::LimitReached);
+        }
+        link.click_count += 1;
+        Ok(link)
+    }
+
+    async fn load(&self, token: &str) -> Result<Option<Link>, Error> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, token, original_url, created_at, starts_at, expires_at, max_clicks, click_count, domain,
+                flag_reason, warned, takedown, is_active, deleted_at
+            FROM urls WHERE token = $1
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(&self.db)
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        if row.get::<Option<String>, _>("deleted_at").is_some() {
+            return Err(Error::Deleted);
+        }
+        if row.get::<Option<String>, _>("takedown").is_some() {
+            return Err(Error::Blocked);
+        }
+        if row.get::<i64, _>("is_active") == 0 {
+            return Err(Error::Disabled);
+        }
+        Ok(Some(link_from_row(&row)))
+    }
+}
+
+fn link_from_row(row: &AnyRow) -> Link {
+    Link {
+        id: row.get("id"),
+        token: row.get("token"),
+        original_url: row.get("original_url"),
+        created_at: storage::get_ts(row, "created_at"),
+        starts_at: storage::get_opt_ts(row, "starts_at"),
+        expires_at: storage::get_opt_ts(row, "expires_at"),
+        max_clicks: row.get("max_clicks"),
+        click_count: row.get("click_count"),
+        domain: row.get("domain"),
+        flagged: row.get::<Option<String>, _>("flag_reason").is_some() || row.get::<i64, _>("warned") != 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shorten_resolve_and_click() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let resolver = Resolver::new(db.clone()).tokens(TokenGenerator::with_length(8));
+
+            let link = resolver.shorten("https://example.com/docs").await.unwrap();
+            assert_eq!(link.token.len(), 8);
+            assert_eq!(resolver.resolve(&link.token).await.unwrap(), link);
+            assert!(matches!(resolver.resolve("missing").await, Err(Error::NotFound)));
+            assert!(matches!(resolver.shorten("http://127.0.0.1/").await, Err(Error::InvalidUrl(_))));
+            assert!(matches!(resolver.shorten("mailto:team@example.com").await, Err(Error::InvalidUrl(_))));
+            let mail = Resolver::new(db.clone()).allowed_schemes(vec!["mailto".into()]);
+            assert!(mail.shorten("mailto:team@example.com").await.is_ok());
+
+            sqlx::query("UPDATE urls SET max_clicks = 1 WHERE id = $1")
+                .bind(&link.id)
+                .execute(&db)
+                .await
+                .unwrap();
+            assert_eq!(resolver.click(&link.token).await.unwrap().click_count, 1);
+            assert!(matches!(resolver.click(&link.token).await, Err(Error::LimitReached)));
+
+            sqlx::query("UPDATE urls SET expires_at = $1 WHERE id = $2")
+                .bind(storage::ts(storage::now() - chrono::Duration::minutes(1)))
+                .bind(&link.id)
+                .execute(&db)
+                .await
+                .unwrap();
+            assert!(matches!(resolver.resolve(&link.token).await, Err(Error::Expired)));
+
+            sqlx::query("UPDATE urls SET is_active = 0 WHERE id = $1")
+                .bind(&link.id)
+                .execute(&db)
+                .await
+                .unwrap();
+            assert!(matches!(resolver.resolve(&link.token).await, Err(Error::Disabled)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_aliases_and_case_insensitive_tokens() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let resolver = Resolver::new(db.clone())
+                .tokens(TokenGenerator::with_length(6).lowercase(true))
+                .case_insensitive(true);
+
+            let link = resolver.shorten("https://example.com").await.unwrap();
+            assert_eq!(resolver.resolve(&link.token.to_uppercase()).await.unwrap().id, link.id);
+
+            sqlx::query("INSERT INTO link_aliases (token, url_id, created_at) VALUES ('old-name', $1, $2)")
+                .bind(&link.id)
+                .bind(storage::ts(storage::now()))
+                .execute(&db)
+                .await
+                .unwrap();
+            assert_eq!(resolver.resolve("Old-Name").await.unwrap().token, link.token);
+
+            sqlx::query("UPDATE urls SET deleted_at = $1 WHERE id = $2")
+                .bind(storage::ts(storage::now()))
+                .bind(&link.id)
+                .execute(&db)
+                .await
+                .unwrap();
+            assert!(matches!(resolver.resolve(&link.token).await, Err(Error::Deleted)));
+            assert_eq!(schedule(Some(Utc::now() + chrono::Duration::hours(1)), None, Utc::now()), Schedule::Pending);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use sqlx::{AnyPool, Row};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::token::TokenGenerator;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+// Random tokens get longer as links accumulate instead of colliding more and more often. Taken
+// tokens are counted on start and every few minutes, links created in between are added as they
+// come, so the count only lags behind what other instances create.
+#[derive(Clone)]
+pub struct TokenScaling {
+    taken: Arc<AtomicU64>,
+    // A new token may hit a taken one once in this many, 0 turns scaling off
+    odds: u64,
+}
+
+impl TokenScaling {
+    pub fn new(odds: u64) -> Self {
+        Self { taken: Arc::new(AtomicU64::new(0)), odds }
+    }
+
+    pub async fn start(db: &AnyPool, odds: u64) -> Result<Self, sqlx::Error> {
+        let scaling = Self::new(odds);
+        if odds == 0 {
+            return Ok(scaling);
+        }
+        scaling.taken.store(count(db).await?, Ordering::Relaxed);
+
+        let taken = scaling.taken.clone();
+        let db = db.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(REFRESH_INTERVAL).await;
+                match count(&db).await {
+                    Ok(count) => taken.store(count, Ordering::Relaxed),
+                    Err(e) => eprintln!("⚠️  Could not count tokens for token length scaling: {}", e),
+                }
+            }
+        });
+        Ok(scaling)
+    }
+
+    pub fn taken(&self) -> u64 {
+        self.taken.load(Ordering::Relaxed)
+    }
+
+    pub fn added(&self) {
+        self.taken.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn generator(&self, tokens: &TokenGenerator) -> TokenGenerator {
+        tokens.scaled(self.taken(), self.odds)
+    }
+}
+
+// Deleted links and aliases keep their tokens, so they count too
+async fn count(db: &AnyPool) -> Result<u64, sqlx::Error> {
+    let row = sqlx::query("SELECT (SELECT COUNT(*) FROM urls) + (SELECT COUNT(*) FROM link_aliases) AS taken")
+        .fetch_one(db)
+        .await?;
+    Ok(row.get::<i64, _>("taken") as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::Resolver;
+    use crate::storage;
+
+    #[tokio::test]
+    async fn test_counts_taken_tokens() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let resolver = Resolver::new(db.clone());
+            for _ in 0..3 {
+                resolver.shorten("https://example.com").await.unwrap();
+            }
+
+            let scaling = TokenScaling::start(&db, 1000).await.unwrap();
+            assert_eq!(scaling.taken(), 3);
+            scaling.added();
+            assert_eq!(scaling.taken(), 4);
+            // A thousand times 4 tokens is more than 62^2, but never below the configured length
+            assert_eq!(scaling.generator(&TokenGenerator::with_length(1)).length(), 3);
+            assert_eq!(scaling.generator(&TokenGenerator::with_length(6)).length(), 6);
+            assert_eq!(TokenScaling::start(&db, 0).await.unwrap().taken(), 0);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use sqlx::{AnyPool, Row};
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::storage;
+use crate::token::TokenGenerator;
+
+// Values taken from the shared counter at once, the unused rest of a block is lost on restart
+const BLOCK_SIZE: u64 = 100;
+
+// Tokens from a counter shared through the database. Each value is handed out once, so tokens
+// never collide and stay as short as the number of links allows. Instances take blocks of values,
+// so tokens are only roughly in creation order across instances.
+#[derive(Clone)]
+pub struct Sequence {
+    db: AnyPool,
+    block: Arc<Mutex<Range<u64>>>,
+    scramble: Option<u64>,
+}
+
+impl Sequence {
+    pub fn new(db: AnyPool) -> Self {
+        Self { db, block: Arc::new(Mutex::new(0..0)), scramble: None }
+    }
+
+    // Shuffles tokens of the same length with this key, so consecutive links do not get
+    // neighbouring tokens. Every instance has to use the same key.
+    pub fn scrambled(mut self, key: Option<u64>) -> Self {
+        self.scramble = key;
+        self
+    }
+
+    pub async fn next_id(&self) -> Result<u64, sqlx::Error> {
+        let mut block = self.block.lock().await;
+        if block.is_empty() {
+            *block = self.reserve(BLOCK_SIZE).await?;
+        }
+        Ok(block.next().expect("a reserved block is never empty"))
+    }
+
+    pub async fn next_token(&self, tokens: &TokenGenerator) -> Result<String, sqlx::Error> {
+        Ok(tokens.sequential(self.next_id().await?, self.scramble))
+    }
+
+    // Makes sure the next `count` values need no round trip, for callers that are about to hold
+    // a write transaction on SQLite, which a reservation would have to wait for
+    pub async fn prefetch(&self, count: usize) -> Result<(), sqlx::Error> {
+        let mut block = self.block.lock().await;
+        let count = count as u64;
+        if block.end - block.start < count {
+            let reserved = self.reserve(count.max(BLOCK_SIZE)).await?;
+            // What was left of the old block is skipped, values only need to be unique
+            *block = reserved;
+        }
+        Ok(())
+    }
+
+    async fn reserve(&self, size: u64) -> Result<Range<u64>, sqlx::Error> {
+        let row = storage::retry_busy(|| {
+            sqlx::query("UPDATE token_sequence SET next_value = next_value + $1 WHERE id = 1 RETURNING next_value")
+                .bind(size as i64)
+                .fetch_one(&self.db)
+        })
+        .await?;
+        let end = row.get::<i64, _>("next_value") as u64;
+        Ok(end - size..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[tokio::test]
+    async fn test_instances_share_the_counter() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let first = Sequence::new(db.clone());
+            let second = Sequence::new(db);
+
+            assert_eq!(first.next_id().await.unwrap(), 0);
+            assert_eq!(second.next_id().await.unwrap(), BLOCK_SIZE);
+            assert_eq!(first.next_id().await.unwrap(), 1);
+
+            second.prefetch(500).await.unwrap();
+            let ids: HashSet<u64> = [first.next_id().await.unwrap(), second.next_id().await.unwrap()].into();
+            assert_eq!(ids, HashSet::from([2, 2 * BLOCK_SIZE]));
+
+            let tokens = TokenGenerator::with_length(4);
+            let token = first.next_token(&tokens).await.unwrap();
+            assert_eq!(token, "AAAD");
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use chrono::Utc;
+use sqlx::{AnyPool, Row};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::storage;
+use crate::token::{self, TokenGenerator};
+
+const WORKER_BITS: u32 = 10;
+const SEQUENCE_BITS: u32 = 12;
+const MAX_WORKERS: u16 = 1 << WORKER_BITS;
+const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+// 2024-01-01T00:00:00Z, 41 bits of milliseconds from here last until 2093
+const EPOCH_MS: i64 = 1_704_067_200_000;
+const LEASE: Duration = Duration::from_secs(60);
+const RENEW_INTERVAL: Duration = Duration::from_secs(20);
+
+// 63-bit ids of milliseconds since EPOCH_MS, worker id and a per-millisecond sequence. Instances
+// with different worker ids can never mint the same id, so they need no coordination per token.
+#[derive(Clone)]
+pub struct Snowflake {
+    worker: Arc<AtomicU16>,
+    // Millisecond and sequence of the last id
+    last: Arc<Mutex<(i64, u64)>>,
+    scramble: Option<u64>,
+}
+
+impl Snowflake {
+    pub fn new(worker: u16) -> Self {
+        Self {
+            worker: Arc::new(AtomicU16::new(worker)),
+            last: Arc::new(Mutex::new((-1, 0))),
+            scramble: None,
+        }
+    }
+
+    // Plain ids count up and give away when a link was made, so the neighbours of a token are easy
+    // to find. With a key tokens are a keyed permutation of the ids instead, still unique but in no
+    // order. Every instance has to use the same key.
+    pub fn scrambled(mut self, key: Option<u64>) -> Self {
+        self.scramble = key;
+        self
+    }
+
+    // Uses instance_id when set, otherwise leases a free worker id and keeps renewing the lease
+    pub async fn start(db: &AnyPool, instance_id: Option<u16>) -> anyhow::Result<Self> {
+        if let Some(worker) = instance_id {
+            return Ok(Self::new(worker));
+        }
+        let holder = Uuid::new_v4().to_string();
+        let snowflake = Self::new(claim(db, &holder).await?);
+        snowflake.spawn_renewal(db.clone(), holder);
+        Ok(snowflake)
+    }
+
+    pub fn worker(&self) -> u16 {
+        self.worker.load(Ordering::Relaxed)
+    }
+
+    pub fn next_id(&self) -> u64 {
+        let mut last = self.last.lock().unwrap();
+        // A clock stepping back keeps counting on the last millisecond instead of repeating ids
+        let mut millis = (Utc::now().timestamp_millis() - EPOCH_MS).max(last.0);
+        let mut sequence = if millis == last.0 { last.1 + 1 } else { 0 };
+        if sequence > MAX_SEQUENCE {
+            // More than 4096 ids in one millisecond borrow from the next one
+            millis += 1;
+            sequence = 0;
+        }
+        *last = (millis, sequence);
+        (millis as u64) << (WORKER_BITS + SEQUENCE_BITS) | u64::from(self.worker()) << SEQUENCE_BITS | sequence
+    }
+
+    pub fn next_token(&self, tokens: &TokenGenerator) -> String {
+        let id = self.next_id();
+        tokens.encode(self.scramble.map_or(id, |key| token::permute(id, 1 << 63, key)))
+    }
+
+    fn spawn_renewal(&self, db: AnyPool, holder: String) {
+        let worker = self.worker.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(RENEW_INTERVAL).await;
+                let current = worker.load(Ordering::Relaxed);
+                let renewed = sqlx::query("UPDATE token_workers SET expires_at = $1 WHERE worker_id = $2 AND holder = $3")
+                    .bind(storage::ts(storage::now() + LEASE))
+                    .bind(i64::from(current))
+                    .bind(&holder)
+                    .execute(&db)
+                    .await;
+                match renewed {
+                    Ok(result) if result.rows_affected() == 1 => {}
+                    // Taken over after we could not renew for a whole lease, move to a free id
+                    Ok(_) => match claim(&db, &holder).await {
+                        Ok(id) => {
+                            worker.store(id, Ordering::Relaxed);
+                            println!("❄️  Lost snowflake worker {}, now using {}", current, id);
+                        }
+                        Err(e) => eprintln!("❌ Could not lease a snowflake worker id: {}", e),
+                    },
+                    Err(e) => eprintln!("⚠️  Could not renew snowflake worker {}: {}", current, e),
+                }
+            }
+        });
+    }
+}
+
+// Lowest worker id whose lease is free or expired
+async fn claim(db: &AnyPool, holder: &str) -> anyhow::Result<u16> {
+    let now = storage::now();
+    let leased: HashSet<i64> = sqlx::query("SELECT worker_id FROM token_workers WHERE expires_at > $1")
+        .bind(storage::ts(now))
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|row| row.get("worker_id"))
+        .collect();
+
+    for worker in (0..MAX_WORKERS).filter(|worker| !leased.contains(&i64::from(*worker))) {
+        // Only takes over an expired lease, another instance may have claimed the id meanwhile
+        let claimed = sqlx::query(
+            r#"
+            INSERT INTO token_workers (worker_id, holder, expires_at) VALUES ($1, $2, $3)
+            ON CONFLICT (worker_id) DO UPDATE SET holder = excluded.holder, expires_at = excluded.expires_at
+            WHERE token_workers.expires_at <= $4
+            "#
+        )
+        .bind(i64::from(worker))
+        .bind(holder)
+        .bind(storage::ts(now + LEASE))
+        .bind(storage::ts(now))
+        .execute(db)
+        .await?
+        .rows_affected();
+        if claimed == 1 {
+            return Ok(worker);
+        }
+    }
+    anyhow::bail!("all {} snowflake worker ids are leased, set instance_id or wait for a lease to expire", MAX_WORKERS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_are_unique_and_increasing() {
+        let snowflake = Snowflake::new(7);
+        let ids: Vec<u64> = (0..10_000).map(|_| snowflake.next_id()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(ids.iter().all(|id| (id >> SEQUENCE_BITS) & u64::from(MAX_WORKERS - 1) == 7));
+        assert!(ids.iter().all(|id| id >> 63 == 0));
+    }
+
+    #[test]
+    fn test_scrambled_tokens_are_unique_and_unordered() {
+        let tokens = TokenGenerator::with_length(6);
+        let snowflake = Snowflake::new(7).scrambled(Some(42));
+        let minted: Vec<String> = (0..1000).map(|_| snowflake.next_token(&tokens)).collect();
+
+        assert_eq!(minted.iter().collect::<HashSet<_>>().len(), minted.len());
+        assert!(minted.iter().all(|token| token.len() <= 11));
+        // Consecutive ids differ in their last digits only, scrambled tokens anywhere
+        let same_start = minted.windows(2).filter(|pair| pair[0][..4] == pair[1][..4]).count();
+        assert!(same_start < 10, "{}", same_start);
+    }
+
+    #[tokio::test]
+    async fn test_instances_lease_distinct_workers() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            assert_eq!(claim(&db, "first").await.unwrap(), 0);
+            assert_eq!(claim(&db, "second").await.unwrap(), 1);
+
+            // An expired lease is handed to the next instance
+            sqlx::query("UPDATE token_workers SET expires_at = $1 WHERE holder = 'first'")
+                .bind(storage::ts(storage::now() - chrono::Duration::seconds(1)))
+                .execute(&db)
+                .await
+                .unwrap();
+            assert_eq!(claim(&db, "third").await.unwrap(), 0);
+        }
+    }
+}
@@ -0,0 +1,412 @@
+// Both backends go through sqlx::Any, so every query has to stay dialect neutral:
+// - placeholders are written as $1, $2, ... (SQLite accepts them too)
+// - timestamps are TEXT in fixed-width RFC 3339 (see `ts`), so they sort and compare as strings
+// - booleans and counters are INTEGER on SQLite and BIGINT on Postgres, decoded as i64
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, SubsecRound, Utc};
+use sqlx::any::{AnyArguments, AnyPoolOptions, AnyRow};
+use sqlx::migrate::Migrator;
+use sqlx::query::Query;
+use sqlx::sqlite::SqliteError;
+use sqlx::{Any, AnyPool, Arguments, Encode, Executor, Row, Type};
+use std::fmt::Write;
+use std::future::Future;
+use std::time::Duration;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6fZ";
+const BUSY_RETRIES: u32 = 5;
+const BUSY_BACKOFF: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+impl Backend {
+    pub fn from_url(database_url: &str) -> anyhow::Result<Self> {
+        if database_url.starts_with("sqlite:") {
+            Ok(Backend::Sqlite)
+        } else if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+            Ok(Backend::Postgres)
+        } else {
+            anyhow::bail!("unsupported database URL, expected sqlite: or postgres://")
+        }
+    }
+}
+
+// How to reach the database, the server fills this in from its config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseOptions {
+    pub url: String,
+    // Optional read replica, see connect_replica
+    pub read_url: Option<String>,
+    pub max_connections: u32,
+    pub acquire_timeout_secs: u64,
+    // SQLite only, applied to every new connection
+    pub sqlite_journal_mode: String,
+    pub sqlite_synchronous: String,
+    pub sqlite_busy_timeout_ms: u64,
+}
+
+impl DatabaseOptions {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            read_url: None,
+            max_connections: 10,
+            acquire_timeout_secs: 10,
+            sqlite_journal_mode: "wal".into(),
+            sqlite_synchronous: "normal".into(),
+            sqlite_busy_timeout_ms: 5000,
+        }
+    }
+}
+
+pub async fn connect(options: &DatabaseOptions) -> anyhow::Result<(AnyPool, Backend)> {
+    let backend = Backend::from_url(&options.url)?;
+    sqlx::any::install_default_drivers();
+
+    let db = pool_options(options, backend)
+        .connect(&options.url)
+        .await
+        .context("failed to connect to the database")?;
+    migrate(&db, backend).await?;
+
+    Ok((db, backend))
+}
+
+// Read replica from read_url, migrations are left to the primary it follows
+pub async fn connect_replica(options: &DatabaseOptions, backend: Backend) -> anyhow::Result<Option<AnyPool>> {
+    let Some(url) = options.read_url.as_deref().filter(|url| !url.is_empty()) else {
+        return Ok(None);
+    };
+    if Backend::from_url(url)? != backend {
+        anyhow::bail!("database_read_url must use the same backend as database_url");
+    }
+    let replica = pool_options(options, backend)
+        .connect(url)
+        .await
+        .context("failed to connect to the read replica")?;
+    Ok(Some(replica))
+}
+
+fn pool_options(options: &DatabaseOptions, backend: Backend) -> AnyPoolOptions {
+    let mut pool = AnyPoolOptions::new()
+        .max_connections(options.max_connections)
+        .acquire_timeout(Duration::from_secs(options.acquire_timeout_secs));
+    if backend == Backend::Sqlite {
+        let pragmas = sqlite_pragmas(options);
+        pool = pool.after_connect(move |conn, _| {
+            let pragmas = pragmas.clone();
+            Box::pin(async move {
+                conn.execute(pragmas.as_str()).await?;
+                Ok(())
+            })
+        });
+    }
+    pool
+}
+
+// Values are checked by the server's Config::validate, journal_mode is a no-op once the file
+// is in WAL mode
+fn sqlite_pragmas(options: &DatabaseOptions) -> String {
+    format!(
+        "PRAGMA journal_mode = {}; PRAGMA synchronous = {}; PRAGMA busy_timeout = {};",
+        options.sqlite_journal_mode, options.sqlite_synchronous, options.sqlite_busy_timeout_ms
+    )
+}
+
+// SQLITE_BUSY or SQLITE_LOCKED, including extended codes like BUSY_SNAPSHOT that busy_timeout
+// does not wait out, e.g. when a transaction that started reading wants to write
+pub fn is_busy(error: &sqlx::Error) -> bool {
+    let sqlx::Error::Database(error) = error else {
+        return false;
+    };
+    error.try_downcast_ref::<SqliteError>().is_some()
+        && error
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| matches!(code & 0xff, 5 | 6))
+}
+
+// Runs a write again with backoff while SQLite reports the database as locked, other errors (and
+// every Postgres error) are returned straight away
+pub async fn retry_busy<T, F, Fut>(mut write: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut backoff = BUSY_BACKOFF;
+    for _ in 0..BUSY_RETRIES {
+        match write().await {
+            Err(e) if is_busy(&e) => {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+    write().await
+}
+
+fn migrator(backend: Backend) -> Migrator {
+    match backend {
+        Backend::Sqlite => sqlx::migrate!("./migrations/sqlite"),
+        Backend::Postgres => sqlx::migrate!("./migrations/postgres"),
+    }
+}
+
+pub async fn migrate(db: &AnyPool, backend: Backend) -> anyhow::Result<()> {
+    migrator(backend).run(db).await?;
+    Ok(())
+}
+
+// Newest migration built into this crate
+pub fn latest_migration(backend: Backend) -> Option<i64> {
+    migrator(backend).iter().map(|migration| migration.version).max()
+}
+
+// Newest migration recorded in the database, which may be ahead when a newer release shares it
+pub async fn applied_migration(db: &AnyPool) -> Result<Option<i64>, sqlx::Error> {
+    Ok(sqlx::query("SELECT MAX(version) AS version FROM _sqlx_migrations")
+        .fetch_one(db)
+        .await?
+        .get("version"))
+}
+
+// Current time at the precision we store, so API responses match what a later read returns
+pub fn now() -> DateTime<Utc> {
+    Utc::now().trunc_subsecs(6)
+}
+
+pub fn ts(value: DateTime<Utc>) -> String {
+    value.format(TIMESTAMP_FORMAT).to_string()
+}
+
+// Midnight UTC of the day, the lower bound of its clicks
+pub fn day_start(date: NaiveDate) -> String {
+    ts(date.and_time(NaiveTime::MIN).and_utc())
+}
+
+// Accepts our own format as well as rows written before timestamps were normalized
+pub fn parse_ts(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|value| value.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f").map(|value| value.and_utc()))
+        .unwrap_or_default()
+}
+
+pub fn get_ts(row: &AnyRow, column: &str) -> DateTime<Utc> {
+    parse_ts(&row.get::<String, _>(column))
+}
+
+pub fn get_opt_ts(row: &AnyRow, column: &str) -> Option<DateTime<Utc>> {
+    row.get::<Option<String>, _>(column).as_deref().map(parse_ts)
+}
+
+// QueryBuilder<Any> always emits `?`, which Postgres rejects, so dynamic queries use this instead
+#[derive(Default)]
+pub struct SqlBuilder {
+    sql: String,
+    args: AnyArguments<'static>,
+    binds: usize,
+}
+
+impl SqlBuilder {
+    pub fn new(sql: impl Into<String>) -> Self {
+        Self {
+            sql: sql.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn push(&mut self, sql: impl AsRef<str>) -> &mut Self {
+        self.sql.push_str(sql.as_ref());
+        self
+    }
+
+    pub fn push_bind<T>(&mut self, value: T) -> &mut Self
+    where
+        T: 'static + Send + Encode<'static, Any> + Type<Any>,
+    {
+        // Encoding owned strings and integers into Any arguments cannot fail
+        self.args.add(value).expect("failed to encode bind value");
+        self.binds += 1;
+        write!(self.sql, "${}", self.binds).expect("writing to a String cannot fail");
+        self
+    }
+
+    pub fn build(&mut self) -> Query<'_, Any, AnyArguments<'_>> {
+        sqlx::query_with(&self.sql, std::mem::take(&mut self.args))
+    }
+}
+
+// Migrated in-memory SQLite database, for tests and short-lived tools. It needs a single
+// connection, otherwise every connection gets its own database.
+pub async fn memory_pool() -> anyhow::Result<AnyPool> {
+    sqlx::any::install_default_drivers();
+    let db = AnyPoolOptions::new().max_connections(1).connect("sqlite::memory:").await?;
+    migrate(&db, Backend::Sqlite).await?;
+    Ok(db)
+}
+
+// Every backend the database tests run against, in-memory SQLite and Postgres when
+// QUICKURL_TEST_POSTGRES_URL points at a scratch database. Each Postgres pool migrates a schema
+// of its own, so tests running side by side start from empty tables there too.
+pub async fn test_pools() -> anyhow::Result<Vec<(AnyPool, Backend)>> {
+    let pools = scratch_pools().await?;
+    for (db, backend) in &pools {
+        migrate(db, *backend).await?;
+    }
+    Ok(pools)
+}
+
+async fn scratch_pools() -> anyhow::Result<Vec<(AnyPool, Backend)>> {
+    sqlx::any::install_default_drivers();
+    let sqlite = AnyPoolOptions::new().max_connections(1).connect("sqlite::memory:").await?;
+    let mut pools = vec![(sqlite, Backend::Sqlite)];
+    if let Some(url) = std::env::var("QUICKURL_TEST_POSTGRES_URL").ok().filter(|url| !url.is_empty()) {
+        let schema = format!("test_{}", uuid::Uuid::new_v4().simple());
+        let setup = format!("CREATE SCHEMA IF NOT EXISTS {0}; SET search_path TO {0}", schema);
+        let postgres = AnyPoolOptions::new()
+            .max_connections(4)
+            .after_connect(move |conn, _| {
+                let setup = setup.clone();
+                Box::pin(async move {
+                    conn.execute(setup.as_str()).await?;
+                    Ok(())
+                })
+            })
+            .connect(&url)
+            .await
+            .context("failed to connect to QUICKURL_TEST_POSTGRES_URL")?;
+        pools.push((postgres, Backend::Postgres));
+    }
+    Ok(pools)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::migrate::Migrate;
+    use sqlx::{AnyConnection, Connection};
+
+    async fn exercise_urls_table(db: &AnyPool) {
+        let id = uuid::Uuid::new_v4().to_string();
+        let token = id[..8].to_string();
+        let created_at = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO urls (id, token, original_url, title, created_at, expires_at, click_count)
+            VALUES ($1, $2, $3, $4, $5, $6, 0)
+            "#,
+        )
+        .bind(&id)
+        .bind(&token)
+        .bind("https://example.com")
+        .bind(None::<String>)
+        .bind(ts(created_at))
+        .bind(ts(created_at + chrono::Duration::days(1)))
+        .execute(db)
+        .await
+        .unwrap();
+
+        let mut builder = SqlBuilder::new("SELECT * FROM urls WHERE token = ");
+        builder.push_bind(token.clone()).push(" AND created_at <= ").push_bind(ts(created_at));
+        let row = builder.build().fetch_one(db).await.unwrap();
+
+        assert_eq!(row.get::<String, _>("id"), id);
+        assert_eq!(row.get::<Option<String>, _>("title"), None);
+        assert_eq!(row.get::<i64, _>("click_count"), 0);
+        assert_eq!(ts(get_ts(&row, "created_at")), ts(created_at));
+    }
+
+    #[tokio::test]
+    async fn test_backends() {
+        for (db, backend) in test_pools().await.unwrap() {
+            exercise_urls_table(&db).await;
+            assert_eq!(applied_migration(&db).await.unwrap(), latest_migration(backend));
+        }
+    }
+
+    // 028 rebuilds expires_at on SQLite, links from before it keep their expiry and stay searchable
+    #[tokio::test]
+    async fn test_nullable_expiry_keeps_existing_links() {
+        for (db, backend) in scratch_pools().await.unwrap() {
+            let mut conn = db.acquire().await.unwrap();
+            conn.ensure_migrations_table().await.unwrap();
+            for migration in migrator(backend).iter().filter(|migration| migration.version < 28) {
+                conn.apply(migration).await.unwrap();
+            }
+            drop(conn);
+            let expires_at = "2030-01-01T00:00:00.000000Z";
+            sqlx::query(
+                "INSERT INTO urls (id, token, original_url, title, created_at, expires_at, click_count) VALUES ('u1', 'old123', 'https://example.com', 'Quarterly report', $1, $2, 0)",
+            )
+            .bind(ts(Utc::now()))
+            .bind(expires_at)
+            .execute(&db)
+            .await
+            .unwrap();
+
+            migrate(&db, backend).await.unwrap();
+            let row = sqlx::query("SELECT expires_at FROM urls WHERE token = 'old123'").fetch_one(&db).await.unwrap();
+            assert_eq!(row.get::<Option<String>, _>("expires_at").as_deref(), Some(expires_at));
+            sqlx::query("INSERT INTO urls (id, token, original_url, created_at, click_count) VALUES ('u2', 'new123', 'https://example.com', $1, 0)")
+                .bind(ts(Utc::now()))
+                .execute(&db)
+                .await
+                .unwrap();
+            if backend == Backend::Sqlite {
+                let found: String = sqlx::query("SELECT u.token FROM urls_fts JOIN urls u ON u.rowid = urls_fts.rowid WHERE urls_fts MATCH 'quarterly'")
+                    .fetch_one(&db)
+                    .await
+                    .unwrap()
+                    .get("token");
+                assert_eq!(found, "old123");
+            }
+        }
+    }
+
+    #[test]
+    fn test_timestamps_sort_as_text() {
+        let earlier = parse_ts("2025-08-21T16:58:19.898744379+00:00");
+        let later = parse_ts("2025-08-21T16:58:20Z");
+
+        assert!(ts(earlier) < ts(later));
+        assert_eq!(ts(earlier), "2025-08-21T16:58:19.898744Z");
+        assert_eq!(Backend::from_url("postgres://localhost/quickurl").unwrap(), Backend::Postgres);
+        assert!(Backend::from_url("mysql://localhost").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_pragmas_and_busy_retry() {
+        let path = std::env::temp_dir().join(format!("quickurl-{}.db", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+        let options = DatabaseOptions { sqlite_busy_timeout_ms: 0, ..DatabaseOptions::new(url.clone()) };
+        let (db, _) = connect(&options).await.unwrap();
+
+        let mode: String = sqlx::query("PRAGMA journal_mode").fetch_one(&db).await.unwrap().get(0);
+        assert_eq!(mode, "wal");
+
+        // Another process holding the write lock makes writes fail straight away without a timeout
+        let mut other = AnyConnection::connect(&url).await.unwrap();
+        other.execute("BEGIN IMMEDIATE").await.unwrap();
+        let write = || sqlx::query("UPDATE urls SET click_count = click_count + 1").execute(&db);
+        assert!(is_busy(&write().await.unwrap_err()));
+
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            other.execute("COMMIT").await.unwrap();
+        });
+        retry_busy(write).await.unwrap();
+        release.await.unwrap();
+
+        db.close().await;
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+// CHARSET without 0/O and 1/l/I, for tokens that get read aloud or typed from print
+const UNAMBIGUOUS_CHARSET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz23456789";
+// For case-insensitive tokens, which cannot tell A from a
+const LOWERCASE_CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+const LOWERCASE_UNAMBIGUOUS_CHARSET: &[u8] = b"abcdefghijkmnopqrstuvwxyz23456789";
+const AMBIGUOUS: &[u8] = b"0O1lI";
+
+#[derive(Clone)] // Додаємо Clone trait
+pub struct TokenGenerator {
+    length: usize,
+    charset: Arc<[u8]>,
+    // Operator supplied alphabet replacing CHARSET, the other options still filter it
+    alphabet: Option<Arc<[u8]>>,
+    exclude_ambiguous: bool,
+    lowercase: bool,
+}
+
+impl Default for TokenGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TokenGenerator {
+    pub fn new() -> Self {
+        Self::with_length(6)
+    }
+
+    pub fn with_length(length: usize) -> Self {
+        Self { length, charset: Arc::from(CHARSET), alphabet: None, exclude_ambiguous: false, lowercase: false }
+    }
+
+    pub fn alphabet(mut self, alphabet: Option<&str>) -> Self {
+        self.alphabet = alphabet.map(|alphabet| Arc::from(alphabet.as_bytes()));
+        self.with_charset()
+    }
+
+    pub fn exclude_ambiguous(mut self, exclude: bool) -> Self {
+        self.exclude_ambiguous = exclude;
+        self.with_charset()
+    }
+
+    pub fn lowercase(mut self, lowercase: bool) -> Self {
+        self.lowercase = lowercase;
+        self.with_charset()
+    }
+
+    fn with_charset(mut self) -> Self {
+        self.charset = match (&self.alphabet, self.lowercase, self.exclude_ambiguous) {
+            (Some(alphabet), lowercase, exclude) => alphabet
+                .iter()
+                .copied()
+                .filter(|c| !lowercase || !c.is_ascii_uppercase())
+                .filter(|c| !exclude || !AMBIGUOUS.contains(c))
+                .collect(),
+            (None, false, false) => Arc::from(CHARSET),
+            (None, false, true) => Arc::from(UNAMBIGUOUS_CHARSET),
+            (None, true, false) => Arc::from(LOWERCASE_CHARSET),
+            (None, true, true) => Arc::from(LOWERCASE_UNAMBIGUOUS_CHARSET),
+        };
+        self
+    }
+
+    // The characters tokens are drawn from after all options are applied
+    pub fn charset(&self) -> &[u8] {
+        &self.charset
+    }
+
+    // Same alphabet, longer tokens, used when retrying after collisions
+    pub fn extended(&self, extra: usize) -> Self {
+        Self {
+            length: self.length + extra,
+            ..self.clone()
+        }
+    }
+
+    // Longer tokens once `existing` tokens are taken, so that a new random token hits one of them
+    // at most one time in `odds`. Never shorter than the configured length, 0 odds keep it.
+    pub fn scaled(&self, existing: u64, odds: u64) -> Self {
+        let base = self.charset.len() as u128;
+        let wanted = u128::from(existing) * u128::from(odds);
+        let mut length = self.length;
+        while base.saturating_pow(length as u32) < wanted {
+            length += 1;
+        }
+        Self { length, ..self.clone() }
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    // A number written in the token alphabet, for ids that are unique by construction
+    pub fn encode(&self, mut value: u64) -> String {
+        let base = self.charset.len() as u64;
+        let mut digits = Vec::new();
+        loop {
+            digits.push(self.charset[(value % base) as usize]);
+            value /= base;
+            if value == 0 {
+                break;
+            }
+        }
+        digits.iter().rev().map(|&digit| digit as char).collect()
+    }
+
+    // The n-th sequential token. Every token of the configured length comes before a longer one;
+    // with a key, the order within a length is a keyed permutation instead of counting up.
+    pub fn sequential(&self, n: u64, key: Option<u64>) -> String {
+        let base = self.charset.len() as u128;
+        let mut length = self.length.max(1);
+        let mut index = u128::from(n);
+        // Capped at the range of u64, no counter gets past that
+        let size = |length: usize| base.saturating_pow(length as u32).min(1 << 64);
+        while index >= size(length) {
+            index -= size(length);
+            length += 1;
+        }
+        if let Some(key) = key {
+            index = u128::from(permute(index as u64, size(length), key));
+        }
+
+        let mut digits = vec![self.charset[0]; length];
+        for digit in digits.iter_mut().rev() {
+            *digit = self.charset[(index % base) as usize];
+            index /= base;
+        }
+        digits.iter().map(|&digit| digit as char).collect()
+    }
+
+    // Token derived from `input` alone, so every instance turns the same input into the same token.
+    // Digest bytes that would favour some characters are skipped, the digest is rehashed when it
+    // runs out.
+    pub fn hashed(&self, input: &str) -> String {
+        self.digest_token(Sha256::digest(input.as_bytes()).into())
+    }
+
+    // Like `hashed` with an HMAC under `key`, without the key nobody can tell which input a token
+    // belongs to. Instances sharing a database need the same key to agree on tokens.
+    pub fn hashed_with_key(&self, key: &[u8], input: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+        mac.update(input.as_bytes());
+        self.digest_token(mac.finalize().into_bytes().into())
+    }
+
+    fn digest_token(&self, mut digest: [u8; 32]) -> String {
+        let base = self.charset.len();
+        let limit = 256 - 256 % base;
+        let mut next = 0;
+        let mut token = String::with_capacity(self.length);
+        while token.len() < self.length {
+            if next == digest.len() {
+                digest = Sha256::digest(digest).into();
+                next = 0;
+            }
+            let byte = usize::from(digest[next]);
+            next += 1;
+            if byte < limit {
+                token.push(self.charset[byte % base] as char);
+            }
+        }
+        token
+    }
+
+    // Tokens are the only thing guarding unlisted links, so draw them from the OS CSPRNG
+    pub fn generate(&self) -> String {
+        (0..self.length)
+            .map(|_| {
+                let idx = OsRng.gen_range(0..self.charset.len());
+                self.charset[idx] as char
+            })
+            .collect()
+    }
+}
+
+// Keyed bijection on 0..domain: a Feistel network on the smallest even number of bits that
+// covers the domain, repeated until the value falls back inside it
+pub(crate) fn permute(value: u64, domain: u128, key: u64) -> u64 {
+    let bits = (128 - (domain - 1).leading_zeros()).max(2);
+    let half = bits.div_ceil(2);
+    let mask = (1u128 << half) - 1;
+    let mut value = u128::from(value);
+    loop {
+        let (mut left, mut right) = (value >> half, value & mask);
+        for round in 0..4 {
+            let next = left ^ (u128::from(mix(right as u64 ^ mix(key.wrapping_add(round)))) & mask);
+            left = right;
+            right = next;
+        }
+        value = (left << half) | right;
+        if value < domain {
+            return value as u64;
+        }
+    }
+}
+
+// splitmix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_generation() {
+        let generator = TokenGenerator::new();
+        let token = generator.generate();
+        
+        assert_eq!(token.len(), 6);
+        assert!(token.chars().all(|c| c.is_alphanumeric()));
+    }
+
+    #[test]
+    fn test_custom_length() {
+        let generator = TokenGenerator::with_length(10);
+        let token = generator.generate();
+        
+        assert_eq!(token.len(), 10);
+    }
+
+    #[test]
+    fn test_exclude_ambiguous() {
+        let generator = TokenGenerator::with_length(64).exclude_ambiguous(true);
+
+        for _ in 0..50 {
+            let token = generator.generate();
+            assert!(!token.contains(['0', 'O', '1', 'l', 'I']));
+        }
+        assert_eq!(generator.extended(2).generate().len(), 66);
+    }
+
+    #[test]
+    fn test_lowercase() {
+        let generator = TokenGenerator::with_length(64).lowercase(true).exclude_ambiguous(true);
+
+        for _ in 0..50 {
+            let token = generator.extended(1).generate();
+            assert!(!token.chars().any(|c| c.is_ascii_uppercase()));
+            assert!(!token.contains(['0', '1', 'l']));
+        }
+        assert_eq!(generator.encode(34), "bb");
+    }
+
+    #[test]
+    fn test_encode() {
+        let generator = TokenGenerator::new();
+        assert_eq!(generator.encode(0), "A");
+        assert_eq!(generator.encode(62), "BA");
+        assert_eq!(generator.encode(u64::MAX >> 1).len(), 11);
+        assert_ne!(generator.encode(1_000), generator.encode(1_001));
+    }
+
+    #[test]
+    fn test_sequential() {
+        let generator = TokenGenerator::with_length(2);
+        assert_eq!(generator.sequential(0, None), "AA");
+        assert_eq!(generator.sequential(63, None), "BB");
+        // 62^2 two-character tokens, then three characters
+        assert_eq!(generator.sequential(3844, None), "AAA");
+        assert_eq!(generator.sequential(u64::MAX, None).len(), 11);
+
+        // Scrambling is a permutation of each length
+        let scrambled: std::collections::HashSet<String> =
+            (0..3844).map(|n| generator.sequential(n, Some(42))).collect();
+        assert_eq!(scrambled.len(), 3844);
+        assert!(scrambled.iter().all(|token| token.len() == 2));
+        assert_ne!(generator.sequential(0, Some(42)), "AA");
+        assert_ne!(generator.sequential(0, Some(42)), generator.sequential(0, Some(43)));
+        assert_eq!(generator.sequential(5000, Some(7)).len(), 3);
+    }
+
+    #[test]
+    fn test_hashed() {
+        let generator = TokenGenerator::with_length(6);
+        let token = generator.hashed("https://example.com/docs");
+        assert_eq!(token.len(), 6);
+        assert_eq!(generator.hashed("https://example.com/docs"), token);
+        assert_ne!(generator.hashed("https://example.com/blog"), token);
+
+        // A longer token starts like the shorter one, more than one digest is needed for 64
+        let longer = generator.extended(58).hashed("https://example.com/docs");
+        assert_eq!(longer.len(), 64);
+        assert!(longer.starts_with(&token));
+
+        let keyed = generator.hashed_with_key(b"secret", "https://example.com/docs");
+        assert_eq!(keyed.len(), 6);
+        assert_eq!(generator.hashed_with_key(b"secret", "https://example.com/docs"), keyed);
+        assert_ne!(generator.hashed_with_key(b"other", "https://example.com/docs"), keyed);
+        assert_ne!(keyed, token);
+
+        assert!(generator.lowercase(true).hashed("x").chars().all(|c| !c.is_ascii_uppercase()));
+    }
+
+    #[test]
+    fn test_custom_alphabet() {
+        let generator = TokenGenerator::with_length(32).alphabet(Some("abcdefXYZ01")).exclude_ambiguous(true);
+        assert_eq!(generator.charset(), b"abcdefXYZ");
+        assert!(generator.generate().bytes().all(|c| b"abcdefXYZ".contains(&c)));
+        assert_eq!(generator.lowercase(true).charset(), b"abcdef");
+        assert_eq!(TokenGenerator::with_length(2).alphabet(Some("ab")).sequential(4, None), "aaa");
+    }
+
+    #[test]
+    fn test_scaled() {
+        let generator = TokenGenerator::with_length(4);
+        assert_eq!(generator.scaled(0, 1000).length(), 4);
+        // 62^4 is about 14.8 million, a thousand times 20,000 links still fits
+        assert_eq!(generator.scaled(14_000, 1000).length(), 4);
+        assert_eq!(generator.scaled(20_000, 1000).length(), 5);
+        assert_eq!(generator.scaled(u64::MAX, u64::MAX).length(), 22);
+        assert_eq!(generator.scaled(u64::MAX, 0).length(), 4);
+    }
+}
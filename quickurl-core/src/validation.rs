@@ -0,0 +1,152 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use url::{Host, Url};
+
+// Refused even when configured, they run script or read local data in the visitor's browser
+const FORBIDDEN_SCHEMES: &[&str] = &["javascript", "vbscript", "data", "blob", "file", "about"];
+
+pub fn is_forbidden_scheme(scheme: &str) -> bool {
+    FORBIDDEN_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str())
+}
+
+// Checks a destination before it is stored. Unless private destinations are allowed,
+// links may not point at loopback, private or link-local addresses, so the service cannot
+// be used to bounce visitors (or previews and health checkers) onto internal hosts.
+pub fn validate_destination(raw: &str, max_length: usize, allow_private: bool) -> Result<Url, String> {
+    validate_destination_with_schemes(raw, max_length, allow_private, &[])
+}
+
+// Also accepts the schemes given besides http and https, e.g. mailto or tel. URLs of those need
+// no host, one they do have is checked like a web URL's.
+pub fn validate_destination_with_schemes(
+    raw: &str,
+    max_length: usize,
+    allow_private: bool,
+    schemes: &[String],
+) -> Result<Url, String> {
+    if raw.is_empty() {
+        return Err("URL must not be empty".into());
+    }
+    if raw.len() > max_length {
+        return Err(format!("URL must be at most {} characters", max_length));
+    }
+    if raw.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("URL must not contain whitespace or control characters".into());
+    }
+
+    let url = Url::parse(raw).map_err(|e| format!("Invalid URL: {}", e))?;
+    let web = url.scheme() == "http" || url.scheme() == "https";
+    if is_forbidden_scheme(url.scheme()) {
+        return Err(format!("URL scheme {} is not allowed", url.scheme()));
+    }
+    if !web && !schemes.iter().any(|scheme| scheme.eq_ignore_ascii_case(url.scheme())) {
+        return Err(match schemes.is_empty() {
+            true => "URL scheme must be http or https".into(),
+            false => format!("URL scheme must be http, https or {}", schemes.join(", ")),
+        });
+    }
+
+    match url.host() {
+        None if web => return Err("URL must include a host".into()),
+        None => {}
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.');
+            if domain.split('.').any(str::is_empty) {
+                return Err(format!("Invalid host: {}", domain));
+            }
+            if !allow_private && (domain == "localhost" || domain.ends_with(".localhost")) {
+                return Err("URL must not point to localhost".into());
+            }
+        }
+        Some(Host::Ipv4(ip)) if !allow_private && is_private_v4(ip) => {
+            return Err(format!("URL must not point to a private address: {}", ip));
+        }
+        Some(Host::Ipv6(ip)) if !allow_private && is_private_v6(ip) => {
+            return Err(format!("URL must not point to a private address: {}", ip));
+        }
+        Some(_) => {}
+    }
+
+    Ok(url)
+}
+
+// Loopback, private, link-local and other addresses that are not on the public internet
+pub fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => is_private_v6(ip),
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (a == 100 && (64..128).contains(&b))
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_private_v4(v4);
+    }
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // Unique local fc00::/7 and link-local fe80::/10
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_bad_and_private_destinations() {
+        assert!(validate_destination("https://example.com/path?q=1", 2048, false).is_ok());
+
+        for bad in [
+            "",
+            "https://",
+            "https://exa mple.com",
+            "ftp://example.com",
+            "https://example..com",
+            "http://localhost:8080",
+            "http://127.0.0.1/admin",
+            "http://10.1.2.3",
+            "http://192.168.0.1",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/",
+            "http://[::ffff:10.0.0.1]/",
+            "http://[fd00::1]/",
+        ] {
+            assert!(validate_destination(bad, 2048, false).is_err(), "{} accepted", bad);
+        }
+
+        assert!(validate_destination("http://localhost:8080", 2048, true).is_ok());
+        assert!(validate_destination("https://example.com/long", 20, false).is_err());
+    }
+
+    #[test]
+    fn test_allowed_schemes() {
+        let schemes = ["mailto".to_string(), "tel".into(), "magnet".into(), "ftp".into()];
+        for good in [
+            "mailto:team@example.com?subject=Hi",
+            "tel:+15551234567",
+            "magnet:?xt=urn:btih:c12fe1c06bba254a9dc9f519b335aa7c1367a88a",
+            "FTP://ftp.example.com/pub/file.iso",
+        ] {
+            assert!(validate_destination_with_schemes(good, 2048, false, &schemes).is_ok(), "{} refused", good);
+        }
+        for bad in ["ftp://192.168.0.1/", "sms:+15551234567", "javascript:alert(1)", "data:text/html,hi"] {
+            assert!(validate_destination_with_schemes(bad, 2048, false, &schemes).is_err(), "{} accepted", bad);
+        }
+        // Not even when configured
+        let dangerous = ["javascript".to_string(), "data".into()];
+        assert!(validate_destination_with_schemes("javascript:alert(1)", 2048, false, &dangerous).is_err());
+        assert!(validate_destination("mailto:team@example.com", 2048, false).is_err());
+    }
+}
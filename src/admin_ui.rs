@@ -0,0 +1,66 @@
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use rust_embed::RustEmbed;
+use std::sync::Arc;
+
+use crate::{AppError, AppState};
+
+// Plain HTML and JS compiled into the binary, so managing links needs no separate frontend.
+// The files hold no data: the page asks for a credential and calls the JSON API with it,
+// which applies the usual bearer auth and owner scoping.
+#[derive(RustEmbed)]
+#[folder = "ui/"]
+struct Assets;
+
+fn serve(path: &str) -> Response {
+    match Assets::get(path) {
+        Some(file) => (
+            [
+                (header::CONTENT_TYPE, file.metadata.mimetype().to_string()),
+                // Asset names are not versioned, revalidate so an upgrade is picked up at once
+                (header::CACHE_CONTROL, "no-cache".to_string()),
+            ],
+            file.data,
+        )
+            .into_response(),
+        None => AppError::NotFound("File not found".into()).into_response(),
+    }
+}
+
+// The single-sign-on link only shows when a provider is configured
+pub async fn index(State(state): State<Arc<AppState>>) -> Response {
+    if state.oidc.is_none() {
+        return serve("index.html");
+    }
+    match Assets::get("index.html") {
+        Some(file) => {
+            let page = String::from_utf8_lossy(&file.data).replace(r#"id="sso" hidden"#, r#"id="sso""#);
+            ([(header::CONTENT_TYPE, "text/html"), (header::CACHE_CONTROL, "no-cache")], page).into_response()
+        }
+        None => AppError::NotFound("File not found".into()).into_response(),
+    }
+}
+
+pub async fn asset(Path(path): Path<String>) -> Response {
+    serve(&format!("assets/{}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    #[test]
+    fn test_serves_embedded_files() {
+        let page = serve("index.html");
+        assert_eq!(page.status(), StatusCode::OK);
+        assert_eq!(page.headers()[header::CONTENT_TYPE], "text/html");
+
+        let script = serve("assets/app.js");
+        assert!(script.headers()[header::CONTENT_TYPE].to_str().unwrap().contains("javascript"));
+        assert_eq!(serve("assets/missing.js").status(), StatusCode::NOT_FOUND);
+    }
+}
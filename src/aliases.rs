@@ -0,0 +1,238 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::{AnyConnection, AnyPool, Row};
+use std::sync::Arc;
+
+use crate::audit::{self, AuditAction};
+use crate::auth::Caller;
+use crate::base_url::BaseUrl;
+use crate::models::{AliasInfo, CreateAliasRequest};
+use crate::storage::{self, SqlBuilder};
+use quickurl_core::resolver;
+use crate::{folded_token_exists, push_owner_filter, token_exists, validate_alias, Access, AppError, AppState};
+
+const MAX_ALIASES: usize = 20;
+
+fn database_error(e: sqlx::Error) -> AppError {
+    AppError::DatabaseError(e.to_string())
+}
+
+// Token of the link an alias points to
+pub async fn canonical_token(db: &AnyPool, alias: &str) -> Result<Option<String>, AppError> {
+    resolver::canonical_token(db, alias).await.map_err(database_error)
+}
+
+pub async fn is_alias(conn: &mut AnyConnection, token: &str) -> Result<bool, AppError> {
+    sqlx::query("SELECT url_id FROM link_aliases WHERE token = $1")
+        .bind(token)
+        .fetch_optional(&mut *conn)
+        .await
+        .map(|row| row.is_some())
+        .map_err(database_error)
+}
+
+async fn load(
+    db: impl sqlx::Executor<'_, Database = sqlx::Any>,
+    url_id: &str,
+) -> Result<Vec<(String, DateTime<Utc>)>, AppError> {
+    let rows = sqlx::query("SELECT token, created_at FROM link_aliases WHERE url_id = $1 ORDER BY created_at, token")
+        .bind(url_id)
+        .fetch_all(db)
+        .await
+        .map_err(database_error)?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get("token"), storage::get_ts(row, "created_at")))
+        .collect())
+}
+
+fn tokens(aliases: &[(String, DateTime<Utc>)]) -> Vec<&str> {
+    aliases.iter().map(|(alias, _)| alias.as_str()).collect()
+}
+
+// Id and domain of a live link the caller may change
+async fn find_writable(conn: &mut AnyConnection, token: &str, caller: &Caller) -> Result<(String, Option<String>), AppError> {
+    let mut lookup = SqlBuilder::new("SELECT id, domain FROM urls WHERE token = ");
+    lookup.push_bind(token.to_string()).push(" AND deleted_at IS NULL");
+    push_owner_filter(&mut lookup, caller, Access::Write);
+    let row = lookup
+        .build()
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(database_error)?
+        .ok_or(AppError::UrlNotFound)?;
+    Ok((row.get("id"), row.get("domain")))
+}
+
+#[utoipa::path(
+    get,
+    path = "/urls/{token}/aliases",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 200, description = "Additional tokens redirecting to the link, oldest first", body = Vec<AliasInfo>),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+    ),
+)]
+pub async fn list_aliases(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    base: BaseUrl,
+) -> Result<impl IntoResponse, AppError> {
+    let row = sqlx::query("SELECT id, domain FROM urls WHERE token = $1 AND deleted_at IS NULL")
+        .bind(&token)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(database_error)?
+        .ok_or(AppError::UrlNotFound)?;
+    let domain: Option<String> = row.get("domain");
+
+    let aliases: Vec<AliasInfo> = load(&state.db, &row.get::<String, _>("id"))
+        .await?
+        .into_iter()
+        .map(|(alias, created_at)| AliasInfo {
+            short_url: base.short_url(domain.as_deref(), &alias),
+            alias,
+            created_at,
+        })
+        .collect();
+    Ok(Json(aliases))
+}
+
+#[utoipa::path(
+    post,
+    path = "/urls/{token}/aliases",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    request_body = CreateAliasRequest,
+    responses(
+        (status = 201, description = "Alias added, it redirects like the link and shares its statistics", body = AliasInfo),
+        (status = 400, description = "Invalid or reserved alias, or too many aliases", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+        (status = 409, description = "Token already in use", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn add_alias(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    base: BaseUrl,
+    caller: Caller,
+    Json(payload): Json<CreateAliasRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let alias = validate_alias(&state.config, &state.reserved, payload.alias)?;
+
+    let mut tx = state.db.begin().await.map_err(database_error)?;
+    let (url_id, domain) = find_writable(&mut tx, &token, &caller).await?;
+    let old = load(&mut *tx, &url_id).await?;
+    if old.len() >= MAX_ALIASES {
+        return Err(AppError::BadRequest(format!("A link can have at most {} aliases", MAX_ALIASES)));
+    }
+    // The primary key only covers other aliases, link tokens live in urls
+    let in_use = || AppError::Conflict(format!("Token {} is already in use", alias));
+    if token_exists(&mut tx, &alias).await? || folded_token_exists(&state, &mut tx, &alias).await? {
+        return Err(in_use());
+    }
+
+    let created_at = storage::now();
+    sqlx::query("INSERT INTO link_aliases (token, url_id, created_at) VALUES ($1, $2, $3)")
+        .bind(&alias)
+        .bind(&url_id)
+        .bind(storage::ts(created_at))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => in_use(),
+            _ => database_error(e),
+        })?;
+
+    let mut new = tokens(&old);
+    new.push(&alias);
+    let changes = audit::diff(&json!({ "aliases": tokens(&old) }), &json!({ "aliases": new }));
+    audit::record(&mut tx, &url_id, &token, AuditAction::Update, &caller, changes).await?;
+    tx.commit().await.map_err(database_error)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(AliasInfo {
+            short_url: base.short_url(domain.as_deref(), &alias),
+            alias,
+            created_at,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/urls/{token}/aliases/{alias}",
+    tag = "urls",
+    params(
+        ("token" = String, Path, description = "Short URL token"),
+        ("alias" = String, Path, description = "Alias to remove"),
+    ),
+    responses(
+        (status = 204, description = "Alias removed, it no longer redirects"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "URL or alias not found", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn delete_alias(
+    Path((token, alias)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = state.db.begin().await.map_err(database_error)?;
+    let (url_id, _) = find_writable(&mut tx, &token, &caller).await?;
+    let old = load(&mut *tx, &url_id).await?;
+
+    let removed = sqlx::query("DELETE FROM link_aliases WHERE token = $1 AND url_id = $2")
+        .bind(&alias)
+        .bind(&url_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(database_error)?
+        .rows_affected();
+    if removed == 0 {
+        return Err(AppError::NotFound("Alias not found".into()));
+    }
+
+    let new: Vec<&str> = tokens(&old).into_iter().filter(|kept| *kept != alias).collect();
+    let changes = audit::diff(&json!({ "aliases": tokens(&old) }), &json!({ "aliases": new }));
+    audit::record(&mut tx, &url_id, &token, AuditAction::Update, &caller, changes).await?;
+    tx.commit().await.map_err(database_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_alias_resolves_to_its_link() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            sqlx::query("INSERT INTO urls (id, token, original_url, created_at, click_count) VALUES ('u1', 'spring', 'https://example.com', '2024-05-01T00:00:00Z', 0)")
+                .execute(&db)
+                .await
+                .unwrap();
+            sqlx::query("INSERT INTO link_aliases (token, url_id, created_at) VALUES ('spring-sale', 'u1', '2024-05-02T00:00:00Z')")
+                .execute(&db)
+                .await
+                .unwrap();
+
+            assert_eq!(canonical_token(&db, "spring-sale").await.unwrap().as_deref(), Some("spring"));
+            assert_eq!(canonical_token(&db, "spring").await.unwrap(), None);
+
+            let mut conn = db.acquire().await.unwrap();
+            assert!(is_alias(&mut conn, "spring-sale").await.unwrap());
+            assert!(!is_alias(&mut conn, "spring").await.unwrap());
+            assert_eq!(tokens(&load(&mut *conn, "u1").await.unwrap()), ["spring-sale"]);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Json},
+};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use sqlx::{AnyConnection, Row};
+use std::sync::Arc;
+
+use crate::auth::Caller;
+use crate::models::AuditEntry;
+use crate::storage::{self, SqlBuilder};
+use crate::{push_owner_filter, Access, AppError, AppState};
+
+// Fields a change is recorded for, counters and derived values like short_url are left out
+const AUDITED_FIELDS: &[&str] = &[
+    "original_url",
+    "title",
+    "starts_at",
+    "expires_at",
+    "max_clicks",
+    "domain",
+    "org_id",
+    "campaign_id",
+    "forward_query",
+    "template",
+    "tags",
+    "notes",
+    "metadata",
+    "rules",
+    "sticky",
+    "variants",
+    "aliases",
+    "takedown",
+    "warned",
+    "is_active",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+            AuditAction::Restore => "restore",
+        }
+    }
+}
+
+fn audited_fields(link: &impl Serialize) -> Map<String, Value> {
+    let Ok(Value::Object(mut fields)) = serde_json::to_value(link) else {
+        return Map::new();
+    };
+    fields.retain(|key, _| AUDITED_FIELDS.contains(&key.as_str()));
+    fields
+}
+
+// Initial values of a new link
+pub fn snapshot(link: &impl Serialize) -> Value {
+    Value::Object(audited_fields(link))
+}
+
+// Old and new value of every audited field that differs between two versions of a link
+pub fn diff(old: &impl Serialize, new: &impl Serialize) -> Value {
+    let old = audited_fields(old);
+    let new = audited_fields(new);
+    let changes = AUDITED_FIELDS
+        .iter()
+        .filter(|field| old.get(**field) != new.get(**field))
+        .map(|field| {
+            let change = json!({ "old": old.get(*field), "new": new.get(*field) });
+            (field.to_string(), change)
+        })
+        .collect();
+    Value::Object(changes)
+}
+
+// Written in the same transaction as the change itself, so the log never misses or invents one
+pub async fn record(
+    conn: &mut AnyConnection,
+    url_id: &str,
+    token: &str,
+    action: AuditAction,
+    caller: &Caller,
+    changes: Value,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log (url_id, token, action, actor, changes, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#
+    )
+    .bind(url_id)
+    .bind(token)
+    .bind(action.as_str())
+    .bind(caller.actor())
+    .bind(changes.to_string())
+    .bind(storage::ts(storage::now()))
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/urls/{token}/history",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 200, description = "Changes to the link, oldest first", body = [AuditEntry]),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn get_history(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    // Actors identify keys and accounts, so the log is not public even for unowned links
+    if caller == Caller::Anonymous {
+        return Err(AppError::Unauthorized("Missing bearer token".into()));
+    }
+
+    // Deleted links keep their history until they are purged
+    let mut lookup = SqlBuilder::new("SELECT id FROM urls WHERE token = ");
+    lookup.push_bind(token);
+    push_owner_filter(&mut lookup, &caller, Access::Read);
+
+    let url_id: String = lookup
+        .build()
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .map(|row| row.get("id"))
+        .ok_or(AppError::UrlNotFound)?;
+
+    let entries = sqlx::query("SELECT * FROM audit_log WHERE url_id = $1 ORDER BY id")
+        .bind(&url_id)
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .iter()
+        .map(|row| AuditEntry {
+            id: row.get("id"),
+            action: row.get("action"),
+            actor: row.get("actor"),
+            changes: serde_json::from_str(&row.get::<String, _>("changes")).unwrap_or(Value::Null),
+            created_at: storage::get_ts(row, "created_at"),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Json(entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_only_lists_changed_fields() {
+        let old = json!({ "token": "abc", "original_url": "https://a.com", "title": null, "click_count": 1, "tags": [] });
+        let new = json!({ "token": "abc", "original_url": "https://b.com", "title": null, "click_count": 5, "tags": ["x"] });
+
+        assert_eq!(
+            diff(&old, &new),
+            json!({
+                "original_url": { "old": "https://a.com", "new": "https://b.com" },
+                "tags": { "old": [], "new": ["x"] },
+            })
+        );
+        assert_eq!(snapshot(&old), json!({ "original_url": "https://a.com", "title": null, "tags": [] }));
+    }
+}
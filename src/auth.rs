@@ -0,0 +1,558 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path, Request, State},
+    http::{header, request::Parts, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{any::AnyRow, AnyPool, Row};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::{ApiKeyInfo, CreateApiKeyRequest, CreateApiKeyResponse, RotateApiKeyRequest, RotateApiKeyResponse};
+use crate::storage;
+use crate::token::TokenGenerator;
+use crate::users;
+use crate::{AppError, AppState};
+
+const KEY_PREFIX: &str = "qk_";
+const KEY_LENGTH: usize = 32;
+const LAST_USED_PRECISION_SECS: i64 = 60;
+const DEFAULT_GRACE_PERIOD_SECS: i64 = 24 * 3600;
+const MAX_GRACE_PERIOD_SECS: i64 = 30 * 24 * 3600;
+
+// Only the hash of a key is persisted, the plain key is shown once on creation
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+// Who is making a request, resolved from the bearer credential
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Caller {
+    Anonymous,
+    Admin,
+    ApiKey(String),
+    User(String),
+}
+
+impl Caller {
+    // Only accounts own links, admin and API key callers act on the whole service
+    pub fn user_id(&self) -> Option<&str> {
+        match self {
+            Caller::User(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    // Recorded in the audit log, API keys and users are identified by id
+    pub fn actor(&self) -> String {
+        match self {
+            Caller::Anonymous => "anonymous".into(),
+            Caller::Admin => "admin".into(),
+            Caller::ApiKey(id) => format!("api_key:{}", id),
+            Caller::User(id) => format!("user:{}", id),
+        }
+    }
+}
+
+// What an API key or account may do. Read-only callers list links and read stats, editors also
+// create, change and delete them, admins also reach the admin routes like the admin key does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    ReadOnly,
+    Editor,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::ReadOnly => "read_only",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read_only" => Some(Role::ReadOnly),
+            "editor" => Some(Role::Editor),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn require(self, needed: Role) -> Result<(), AppError> {
+        if self < needed {
+            return Err(AppError::Forbidden(format!("Requires the {} role", needed.as_str())));
+        }
+        Ok(())
+    }
+}
+
+// Narrows what an API key may do within its role, keys without scopes may do all the role allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum Scope {
+    #[serde(rename = "links:read")]
+    LinksRead,
+    #[serde(rename = "links:write")]
+    LinksWrite,
+    #[serde(rename = "stats:read")]
+    StatsRead,
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::LinksRead => "links:read",
+            Scope::LinksWrite => "links:write",
+            Scope::StatsRead => "stats:read",
+            Scope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "links:read" => Some(Scope::LinksRead),
+            "links:write" => Some(Scope::LinksWrite),
+            "stats:read" => Some(Scope::StatsRead),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+
+    // Least role that grants the scope
+    fn role(self) -> Role {
+        match self {
+            Scope::LinksRead | Scope::StatsRead => Role::ReadOnly,
+            Scope::LinksWrite => Role::Editor,
+            Scope::Admin => Role::Admin,
+        }
+    }
+
+    // Reading needs links:read, anything else changes something
+    fn for_method(method: &Method) -> Scope {
+        match *method {
+            Method::GET | Method::HEAD | Method::OPTIONS => Scope::LinksRead,
+            _ => Scope::LinksWrite,
+        }
+    }
+}
+
+// Stored space separated, unknown names are dropped
+fn parse_scopes(value: &str) -> Vec<Scope> {
+    value.split_whitespace().filter_map(Scope::parse).collect()
+}
+
+// What a resolved credential may do: its role, narrowed by the key's scopes when it has any
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permissions {
+    pub role: Role,
+    pub scopes: Option<Vec<Scope>>,
+}
+
+impl Permissions {
+    fn of_role(role: Role) -> Self {
+        Self { role, scopes: None }
+    }
+
+    pub fn require(&self, scope: Scope) -> Result<(), AppError> {
+        self.role.require(scope.role())?;
+        if self.scopes.as_ref().is_some_and(|scopes| !scopes.contains(&scope)) {
+            return Err(AppError::Forbidden(format!("This key lacks the {} scope", scope.as_str())));
+        }
+        Ok(())
+    }
+}
+
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+fn new_key() -> String {
+    format!("{}{}", KEY_PREFIX, TokenGenerator::with_length(KEY_LENGTH).generate())
+}
+
+fn is_admin_key(state: &AppState, key: &str) -> bool {
+    state
+        .admin_key_hash
+        .as_deref()
+        .is_some_and(|admin_hash| admin_hash == hash_key(key))
+}
+
+async fn find_api_key(db: &AnyPool, key_hash: &str, now: DateTime<Utc>) -> Result<Option<AnyRow>, sqlx::Error> {
+    sqlx::query(
+        r#"
+        SELECT id, role, scopes, last_used_at FROM api_keys
+        WHERE key_hash = $1 OR (previous_key_hash = $1 AND previous_expires_at > $2)
+        "#
+    )
+    .bind(key_hash)
+    .bind(storage::ts(now))
+    .fetch_optional(db)
+    .await
+}
+
+// One statement, so the old secret is never both replaced and forgotten. A secret still in the
+// grace period of an earlier rotation stops working now. False when there is no such key.
+async fn replace_secret(db: &AnyPool, id: &str, key_hash: &str, previous_expires_at: DateTime<Utc>) -> Result<bool, sqlx::Error> {
+    let rotated = sqlx::query(
+        "UPDATE api_keys SET previous_key_hash = key_hash, previous_expires_at = $1, key_hash = $2 WHERE id = $3"
+    )
+    .bind(storage::ts(previous_expires_at))
+    .bind(key_hash)
+    .bind(id)
+    .execute(db)
+    .await?;
+    Ok(rotated.rows_affected() > 0)
+}
+
+// API keys carry our prefix, anything else that is not the admin key must be a user JWT.
+// Anonymous callers come back as read-only. A rotated key's old secret keeps working until its
+// grace period ends.
+pub async fn resolve_caller(state: &AppState, key: Option<&str>) -> Result<(Caller, Permissions), AppError> {
+    let database_error = |e: sqlx::Error| AppError::DatabaseError(e.to_string());
+    let Some(key) = key else {
+        return Ok((Caller::Anonymous, Permissions::of_role(Role::ReadOnly)));
+    };
+    if is_admin_key(state, key) {
+        return Ok((Caller::Admin, Permissions::of_role(Role::Admin)));
+    }
+    if !key.starts_with(KEY_PREFIX) {
+        let user_id = users::verify_token(&state.jwt_secret, key)
+            .ok_or_else(|| AppError::Unauthorized("Invalid or expired token".into()))?;
+        // Looked up on every request, so a changed role applies to tokens issued before
+        let row = sqlx::query("SELECT role FROM users WHERE id = $1")
+            .bind(&user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(database_error)?
+            .ok_or_else(|| AppError::Unauthorized("Account no longer exists".into()))?;
+        let role = Role::parse(&row.get::<String, _>("role")).unwrap_or(Role::ReadOnly);
+        return Ok((Caller::User(user_id), Permissions::of_role(role)));
+    }
+
+    let now = storage::now();
+    let row = find_api_key(&state.db, &hash_key(key), now)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(|| AppError::Unauthorized("Invalid API key".into()))?;
+
+    let id: String = row.get("id");
+    // Kept to the minute, so busy keys are not written on every request
+    let stale = storage::get_opt_ts(&row, "last_used_at")
+        .is_none_or(|used| now - used >= chrono::Duration::seconds(LAST_USED_PRECISION_SECS));
+    if stale {
+        sqlx::query("UPDATE api_keys SET last_used_at = $1 WHERE id = $2")
+            .bind(storage::ts(now))
+            .bind(&id)
+            .execute(&state.db)
+            .await
+            .map_err(database_error)?;
+    }
+    let permissions = Permissions {
+        role: Role::parse(&row.get::<String, _>("role")).unwrap_or(Role::ReadOnly),
+        scopes: row.get::<Option<String>, _>("scopes").as_deref().map(parse_scopes),
+    };
+    Ok((Caller::ApiKey(id), permissions))
+}
+
+// Public routes resolve the caller on demand, routes behind require_api_key reuse its result
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for Caller {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, AppError> {
+        if let Some(caller) = parts.extensions.get::<Caller>() {
+            return Ok(caller.clone());
+        }
+        Ok(resolve_caller(state, bearer_token(&parts.headers)).await?.0)
+    }
+}
+
+// The caller the rate limiter already resolved, or the request's own credential
+async fn request_caller(state: &AppState, req: &mut Request) -> Result<(Caller, Permissions), AppError> {
+    let extensions = req.extensions();
+    if let (Some(caller), Some(permissions)) = (extensions.get::<Caller>(), extensions.get::<Permissions>()) {
+        return Ok((caller.clone(), permissions.clone()));
+    }
+    resolve_caller(state, bearer_token(req.headers())).await
+}
+
+pub async fn require_api_key(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (caller, permissions) = request_caller(&state, &mut req).await?;
+    if caller == Caller::Anonymous {
+        return Err(AppError::Unauthorized("Missing bearer token".into()));
+    }
+
+    req.extensions_mut().insert(caller);
+    req.extensions_mut().insert(permissions);
+    Ok(next.run(req).await)
+}
+
+// Runs inside require_api_key. Read requests need links:read, the others links:write.
+pub async fn enforce_role(req: Request, next: Next) -> Result<Response, AppError> {
+    let permissions = req
+        .extensions()
+        .get::<Permissions>()
+        .cloned()
+        .unwrap_or(Permissions::of_role(Role::ReadOnly));
+    permissions.require(Scope::for_method(req.method()))?;
+    Ok(next.run(req).await)
+}
+
+// Scopes narrow what credentials may do, anonymous callers have none to narrow. Once an admin key
+// puts access control in place, stats are no longer for anonymous callers; link lookups stay public.
+pub fn require_anonymous_scope(state: &AppState, caller: &Caller, scope: Scope) -> Result<(), AppError> {
+    if *caller == Caller::Anonymous && scope == Scope::StatsRead && state.admin_key_hash.is_some() {
+        return Err(AppError::Unauthorized("Stats need an API key or account".into()));
+    }
+    Ok(())
+}
+
+// For public routes: anonymous callers pass unless require_anonymous_scope refuses them, a
+// credential needs the scope
+pub async fn require_scope(
+    State((state, scope)): State<(Arc<AppState>, Scope)>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (caller, permissions) = request_caller(&state, &mut req).await?;
+    require_anonymous_scope(&state, &caller, scope)?;
+    permissions.require(scope)?;
+
+    req.extensions_mut().insert(caller);
+    req.extensions_mut().insert(permissions);
+    Ok(next.run(req).await)
+}
+
+// The admin key, or an API key or account with the admin role
+pub async fn require_admin_key(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let key = bearer_token(req.headers())
+        .ok_or_else(|| AppError::Unauthorized("Missing bearer token".into()))?;
+
+    let (caller, permissions) = resolve_caller(&state, Some(key)).await?;
+    if permissions.role != Role::Admin {
+        return Err(AppError::Forbidden("Admin key required".into()));
+    }
+    permissions.require(Scope::Admin)?;
+
+    req.extensions_mut().insert(caller);
+    Ok(next.run(req).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/keys",
+    tag = "admin",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "API key created, the key is only shown once", body = CreateApiKeyResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn create_api_key(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if payload.name.trim().is_empty() {
+        return Err(AppError::BadRequest("Key name must not be empty".into()));
+    }
+
+    let role = payload.role.unwrap_or(Role::Editor);
+    if let Some(scopes) = &payload.scopes {
+        if scopes.is_empty() {
+            return Err(AppError::BadRequest("scopes must not be empty, leave them out for all the role allows".into()));
+        }
+        if let Some(scope) = scopes.iter().find(|scope| role < scope.role()) {
+            return Err(AppError::BadRequest(format!("{} needs the {} role", scope.as_str(), scope.role().as_str())));
+        }
+    }
+    let scopes_text = payload
+        .scopes
+        .as_ref()
+        .map(|scopes| scopes.iter().map(|scope| scope.as_str()).collect::<Vec<_>>().join(" "));
+    let id = Uuid::new_v4().to_string();
+    let key = new_key();
+    let created_at = Utc::now();
+
+    sqlx::query(
+        r#"
+        INSERT INTO api_keys (id, name, key_hash, created_at, role, scopes)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#
+    )
+    .bind(&id)
+    .bind(&payload.name)
+    .bind(hash_key(&key))
+    .bind(storage::ts(created_at))
+    .bind(role.as_str())
+    .bind(scopes_text)
+    .execute(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let response = CreateApiKeyResponse {
+        id,
+        name: payload.name,
+        key,
+        role,
+        scopes: payload.scopes,
+        created_at,
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/keys",
+    tag = "admin",
+    responses(
+        (status = 200, description = "All API keys, without their secrets", body = [ApiKeyInfo]),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn list_api_keys(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let now = storage::now();
+    let rows = sqlx::query("SELECT * FROM api_keys ORDER BY created_at, id")
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let keys: Vec<ApiKeyInfo> = rows
+        .iter()
+        .map(|row| ApiKeyInfo {
+            id: row.get("id"),
+            name: row.get("name"),
+            role: Role::parse(&row.get::<String, _>("role")).unwrap_or(Role::ReadOnly),
+            scopes: row.get::<Option<String>, _>("scopes").as_deref().map(parse_scopes),
+            created_at: storage::get_ts(row, "created_at"),
+            last_used_at: storage::get_opt_ts(row, "last_used_at"),
+            previous_key_expires_at: storage::get_opt_ts(row, "previous_expires_at").filter(|expires_at| *expires_at > now),
+        })
+        .collect();
+    Ok(Json(keys))
+}
+
+#[utoipa::path(
+    post,
+    path = "/keys/{id}/rotate",
+    tag = "admin",
+    params(("id" = String, Path, description = "API key id")),
+    request_body = Option<RotateApiKeyRequest>,
+    responses(
+        (status = 200, description = "New secret, only shown once. The old one works until previous_key_expires_at.", body = RotateApiKeyResponse),
+        (status = 400, description = "Invalid grace period", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+        (status = 404, description = "API key not found", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn rotate_api_key(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    payload: Option<Json<RotateApiKeyRequest>>,
+) -> Result<impl IntoResponse, AppError> {
+    let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+    let grace_period_secs = payload.grace_period_secs.unwrap_or(DEFAULT_GRACE_PERIOD_SECS);
+    if !(0..=MAX_GRACE_PERIOD_SECS).contains(&grace_period_secs) {
+        return Err(AppError::BadRequest(format!("grace_period_secs must be between 0 and {}", MAX_GRACE_PERIOD_SECS)));
+    }
+
+    let key = new_key();
+    let previous_key_expires_at = storage::now() + chrono::Duration::seconds(grace_period_secs);
+    let rotated = replace_secret(&state.db, &id, &hash_key(&key), previous_key_expires_at)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if !rotated {
+        return Err(AppError::NotFound("API key not found".into()));
+    }
+
+    Ok(Json(RotateApiKeyResponse { id, key, previous_key_expires_at }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_key_is_stable() {
+        assert_eq!(hash_key("qk_secret"), hash_key("qk_secret"));
+        assert_ne!(hash_key("qk_secret"), hash_key("qk_other"));
+        assert_eq!(hash_key("qk_secret").len(), 64);
+    }
+
+    #[test]
+    fn test_scopes_narrow_roles() {
+        let editor = Permissions::of_role(Role::Editor);
+        assert!(editor.require(Scope::for_method(&Method::GET)).is_ok());
+        assert!(editor.require(Scope::for_method(&Method::DELETE)).is_ok());
+        assert!(matches!(editor.require(Scope::Admin), Err(AppError::Forbidden(_))));
+        let read_only = Permissions::of_role(Role::ReadOnly);
+        assert!(read_only.require(Scope::StatsRead).is_ok() && read_only.require(Scope::LinksWrite).is_err());
+
+        let stats_only = Permissions { role: Role::Admin, scopes: Some(parse_scopes("stats:read bogus")) };
+        assert_eq!(stats_only.scopes, Some(vec![Scope::StatsRead]));
+        assert!(stats_only.require(Scope::StatsRead).is_ok());
+        assert!(stats_only.require(Scope::LinksRead).is_err() && stats_only.require(Scope::Admin).is_err());
+        for role in [Role::ReadOnly, Role::Editor, Role::Admin] {
+            assert_eq!(Role::parse(role.as_str()), Some(role));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotated_key_keeps_grace_period() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let now = storage::now();
+            sqlx::query("INSERT INTO api_keys (id, name, key_hash, created_at) VALUES ('k1', 'ci', $1, $2)")
+                .bind(hash_key("qk_old"))
+                .bind(storage::ts(now))
+                .execute(&db)
+                .await
+                .unwrap();
+            assert!(replace_secret(&db, "k1", &hash_key("qk_new"), now + chrono::Duration::seconds(60)).await.unwrap());
+            assert!(!replace_secret(&db, "k2", &hash_key("qk_other"), now).await.unwrap());
+
+            let finds = |key: &str, at| {
+                let db = db.clone();
+                let key_hash = hash_key(key);
+                async move { find_api_key(&db, &key_hash, at).await.unwrap().is_some() }
+            };
+            assert!(finds("qk_new", now).await && finds("qk_old", now).await);
+            let later = now + chrono::Duration::seconds(61);
+            assert!(finds("qk_new", later).await && !finds("qk_old", later).await);
+
+            // Rotating again ends the previous grace period at once
+            assert!(replace_secret(&db, "k1", &hash_key("qk_newest"), now + chrono::Duration::seconds(60)).await.unwrap());
+            assert!(!finds("qk_old", now).await && finds("qk_new", now).await);
+        }
+    }
+}
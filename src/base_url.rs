@@ -0,0 +1,109 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap},
+};
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::{AppError, AppState};
+
+const FORWARDED_HOST: &str = "x-forwarded-host";
+const FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+fn valid_host(host: &str) -> bool {
+    !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'))
+}
+
+// Origin the short URLs of a response are built on: base_url, or with trust_forwarded_headers
+// the scheme and host the client used to reach the proxy in front of us
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaseUrl(String);
+
+impl BaseUrl {
+    pub fn configured(config: &Config) -> Self {
+        Self(config.base_url.trim_end_matches('/').to_string())
+    }
+
+    // Path prefixes of base_url are kept, only scheme and host come from the request
+    pub fn from_headers(config: &Config, headers: &HeaderMap) -> Self {
+        let configured = Self::configured(config);
+        if !config.trust_forwarded_headers {
+            return configured;
+        }
+
+        // Proxies append to these, the first value is what the client sent
+        let first = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        let Some(host) = first(FORWARDED_HOST)
+            .or_else(|| first(header::HOST.as_str()))
+            .filter(|host| valid_host(host))
+        else {
+            return configured;
+        };
+        let scheme = first(FORWARDED_PROTO)
+            .filter(|proto| matches!(*proto, "http" | "https"))
+            .unwrap_or(configured.scheme());
+        let rest = configured.0.split_once("://").map_or("", |(_, rest)| rest);
+        let path = rest.find('/').map_or("", |start| &rest[start..]);
+        Self(format!("{}://{}{}", scheme, host, path))
+    }
+
+    fn scheme(&self) -> &str {
+        self.0.split_once("://").map_or("https", |(scheme, _)| scheme)
+    }
+
+    // Links on a custom domain keep the scheme but use the domain as host
+    pub fn short_url(&self, domain: Option<&str>, token: &str) -> String {
+        match domain {
+            Some(domain) => format!("{}://{}/{}", self.scheme(), domain, token),
+            None => format!("{}/{}", self.0, token),
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for BaseUrl {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, AppError> {
+        Ok(Self::from_headers(&state.config, &parts.headers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_forwarded_headers_only_when_trusted() {
+        let mut config = Config::from_toml(r#"base_url = "http://localhost:3000/go/""#).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("internal:3000"));
+        headers.insert(FORWARDED_HOST, HeaderValue::from_static("qurl.example.com, proxy.local"));
+        headers.insert(FORWARDED_PROTO, HeaderValue::from_static("https"));
+
+        let base = BaseUrl::from_headers(&config, &headers);
+        assert_eq!(base.short_url(None, "abc123"), "http://localhost:3000/go/abc123");
+
+        config.trust_forwarded_headers = true;
+        let base = BaseUrl::from_headers(&config, &headers);
+        assert_eq!(base.short_url(None, "abc123"), "https://qurl.example.com/go/abc123");
+        assert_eq!(base.short_url(Some("go.example.org"), "abc123"), "https://go.example.org/abc123");
+
+        headers.insert(FORWARDED_HOST, HeaderValue::from_static("evil.example/<script>"));
+        headers.insert(FORWARDED_PROTO, HeaderValue::from_static("javascript"));
+        let base = BaseUrl::from_headers(&config, &headers);
+        assert_eq!(base, BaseUrl::configured(&config));
+    }
+}
@@ -0,0 +1,539 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use sqlx::{any::AnyRow, AnyConnection, Row};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::Caller;
+use crate::base_url::BaseUrl;
+use crate::models::{BioPageInfo, BioPageLinkInfo, BioPageLinkRequest, CreateBioPageRequest, UpdateBioPageRequest};
+use crate::preview::escape_html;
+use crate::storage::{self, SqlBuilder};
+use crate::{push_owner_filter, Access, AppError, AppState};
+use quickurl_core::resolver::{self, Schedule};
+
+const MIN_SLUG_LENGTH: usize = 2;
+const MAX_SLUG_LENGTH: usize = 40;
+const MAX_TITLE_LENGTH: usize = 100;
+const MAX_DESCRIPTION_LENGTH: usize = 500;
+const MAX_LINKS: usize = 50;
+const MAX_ICON_LENGTH: usize = 8;
+const MAX_ICON_URL_LENGTH: usize = 2048;
+
+fn database_error(e: sqlx::Error) -> AppError {
+    AppError::DatabaseError(e.to_string())
+}
+
+fn page_url(base: &BaseUrl, slug: &str) -> String {
+    base.short_url(None, &format!("@{}", slug))
+}
+
+fn validate_slug(slug: &str) -> Result<String, AppError> {
+    let slug = slug.trim().to_ascii_lowercase();
+    let valid = (MIN_SLUG_LENGTH..=MAX_SLUG_LENGTH).contains(&slug.len())
+        && slug.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(AppError::BadRequest(format!(
+            "slug must be {} to {} letters, digits, - or _",
+            MIN_SLUG_LENGTH, MAX_SLUG_LENGTH
+        )));
+    }
+    Ok(slug)
+}
+
+fn validate_title(title: &str) -> Result<String, AppError> {
+    let title = title.trim();
+    if title.is_empty() || title.chars().count() > MAX_TITLE_LENGTH {
+        return Err(AppError::BadRequest(format!("title must be 1 to {} characters", MAX_TITLE_LENGTH)));
+    }
+    Ok(title.to_string())
+}
+
+fn validate_description(description: Option<String>) -> Result<Option<String>, AppError> {
+    let description = description.map(|description| description.trim().to_string()).filter(|d| !d.is_empty());
+    if description.as_ref().is_some_and(|description| description.chars().count() > MAX_DESCRIPTION_LENGTH) {
+        return Err(AppError::BadRequest(format!("description must be at most {} characters", MAX_DESCRIPTION_LENGTH)));
+    }
+    Ok(description)
+}
+
+fn is_image_url(icon: &str) -> bool {
+    icon.starts_with("http://") || icon.starts_with("https://")
+}
+
+fn validate_icon(icon: Option<String>) -> Result<Option<String>, AppError> {
+    let Some(icon) = icon.map(|icon| icon.trim().to_string()).filter(|icon| !icon.is_empty()) else {
+        return Ok(None);
+    };
+    let valid = if is_image_url(&icon) {
+        icon.len() <= MAX_ICON_URL_LENGTH && url::Url::parse(&icon).is_ok()
+    } else {
+        icon.chars().count() <= MAX_ICON_LENGTH
+    };
+    if !valid {
+        return Err(AppError::BadRequest(format!(
+            "icon must be at most {} characters or an http(s) image URL",
+            MAX_ICON_LENGTH
+        )));
+    }
+    Ok(Some(icon))
+}
+
+// Accounts see the pages they created, API keys and the admin key see every page
+async fn find_page(conn: &mut AnyConnection, slug: &str, caller: &Caller) -> Result<AnyRow, AppError> {
+    let not_found = || AppError::NotFound("Page not found".into());
+    if *caller == Caller::Anonymous {
+        return Err(not_found());
+    }
+    let mut query = SqlBuilder::new("SELECT * FROM bio_pages WHERE slug = ");
+    query.push_bind(slug.to_lowercase());
+    if let Some(user_id) = caller.user_id() {
+        query.push(" AND user_id = ").push_bind(user_id.to_string());
+    }
+    query.build().fetch_optional(&mut *conn).await.map_err(database_error)?.ok_or_else(not_found)
+}
+
+// Links go on a page by token and only if the caller may see them
+async fn put_links(
+    conn: &mut AnyConnection,
+    page_id: &str,
+    links: Vec<BioPageLinkRequest>,
+    caller: &Caller,
+) -> Result<(), AppError> {
+    if links.len() > MAX_LINKS {
+        return Err(AppError::BadRequest(format!("A page can have at most {} links", MAX_LINKS)));
+    }
+    let mut resolved = Vec::with_capacity(links.len());
+    for link in links {
+        let mut lookup = SqlBuilder::new("SELECT id FROM urls WHERE token = ");
+        lookup.push_bind(link.token.clone()).push(" AND deleted_at IS NULL");
+        push_owner_filter(&mut lookup, caller, Access::Read);
+        let row = lookup
+            .build()
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(database_error)?
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown link: {}", link.token)))?;
+        let title = link.title.map(|title| validate_title(&title)).transpose()?;
+        resolved.push((row.get::<String, _>("id"), title, validate_icon(link.icon)?));
+    }
+
+    sqlx::query("DELETE FROM bio_page_links WHERE page_id = $1")
+        .bind(page_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(database_error)?;
+    for (position, (url_id, title, icon)) in resolved.into_iter().enumerate() {
+        sqlx::query("INSERT INTO bio_page_links (page_id, position, url_id, title, icon) VALUES ($1, $2, $3, $4, $5)")
+            .bind(page_id)
+            .bind(position as i64)
+            .bind(url_id)
+            .bind(title)
+            .bind(icon)
+            .execute(&mut *conn)
+            .await
+            .map_err(database_error)?;
+    }
+    Ok(())
+}
+
+// In page order, with what a visitor needs to tell whether each link still works
+async fn page_links(db: impl sqlx::Executor<'_, Database = sqlx::Any>, page_id: &str) -> Result<Vec<AnyRow>, AppError> {
+    sqlx::query(
+        r#"
+        SELECT l.title, l.icon, u.token, u.domain, u.title AS link_title, u.starts_at, u.expires_at,
+            u.takedown, u.is_active, u.deleted_at
+        FROM bio_page_links l JOIN urls u ON u.id = l.url_id
+        WHERE l.page_id = $1
+        ORDER BY l.position
+        "#,
+    )
+    .bind(page_id)
+    .fetch_all(db)
+    .await
+    .map_err(database_error)
+}
+
+fn link_info(base: &BaseUrl, row: &AnyRow) -> BioPageLinkInfo {
+    let token: String = row.get("token");
+    let domain: Option<String> = row.get("domain");
+    BioPageLinkInfo {
+        short_url: base.short_url(domain.as_deref(), &token),
+        token,
+        title: row.get("title"),
+        icon: row.get("icon"),
+    }
+}
+
+async fn page_info(conn: &mut AnyConnection, base: &BaseUrl, page: &AnyRow) -> Result<BioPageInfo, AppError> {
+    let id: String = page.get("id");
+    let slug: String = page.get("slug");
+    let links = page_links(&mut *conn, &id).await?;
+    Ok(BioPageInfo {
+        page_url: page_url(base, &slug),
+        slug,
+        title: page.get("title"),
+        description: page.get("description"),
+        links: links.iter().map(|row| link_info(base, row)).collect(),
+        created_at: storage::get_ts(page, "created_at"),
+        updated_at: storage::get_ts(page, "updated_at"),
+        id,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/pages",
+    tag = "pages",
+    request_body = CreateBioPageRequest,
+    responses(
+        (status = 201, description = "Page created", body = BioPageInfo),
+        (status = 400, description = "Invalid slug, title or links", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 409, description = "Slug already taken", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn create_page(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+    Json(payload): Json<CreateBioPageRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let slug = validate_slug(&payload.slug)?;
+    let title = validate_title(&payload.title)?;
+    let description = validate_description(payload.description)?;
+
+    let id = Uuid::new_v4().to_string();
+    let now = storage::ts(storage::now());
+    let mut tx = state.db.begin().await.map_err(database_error)?;
+    sqlx::query(
+        "INSERT INTO bio_pages (id, slug, title, description, user_id, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $6)",
+    )
+    .bind(&id)
+    .bind(&slug)
+    .bind(&title)
+    .bind(&description)
+    .bind(caller.user_id())
+    .bind(&now)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db_error) if db_error.is_unique_violation() => AppError::Conflict("Slug already taken".into()),
+        _ => database_error(e),
+    })?;
+    put_links(&mut tx, &id, payload.links, &caller).await?;
+
+    let page = find_page(&mut tx, &slug, &caller).await?;
+    let info = page_info(&mut tx, &base, &page).await?;
+    tx.commit().await.map_err(database_error)?;
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/pages",
+    tag = "pages",
+    responses(
+        (status = 200, description = "Pages of the caller's account, every one for API keys and the admin key", body = [BioPageInfo]),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn list_pages(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+) -> Result<impl IntoResponse, AppError> {
+    let mut query = SqlBuilder::new("SELECT * FROM bio_pages");
+    if let Some(user_id) = caller.user_id() {
+        query.push(" WHERE user_id = ").push_bind(user_id.to_string());
+    }
+    query.push(" ORDER BY slug");
+    let mut conn = state.db.acquire().await.map_err(database_error)?;
+    let rows = query.build().fetch_all(&mut *conn).await.map_err(database_error)?;
+    let mut pages = Vec::with_capacity(rows.len());
+    for row in &rows {
+        pages.push(page_info(&mut conn, &base, row).await?);
+    }
+    Ok(Json(pages))
+}
+
+#[utoipa::path(
+    get,
+    path = "/pages/{slug}",
+    tag = "pages",
+    params(("slug" = String, Path, description = "Page slug")),
+    responses(
+        (status = 200, description = "Page details", body = BioPageInfo),
+        (status = 404, description = "Page not found", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn get_page(
+    Path(slug): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = state.db.acquire().await.map_err(database_error)?;
+    let page = find_page(&mut conn, &slug, &caller).await?;
+    Ok(Json(page_info(&mut conn, &base, &page).await?))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/pages/{slug}",
+    tag = "pages",
+    params(("slug" = String, Path, description = "Page slug")),
+    request_body = UpdateBioPageRequest,
+    responses(
+        (status = 200, description = "Page updated", body = BioPageInfo),
+        (status = 400, description = "Invalid title or links", body = ErrorResponse),
+        (status = 404, description = "Page not found", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn update_page(
+    Path(slug): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+    Json(payload): Json<UpdateBioPageRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = state.db.begin().await.map_err(database_error)?;
+    let page = find_page(&mut tx, &slug, &caller).await?;
+    let id: String = page.get("id");
+
+    let title = payload.title.as_deref().map(validate_title).transpose()?;
+    let description = payload.description.map(validate_description).transpose()?;
+    let mut update = SqlBuilder::new("UPDATE bio_pages SET updated_at = ");
+    update.push_bind(storage::ts(storage::now()));
+    if let Some(title) = title {
+        update.push(", title = ").push_bind(title);
+    }
+    if let Some(description) = description {
+        update.push(", description = ").push_bind(description);
+    }
+    update.push(" WHERE id = ").push_bind(id.clone());
+    update.build().execute(&mut *tx).await.map_err(database_error)?;
+    if let Some(links) = payload.links {
+        put_links(&mut tx, &id, links, &caller).await?;
+    }
+
+    let page = find_page(&mut tx, &slug, &caller).await?;
+    let info = page_info(&mut tx, &base, &page).await?;
+    tx.commit().await.map_err(database_error)?;
+    Ok(Json(info))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/pages/{slug}",
+    tag = "pages",
+    params(("slug" = String, Path, description = "Page slug")),
+    responses(
+        (status = 204, description = "Page deleted, its links stay"),
+        (status = 404, description = "Page not found", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn delete_page(
+    Path(slug): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = state.db.acquire().await.map_err(database_error)?;
+    let page = find_page(&mut conn, &slug, &caller).await?;
+    sqlx::query("DELETE FROM bio_pages WHERE id = $1")
+        .bind(page.get::<String, _>("id"))
+        .execute(&mut *conn)
+        .await
+        .map_err(database_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Links that would not redirect right now are left out, they come back once they work again
+fn is_live(row: &AnyRow) -> bool {
+    let schedule = resolver::schedule(
+        storage::get_opt_ts(row, "starts_at"),
+        storage::get_opt_ts(row, "expires_at"),
+        chrono::Utc::now(),
+    );
+    schedule == Schedule::Live
+        && row.get::<Option<String>, _>("deleted_at").is_none()
+        && row.get::<Option<String>, _>("takedown").is_none()
+        && row.get::<i64, _>("is_active") != 0
+}
+
+struct Entry {
+    short_url: String,
+    title: String,
+    icon: Option<String>,
+}
+
+fn render(title: &str, description: Option<&str>, entries: &[Entry]) -> String {
+    let description_html = description
+        .map(|description| format!("<p class=\"description\">{}</p>", escape_html(description)))
+        .unwrap_or_default();
+    let links: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let icon = match entry.icon.as_deref() {
+                Some(icon) if is_image_url(icon) => format!(r#"<img src="{}" alt="">"#, escape_html(icon)),
+                Some(icon) => format!("<span>{}</span>", escape_html(icon)),
+                None => String::new(),
+            };
+            format!(
+                r#"<li><a href="{}">{}{}</a></li>"#,
+                escape_html(&entry.short_url),
+                icon,
+                escape_html(&entry.title)
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>{title}</title>
+  <meta property="og:type" content="website">
+  <meta property="og:title" content="{title}">
+  <meta property="og:description" content="{og_description}">
+  <style>
+    body {{ font-family: system-ui, sans-serif; max-width: 30rem; margin: 3rem auto; padding: 0 1rem; color: #222; text-align: center; }}
+    .description {{ color: #555; }}
+    ul {{ list-style: none; padding: 0; margin: 2rem 0; }}
+    li a {{ display: flex; align-items: center; justify-content: center; gap: 0.5rem; margin: 0.75rem 0; padding: 0.9rem 1rem; border: 1px solid #ddd; border-radius: 0.5rem; color: inherit; text-decoration: none; }}
+    li a:hover {{ background: #f4f4f4; }}
+    li img {{ width: 1.5rem; height: 1.5rem; object-fit: cover; border-radius: 0.25rem; }}
+  </style>
+</head>
+<body>
+  <h1>{title}</h1>
+  {description}
+  <ul>
+    {links}
+  </ul>
+</body>
+</html>
+"#,
+        title = escape_html(title),
+        og_description = escape_html(description.unwrap_or_default()),
+        description = description_html,
+        links = links.join("\n    "),
+    )
+}
+
+// GET /@slug, public like the short links on it
+pub async fn show_page(state: &AppState, slug: &str, headers: &HeaderMap) -> Result<Response, AppError> {
+    let page = sqlx::query("SELECT id, title, description FROM bio_pages WHERE slug = $1")
+        .bind(slug.to_lowercase())
+        .fetch_optional(&state.db)
+        .await
+        .map_err(database_error)?
+        .ok_or(AppError::UrlNotFound)?;
+    let base = BaseUrl::from_headers(&state.config, headers);
+    let entries: Vec<Entry> = page_links(&state.db, &page.get::<String, _>("id"))
+        .await?
+        .iter()
+        .filter(|row| is_live(row))
+        .map(|row| {
+            let info = link_info(&base, row);
+            Entry {
+                title: info.title.or_else(|| row.get("link_title")).unwrap_or_else(|| info.short_url.clone()),
+                short_url: info.short_url,
+                icon: info.icon,
+            }
+        })
+        .collect();
+
+    let title: String = page.get("title");
+    let description: Option<String> = page.get("description");
+    // Edits show up at once, so caches have to check back
+    Ok((
+        [(header::CONTENT_TYPE, "text/html; charset=utf-8"), (header::CACHE_CONTROL, "no-cache")],
+        render(&title, description.as_deref(), &entries),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::AnyPool;
+
+    async fn insert_link(db: &AnyPool, id: &str, user_id: &str) {
+        sqlx::query("INSERT INTO urls (id, token, original_url, user_id, created_at, updated_at, click_count) VALUES ($1, $1, 'https://example.com', $2, $3, $3, 0)")
+            .bind(id)
+            .bind(user_id)
+            .bind(storage::ts(storage::now()))
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_page_links_follow_visibility_and_order() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            sqlx::query("INSERT INTO users (id, email, password_hash, created_at) VALUES ('owner', 'owner', 'x', $1)")
+                .bind(storage::ts(storage::now()))
+                .execute(&db)
+                .await
+                .unwrap();
+            for id in ["mine", "open", "gone"] {
+                insert_link(&db, id, "owner").await;
+            }
+            sqlx::query("INSERT INTO bio_pages (id, slug, title, user_id, created_at, updated_at) VALUES ('p1', 'me', 'Me', 'owner', $1, $1)")
+                .bind(storage::ts(storage::now()))
+                .execute(&db)
+                .await
+                .unwrap();
+
+            let owner = Caller::User("owner".into());
+            let link = |token: &str, icon: Option<&str>| BioPageLinkRequest {
+                token: token.into(),
+                title: None,
+                icon: icon.map(String::from),
+            };
+            let mut conn = db.acquire().await.unwrap();
+            let forbidden = put_links(&mut conn, "p1", vec![link("mine", None)], &Caller::Anonymous).await;
+            assert!(matches!(forbidden, Err(AppError::BadRequest(_))));
+            let icon = put_links(&mut conn, "p1", vec![link("open", Some("ftp is not an icon"))], &owner).await;
+            assert!(matches!(icon, Err(AppError::BadRequest(_))));
+
+            let links = vec![link("open", Some("🎵")), link("mine", Some("https://example.com/i.png")), link("gone", None)];
+            put_links(&mut conn, "p1", links, &owner).await.unwrap();
+            sqlx::query("UPDATE urls SET is_active = 0 WHERE id = 'gone'").execute(&mut *conn).await.unwrap();
+
+            let rows = page_links(&mut *conn, "p1").await.unwrap();
+            let tokens: Vec<String> = rows.iter().map(|row| row.get("token")).collect();
+            assert_eq!(tokens, ["open", "mine", "gone"]);
+            assert_eq!(rows.iter().filter(|row| is_live(row)).count(), 2);
+            assert!(find_page(&mut conn, "ME", &owner).await.is_ok());
+            assert!(matches!(find_page(&mut conn, "me", &Caller::User("other".into())).await, Err(AppError::NotFound(_))));
+
+            assert_eq!(validate_slug(" My_Page ").unwrap(), "my_page");
+            assert!(validate_slug("a").is_err());
+            assert!(validate_slug("no/slash").is_err());
+        }
+    }
+
+    #[test]
+    fn test_render_escapes_page_fields() {
+        let entries = [Entry {
+            short_url: "https://qurl.example/abc".into(),
+            title: "<b>Shop</b>".into(),
+            icon: Some("https://example.com/i.png?a=1&b=2".into()),
+        }];
+        let page = render("Me & you", Some("\"quoted\""), &entries);
+        assert!(page.contains("<h1>Me &amp; you</h1>"));
+        assert!(page.contains("&quot;quoted&quot;"));
+        assert!(page.contains(r#"<a href="https://qurl.example/abc"><img src="https://example.com/i.png?a=1&amp;b=2" alt="">&lt;b&gt;Shop&lt;/b&gt;</a>"#));
+    }
+}
@@ -0,0 +1,54 @@
+// Lowercase substrings of crawler and link preview user agents. Most announce themselves with
+// "bot", the rest are the unfurlers and fetchers that do not.
+const MARKERS: &[&str] = &[
+    "bot",
+    "crawler",
+    "spider",
+    "slurp",
+    "facebookexternalhit",
+    "facebookcatalog",
+    "embedly",
+    "iframely",
+    "whatsapp",
+    "skypeuripreview",
+    "bitlypreview",
+    "vkshare",
+    "pinterest",
+    "redditbot",
+    "headlesschrome",
+    "lighthouse",
+    "python-requests",
+    "go-http-client",
+];
+
+// Substring checks like Device::detect, good enough to keep previews and crawlers out of the
+// counts. Requests without a user agent are counted, curl and the like always send one.
+pub fn is_bot(user_agent: Option<&str>) -> bool {
+    let Some(user_agent) = user_agent else {
+        return false;
+    };
+    let user_agent = user_agent.to_ascii_lowercase();
+    MARKERS.iter().any(|marker| user_agent.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_bots() {
+        let slack = "Slackbot-LinkExpanding 1.0 (+https://api.slack.com/robots)";
+        let facebook = "facebookexternalhit/1.1 (+http://www.facebook.com/externalhit_uatext.php)";
+        let google = "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)";
+        let whatsapp = "WhatsApp/2.23.20.0";
+        let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+
+        assert!(is_bot(Some(slack)));
+        assert!(is_bot(Some(facebook)));
+        assert!(is_bot(Some(google)));
+        assert!(is_bot(Some(whatsapp)));
+        assert!(!is_bot(Some(firefox)));
+        assert!(!is_bot(Some("curl/8.5.0")));
+        assert!(!is_bot(None));
+    }
+}
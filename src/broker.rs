@@ -0,0 +1,311 @@
+use async_nats::jetstream;
+use chrono::{DateTime, Utc};
+use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+use rskafka::client::ClientBuilder;
+use rskafka::record::Record;
+use serde::{Deserialize, Serialize};
+use sqlx::{AnyConnection, AnyPool};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::ClickEvent;
+use crate::{outbox, telemetry};
+
+// Events sent to the broker in one request
+const MAX_BATCH: usize = 500;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// Longer than a batch takes to be acknowledged, another instance sends it again after that
+const LEASE: Duration = Duration::from_secs(300);
+// JetStream drops a message whose id it saw within its duplicate window, so resent batches
+// are only stored once
+const NATS_MSG_ID: &str = "Nats-Msg-Id";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BrokerUrl {
+    Nats(String),
+    // Bootstrap brokers as host:port
+    Kafka(Vec<String>),
+}
+
+impl BrokerUrl {
+    pub fn parse(url: &str) -> anyhow::Result<Self> {
+        match url.split_once("://") {
+            Some(("nats" | "tls", _)) => Ok(BrokerUrl::Nats(url.to_string())),
+            Some(("kafka", brokers)) => {
+                let brokers: Vec<String> = brokers
+                    .trim_end_matches('/')
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|broker| !broker.is_empty())
+                    .map(String::from)
+                    .collect();
+                if brokers.is_empty() {
+                    anyhow::bail!("event_broker_url must name at least one Kafka broker");
+                }
+                Ok(BrokerUrl::Kafka(brokers))
+            }
+            _ => anyhow::bail!("event_broker_url must start with nats://, tls:// or kafka://"),
+        }
+    }
+}
+
+// What consumers receive, a resent event keeps its id so they can drop the duplicate
+#[derive(Debug, Serialize)]
+struct PublishedClick<'a> {
+    id: &'a str,
+    url_id: &'a str,
+    #[serde(flatten)]
+    click: &'a ClickEvent,
+}
+
+#[derive(Debug)]
+struct Message {
+    id: String,
+    // Kafka partitions by the link, so the clicks of one link stay in order
+    key: String,
+    payload: Vec<u8>,
+    at: DateTime<Utc>,
+}
+
+enum Broker {
+    Nats(jetstream::Context),
+    Kafka(Vec<PartitionClient>),
+}
+
+impl Broker {
+    async fn connect(url: &BrokerUrl, topic: &str) -> anyhow::Result<Self> {
+        match url {
+            BrokerUrl::Nats(url) => Ok(Broker::Nats(jetstream::new(async_nats::connect(url.as_str()).await?))),
+            BrokerUrl::Kafka(brokers) => {
+                let client = ClientBuilder::new(brokers.clone()).build().await?;
+                let partitions = client
+                    .list_topics()
+                    .await?
+                    .into_iter()
+                    .find(|found| found.name == topic)
+                    .map(|found| found.partitions)
+                    .filter(|partitions| !partitions.is_empty())
+                    .ok_or_else(|| anyhow::anyhow!("Kafka topic {} does not exist", topic))?;
+                let mut clients = Vec::with_capacity(partitions.len());
+                for partition in partitions {
+                    clients.push(client.partition_client(topic, partition, UnknownTopicHandling::Retry).await?);
+                }
+                Ok(Broker::Kafka(clients))
+            }
+        }
+    }
+
+    // Succeeds once the broker has acknowledged every message of the batch
+    async fn send(&self, topic: &str, batch: &[Message]) -> anyhow::Result<()> {
+        match self {
+            Broker::Nats(jetstream) => {
+                let mut acks = Vec::with_capacity(batch.len());
+                for message in batch {
+                    let mut headers = async_nats::HeaderMap::new();
+                    headers.insert(NATS_MSG_ID, message.id.as_str());
+                    acks.push(
+                        jetstream
+                            .publish_with_headers(topic.to_string(), headers, message.payload.clone().into())
+                            .await?,
+                    );
+                }
+                for ack in acks {
+                    ack.await?;
+                }
+            }
+            Broker::Kafka(partitions) => {
+                let mut records: Vec<Vec<Record>> = partitions.iter().map(|_| Vec::new()).collect();
+                for message in batch {
+                    let record = Record {
+                        key: Some(message.key.clone().into_bytes()),
+                        value: Some(message.payload.clone()),
+                        headers: BTreeMap::from([("id".to_string(), message.id.clone().into_bytes())]),
+                        timestamp: message.at,
+                    };
+                    records[partition(&message.key, partitions.len())].push(record);
+                }
+                for (client, records) in partitions.iter().zip(records) {
+                    client.produce(records, Compression::NoCompression).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn partition(key: &str, partitions: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % partitions as u64) as usize
+}
+
+// Queues the click for the broker in the transaction that records it, see clicks::flush
+pub async fn enqueue(conn: &mut AnyConnection, url_id: &str, click: &ClickEvent) -> Result<(), sqlx::Error> {
+    let id = Uuid::new_v4().to_string();
+    let event = PublishedClick { id: &id, url_id, click };
+    outbox::push(conn, outbox::EVENTS, &id, &serde_json::to_string(&event).unwrap_or_default()).await
+}
+
+// The parts of a queued event the broker needs besides its body
+#[derive(Deserialize)]
+struct Queued {
+    url_id: String,
+    clicked_at: DateTime<Utc>,
+}
+
+fn message(entry: outbox::Entry) -> serde_json::Result<Message> {
+    let queued: Queued = serde_json::from_str(&entry.payload)?;
+    Ok(Message {
+        id: entry.id,
+        key: queued.url_id,
+        payload: entry.payload.into_bytes(),
+        at: queued.clicked_at,
+    })
+}
+
+// Publishes queued clicks from a background task in batches. A batch stays in the outbox
+// until the broker acknowledges it, so consumers see every recorded click at least once,
+// also across restarts.
+#[derive(Clone, Default)]
+pub struct EventPublisher {
+    enabled: bool,
+}
+
+impl EventPublisher {
+    pub fn spawn(config: &Config, db: AnyPool) -> anyhow::Result<Self> {
+        let Some(url) = config.event_broker_url.as_deref().filter(|url| !url.is_empty()) else {
+            return Ok(Self::default());
+        };
+        let url = BrokerUrl::parse(url)?;
+        let topic = config.event_topic.clone();
+        let poll_interval = Duration::from_millis(config.outbox_poll_interval_ms);
+        tokio::spawn(run(url, topic, db, poll_interval));
+        Ok(Self { enabled: true })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+async fn run(url: BrokerUrl, topic: String, db: AnyPool, poll_interval: Duration) {
+    let mut backoff = MIN_BACKOFF;
+    let broker = loop {
+        match Broker::connect(&url, &topic).await {
+            Ok(broker) => break broker,
+            Err(e) => {
+                eprintln!("⚠️  Could not connect to the event broker, retrying in {:?}: {}", backoff, e);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    };
+
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let entries = match outbox::claim(&db, outbox::EVENTS, MAX_BATCH, LEASE).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("⚠️  Failed to claim queued click events: {}", e);
+                Vec::new()
+            }
+        };
+        if entries.is_empty() {
+            tokio::time::sleep(poll_interval).await;
+            continue;
+        }
+
+        let mut batch = Vec::with_capacity(entries.len());
+        let mut unreadable = Vec::new();
+        for entry in entries {
+            let id = entry.id.clone();
+            match message(entry) {
+                Ok(message) => batch.push(message),
+                Err(e) => {
+                    eprintln!("⚠️  Dropping unreadable click event {}: {}", id, e);
+                    unreadable.push(id);
+                }
+            }
+        }
+        let ids: Vec<String> = batch.iter().map(|message| message.id.clone()).collect();
+
+        let updated = match broker.send(&topic, &batch).await {
+            Ok(()) => {
+                metrics::counter!(telemetry::EVENTS_PUBLISHED_TOTAL).increment(batch.len() as u64);
+                backoff = MIN_BACKOFF;
+                unreadable.extend(ids);
+                outbox::finish(&db, &unreadable).await
+            }
+            Err(e) => {
+                eprintln!("⚠️  Failed to publish {} click events, retrying in {:?}: {}", batch.len(), backoff, e);
+                let postponed = outbox::postpone(&db, &ids, backoff).await;
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                postponed.and(outbox::finish(&db, &unreadable).await)
+            }
+        };
+        if let Err(e) = updated {
+            eprintln!("⚠️  Failed to update {} queued click events: {}", batch.len(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::Device;
+    use crate::storage;
+
+    #[test]
+    fn test_broker_url() {
+        assert_eq!(
+            BrokerUrl::parse("nats://127.0.0.1:4222").unwrap(),
+            BrokerUrl::Nats("nats://127.0.0.1:4222".into())
+        );
+        assert_eq!(
+            BrokerUrl::parse("kafka://k1:9092, k2:9092/").unwrap(),
+            BrokerUrl::Kafka(vec!["k1:9092".into(), "k2:9092".into()])
+        );
+        assert!(BrokerUrl::parse("kafka://").is_err());
+        assert!(BrokerUrl::parse("amqp://127.0.0.1").is_err());
+        assert!(BrokerUrl::parse("127.0.0.1:4222").is_err());
+
+        // Always the same partition for a link
+        assert_eq!(partition("u1", 6), partition("u1", 6));
+        assert!(partition("u2", 6) < 6);
+    }
+
+    #[tokio::test]
+    async fn test_enqueued_clicks_become_messages() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let click = ClickEvent {
+                token: "abc123".into(),
+                clicked_at: Utc::now(),
+                referrer: None,
+                country: Some("DE".into()),
+                city: None,
+                device: Device::Desktop,
+                variant: None,
+            };
+            let mut conn = db.acquire().await.unwrap();
+            enqueue(&mut conn, "u1", &click).await.unwrap();
+            drop(conn);
+
+            let entries = outbox::claim(&db, outbox::EVENTS, MAX_BATCH, LEASE).await.unwrap();
+            let message = message(entries.into_iter().next().unwrap()).unwrap();
+            assert_eq!(message.key, "u1");
+            assert_eq!(message.at, click.clicked_at);
+            let event: serde_json::Value = serde_json::from_slice(&message.payload).unwrap();
+            assert_eq!(event["id"], message.id);
+            assert_eq!(event["url_id"], "u1");
+            assert_eq!(event["token"], "abc123");
+            assert_eq!(event["country"], "DE");
+            assert!(!EventPublisher::default().is_enabled());
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use axum::{
+    extract::State,
+    response::{IntoResponse, Json},
+};
+use serde_json::json;
+use sqlx::{AnyConnection, Row};
+use std::sync::Arc;
+
+use crate::audit::{self, AuditAction};
+use crate::auth::Caller;
+use crate::models::{BulkItemResult, BulkOperation, BulkRequest, BulkResponse};
+use crate::storage::{self, SqlBuilder};
+use crate::webhooks::{self, WebhookEvent};
+use crate::{
+    insert_tags, normalize_tags, push_owner_filter, tombstone, validate_schedule, Access, AppError, AppState, MAX_TAGS,
+};
+
+fn database_error(e: sqlx::Error) -> AppError {
+    AppError::DatabaseError(e.to_string())
+}
+
+// Applies the operation to one live link the caller may change and returns its id. Items are
+// checked before anything is written, so a rejected one leaves nothing behind in the transaction.
+async fn apply(
+    conn: &mut AnyConnection,
+    caller: &Caller,
+    token: &str,
+    operation: &BulkOperation,
+) -> Result<String, AppError> {
+    let mut lookup = SqlBuilder::new("SELECT id, starts_at, expires_at FROM urls WHERE token = ");
+    lookup.push_bind(token.to_string()).push(" AND deleted_at IS NULL");
+    push_owner_filter(&mut lookup, caller, Access::Write);
+    let row = lookup
+        .build()
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(database_error)?
+        .ok_or(AppError::UrlNotFound)?;
+    let id: String = row.get("id");
+    let now = storage::ts(storage::now());
+
+    match operation {
+        BulkOperation::Delete => tombstone(conn, &id, token, caller, true).await?,
+        BulkOperation::SetExpiry { expires_at } => {
+            validate_schedule(storage::get_opt_ts(&row, "starts_at"), *expires_at)?;
+            sqlx::query("UPDATE urls SET expires_at = $1, updated_at = $2 WHERE id = $3")
+                .bind(expires_at.map(storage::ts))
+                .bind(&now)
+                .bind(&id)
+                .execute(&mut *conn)
+                .await
+                .map_err(database_error)?;
+            let old = storage::get_opt_ts(&row, "expires_at");
+            let changes = audit::diff(&json!({ "expires_at": old }), &json!({ "expires_at": expires_at }));
+            audit::record(conn, &id, token, AuditAction::Update, caller, changes).await?;
+        }
+        BulkOperation::AddTag { tag } => {
+            let tags: Vec<String> = sqlx::query("SELECT tag FROM url_tags WHERE url_id = $1 ORDER BY tag")
+                .bind(&id)
+                .fetch_all(&mut *conn)
+                .await
+                .map_err(database_error)?
+                .iter()
+                .map(|row| row.get("tag"))
+                .collect();
+            if tags.contains(tag) {
+                return Ok(id);
+            }
+            if tags.len() >= MAX_TAGS {
+                return Err(AppError::BadRequest(format!("A link can have at most {} tags", MAX_TAGS)));
+            }
+
+            insert_tags(conn, &id, std::slice::from_ref(tag)).await?;
+            sqlx::query("UPDATE urls SET updated_at = $1 WHERE id = $2")
+                .bind(&now)
+                .bind(&id)
+                .execute(&mut *conn)
+                .await
+                .map_err(database_error)?;
+            let mut new = tags.clone();
+            new.push(tag.clone());
+            new.sort();
+            let changes = audit::diff(&json!({ "tags": tags }), &json!({ "tags": new }));
+            audit::record(conn, &id, token, AuditAction::Update, caller, changes).await?;
+        }
+    }
+    Ok(id)
+}
+
+#[utoipa::path(
+    post,
+    path = "/urls/bulk",
+    tag = "urls",
+    request_body = BulkRequest,
+    responses(
+        (status = 200, description = "Per-item results, the successful items are committed together", body = BulkResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn bulk_update(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<BulkRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let max = state.config.max_batch_size;
+    if payload.tokens.is_empty() || payload.tokens.len() > max {
+        return Err(AppError::BadRequest(format!(
+            "Bulk request must contain between 1 and {} tokens",
+            max
+        )));
+    }
+    let operation = match payload.operation {
+        BulkOperation::AddTag { tag } => BulkOperation::AddTag {
+            tag: normalize_tags(vec![tag])?.remove(0),
+        },
+        operation => operation,
+    };
+
+    let mut tx = state.db.begin().await.map_err(database_error)?;
+    let mut results = Vec::with_capacity(payload.tokens.len());
+    let mut changed = Vec::new();
+    for token in payload.tokens {
+        let result = match apply(&mut tx, &caller, &token, &operation).await {
+            Ok(id) => {
+                if matches!(operation, BulkOperation::Delete) {
+                    webhooks::enqueue(&mut tx, WebhookEvent::LinkDeleted, &id, &json!({ "token": token }))
+                        .await
+                        .map_err(database_error)?;
+                }
+                changed.push(token.clone());
+                BulkItemResult::Done { token }
+            }
+            Err(AppError::UrlNotFound) => BulkItemResult::Error { token, error: "URL not found".into() },
+            Err(AppError::BadRequest(error)) => BulkItemResult::Error { token, error },
+            Err(e) => return Err(e),
+        };
+        results.push(result);
+    }
+    tx.commit().await.map_err(database_error)?;
+
+    for token in &changed {
+        state.links.invalidate(token).await;
+    }
+
+    Ok(Json(BulkResponse {
+        succeeded: changed.len(),
+        failed: results.len() - changed.len(),
+        results,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_apply_checks_each_link() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            sqlx::query(
+                r#"
+                INSERT INTO urls (id, token, original_url, created_at, starts_at, click_count) VALUES
+                    ('u1', 'one', 'https://example.com/1', '2024-05-01T00:00:00Z', NULL, 0),
+                    ('u2', 'two', 'https://example.com/2', '2024-05-01T00:00:00Z', '2024-06-01T00:00:00Z', 0)
+                "#
+            )
+            .execute(&db)
+            .await
+            .unwrap();
+            let mut conn = db.acquire().await.unwrap();
+            let caller = Caller::Admin;
+
+            let tag = BulkOperation::AddTag { tag: "spring".into() };
+            assert_eq!(apply(&mut conn, &caller, "one", &tag).await.unwrap(), "u1");
+            // Adding it again changes nothing
+            apply(&mut conn, &caller, "one", &tag).await.unwrap();
+            let tags: i64 = sqlx::query("SELECT COUNT(*) AS tags FROM url_tags WHERE url_id = 'u1'")
+                .fetch_one(&mut *conn)
+                .await
+                .unwrap()
+                .get("tags");
+            assert_eq!(tags, 1);
+
+            let expiry = BulkOperation::SetExpiry {
+                expires_at: Some(storage::parse_ts("2024-05-15T00:00:00Z")),
+            };
+            apply(&mut conn, &caller, "one", &expiry).await.unwrap();
+            assert!(matches!(apply(&mut conn, &caller, "two", &expiry).await, Err(AppError::BadRequest(_))));
+
+            apply(&mut conn, &caller, "one", &BulkOperation::Delete).await.unwrap();
+            assert!(matches!(
+                apply(&mut conn, &caller, "one", &BulkOperation::Delete).await,
+                Err(AppError::UrlNotFound)
+            ));
+            assert!(matches!(apply(&mut conn, &caller, "missing", &tag).await, Err(AppError::UrlNotFound)));
+        }
+    }
+}
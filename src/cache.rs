@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use moka::sync::Cache;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::models::{RedirectRule, SplitVariant};
+use crate::reports::Takedown;
+use crate::telemetry;
+
+// What a redirect needs to know about a link, without going back to the database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedLink {
+    pub id: String,
+    pub original_url: String,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_clicks: Option<i64>,
+    pub domain: Option<String>,
+    pub rules: Vec<RedirectRule>,
+    pub variants: Vec<SplitVariant>,
+    pub sticky_variants: bool,
+    pub flagged: bool,
+    // A moderator wants visitors warned before the redirect
+    pub warned: bool,
+    pub takedown: Option<Takedown>,
+    // Switched off by its owner or a moderator
+    pub active: bool,
+    // The last health check found the destination gone
+    pub dead: bool,
+    pub forward_query: bool,
+    pub template: bool,
+}
+
+// Bounded token -> destination cache for redirects. Entries are dropped on update and delete;
+// the TTL bounds how stale other instances sharing the database can get.
+#[derive(Clone)]
+pub struct LinkCache {
+    links: Option<Cache<String, CachedLink>>,
+}
+
+impl LinkCache {
+    pub fn new(capacity: u64, ttl: Duration) -> Self {
+        let links = (capacity > 0 && !ttl.is_zero()).then(|| {
+            Cache::builder().max_capacity(capacity).time_to_live(ttl).build()
+        });
+        Self { links }
+    }
+
+    pub fn get(&self, token: &str) -> Option<CachedLink> {
+        let links = self.links.as_ref()?;
+        let link = links.get(token);
+        let result = if link.is_some() { "hit" } else { "miss" };
+        metrics::counter!(telemetry::REDIRECT_CACHE_TOTAL, "result" => result).increment(1);
+        link
+    }
+
+    pub fn insert(&self, token: String, link: CachedLink) {
+        if let Some(links) = &self.links {
+            links.insert(token, link);
+        }
+    }
+
+    // Approximate number of cached links, None when the cache is disabled
+    pub fn entry_count(&self) -> Option<u64> {
+        self.links.as_ref().map(|links| links.entry_count())
+    }
+
+    pub fn invalidate(&self, token: &str) {
+        if let Some(links) = &self.links {
+            links.invalidate(token);
+        }
+    }
+
+    pub fn invalidate_all(&self) {
+        if let Some(links) = &self.links {
+            links.invalidate_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link() -> CachedLink {
+        CachedLink {
+            id: "id".into(),
+            original_url: "https://example.com".into(),
+            starts_at: None,
+            expires_at: Some(Utc::now()),
+            max_clicks: None,
+            domain: None,
+            rules: Vec::new(),
+            variants: Vec::new(),
+            sticky_variants: false,
+            flagged: false,
+            warned: false,
+            takedown: None,
+            active: true,
+            dead: false,
+            forward_query: false,
+            template: false,
+        }
+    }
+
+    #[test]
+    fn test_insert_get_invalidate() {
+        let cache = LinkCache::new(10, Duration::from_secs(60));
+        cache.insert("abc".into(), link());
+        assert_eq!(cache.get("abc").unwrap().original_url, "https://example.com");
+
+        cache.invalidate("abc");
+        assert!(cache.get("abc").is_none());
+
+        let disabled = LinkCache::new(0, Duration::from_secs(60));
+        disabled.insert("abc".into(), link());
+        assert!(disabled.get("abc").is_none());
+    }
+}
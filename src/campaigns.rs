@@ -0,0 +1,345 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use chrono::{Duration, NaiveDate, Utc};
+use sqlx::{AnyPool, Row};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::Caller;
+use crate::base_url::BaseUrl;
+use crate::models::{
+    CampaignInfo, CampaignLinkStats, CampaignStatsQuery, CampaignStatsResponse, CreateCampaignRequest,
+};
+use crate::rollup::{self, Links};
+use crate::stats::{DEFAULT_STATS_DAYS, MAX_STATS_DAYS};
+use crate::storage::{self, SqlBuilder};
+use crate::{AppError, AppState};
+
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_DESCRIPTION_LENGTH: usize = 1000;
+
+fn database_error(e: sqlx::Error) -> AppError {
+    AppError::DatabaseError(e.to_string())
+}
+
+// Accounts see the campaigns they created, API keys and the admin key see every campaign
+fn campaign_query(caller: &Caller, id: Option<&str>) -> SqlBuilder {
+    let mut query = SqlBuilder::new(
+        r#"
+        SELECT id, name, description, created_at,
+            (SELECT COUNT(*) FROM urls WHERE urls.campaign_id = campaigns.id AND urls.deleted_at IS NULL) AS link_count
+        FROM campaigns
+        "#,
+    );
+    let mut keyword = " WHERE ";
+    if let Some(id) = id {
+        query.push(keyword).push("id = ").push_bind(id.to_string());
+        keyword = " AND ";
+    }
+    if let Some(user_id) = caller.user_id() {
+        query.push(keyword).push("user_id = ").push_bind(user_id.to_string());
+    }
+    query
+}
+
+fn campaign_from_row(row: &sqlx::any::AnyRow) -> CampaignInfo {
+    CampaignInfo {
+        id: row.get("id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        created_at: storage::get_ts(row, "created_at"),
+        link_count: row.get("link_count"),
+    }
+}
+
+pub async fn find_campaign(db: &AnyPool, id: &str, caller: &Caller) -> Result<CampaignInfo, AppError> {
+    let not_found = || AppError::NotFound("Campaign not found".into());
+    if *caller == Caller::Anonymous {
+        return Err(not_found());
+    }
+    campaign_query(caller, Some(id))
+        .build()
+        .fetch_optional(db)
+        .await
+        .map_err(database_error)?
+        .map(|row| campaign_from_row(&row))
+        .ok_or_else(not_found)
+}
+
+// Links can only join campaigns their creator or editor can see
+pub async fn ensure_visible(db: &AnyPool, id: &str, caller: &Caller) -> Result<(), AppError> {
+    match find_campaign(db, id, caller).await {
+        Err(AppError::NotFound(_)) => Err(AppError::BadRequest(format!("Unknown campaign: {}", id))),
+        result => result.map(|_| ()),
+    }
+}
+
+// Both ends are whole UTC days, `to` included
+pub fn date_range(query: &CampaignStatsQuery, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), AppError> {
+    let to = query.to.unwrap_or(today);
+    let from = query.from.unwrap_or(to - Duration::days(DEFAULT_STATS_DAYS - 1));
+    if from > to {
+        return Err(AppError::BadRequest("from must not be after to".into()));
+    }
+    if (to - from).num_days() >= MAX_STATS_DAYS {
+        return Err(AppError::BadRequest(format!("The range can span at most {} days", MAX_STATS_DAYS)));
+    }
+    Ok((from, to))
+}
+
+#[utoipa::path(
+    post,
+    path = "/campaigns",
+    tag = "campaigns",
+    request_body = CreateCampaignRequest,
+    responses(
+        (status = 201, description = "Campaign created", body = CampaignInfo),
+        (status = 400, description = "Invalid name or description", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn create_campaign(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<CreateCampaignRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let name = payload.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AppError::BadRequest(format!("name must be 1 to {} characters", MAX_NAME_LENGTH)));
+    }
+    let description = payload.description.map(|description| description.trim().to_string()).filter(|d| !d.is_empty());
+    if description.as_ref().is_some_and(|description| description.chars().count() > MAX_DESCRIPTION_LENGTH) {
+        return Err(AppError::BadRequest(format!("description must be at most {} characters", MAX_DESCRIPTION_LENGTH)));
+    }
+
+    let campaign = CampaignInfo {
+        id: Uuid::new_v4().to_string(),
+        name,
+        description,
+        created_at: storage::now(),
+        link_count: 0,
+    };
+    sqlx::query("INSERT INTO campaigns (id, name, description, user_id, created_at) VALUES ($1, $2, $3, $4, $5)")
+        .bind(&campaign.id)
+        .bind(&campaign.name)
+        .bind(&campaign.description)
+        .bind(caller.user_id())
+        .bind(storage::ts(campaign.created_at))
+        .execute(&state.db)
+        .await
+        .map_err(database_error)?;
+
+    Ok((StatusCode::CREATED, Json(campaign)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/campaigns",
+    tag = "campaigns",
+    responses(
+        (status = 200, description = "Campaigns of the caller's account, every one for API keys and the admin key", body = [CampaignInfo]),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn list_campaigns(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(visible_campaigns(&state.db, &caller).await?))
+}
+
+// Newest first
+pub async fn visible_campaigns(db: &AnyPool, caller: &Caller) -> Result<Vec<CampaignInfo>, AppError> {
+    let mut query = campaign_query(caller, None);
+    query.push(" ORDER BY created_at DESC, id");
+    Ok(query
+        .build()
+        .fetch_all(db)
+        .await
+        .map_err(database_error)?
+        .iter()
+        .map(campaign_from_row)
+        .collect())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/campaigns/{id}",
+    tag = "campaigns",
+    params(("id" = String, Path, description = "Campaign id")),
+    responses(
+        (status = 204, description = "Campaign deleted, its links stay and leave the campaign"),
+        (status = 404, description = "Campaign not found", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn delete_campaign(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    find_campaign(&state.db, &id, &caller).await?;
+
+    let mut tx = state.db.begin().await.map_err(database_error)?;
+    sqlx::query("UPDATE urls SET campaign_id = NULL, updated_at = $1 WHERE campaign_id = $2")
+        .bind(storage::ts(storage::now()))
+        .bind(&id)
+        .execute(&mut *tx)
+        .await
+        .map_err(database_error)?;
+    sqlx::query("DELETE FROM campaigns WHERE id = $1")
+        .bind(&id)
+        .execute(&mut *tx)
+        .await
+        .map_err(database_error)?;
+    tx.commit().await.map_err(database_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/campaigns/{id}/stats",
+    tag = "campaigns",
+    params(("id" = String, Path, description = "Campaign id"), CampaignStatsQuery),
+    responses(
+        (status = 200, description = "Clicks and unique visitors over all links of the campaign", body = CampaignStatsResponse),
+        (status = 400, description = "Invalid date range", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "Campaign not found", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn get_campaign_stats(
+    Path(id): Path<String>,
+    Query(query): Query<CampaignStatsQuery>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+) -> Result<impl IntoResponse, AppError> {
+    // Campaigns have owners, unlike the stats of a single link these are not public
+    if caller == Caller::Anonymous {
+        return Err(AppError::Unauthorized("Missing bearer token".into()));
+    }
+    let (from, to) = date_range(&query, Utc::now().date_naive())?;
+    let campaign = find_campaign(state.links.reader(), &id, &caller).await?;
+    Ok(Json(campaign_stats(state.links.reader(), &base, campaign, from, to).await?))
+}
+
+// Clicks on the live links of the campaign, `from` and `to` are whole UTC days
+pub async fn campaign_stats(
+    db: &AnyPool,
+    base: &BaseUrl,
+    campaign: CampaignInfo,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<CampaignStatsResponse, AppError> {
+    let since = storage::day_start(from);
+    let until = storage::day_start(to + Duration::days(1));
+
+    let totals = sqlx::query(
+        r#"
+        SELECT COUNT(*) AS total, COUNT(DISTINCT c.ip_hash) AS unique_visitors FROM clicks c
+        JOIN urls u ON u.id = c.url_id
+        WHERE u.campaign_id = $1 AND u.deleted_at IS NULL AND c.clicked_at >= $2 AND c.clicked_at < $3
+        "#
+    )
+    .bind(&campaign.id)
+    .bind(&since)
+    .bind(&until)
+    .fetch_one(db)
+    .await
+    .map_err(database_error)?;
+
+    let daily = rollup::daily_clicks(db, Links::Campaign(&campaign.id), from, to).await?;
+
+    // Links without clicks in the range are listed with zeros
+    let links = sqlx::query(
+        r#"
+        SELECT u.token, u.domain, u.original_url, COUNT(c.id) AS clicks, COUNT(DISTINCT c.ip_hash) AS unique_visitors
+        FROM urls u
+        LEFT JOIN clicks c ON c.url_id = u.id AND c.clicked_at >= $2 AND c.clicked_at < $3
+        WHERE u.campaign_id = $1 AND u.deleted_at IS NULL
+        GROUP BY u.id, u.token, u.domain, u.original_url
+        ORDER BY clicks DESC, u.token ASC
+        "#
+    )
+    .bind(&campaign.id)
+    .bind(&since)
+    .bind(&until)
+    .fetch_all(db)
+    .await
+    .map_err(database_error)?
+    .iter()
+    .map(|row| {
+        let token: String = row.get("token");
+        let domain: Option<String> = row.get("domain");
+        CampaignLinkStats {
+            short_url: base.short_url(domain.as_deref(), &token),
+            token,
+            original_url: row.get("original_url"),
+            clicks: row.get("clicks"),
+            unique_visitors: row.get("unique_visitors"),
+        }
+    })
+    .collect();
+
+    Ok(CampaignStatsResponse {
+        campaign,
+        from,
+        to,
+        total_clicks: totals.get("total"),
+        unique_visitors: totals.get("unique_visitors"),
+        daily,
+        links,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_campaign_visibility_and_range() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let now = storage::ts(storage::now());
+            for user in ["owner", "other"] {
+                sqlx::query("INSERT INTO users (id, email, password_hash, created_at) VALUES ($1, $1, 'x', $2)")
+                    .bind(user)
+                    .bind(&now)
+                    .execute(&db)
+                    .await
+                    .unwrap();
+            }
+            sqlx::query("INSERT INTO campaigns (id, name, user_id, created_at) VALUES ('c1', 'Launch', 'owner', $1)")
+                .bind(&now)
+                .execute(&db)
+                .await
+                .unwrap();
+
+            let owner = Caller::User("owner".into());
+            let other = Caller::User("other".into());
+            assert_eq!(find_campaign(&db, "c1", &owner).await.unwrap().link_count, 0);
+            assert!(find_campaign(&db, "c1", &Caller::Admin).await.is_ok());
+            assert!(matches!(find_campaign(&db, "c1", &other).await, Err(AppError::NotFound(_))));
+            assert!(matches!(ensure_visible(&db, "c1", &other).await, Err(AppError::BadRequest(_))));
+            assert!(matches!(ensure_visible(&db, "c1", &Caller::Anonymous).await, Err(AppError::BadRequest(_))));
+
+            let today = NaiveDate::from_ymd_opt(2024, 5, 31).unwrap();
+            let range = |from: Option<&str>, to: Option<&str>| {
+                let query = CampaignStatsQuery {
+                    from: from.map(|date| date.parse().unwrap()),
+                    to: to.map(|date| date.parse().unwrap()),
+                };
+                date_range(&query, today)
+            };
+            assert_eq!(range(None, None).unwrap(), ("2024-05-02".parse().unwrap(), today));
+            assert_eq!(range(Some("2024-05-10"), Some("2024-05-10")).unwrap().0, "2024-05-10".parse().unwrap());
+            assert!(range(Some("2024-05-11"), Some("2024-05-10")).is_err());
+            assert!(range(Some("2023-01-01"), None).is_err());
+        }
+    }
+}
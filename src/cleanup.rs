@@ -0,0 +1,282 @@
+use axum::{extract::{Query, State}, response::{IntoResponse, Json}};
+use sqlx::{AnyPool, Row};
+use std::sync::Arc;
+
+use crate::config::CleanupMode;
+use crate::models::{CleanupReport, PurgeQuery, PurgeReport};
+use crate::{rollup, scheduler, storage};
+use crate::webhooks::{self, WebhookEvent};
+use crate::{AppError, AppState};
+
+// Delivery log entries are kept this long, they only matter for debugging recent failures
+const DELIVERY_RETENTION_DAYS: i64 = 30;
+
+// Anything older than a century is everything, and chrono panics well past that
+const MAX_PURGE_DAYS: i64 = 36_500;
+
+pub async fn run_cleanup(db: &AnyPool, mode: CleanupMode, click_retention_days: i64) -> Result<CleanupReport, sqlx::Error> {
+    let now = storage::now();
+    if click_retention_days > 0 {
+        rollup::run_rollup(db).await?;
+    }
+    let rolled_up = rollup::first_open_day(db).await?.map(storage::day_start).unwrap_or_default();
+
+    let pruned_before = storage::ts(now - chrono::Duration::days(DELIVERY_RETENTION_DAYS));
+    let clicks_before = storage::ts(now - chrono::Duration::days(click_retention_days));
+    let now = storage::ts(now);
+    let mut tx = db.begin().await?;
+
+    // Announced before the links go, link scoped webhooks are removed together with them
+    let expired = sqlx::query("SELECT id, token, original_url, expires_at FROM urls WHERE expires_at <= $1 AND deleted_at IS NULL")
+        .bind(&now)
+        .fetch_all(&mut *tx)
+        .await?;
+    for row in &expired {
+        let data = serde_json::json!({
+            "token": row.get::<String, _>("token"),
+            "original_url": row.get::<String, _>("original_url"),
+            "expires_at": storage::get_ts(row, "expires_at"),
+        });
+        webhooks::enqueue(&mut tx, WebhookEvent::LinkExpired, &row.get::<String, _>("id"), &data).await?;
+    }
+
+    let archived = match mode {
+        CleanupMode::Delete => 0,
+        CleanupMode::Archive => sqlx::query(
+            r#"
+            INSERT INTO archived_urls (id, token, original_url, title, created_at, expires_at, click_count, user_id, domain, archived_at)
+            SELECT id, token, original_url, title, created_at, expires_at, click_count, user_id, domain, $1
+            FROM urls WHERE expires_at <= $1
+            "#
+        )
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected(),
+    };
+
+    let removed = sqlx::query("DELETE FROM urls WHERE expires_at <= $1")
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    sqlx::query("DELETE FROM webhook_deliveries WHERE created_at <= $1")
+        .bind(pruned_before)
+        .execute(&mut *tx)
+        .await?;
+
+    // Only the events go, click_count and unique_clicks on the link stay as they are. Days
+    // that are not rolled up yet keep their events, the daily series still counts them.
+    let clicks_purged = if click_retention_days > 0 {
+        sqlx::query("DELETE FROM clicks WHERE clicked_at <= $1 AND clicked_at < $2")
+            .bind(clicks_before)
+            .bind(rolled_up)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+    } else {
+        0
+    };
+
+    tx.commit().await?;
+
+    Ok(CleanupReport {
+        mode: mode.as_str().to_string(),
+        removed,
+        archived,
+        clicks_purged,
+    })
+}
+
+pub fn spawn(state: Arc<AppState>) {
+    let interval_secs = state.config.cleanup_interval_secs;
+    let scheduled = scheduler::spawn(state, "cleanup", interval_secs, |state| async move {
+        match run_cleanup(&state.db, state.config.cleanup_mode, state.config.click_retention_days).await {
+            Ok(report) if report.removed > 0 || report.clicks_purged > 0 => println!(
+                "🧹 Cleanup removed {} expired links and {} old clicks",
+                report.removed, report.clicks_purged
+            ),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("❌ Cleanup failed: {}", e);
+                return Err(e.to_string());
+            }
+        }
+        Ok(())
+    });
+    if !scheduled {
+        println!("🧹 Expired link cleanup is disabled");
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/cleanup",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Expired links removed", body = CleanupReport),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn trigger_cleanup(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let report = run_cleanup(&state.db, state.config.cleanup_mode, state.config.click_retention_days)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(report))
+}
+
+// Permanently removes soft-deleted links, clicks and tags go with them through the foreign keys
+pub async fn run_purge(db: &AnyPool, deleted_before: chrono::DateTime<chrono::Utc>) -> Result<u64, sqlx::Error> {
+    let purged = sqlx::query("DELETE FROM urls WHERE deleted_at IS NOT NULL AND deleted_at <= $1")
+        .bind(storage::ts(deleted_before))
+        .execute(db)
+        .await?
+        .rows_affected();
+    Ok(purged)
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/purge",
+    tag = "admin",
+    params(PurgeQuery),
+    responses(
+        (status = 200, description = "Deleted links removed for good", body = PurgeReport),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn purge_deleted(
+    Query(query): Query<PurgeQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let days = query.older_than_days.unwrap_or(0);
+    if !(0..=MAX_PURGE_DAYS).contains(&days) {
+        return Err(AppError::BadRequest(format!("older_than_days must be between 0 and {}", MAX_PURGE_DAYS)));
+    }
+
+    let purged = run_purge(&state.db, chrono::Utc::now() - chrono::Duration::days(days))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(PurgeReport { purged }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Row;
+
+    #[tokio::test]
+    async fn test_purge_only_removes_old_tombstones() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let now = chrono::Utc::now();
+            let expires = storage::ts(now + chrono::Duration::days(1));
+            for (id, deleted_at) in [
+                ("live", None),
+                ("recent", Some(storage::ts(now))),
+                ("old", Some(storage::ts(now - chrono::Duration::days(10)))),
+            ] {
+                sqlx::query(
+                    r#"
+                    INSERT INTO urls (id, token, original_url, created_at, expires_at, click_count, deleted_at)
+                    VALUES ($1, $1, 'https://example.com', $2, $2, 0, $3)
+                    "#
+                )
+                .bind(id)
+                .bind(&expires)
+                .bind(deleted_at)
+                .execute(&db)
+                .await
+                .unwrap();
+            }
+
+            assert_eq!(run_purge(&db, now - chrono::Duration::days(7)).await.unwrap(), 1);
+            assert_eq!(run_purge(&db, now).await.unwrap(), 1);
+
+            let row = sqlx::query("SELECT COUNT(*) AS total FROM urls").fetch_one(&db).await.unwrap();
+            assert_eq!(row.get::<i64, _>("total"), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_purges_old_clicks() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let now = chrono::Utc::now();
+            sqlx::query(
+                r#"
+                INSERT INTO urls (id, token, original_url, created_at, expires_at, click_count)
+                VALUES ('u1', 'abc123', 'https://example.com', $1, NULL, 2)
+                "#
+            )
+            .bind(storage::ts(now))
+            .execute(&db)
+            .await
+            .unwrap();
+            for days_ago in [1, 100] {
+                sqlx::query("INSERT INTO clicks (url_id, clicked_at) VALUES ('u1', $1)")
+                    .bind(storage::ts(now - chrono::Duration::days(days_ago)))
+                    .execute(&db)
+                    .await
+                    .unwrap();
+            }
+
+            let report = run_cleanup(&db, CleanupMode::Delete, 0).await.unwrap();
+            assert_eq!(report.clicks_purged, 0);
+            let report = run_cleanup(&db, CleanupMode::Delete, 30).await.unwrap();
+            assert_eq!((report.removed, report.clicks_purged), (0, 1));
+
+            let row = sqlx::query("SELECT click_count, (SELECT COUNT(*) FROM clicks) AS clicks FROM urls").fetch_one(&db).await.unwrap();
+            assert_eq!(row.get::<i64, _>("click_count"), 2);
+            assert_eq!(row.get::<i64, _>("clicks"), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_expired_links_are_announced() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let now = storage::now();
+            sqlx::query(
+                r#"
+                INSERT INTO urls (id, token, original_url, created_at, expires_at, click_count)
+                VALUES ('u1', 'abc123', 'https://example.com', $1, $1, 0)
+                "#
+            )
+            .bind(storage::ts(now - chrono::Duration::hours(1)))
+            .execute(&db)
+            .await
+            .unwrap();
+            sqlx::query(
+                r#"
+                INSERT INTO webhooks (id, url, secret, events, owner, url_id, created_at)
+                VALUES ('w1', 'https://hooks.example.com', 'whsec_test', 'link.expired', 'admin', 'u1', $1)
+                "#
+            )
+            .bind(storage::ts(now))
+            .execute(&db)
+            .await
+            .unwrap();
+
+            let report = run_cleanup(&db, CleanupMode::Delete, 0).await.unwrap();
+            assert_eq!(report.removed, 1);
+
+            // The webhook went with its link, its delivery is still queued
+            let row = sqlx::query("SELECT (SELECT COUNT(*) FROM webhooks) AS webhooks, payload FROM outbox WHERE topic = 'webhooks'")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+            assert_eq!(row.get::<i64, _>("webhooks"), 0);
+            let delivery: serde_json::Value = serde_json::from_str(&row.get::<String, _>("payload")).unwrap();
+            assert_eq!(delivery["event"], "link.expired");
+            assert_eq!(delivery["target"], "https://hooks.example.com");
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use reqwest::{header, Method};
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::config::Config;
+use crate::validation;
+use quickurl_core::Signer;
+
+// Everything but `serve` and `sign` is a client of a running server's API, so links managed
+// from the terminal go through the same validation, webhooks, audit log and cache invalidation
+#[derive(Debug, Parser)]
+#[command(name = "quickurl", version, about = "URL shortener with click analytics")]
+pub struct Cli {
+    #[arg(long, global = true, env = "QUICKURL_SERVER", help = "Server to talk to, base_url when omitted")]
+    pub server: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        env = "QUICKURL_API_KEY",
+        hide_env_values = true,
+        help = "API key, user token or admin key, admin_key when omitted"
+    )]
+    pub key: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum Command {
+    #[command(about = "Run the API server (the default)")]
+    Serve,
+    #[command(about = "Shorten a URL and print the short URL")]
+    Add {
+        url: String,
+        #[arg(long, help = "Use this token instead of a generated one")]
+        alias: Option<String>,
+        #[arg(long)]
+        title: Option<String>,
+        #[arg(long = "tag", help = "Tag the link, can be repeated")]
+        tags: Vec<String>,
+        #[arg(long)]
+        max_clicks: Option<i64>,
+        #[arg(long, help = "Free-form notes kept with the link")]
+        notes: Option<String>,
+    },
+    #[command(about = "List links, newest first")]
+    List {
+        #[arg(long, short, help = "Only links whose title contains this")]
+        query: Option<String>,
+        #[arg(long)]
+        tag: Option<String>,
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+        #[arg(long, help = "List deleted links instead")]
+        deleted: bool,
+        #[arg(long, help = "Only links whose destination failed its last check")]
+        broken: bool,
+    },
+    #[command(about = "Delete a link, it can be restored until purged")]
+    Rm { token: String },
+    #[command(about = "Show a link's click analytics")]
+    Stats {
+        token: String,
+        #[arg(long, default_value_t = 30)]
+        days: i64,
+    },
+    #[command(about = "Print a signed link to the URL, minted offline with the signing_secret")]
+    Sign {
+        url: String,
+        #[arg(long, help = "Stop redirecting at this time, e.g. 2026-12-31T23:59:59Z")]
+        expires_at: Option<DateTime<Utc>>,
+    },
+}
+
+struct Client {
+    http: reqwest::Client,
+    server: Url,
+    key: Option<String>,
+}
+
+impl Client {
+    fn new(cli: &Cli, config: &Config) -> anyhow::Result<Self> {
+        let server = cli.server.as_deref().unwrap_or(&config.base_url);
+        let key = cli.key.clone().or_else(|| config.admin_key.clone()).filter(|key| !key.is_empty());
+        // A trailing slash keeps a path prefix like https://example.com/go when joining
+        let base = format!("{}{}/", server.trim_end_matches('/'), crate::versioning::API_PREFIX);
+        Ok(Self {
+            http: reqwest::Client::new(),
+            server: Url::parse(&base).with_context(|| format!("invalid server address {}", server))?,
+            key,
+        })
+    }
+
+    fn url(&self, path: &str, params: &[(&str, String)]) -> anyhow::Result<Url> {
+        let mut url = self.server.join(path)?;
+        if !params.is_empty() {
+            url.query_pairs_mut().extend_pairs(params);
+        }
+        Ok(url)
+    }
+
+    // Errors come back as problem+json, their detail is what the user needs to see
+    async fn send(&self, method: Method, url: Url, body: Option<Value>) -> anyhow::Result<Option<Value>> {
+        let mut request = self.http.request(method, url.clone());
+        if let Some(key) = &self.key {
+            request = request.bearer_auth(key);
+        }
+        if let Some(body) = body {
+            request = request.header(header::CONTENT_TYPE, "application/json").body(body.to_string());
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("could not reach {}", url))?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            let detail = serde_json::from_str::<Value>(&text)
+                .ok()
+                .and_then(|problem| problem["detail"].as_str().map(str::to_string))
+                .unwrap_or(text);
+            bail!("{} ({})", detail, status);
+        }
+        if text.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&text)?))
+    }
+}
+
+pub async fn run(cli: Cli, config: &Config) -> anyhow::Result<()> {
+    let client = Client::new(&cli, config)?;
+    match cli.command {
+        None | Some(Command::Serve) => unreachable!("main runs the server itself"),
+        Some(Command::Add { url, alias, title, tags, max_clicks, notes }) => {
+            let body = json!({
+                "url": url,
+                "custom_alias": alias,
+                "title": title,
+                "tags": tags,
+                "max_clicks": max_clicks,
+                "notes": notes,
+            });
+            let created = client.send(Method::POST, client.url("shorten", &[])?, Some(body)).await?.unwrap_or_default();
+            println!("{}", created["short_url"].as_str().unwrap_or_default());
+        }
+        Some(Command::List { query, tag, limit, deleted, broken }) => {
+            let mut params = vec![("per_page", limit.to_string())];
+            params.extend(query.map(|query| ("q", query)));
+            params.extend(tag.map(|tag| ("tag", tag)));
+            if deleted {
+                params.push(("deleted", "true".into()));
+            }
+            if broken {
+                params.push(("health", "broken".into()));
+            }
+            let page = client.send(Method::GET, client.url("urls", &params)?, None).await?.unwrap_or_default();
+            let urls = page["urls"].as_array().cloned().unwrap_or_default();
+            for url in &urls {
+                println!(
+                    "{:<12} {:>8}  {}  {}",
+                    url["token"].as_str().unwrap_or_default(),
+                    url["click_count"].as_i64().unwrap_or_default(),
+                    url["created_at"].as_str().unwrap_or_default().get(..10).unwrap_or_default(),
+                    url["original_url"].as_str().unwrap_or_default(),
+                );
+            }
+            println!("{} of {} links", urls.len(), page["total"].as_i64().unwrap_or_default());
+        }
+        Some(Command::Rm { token }) => {
+            client.send(Method::DELETE, client.url(&format!("urls/{}", token), &[])?, None).await?;
+            println!("Deleted {}", token);
+        }
+        Some(Command::Stats { token, days }) => {
+            let url = client.url(&format!("urls/{}/stats", token), &[("days", days.to_string())])?;
+            let stats = client.send(Method::GET, url, None).await?.unwrap_or_default();
+            println!(
+                "{}: {} clicks ({} unique), {} unique visitors, {} from bots",
+                token,
+                stats["total_clicks"].as_i64().unwrap_or_default(),
+                stats["unique_clicks"].as_i64().unwrap_or_default(),
+                stats["unique_visitors"].as_i64().unwrap_or_default(),
+                stats["bot_clicks"].as_i64().unwrap_or_default(),
+            );
+            print_counts("Daily", &stats["daily"], "date", "clicks");
+            print_counts("Top referrers", &stats["top_referrers"], "value", "count");
+            print_counts("Top user agents", &stats["top_user_agents"], "value", "count");
+            print_counts("Variants", &stats["variants"], "value", "count");
+        }
+        Some(Command::Sign { url, expires_at }) => {
+            let Some(secret) = config.signing_secret.as_deref().filter(|secret| !secret.is_empty()) else {
+                bail!("Set signing_secret to sign links");
+            };
+            validation::validate_link_destination(config, &url).map_err(anyhow::Error::msg)?;
+            println!("{}/{}", config.base_url.trim_end_matches('/'), Signer::new(secret).sign_until(&url, expires_at));
+        }
+    }
+    Ok(())
+}
+
+fn print_counts(heading: &str, entries: &Value, label: &str, count: &str) {
+    let Some(entries) = entries.as_array().filter(|entries| !entries.is_empty()) else {
+        return;
+    };
+    println!("{}:", heading);
+    for entry in entries {
+        println!("  {:>8}  {}", entry[count].as_i64().unwrap_or_default(), entry[label].as_str().unwrap_or_default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_parses_subcommands() {
+        Cli::command().debug_assert();
+        assert_eq!(Cli::try_parse_from(["quickurl"]).unwrap().command, None);
+
+        let cli = Cli::try_parse_from(["quickurl", "add", "https://example.com", "--tag", "a", "--tag", "b", "--key", "qk_x"]).unwrap();
+        assert_eq!(cli.key.as_deref(), Some("qk_x"));
+        assert!(matches!(cli.command, Some(Command::Add { ref tags, .. }) if tags == &["a", "b"]));
+        assert!(Cli::try_parse_from(["quickurl", "rm"]).is_err());
+        let cli = Cli::try_parse_from(["quickurl", "sign", "https://example.com"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Sign { url: "https://example.com".into(), expires_at: None }));
+        let cli = Cli::try_parse_from(["quickurl", "sign", "https://example.com", "--expires-at", "2030-01-01T00:00:00Z"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Sign { expires_at: Some(_), .. })));
+    }
+}
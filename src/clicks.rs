@@ -0,0 +1,298 @@
+use axum::http::{header, HeaderMap};
+use chrono::{DateTime, Utc};
+use sqlx::AnyPool;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::geo::Location;
+use crate::models::ClickEvent;
+use crate::webhooks::{self, WebhookEvent};
+use crate::{broker, storage, telemetry};
+
+// Keep oversized headers from bloating the clicks table
+const MAX_HEADER_LENGTH: usize = 512;
+
+#[derive(Debug)]
+pub struct Click {
+    pub url_id: String,
+    pub clicked_at: DateTime<Utc>,
+    pub referrer: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_hash: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    // A/B variant the visitor was sent to
+    pub variant: Option<String>,
+    // Links with max_clicks are incremented synchronously during the redirect
+    pub counted: bool,
+    // Crawlers and link previews only add to bot_clicks
+    pub bot: bool,
+    // Counted in unique_clicks unless the same visitor clicked within the dedupe window
+    pub visitor_hash: Option<String>,
+    // Queued in the outbox together with the click, as click.recorded webhooks when sampled
+    // and for the event broker when publish is set
+    pub event: Option<ClickEvent>,
+    pub sampled: bool,
+    pub publish: bool,
+}
+
+fn header_value(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.chars().take(MAX_HEADER_LENGTH).collect())
+}
+
+impl Click {
+    pub fn new(url_id: String, headers: &HeaderMap, ip_hash: String, location: Location, counted: bool) -> Self {
+        Self {
+            url_id,
+            clicked_at: storage::now(),
+            referrer: header_value(headers, header::REFERER),
+            user_agent: header_value(headers, header::USER_AGENT),
+            ip_hash: Some(ip_hash),
+            country: location.country,
+            city: location.city,
+            variant: None,
+            counted,
+            bot: false,
+            visitor_hash: None,
+            event: None,
+            sampled: false,
+            publish: false,
+        }
+    }
+
+    // Keeps the click in the counts and daily stats but nothing about who made it
+    pub fn forget_visitor(&mut self) {
+        self.referrer = None;
+        self.user_agent = None;
+        self.ip_hash = None;
+        self.country = None;
+        self.city = None;
+        self.visitor_hash = None;
+    }
+}
+
+// DNT: 1 or its successor Global Privacy Control
+pub fn do_not_track(headers: &HeaderMap) -> bool {
+    ["dnt", "sec-gpc"]
+        .iter()
+        .any(|name| headers.get(*name).is_some_and(|value| value.as_bytes() == b"1"))
+}
+
+// Redirects hand clicks to a background task that writes them in batches, so a busy
+// database never holds up the response. Counts and stats lag by up to one flush interval.
+#[derive(Clone)]
+pub struct ClickRecorder {
+    sender: mpsc::Sender<Click>,
+}
+
+impl ClickRecorder {
+    pub fn spawn(db: AnyPool, flush_interval: Duration, buffer_size: usize, unique_window: Duration) -> Self {
+        let (sender, mut receiver) = mpsc::channel(buffer_size);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(flush_interval);
+            let mut open = true;
+            while open {
+                interval.tick().await;
+
+                let mut batch = Vec::new();
+                loop {
+                    match receiver.try_recv() {
+                        Ok(click) => batch.push(click),
+                        Err(mpsc::error::TryRecvError::Empty) => break,
+                        Err(mpsc::error::TryRecvError::Disconnected) => {
+                            open = false;
+                            break;
+                        }
+                    }
+                }
+
+                if !batch.is_empty() {
+                    if let Err(e) = flush(&db, &batch, unique_window).await {
+                        eprintln!("⚠️  Failed to write {} clicks: {}", batch.len(), e);
+                    }
+                }
+            }
+        });
+
+        Self { sender }
+    }
+
+    pub fn record(&self, click: Click) {
+        if self.sender.try_send(click).is_err() {
+            metrics::counter!(telemetry::CLICKS_DROPPED_TOTAL).increment(1);
+        }
+    }
+}
+
+pub async fn flush(db: &AnyPool, batch: &[Click], unique_window: Duration) -> Result<(), sqlx::Error> {
+    let unique_window = chrono::Duration::from_std(unique_window).unwrap_or(chrono::Duration::MAX);
+    let mut increments: HashMap<&str, (i64, i64)> = HashMap::new();
+    for click in batch.iter().filter(|click| !click.counted) {
+        let (clicks, bots) = increments.entry(&click.url_id).or_default();
+        if click.bot {
+            *bots += 1;
+        } else {
+            *clicks += 1;
+        }
+    }
+
+    let increments = &increments;
+    let write = storage::retry_busy(|| async move {
+        let mut tx = db.begin().await?;
+
+        for (url_id, (clicks, bots)) in increments {
+            sqlx::query("UPDATE urls SET click_count = click_count + $1, bot_clicks = bot_clicks + $2 WHERE id = $3")
+                .bind(*clicks)
+                .bind(*bots)
+                .bind(*url_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // Earlier clicks of the batch are inserted by the time later ones are looked up, so
+        // repeats within one batch are caught as well
+        let mut unique: HashMap<&str, i64> = HashMap::new();
+        for click in batch.iter().filter(|click| !click.bot) {
+            if let Some(visitor_hash) = &click.visitor_hash {
+                let repeat = sqlx::query(
+                    "SELECT 1 FROM clicks WHERE url_id = $1 AND visitor_hash = $2 AND clicked_at > $3 LIMIT 1"
+                )
+                .bind(&click.url_id)
+                .bind(visitor_hash)
+                .bind(storage::ts(click.clicked_at - unique_window))
+                .fetch_optional(&mut *tx)
+                .await?;
+                if repeat.is_none() {
+                    *unique.entry(&click.url_id).or_default() += 1;
+                }
+            }
+
+            // The link may have been deleted since the redirect, skip rather than fail the batch
+            let inserted = sqlx::query(
+                r#"
+                INSERT INTO clicks (url_id, clicked_at, referrer, user_agent, ip_hash, country, city, variant, visitor_hash)
+                SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9
+                WHERE EXISTS (SELECT 1 FROM urls WHERE id = $1)
+                "#
+            )
+            .bind(&click.url_id)
+            .bind(storage::ts(click.clicked_at))
+            .bind(&click.referrer)
+            .bind(&click.user_agent)
+            .bind(&click.ip_hash)
+            .bind(&click.country)
+            .bind(&click.city)
+            .bind(&click.variant)
+            .bind(&click.visitor_hash)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+            if let Some(event) = click.event.as_ref().filter(|_| inserted > 0) {
+                if click.sampled {
+                    let data = serde_json::to_value(event).unwrap_or_default();
+                    webhooks::enqueue(&mut tx, WebhookEvent::ClickRecorded, &click.url_id, &data).await?;
+                }
+                if click.publish {
+                    broker::enqueue(&mut tx, &click.url_id, event).await?;
+                }
+            }
+        }
+
+        for (url_id, visits) in &unique {
+            sqlx::query("UPDATE urls SET unique_clicks = unique_clicks + $1 WHERE id = $2")
+                .bind(*visits)
+                .bind(*url_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await
+    });
+
+    telemetry::timed("flush_clicks", write).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Row;
+
+    #[tokio::test]
+    async fn test_flush_counts_uncounted_clicks() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let now = storage::ts(Utc::now());
+            sqlx::query(
+                r#"
+                INSERT INTO urls (id, token, original_url, created_at, expires_at, click_count)
+                VALUES ('u1', 'abc123', 'https://example.com', $1, $1, 1)
+                "#
+            )
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+
+            let click = |url_id: &str, counted| Click::new(url_id.into(), &HeaderMap::new(), "ip".into(), Location::default(), counted);
+            let mut bot = click("u1", false);
+            bot.bot = true;
+            let batch = vec![click("u1", false), click("u1", false), click("u1", true), click("gone", false), bot];
+            flush(&db, &batch, Duration::from_secs(60)).await.unwrap();
+
+            let row = sqlx::query("SELECT click_count, bot_clicks, (SELECT COUNT(*) FROM clicks) AS clicks FROM urls")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+            assert_eq!(row.get::<i64, _>("click_count"), 3);
+            assert_eq!(row.get::<i64, _>("bot_clicks"), 1);
+            assert_eq!(row.get::<i64, _>("clicks"), 3);
+        }
+    }
+
+    #[test]
+    fn test_do_not_track() {
+        let mut headers = HeaderMap::new();
+        assert!(!do_not_track(&headers));
+        headers.insert("dnt", "0".parse().unwrap());
+        assert!(!do_not_track(&headers));
+        headers.insert("sec-gpc", "1".parse().unwrap());
+        assert!(do_not_track(&headers));
+    }
+
+    #[tokio::test]
+    async fn test_flush_dedupes_visits_within_window() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            sqlx::query(
+                r#"
+                INSERT INTO urls (id, token, original_url, created_at, expires_at, click_count)
+                VALUES ('u1', 'abc123', 'https://example.com', $1, $1, 0)
+                "#
+            )
+            .bind(storage::ts(Utc::now()))
+            .execute(&db)
+            .await
+            .unwrap();
+
+            let visit = |visitor: &str, minutes_ago| {
+                let mut click = Click::new("u1".into(), &HeaderMap::new(), "ip".into(), Location::default(), false);
+                click.clicked_at = Utc::now() - chrono::Duration::minutes(minutes_ago);
+                click.visitor_hash = Some(visitor.into());
+                click
+            };
+            let window = Duration::from_secs(30 * 60);
+            // a twice within the window, b once, then a again after the window has passed
+            flush(&db, &[visit("a", 90), visit("a", 80), visit("b", 80)], window).await.unwrap();
+            flush(&db, &[visit("a", 0)], window).await.unwrap();
+
+            let row = sqlx::query("SELECT click_count, unique_clicks FROM urls").fetch_one(&db).await.unwrap();
+            assert_eq!(row.get::<i64, _>("click_count"), 4);
+            assert_eq!(row.get::<i64, _>("unique_clicks"), 3);
+        }
+    }
+}
@@ -0,0 +1,1010 @@
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::storage::DatabaseOptions;
+use crate::token::TokenGenerator;
+use quickurl_core::signed;
+
+const DEFAULT_CONFIG_PATH: &str = "quickurl.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CleanupMode {
+    Delete,
+    Archive,
+}
+
+impl CleanupMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CleanupMode::Delete => "delete",
+            CleanupMode::Archive => "archive",
+        }
+    }
+}
+
+impl std::str::FromStr for CleanupMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "delete" => Ok(CleanupMode::Delete),
+            "archive" => Ok(CleanupMode::Archive),
+            _ => anyhow::bail!("cleanup mode must be \"delete\" or \"archive\""),
+        }
+    }
+}
+
+// What happens to a link whose destination turns up on a Safe Browsing list
+// How generated tokens are minted. Random tokens are short but instances sharing a database can
+// draw the same one, snowflake ids are unique per worker and millisecond, sequential tokens count
+// up a counter in the database and hash tokens are derived from the destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenMode {
+    Random,
+    Snowflake,
+    Sequential,
+    Hash,
+}
+
+impl std::str::FromStr for TokenMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "random" => Ok(TokenMode::Random),
+            "snowflake" => Ok(TokenMode::Snowflake),
+            "sequential" => Ok(TokenMode::Sequential),
+            "hash" => Ok(TokenMode::Hash),
+            _ => anyhow::bail!("token mode must be \"random\", \"snowflake\", \"sequential\" or \"hash\""),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnsafeLinkAction {
+    Flag,
+    // Visitors see a warning page and have to confirm before they are sent on
+    Warn,
+    Disable,
+}
+
+impl std::str::FromStr for UnsafeLinkAction {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "flag" => Ok(UnsafeLinkAction::Flag),
+            "warn" => Ok(UnsafeLinkAction::Warn),
+            "disable" => Ok(UnsafeLinkAction::Disable),
+            _ => anyhow::bail!("unsafe link action must be \"flag\", \"warn\" or \"disable\""),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub database_url: String,
+    // Replica (e.g. a Postgres hot standby) for redirect lookups, listings, exports and stats,
+    // everything else and links it has not caught up on yet are read from database_url
+    pub database_read_url: Option<String>,
+    // Pool size, and how long a query waits for a free connection before it fails
+    pub database_max_connections: u32,
+    pub database_acquire_timeout_secs: u64,
+    // Pragmas for every SQLite connection: WAL lets redirects read while a click batch is written,
+    // busy_timeout is how long a write waits for the lock before it fails with SQLITE_BUSY
+    pub sqlite_journal_mode: String,
+    pub sqlite_synchronous: String,
+    pub sqlite_busy_timeout_ms: u64,
+    pub base_url: String,
+    // Build short URLs from X-Forwarded-Proto and X-Forwarded-Host (or Host) instead of base_url's
+    // scheme and host, only safe behind a proxy that sets them
+    pub trust_forwarded_headers: bool,
+    // Also serve the JSON endpoints at their paths from before /api/v1, marked deprecated
+    pub legacy_api_paths: bool,
+    // Expiry of links created without expires_at, 0 keeps them forever
+    pub default_ttl_days: i64,
+    pub token_length: usize,
+    // Random tokens grow past token_length once a new one would hit a taken token more often than
+    // once in this many, 0 keeps token_length
+    pub token_collision_odds: u64,
+    // Leave 0/O and 1/l/I out of generated tokens
+    pub exclude_ambiguous_chars: bool,
+    // Characters generated tokens are drawn from instead of A-Z, a-z and 0-9. Letters, digits, '-'
+    // and '_'; exclude_ambiguous_chars and case_insensitive_tokens still filter it.
+    pub token_alphabet: Option<String>,
+    // Draw generated tokens again when they contain an offensive word from the built-in list or
+    // token_blocked_words
+    pub token_word_filter: bool,
+    pub token_blocked_words: Vec<String>,
+    // Serve /token/ like /token
+    pub ignore_trailing_slash: bool,
+    // Match tokens regardless of case. Generated tokens and custom aliases are lowercase then,
+    // snowflake tokens grow to 13 characters.
+    pub case_insensitive_tokens: bool,
+    // "snowflake" ignores token_length, tokens grow to 11 characters. Each instance needs its own
+    // worker id (0 to 1023), one is leased from the database unless instance_id sets it.
+    // "sequential" starts at token_length and only gets longer once every token of that length
+    // is taken. "hash" gives a destination the same token on every instance and dedupes /shorten.
+    pub token_mode: TokenMode,
+    pub instance_id: Option<u16>,
+    // Shuffles sequential and snowflake tokens so consecutive links do not get neighbouring tokens,
+    // the same on every instance. Changing it can map new links onto taken tokens, those are
+    // skipped. Required in hash mode, where it keys the hash; changing it then gives destinations
+    // new tokens.
+    pub token_scramble_key: Option<String>,
+    // Extra words and prefixes links may not use, on top of the built-in system routes
+    pub reserved_tokens: Vec<String>,
+    pub reserved_prefixes: Vec<String>,
+    pub admin_key: Option<String>,
+    pub ip_hash_salt: String,
+    // Repeat clicks by one visitor within this many seconds count as one unique click, at most
+    // a day since visitor hashes change daily
+    pub unique_click_window_secs: u64,
+    // Hash only the network part of IPs, keep no user agents and nothing about visitors that send
+    // DNT or Sec-GPC
+    pub privacy_mode: bool,
+    // Click events older than this are deleted by the cleanup job, 0 keeps them forever
+    pub click_retention_days: i64,
+    // Seconds between roll-ups of finished days into daily aggregates, 0 leaves it to the cleanup job
+    pub stats_rollup_interval_secs: u64,
+    // Per user account limits checked when links are created, 0 for no limit. The daily one counts
+    // links created since midnight UTC, not requests.
+    pub max_links_per_user: u64,
+    pub max_links_per_user_per_day: u64,
+    pub max_custom_aliases_per_user: u64,
+    // Path to a MaxMind GeoLite2/GeoIP2 City database, clicks get no location when unset
+    pub geoip_database: Option<String>,
+    // Requests per minute per client, 0 disables the limit
+    pub write_rate_limit_per_minute: u32,
+    pub redirect_rate_limit_per_minute: u32,
+    pub max_batch_size: usize,
+    pub max_import_rows: usize,
+    // Seconds between expired link sweeps, 0 disables the background job
+    pub cleanup_interval_secs: u64,
+    pub cleanup_mode: CleanupMode,
+    // Cron schedules by job name (cleanup, stats_rollup, link_check, safe_browsing_rescan) in
+    // place of their interval setting
+    pub job_schedules: HashMap<String, String>,
+    // Up to this many seconds of random delay before a due job is claimed
+    pub job_jitter_secs: u64,
+    // Return the existing link for an already shortened destination unless ?dedupe=false
+    pub dedupe_by_default: bool,
+    // GET /shorten creates links without credentials, e.g. for a public bookmarklet
+    pub anonymous_get_shorten: bool,
+    pub max_url_length: usize,
+    // Query parameters dropped from destinations before they are stored, a trailing * matches
+    // any suffix, e.g. "utm_*"
+    pub strip_query_params: Vec<String>,
+    // Also put the remaining query parameters in order, so reordered copies dedupe
+    pub sort_query_params: bool,
+    // Permit destinations on localhost and private networks, off to avoid SSRF-style abuse
+    pub allow_private_destinations: bool,
+    // Destination schemes accepted besides http and https, e.g. "mailto" or "tel". javascript,
+    // data and other schemes that run in the visitor's browser are refused even when listed.
+    pub allowed_schemes: Vec<String>,
+    // Apply the destination block and allow lists to existing links when they are followed
+    pub check_destinations_on_redirect: bool,
+    // HEAD requests on short links, usually link checkers, count as clicks
+    pub count_head_requests: bool,
+    // Crawlers and link previews, recognised by their user agent, count as clicks
+    pub count_bot_clicks: bool,
+    // Google Safe Browsing Lookup API key and/or a file of hex SHA-256 hash prefixes, new
+    // destinations are refused and live links rescanned when either is set
+    pub safe_browsing_api_key: Option<String>,
+    pub safe_browsing_prefix_file: Option<String>,
+    // Seconds between rescans of live links, 0 only checks on creation
+    pub safe_browsing_rescan_interval_secs: u64,
+    // Seconds between checks that destinations still answer, 0 only checks on POST /admin/check-links
+    pub link_check_interval_secs: u64,
+    pub link_check_timeout_secs: u64,
+    // Where links whose destination the check found gone (404, 410, unreachable) redirect
+    // instead: "wayback" or a URL in which {url} is replaced by the encoded destination
+    pub dead_link_fallback: Option<String>,
+    pub unsafe_link_action: UnsafeLinkAction,
+    // HTML file visitors of a disabled link see with the 410, a built-in page when unset
+    pub disabled_link_page: Option<String>,
+    // What visitors of an unknown token, or of an expired or used up link, get instead of the
+    // JSON problem: an HTML file sent with the 404 or 410, or an http(s) URL they are sent on to
+    // with a 302. {token} is replaced with the requested token in either.
+    pub not_found_page: Option<String>,
+    pub expired_link_page: Option<String>,
+    // Unknown tokens redirect here (302) instead, on custom domains without their own fallback_url
+    pub fallback_url: Option<String>,
+    // Served instead of the built-in robots.txt (everything but /api/ and /admin may be crawled)
+    // and favicon, security.txt is only served when set
+    pub robots_txt_file: Option<String>,
+    pub favicon_file: Option<String>,
+    pub security_txt_file: Option<String>,
+    // Signs user session tokens, a random secret is generated when unset
+    pub jwt_secret: Option<String>,
+    pub jwt_ttl_hours: i64,
+    // Shared with the services minting signed links (/:payload/:signature), they are refused when
+    // unset. At least 32 bytes.
+    pub signing_secret: Option<String>,
+    // OpenID Connect sign-in (Keycloak, Google, ...), on when oidc_issuer is set. Register
+    // {base_url}/auth/oidc/callback as the redirect URI with the provider.
+    pub oidc_issuer: Option<String>,
+    pub oidc_client_id: String,
+    pub oidc_client_secret: Option<String>,
+    pub oidc_scopes: String,
+    // Claim listing the provider's roles or groups, dotted for nested ones like
+    // realm_access.roles. When set, each sign-in makes the account admin if one of them is in
+    // oidc_admin_values, read_only if one is in oidc_read_only_values, and editor otherwise.
+    pub oidc_role_claim: Option<String>,
+    pub oidc_admin_values: Vec<String>,
+    pub oidc_read_only_values: Vec<String>,
+    // Links kept in the in-memory redirect cache, 0 disables it
+    pub redirect_cache_capacity: u64,
+    pub redirect_cache_ttl_secs: u64,
+    // Cache-Control max-age for plain redirects, so browsers and CDNs can reuse them. Clicks
+    // served from their caches are not counted, 0 makes every click reach the server.
+    pub redirect_max_age_secs: u64,
+    // Shares cached links (for redirect_cache_ttl_secs) and click limit counters between
+    // instances, e.g. redis://127.0.0.1:6379, keys start with redis_key_prefix
+    pub redis_url: Option<String>,
+    pub redis_key_prefix: String,
+    // Clicks are buffered in memory and written in batches at this interval
+    pub click_flush_interval_ms: u64,
+    pub click_buffer_size: usize,
+    // Webhook deliveries are retried with backoff up to max_attempts, each with this timeout
+    pub webhook_max_attempts: u32,
+    pub webhook_timeout_secs: u64,
+    // Share of redirects sent as click.recorded events, 0 to 100
+    pub webhook_click_sample_percent: u32,
+    // Every click is also published as JSON on event_topic, to nats://host:4222 (JetStream) or
+    // kafka://host:9092,host2:9092. Events wait in the outbox until the broker acknowledges them.
+    pub event_broker_url: Option<String>,
+    pub event_topic: String,
+    // How often webhook deliveries and click events are looked for in the outbox when it was empty
+    pub outbox_poll_interval_ms: u64,
+    // Links created without a title get one from the destination page, fetched in the background
+    pub fetch_titles: bool,
+    pub title_fetch_timeout_secs: u64,
+    // OTLP/HTTP collector (Jaeger, Tempo, ...) such as http://localhost:4318, spans of requests and
+    // their database queries are exported when set
+    pub otlp_endpoint: Option<String>,
+    pub otlp_service_name: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".into(),
+            port: 3000,
+            database_url: "sqlite:quickurl.db".into(),
+            database_read_url: None,
+            database_max_connections: 10,
+            database_acquire_timeout_secs: 10,
+            sqlite_journal_mode: "wal".into(),
+            sqlite_synchronous: "normal".into(),
+            sqlite_busy_timeout_ms: 5000,
+            base_url: "http://localhost:3000".into(),
+            trust_forwarded_headers: false,
+            legacy_api_paths: true,
+            default_ttl_days: 30,
+            token_length: 6,
+            token_collision_odds: 1000,
+            exclude_ambiguous_chars: false,
+            token_alphabet: None,
+            token_word_filter: true,
+            token_blocked_words: Vec::new(),
+            ignore_trailing_slash: true,
+            case_insensitive_tokens: false,
+            token_mode: TokenMode::Random,
+            instance_id: None,
+            token_scramble_key: None,
+            reserved_tokens: Vec::new(),
+            reserved_prefixes: Vec::new(),
+            admin_key: None,
+            ip_hash_salt: "quickurl".into(),
+            unique_click_window_secs: 1800,
+            privacy_mode: false,
+            click_retention_days: 0,
+            stats_rollup_interval_secs: 3600,
+            max_links_per_user: 0,
+            max_links_per_user_per_day: 0,
+            max_custom_aliases_per_user: 0,
+            geoip_database: None,
+            write_rate_limit_per_minute: 30,
+            redirect_rate_limit_per_minute: 600,
+            max_batch_size: 500,
+            max_import_rows: 10_000,
+            cleanup_interval_secs: 3600,
+            cleanup_mode: CleanupMode::Delete,
+            job_schedules: HashMap::new(),
+            job_jitter_secs: 30,
+            dedupe_by_default: false,
+            anonymous_get_shorten: false,
+            max_url_length: 2048,
+            strip_query_params: ["fbclid", "gclid", "dclid", "msclkid", "igshid", "mc_eid"]
+                .map(String::from)
+                .to_vec(),
+            sort_query_params: false,
+            allow_private_destinations: false,
+            allowed_schemes: Vec::new(),
+            check_destinations_on_redirect: false,
+            count_head_requests: false,
+            count_bot_clicks: false,
+            safe_browsing_api_key: None,
+            safe_browsing_prefix_file: None,
+            safe_browsing_rescan_interval_secs: 86_400,
+            link_check_interval_secs: 0,
+            link_check_timeout_secs: 10,
+            dead_link_fallback: None,
+            disabled_link_page: None,
+            not_found_page: None,
+            expired_link_page: None,
+            fallback_url: None,
+            robots_txt_file: None,
+            favicon_file: None,
+            security_txt_file: None,
+            unsafe_link_action: UnsafeLinkAction::Flag,
+            jwt_secret: None,
+            jwt_ttl_hours: 24,
+            signing_secret: None,
+            oidc_issuer: None,
+            oidc_client_id: String::new(),
+            oidc_client_secret: None,
+            oidc_scopes: "openid email profile".into(),
+            oidc_role_claim: None,
+            oidc_admin_values: Vec::new(),
+            oidc_read_only_values: Vec::new(),
+            redirect_cache_capacity: 10_000,
+            redirect_cache_ttl_secs: 60,
+            redirect_max_age_secs: 0,
+            redis_url: None,
+            redis_key_prefix: "quickurl:".into(),
+            click_flush_interval_ms: 1000,
+            click_buffer_size: 10_000,
+            webhook_max_attempts: 5,
+            webhook_timeout_secs: 10,
+            webhook_click_sample_percent: 10,
+            event_broker_url: None,
+            event_topic: "quickurl.clicks".into(),
+            outbox_poll_interval_ms: 1000,
+            fetch_titles: false,
+            title_fetch_timeout_secs: 5,
+            otlp_endpoint: None,
+            otlp_service_name: "quickurl".into(),
+        }
+    }
+}
+
+impl Config {
+    // Defaults, then the TOML file (QUICKURL_CONFIG or ./quickurl.toml), then QUICKURL_* env vars
+    pub fn load() -> anyhow::Result<Self> {
+        let explicit_path = std::env::var("QUICKURL_CONFIG").ok();
+        let path = explicit_path.as_deref().unwrap_or(DEFAULT_CONFIG_PATH);
+
+        let mut config = if explicit_path.is_some() || Path::new(path).exists() {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read config file {}", path))?;
+            Self::from_toml(&contents).with_context(|| format!("invalid config file {}", path))?
+        } else {
+            Self::default()
+        };
+
+        config.apply_env(|name| std::env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_toml(contents: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> anyhow::Result<()> {
+        if let Some(host) = var("QUICKURL_HOST") {
+            self.host = host;
+        }
+        if let Some(port) = var("QUICKURL_PORT") {
+            self.port = port.parse().context("QUICKURL_PORT must be a port number")?;
+        }
+        if let Some(database_url) = var("QUICKURL_DATABASE_URL") {
+            self.database_url = database_url;
+        }
+        if let Some(database_read_url) = var("QUICKURL_DATABASE_READ_URL") {
+            self.database_read_url = Some(database_read_url);
+        }
+        if let Some(size) = var("QUICKURL_DATABASE_MAX_CONNECTIONS") {
+            self.database_max_connections = size
+                .parse()
+                .context("QUICKURL_DATABASE_MAX_CONNECTIONS must be an integer")?;
+        }
+        if let Some(secs) = var("QUICKURL_DATABASE_ACQUIRE_TIMEOUT_SECS") {
+            self.database_acquire_timeout_secs = secs
+                .parse()
+                .context("QUICKURL_DATABASE_ACQUIRE_TIMEOUT_SECS must be an integer")?;
+        }
+        if let Some(mode) = var("QUICKURL_SQLITE_JOURNAL_MODE") {
+            self.sqlite_journal_mode = mode;
+        }
+        if let Some(level) = var("QUICKURL_SQLITE_SYNCHRONOUS") {
+            self.sqlite_synchronous = level;
+        }
+        if let Some(ms) = var("QUICKURL_SQLITE_BUSY_TIMEOUT_MS") {
+            self.sqlite_busy_timeout_ms = ms
+                .parse()
+                .context("QUICKURL_SQLITE_BUSY_TIMEOUT_MS must be an integer")?;
+        }
+        if let Some(base_url) = var("QUICKURL_BASE_URL") {
+            self.base_url = base_url;
+        }
+        if let Some(trust) = var("QUICKURL_TRUST_FORWARDED_HEADERS") {
+            self.trust_forwarded_headers = trust
+                .parse()
+                .context("QUICKURL_TRUST_FORWARDED_HEADERS must be true or false")?;
+        }
+        if let Some(days) = var("QUICKURL_DEFAULT_TTL_DAYS") {
+            self.default_ttl_days = days
+                .parse()
+                .context("QUICKURL_DEFAULT_TTL_DAYS must be an integer")?;
+        }
+        if let Some(legacy) = var("QUICKURL_LEGACY_API_PATHS") {
+            self.legacy_api_paths = legacy
+                .parse()
+                .context("QUICKURL_LEGACY_API_PATHS must be true or false")?;
+        }
+        if let Some(length) = var("QUICKURL_TOKEN_LENGTH") {
+            self.token_length = length
+                .parse()
+                .context("QUICKURL_TOKEN_LENGTH must be an integer")?;
+        }
+        if let Some(odds) = var("QUICKURL_TOKEN_COLLISION_ODDS") {
+            self.token_collision_odds = odds
+                .parse()
+                .context("QUICKURL_TOKEN_COLLISION_ODDS must be an integer")?;
+        }
+        if let Some(exclude) = var("QUICKURL_EXCLUDE_AMBIGUOUS_CHARS") {
+            self.exclude_ambiguous_chars = exclude
+                .parse()
+                .context("QUICKURL_EXCLUDE_AMBIGUOUS_CHARS must be true or false")?;
+        }
+        if let Some(alphabet) = var("QUICKURL_TOKEN_ALPHABET") {
+            self.token_alphabet = Some(alphabet);
+        }
+        if let Some(filter) = var("QUICKURL_TOKEN_WORD_FILTER") {
+            self.token_word_filter = filter
+                .parse()
+                .context("QUICKURL_TOKEN_WORD_FILTER must be true or false")?;
+        }
+        if let Some(words) = var("QUICKURL_TOKEN_BLOCKED_WORDS") {
+            self.token_blocked_words = split_list(&words);
+        }
+        if let Some(ignore) = var("QUICKURL_IGNORE_TRAILING_SLASH") {
+            self.ignore_trailing_slash = ignore
+                .parse()
+                .context("QUICKURL_IGNORE_TRAILING_SLASH must be true or false")?;
+        }
+        if let Some(insensitive) = var("QUICKURL_CASE_INSENSITIVE_TOKENS") {
+            self.case_insensitive_tokens = insensitive
+                .parse()
+                .context("QUICKURL_CASE_INSENSITIVE_TOKENS must be true or false")?;
+        }
+        if let Some(mode) = var("QUICKURL_TOKEN_MODE") {
+            self.token_mode = mode.parse()?;
+        }
+        if let Some(id) = var("QUICKURL_INSTANCE_ID") {
+            self.instance_id = Some(id.parse().context("QUICKURL_INSTANCE_ID must be an integer")?);
+        }
+        if let Some(key) = var("QUICKURL_TOKEN_SCRAMBLE_KEY") {
+            self.token_scramble_key = Some(key);
+        }
+        if let Some(words) = var("QUICKURL_RESERVED_TOKENS") {
+            self.reserved_tokens = split_list(&words);
+        }
+        if let Some(prefixes) = var("QUICKURL_RESERVED_PREFIXES") {
+            self.reserved_prefixes = split_list(&prefixes);
+        }
+        if let Some(admin_key) = var("QUICKURL_ADMIN_KEY") {
+            self.admin_key = Some(admin_key);
+        }
+        if let Some(salt) = var("QUICKURL_IP_HASH_SALT") {
+            self.ip_hash_salt = salt;
+        }
+        if let Some(secs) = var("QUICKURL_UNIQUE_CLICK_WINDOW_SECS") {
+            self.unique_click_window_secs = secs
+                .parse()
+                .context("QUICKURL_UNIQUE_CLICK_WINDOW_SECS must be an integer")?;
+        }
+        if let Some(privacy) = var("QUICKURL_PRIVACY_MODE") {
+            self.privacy_mode = privacy
+                .parse()
+                .context("QUICKURL_PRIVACY_MODE must be true or false")?;
+        }
+        if let Some(days) = var("QUICKURL_CLICK_RETENTION_DAYS") {
+            self.click_retention_days = days
+                .parse()
+                .context("QUICKURL_CLICK_RETENTION_DAYS must be an integer")?;
+        }
+        if let Some(secs) = var("QUICKURL_STATS_ROLLUP_INTERVAL_SECS") {
+            self.stats_rollup_interval_secs = secs
+                .parse()
+                .context("QUICKURL_STATS_ROLLUP_INTERVAL_SECS must be an integer")?;
+        }
+        if let Some(max) = var("QUICKURL_MAX_LINKS_PER_USER") {
+            self.max_links_per_user = max
+                .parse()
+                .context("QUICKURL_MAX_LINKS_PER_USER must be an integer")?;
+        }
+        if let Some(max) = var("QUICKURL_MAX_LINKS_PER_USER_PER_DAY") {
+            self.max_links_per_user_per_day = max
+                .parse()
+                .context("QUICKURL_MAX_LINKS_PER_USER_PER_DAY must be an integer")?;
+        }
+        if let Some(max) = var("QUICKURL_MAX_CUSTOM_ALIASES_PER_USER") {
+            self.max_custom_aliases_per_user = max
+                .parse()
+                .context("QUICKURL_MAX_CUSTOM_ALIASES_PER_USER must be an integer")?;
+        }
+        if let Some(path) = var("QUICKURL_GEOIP_DATABASE") {
+            self.geoip_database = Some(path);
+        }
+        if let Some(limit) = var("QUICKURL_WRITE_RATE_LIMIT_PER_MINUTE") {
+            self.write_rate_limit_per_minute = limit
+                .parse()
+                .context("QUICKURL_WRITE_RATE_LIMIT_PER_MINUTE must be an integer")?;
+        }
+        if let Some(limit) = var("QUICKURL_REDIRECT_RATE_LIMIT_PER_MINUTE") {
+            self.redirect_rate_limit_per_minute = limit
+                .parse()
+                .context("QUICKURL_REDIRECT_RATE_LIMIT_PER_MINUTE must be an integer")?;
+        }
+        if let Some(size) = var("QUICKURL_MAX_BATCH_SIZE") {
+            self.max_batch_size = size
+                .parse()
+                .context("QUICKURL_MAX_BATCH_SIZE must be an integer")?;
+        }
+        if let Some(rows) = var("QUICKURL_MAX_IMPORT_ROWS") {
+            self.max_import_rows = rows
+                .parse()
+                .context("QUICKURL_MAX_IMPORT_ROWS must be an integer")?;
+        }
+        if let Some(secs) = var("QUICKURL_CLEANUP_INTERVAL_SECS") {
+            self.cleanup_interval_secs = secs
+                .parse()
+                .context("QUICKURL_CLEANUP_INTERVAL_SECS must be an integer")?;
+        }
+        if let Some(mode) = var("QUICKURL_CLEANUP_MODE") {
+            self.cleanup_mode = mode.parse()?;
+        }
+        // Cron expressions can contain commas, so entries are separated by semicolons
+        if let Some(schedules) = var("QUICKURL_JOB_SCHEDULES") {
+            self.job_schedules = schedules
+                .split(';')
+                .filter(|entry| !entry.trim().is_empty())
+                .map(|entry| {
+                    let (name, schedule) = entry
+                        .split_once('=')
+                        .context("QUICKURL_JOB_SCHEDULES entries must look like name=schedule")?;
+                    Ok((name.trim().to_string(), schedule.trim().to_string()))
+                })
+                .collect::<anyhow::Result<_>>()?;
+        }
+        if let Some(secs) = var("QUICKURL_JOB_JITTER_SECS") {
+            self.job_jitter_secs = secs
+                .parse()
+                .context("QUICKURL_JOB_JITTER_SECS must be an integer")?;
+        }
+        if let Some(dedupe) = var("QUICKURL_DEDUPE_BY_DEFAULT") {
+            self.dedupe_by_default = dedupe
+                .parse()
+                .context("QUICKURL_DEDUPE_BY_DEFAULT must be true or false")?;
+        }
+        if let Some(anonymous) = var("QUICKURL_ANONYMOUS_GET_SHORTEN") {
+            self.anonymous_get_shorten = anonymous
+                .parse()
+                .context("QUICKURL_ANONYMOUS_GET_SHORTEN must be true or false")?;
+        }
+        if let Some(length) = var("QUICKURL_MAX_URL_LENGTH") {
+            self.max_url_length = length
+                .parse()
+                .context("QUICKURL_MAX_URL_LENGTH must be an integer")?;
+        }
+        if let Some(params) = var("QUICKURL_STRIP_QUERY_PARAMS") {
+            self.strip_query_params = split_list(&params);
+        }
+        if let Some(sort) = var("QUICKURL_SORT_QUERY_PARAMS") {
+            self.sort_query_params = sort
+                .parse()
+                .context("QUICKURL_SORT_QUERY_PARAMS must be true or false")?;
+        }
+        if let Some(allow) = var("QUICKURL_ALLOW_PRIVATE_DESTINATIONS") {
+            self.allow_private_destinations = allow
+                .parse()
+                .context("QUICKURL_ALLOW_PRIVATE_DESTINATIONS must be true or false")?;
+        }
+        if let Some(schemes) = var("QUICKURL_ALLOWED_SCHEMES") {
+            self.allowed_schemes = split_list(&schemes);
+        }
+        if let Some(check) = var("QUICKURL_CHECK_DESTINATIONS_ON_REDIRECT") {
+            self.check_destinations_on_redirect = check
+                .parse()
+                .context("QUICKURL_CHECK_DESTINATIONS_ON_REDIRECT must be true or false")?;
+        }
+        if let Some(count) = var("QUICKURL_COUNT_HEAD_REQUESTS") {
+            self.count_head_requests = count
+                .parse()
+                .context("QUICKURL_COUNT_HEAD_REQUESTS must be true or false")?;
+        }
+        if let Some(count) = var("QUICKURL_COUNT_BOT_CLICKS") {
+            self.count_bot_clicks = count
+                .parse()
+                .context("QUICKURL_COUNT_BOT_CLICKS must be true or false")?;
+        }
+        if let Some(key) = var("QUICKURL_SAFE_BROWSING_API_KEY") {
+            self.safe_browsing_api_key = Some(key);
+        }
+        if let Some(path) = var("QUICKURL_SAFE_BROWSING_PREFIX_FILE") {
+            self.safe_browsing_prefix_file = Some(path);
+        }
+        if let Some(secs) = var("QUICKURL_SAFE_BROWSING_RESCAN_INTERVAL_SECS") {
+            self.safe_browsing_rescan_interval_secs = secs
+                .parse()
+                .context("QUICKURL_SAFE_BROWSING_RESCAN_INTERVAL_SECS must be an integer")?;
+        }
+        if let Some(secs) = var("QUICKURL_LINK_CHECK_INTERVAL_SECS") {
+            self.link_check_interval_secs = secs
+                .parse()
+                .context("QUICKURL_LINK_CHECK_INTERVAL_SECS must be an integer")?;
+        }
+        if let Some(secs) = var("QUICKURL_LINK_CHECK_TIMEOUT_SECS") {
+            self.link_check_timeout_secs = secs
+                .parse()
+                .context("QUICKURL_LINK_CHECK_TIMEOUT_SECS must be an integer")?;
+        }
+        if let Some(fallback) = var("QUICKURL_DEAD_LINK_FALLBACK") {
+            self.dead_link_fallback = Some(fallback);
+        }
+        if let Some(path) = var("QUICKURL_DISABLED_LINK_PAGE") {
+            self.disabled_link_page = Some(path);
+        }
+        if let Some(page) = var("QUICKURL_NOT_FOUND_PAGE") {
+            self.not_found_page = Some(page);
+        }
+        if let Some(page) = var("QUICKURL_EXPIRED_LINK_PAGE") {
+            self.expired_link_page = Some(page);
+        }
+        if let Some(url) = var("QUICKURL_FALLBACK_URL") {
+            self.fallback_url = Some(url);
+        }
+        if let Some(path) = var("QUICKURL_ROBOTS_TXT_FILE") {
+            self.robots_txt_file = Some(path);
+        }
+        if let Some(path) = var("QUICKURL_FAVICON_FILE") {
+            self.favicon_file = Some(path);
+        }
+        if let Some(path) = var("QUICKURL_SECURITY_TXT_FILE") {
+            self.security_txt_file = Some(path);
+        }
+        if let Some(action) = var("QUICKURL_UNSAFE_LINK_ACTION") {
+            self.unsafe_link_action = action.parse()?;
+        }
+        if let Some(secret) = var("QUICKURL_JWT_SECRET") {
+            self.jwt_secret = Some(secret);
+        }
+        if let Some(hours) = var("QUICKURL_JWT_TTL_HOURS") {
+            self.jwt_ttl_hours = hours
+                .parse()
+                .context("QUICKURL_JWT_TTL_HOURS must be an integer")?;
+        }
+        if let Some(secret) = var("QUICKURL_SIGNING_SECRET") {
+            self.signing_secret = Some(secret);
+        }
+        if let Some(issuer) = var("QUICKURL_OIDC_ISSUER") {
+            self.oidc_issuer = Some(issuer);
+        }
+        if let Some(client_id) = var("QUICKURL_OIDC_CLIENT_ID") {
+            self.oidc_client_id = client_id;
+        }
+        if let Some(secret) = var("QUICKURL_OIDC_CLIENT_SECRET") {
+            self.oidc_client_secret = Some(secret);
+        }
+        if let Some(scopes) = var("QUICKURL_OIDC_SCOPES") {
+            self.oidc_scopes = scopes;
+        }
+        if let Some(claim) = var("QUICKURL_OIDC_ROLE_CLAIM") {
+            self.oidc_role_claim = Some(claim);
+        }
+        if let Some(values) = var("QUICKURL_OIDC_ADMIN_VALUES") {
+            self.oidc_admin_values = split_list(&values);
+        }
+        if let Some(values) = var("QUICKURL_OIDC_READ_ONLY_VALUES") {
+            self.oidc_read_only_values = split_list(&values);
+        }
+        if let Some(capacity) = var("QUICKURL_REDIRECT_CACHE_CAPACITY") {
+            self.redirect_cache_capacity = capacity
+                .parse()
+                .context("QUICKURL_REDIRECT_CACHE_CAPACITY must be an integer")?;
+        }
+        if let Some(secs) = var("QUICKURL_REDIRECT_CACHE_TTL_SECS") {
+            self.redirect_cache_ttl_secs = secs
+                .parse()
+                .context("QUICKURL_REDIRECT_CACHE_TTL_SECS must be an integer")?;
+        }
+        if let Some(secs) = var("QUICKURL_REDIRECT_MAX_AGE_SECS") {
+            self.redirect_max_age_secs = secs
+                .parse()
+                .context("QUICKURL_REDIRECT_MAX_AGE_SECS must be an integer")?;
+        }
+        if let Some(url) = var("QUICKURL_REDIS_URL") {
+            self.redis_url = Some(url);
+        }
+        if let Some(prefix) = var("QUICKURL_REDIS_KEY_PREFIX") {
+            self.redis_key_prefix = prefix;
+        }
+        if let Some(ms) = var("QUICKURL_CLICK_FLUSH_INTERVAL_MS") {
+            self.click_flush_interval_ms = ms
+                .parse()
+                .context("QUICKURL_CLICK_FLUSH_INTERVAL_MS must be an integer")?;
+        }
+        if let Some(size) = var("QUICKURL_CLICK_BUFFER_SIZE") {
+            self.click_buffer_size = size
+                .parse()
+                .context("QUICKURL_CLICK_BUFFER_SIZE must be an integer")?;
+        }
+        if let Some(attempts) = var("QUICKURL_WEBHOOK_MAX_ATTEMPTS") {
+            self.webhook_max_attempts = attempts
+                .parse()
+                .context("QUICKURL_WEBHOOK_MAX_ATTEMPTS must be an integer")?;
+        }
+        if let Some(secs) = var("QUICKURL_WEBHOOK_TIMEOUT_SECS") {
+            self.webhook_timeout_secs = secs
+                .parse()
+                .context("QUICKURL_WEBHOOK_TIMEOUT_SECS must be an integer")?;
+        }
+        if let Some(percent) = var("QUICKURL_WEBHOOK_CLICK_SAMPLE_PERCENT") {
+            self.webhook_click_sample_percent = percent
+                .parse()
+                .context("QUICKURL_WEBHOOK_CLICK_SAMPLE_PERCENT must be an integer")?;
+        }
+        if let Some(url) = var("QUICKURL_EVENT_BROKER_URL") {
+            self.event_broker_url = Some(url);
+        }
+        if let Some(topic) = var("QUICKURL_EVENT_TOPIC") {
+            self.event_topic = topic;
+        }
+        if let Some(ms) = var("QUICKURL_OUTBOX_POLL_INTERVAL_MS") {
+            self.outbox_poll_interval_ms = ms
+                .parse()
+                .context("QUICKURL_OUTBOX_POLL_INTERVAL_MS must be an integer")?;
+        }
+        if let Some(fetch) = var("QUICKURL_FETCH_TITLES") {
+            self.fetch_titles = fetch
+                .parse()
+                .context("QUICKURL_FETCH_TITLES must be true or false")?;
+        }
+        if let Some(secs) = var("QUICKURL_TITLE_FETCH_TIMEOUT_SECS") {
+            self.title_fetch_timeout_secs = secs
+                .parse()
+                .context("QUICKURL_TITLE_FETCH_TIMEOUT_SECS must be an integer")?;
+        }
+        if let Some(endpoint) = var("QUICKURL_OTLP_ENDPOINT") {
+            self.otlp_endpoint = Some(endpoint);
+        }
+        if let Some(name) = var("QUICKURL_OTLP_SERVICE_NAME") {
+            self.otlp_service_name = name;
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        // Checked here, adding a bigger lifetime to created_at would overflow on the first link
+        if !(0..=36_500).contains(&self.default_ttl_days) {
+            anyhow::bail!("default_ttl_days must be between 0 and 36500");
+        }
+        // Unkeyed, anyone could compute the token of a destination and see whether it was shortened
+        if self.token_mode == TokenMode::Hash && self.token_scramble_key.as_deref().unwrap_or_default().is_empty() {
+            anyhow::bail!("token_mode = \"hash\" needs token_scramble_key, the same on every instance");
+        }
+        if let Some(secret) = self.signing_secret.as_deref().filter(|secret| !secret.is_empty()) {
+            if secret.len() < signed::MIN_SECRET_BYTES {
+                anyhow::bail!("signing_secret must be at least {} bytes", signed::MIN_SECRET_BYTES);
+            }
+        }
+        if !(4..=64).contains(&self.token_length) {
+            anyhow::bail!("token_length must be between 4 and 64");
+        }
+        if let Some(alphabet) = self.token_alphabet.as_deref() {
+            if !alphabet.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                anyhow::bail!("token_alphabet may only contain letters, digits, '-' and '_'");
+            }
+            if alphabet.bytes().enumerate().any(|(i, c)| alphabet.as_bytes()[..i].contains(&c)) {
+                anyhow::bail!("token_alphabet must not repeat characters");
+            }
+            let charset = TokenGenerator::with_length(self.token_length)
+                .alphabet(Some(alphabet))
+                .exclude_ambiguous(self.exclude_ambiguous_chars)
+                .lowercase(self.case_insensitive_tokens);
+            if charset.charset().len() < 2 {
+                anyhow::bail!(
+                    "token_alphabet needs at least 2 characters left after exclude_ambiguous_chars and case_insensitive_tokens"
+                );
+            }
+        }
+        if !(1..=86400).contains(&self.unique_click_window_secs) {
+            anyhow::bail!("unique_click_window_secs must be between 1 and 86400");
+        }
+        if self.click_retention_days < 0 {
+            anyhow::bail!("click_retention_days must not be negative");
+        }
+        if self.jwt_ttl_hours < 1 {
+            anyhow::bail!("jwt_ttl_hours must be at least 1");
+        }
+        if self.oidc_issuer.as_deref().is_some_and(|issuer| !issuer.is_empty()) {
+            if self.oidc_client_id.is_empty() {
+                anyhow::bail!("oidc_client_id is required with oidc_issuer");
+            }
+            if !self.oidc_scopes.split_whitespace().any(|scope| scope == "openid") {
+                anyhow::bail!("oidc_scopes must include openid");
+            }
+        }
+        if self.click_flush_interval_ms == 0 || self.click_buffer_size == 0 {
+            anyhow::bail!("click_flush_interval_ms and click_buffer_size must be at least 1");
+        }
+        if self.webhook_max_attempts == 0 || self.webhook_timeout_secs == 0 {
+            anyhow::bail!("webhook_max_attempts and webhook_timeout_secs must be at least 1");
+        }
+        if self.webhook_click_sample_percent > 100 {
+            anyhow::bail!("webhook_click_sample_percent must be between 0 and 100");
+        }
+        if self.outbox_poll_interval_ms == 0 {
+            anyhow::bail!("outbox_poll_interval_ms must be at least 1");
+        }
+        if let Some(url) = self.event_broker_url.as_deref().filter(|url| !url.is_empty()) {
+            crate::broker::BrokerUrl::parse(url)?;
+            if self.event_topic.is_empty() {
+                anyhow::bail!("event_topic must not be empty");
+            }
+        }
+        if let Some(fallback) = self.dead_link_fallback.as_deref().filter(|fallback| !fallback.is_empty()) {
+            if fallback != "wayback" && url::Url::parse(fallback).is_err() {
+                anyhow::bail!("dead_link_fallback must be \"wayback\" or a URL");
+            }
+        }
+        if self.title_fetch_timeout_secs == 0 || self.link_check_timeout_secs == 0 {
+            anyhow::bail!("title_fetch_timeout_secs and link_check_timeout_secs must be at least 1");
+        }
+        if self.database_max_connections == 0 || self.database_acquire_timeout_secs == 0 {
+            anyhow::bail!("database_max_connections and database_acquire_timeout_secs must be at least 1");
+        }
+        // Both end up in PRAGMA statements, so only the documented values get through
+        let journal_mode = self.sqlite_journal_mode.to_ascii_lowercase();
+        if !["delete", "truncate", "persist", "memory", "wal", "off"].contains(&journal_mode.as_str()) {
+            anyhow::bail!("sqlite_journal_mode must be one of delete, truncate, persist, memory, wal or off");
+        }
+        let synchronous = self.sqlite_synchronous.to_ascii_lowercase();
+        if !["off", "normal", "full", "extra"].contains(&synchronous.as_str()) {
+            anyhow::bail!("sqlite_synchronous must be one of off, normal, full or extra");
+        }
+        if let Some(scheme) = self.allowed_schemes.iter().find(|scheme| crate::validation::is_forbidden_scheme(scheme)) {
+            anyhow::bail!("allowed_schemes: {} can never be allowed", scheme);
+        }
+        if self.instance_id.is_some_and(|id| id > 1023) {
+            anyhow::bail!("instance_id must be between 0 and 1023");
+        }
+        for (name, schedule) in &self.job_schedules {
+            if !crate::scheduler::JOBS.contains(&name.as_str()) {
+                anyhow::bail!("job_schedules: unknown job {:?}, expected one of {}", name, crate::scheduler::JOBS.join(", "));
+            }
+            let parsed: crate::scheduler::Schedule =
+                schedule.parse().with_context(|| format!("job_schedules: invalid schedule for {}", name))?;
+            if parsed.next_after(chrono::Utc::now()).is_none() {
+                anyhow::bail!("job_schedules: the schedule for {} never matches", name);
+            }
+        }
+        if self.max_url_length == 0 {
+            anyhow::bail!("max_url_length must be at least 1");
+        }
+        if !self.base_url.starts_with("http://") && !self.base_url.starts_with("https://") {
+            anyhow::bail!("base_url must start with http:// or https://");
+        }
+        Ok(())
+    }
+
+    pub fn bind_address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    pub fn database(&self) -> DatabaseOptions {
+        DatabaseOptions {
+            url: self.database_url.clone(),
+            read_url: self.database_read_url.clone(),
+            max_connections: self.database_max_connections,
+            acquire_timeout_secs: self.database_acquire_timeout_secs,
+            sqlite_journal_mode: self.sqlite_journal_mode.clone(),
+            sqlite_synchronous: self.sqlite_synchronous.clone(),
+            sqlite_busy_timeout_ms: self.sqlite_busy_timeout_ms,
+        }
+    }
+}
+
+// List settings come from env vars as comma separated values
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_overrides_defaults() {
+        let config = Config::from_toml(
+            r#"
+            port = 8080
+            base_url = "https://qurl.example.com/"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.host, "0.0.0.0");
+        let base = crate::base_url::BaseUrl::configured(&config);
+        assert_eq!(base.short_url(None, "abc123"), "https://qurl.example.com/abc123");
+        assert_eq!(base.short_url(Some("go.example.org"), "abc123"), "https://go.example.org/abc123");
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let mut config = Config::from_toml("token_length = 8").unwrap();
+        config
+            .apply_env(|name| match name {
+                "QUICKURL_TOKEN_LENGTH" => Some("10".into()),
+                "QUICKURL_DATABASE_URL" => Some("sqlite::memory:".into()),
+                "QUICKURL_RESERVED_TOKENS" => Some("pricing, blog,".into()),
+                "QUICKURL_JOB_SCHEDULES" => Some("cleanup=0 3 * * 1,4; stats_rollup=@hourly".into()),
+                "QUICKURL_ALLOWED_SCHEMES" => Some("mailto,tel".into()),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(config.token_length, 10);
+        assert_eq!(config.database_url, "sqlite::memory:");
+        assert_eq!(config.reserved_tokens, ["pricing", "blog"]);
+        assert_eq!(config.job_schedules["cleanup"], "0 3 * * 1,4");
+        assert_eq!(config.allowed_schemes, ["mailto", "tel"]);
+        assert!(config.validate().is_ok());
+        config.allowed_schemes.push("JavaScript".into());
+        assert!(config.validate().is_err());
+        config.allowed_schemes.pop();
+        config.job_schedules.insert("backup".into(), "@daily".into());
+        assert!(config.validate().is_err());
+        config.job_schedules.remove("backup");
+        config.signing_secret = Some("short".into());
+        assert!(config.validate().is_err());
+        config.signing_secret = Some("x".repeat(32));
+        assert!(config.validate().is_ok());
+        config.default_ttl_days = i64::MAX;
+        assert!(config.validate().is_err());
+        config.default_ttl_days = 30;
+        assert!(config.apply_env(|_| Some("not-a-number".into())).is_err());
+    }
+
+    #[test]
+    fn test_token_alphabet() {
+        let mut config = Config::from_toml(r#"token_alphabet = "abcdef0123""#).unwrap();
+        assert!(config.validate().is_ok());
+        config.token_alphabet = Some("ab/cd".into());
+        assert!(config.validate().is_err());
+        config.token_alphabet = Some("abca".into());
+        assert!(config.validate().is_err());
+        // Case-insensitive tokens drop the uppercase letters, leaving a single character
+        config.token_alphabet = Some("ABC0".into());
+        config.case_insensitive_tokens = true;
+        assert!(config.validate().is_err());
+    }
+}
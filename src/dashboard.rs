@@ -0,0 +1,159 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::IntoResponse,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::models::{CountEntry, DashboardSnapshot};
+use crate::AppState;
+
+const WINDOW_SECS: i64 = 60;
+const TOP_TOKENS: usize = 10;
+const PUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default)]
+struct Bucket {
+    second: i64,
+    total: i64,
+    tokens: HashMap<String, i64>,
+}
+
+// Per-second click counts for the last minute, kept in memory only. Each process counts its
+// own redirects, behind a load balancer every instance shows its share of the traffic.
+#[derive(Clone)]
+pub struct RollingWindow {
+    buckets: Arc<Mutex<Vec<Bucket>>>,
+}
+
+impl RollingWindow {
+    pub fn new() -> Self {
+        let buckets = (0..WINDOW_SECS).map(|_| Bucket::default()).collect();
+        Self {
+            buckets: Arc::new(Mutex::new(buckets)),
+        }
+    }
+
+    pub fn record(&self, token: &str) {
+        self.record_at(chrono::Utc::now().timestamp(), token);
+    }
+
+    fn record_at(&self, second: i64, token: &str) {
+        let mut buckets = self.buckets.lock().unwrap();
+        // Buckets are reused round robin, one still holding an older second is reset first
+        let bucket = &mut buckets[second.rem_euclid(WINDOW_SECS) as usize];
+        if bucket.second != second {
+            *bucket = Bucket {
+                second,
+                ..Bucket::default()
+            };
+        }
+        bucket.total += 1;
+        *bucket.tokens.entry(token.to_string()).or_default() += 1;
+    }
+
+    pub fn snapshot(&self) -> DashboardSnapshot {
+        let mut snapshot = self.snapshot_at(chrono::Utc::now().timestamp());
+        snapshot.at = crate::storage::now();
+        snapshot
+    }
+
+    // clicks_per_second covers the last complete second, the rest the full window
+    fn snapshot_at(&self, now: i64) -> DashboardSnapshot {
+        let buckets = self.buckets.lock().unwrap();
+        let mut clicks_per_second = 0;
+        let mut clicks_last_minute = 0;
+        let mut tokens: HashMap<&str, i64> = HashMap::new();
+        for bucket in buckets.iter().filter(|bucket| now - WINDOW_SECS < bucket.second && bucket.second <= now) {
+            if bucket.second == now - 1 {
+                clicks_per_second = bucket.total;
+            }
+            clicks_last_minute += bucket.total;
+            for (token, count) in &bucket.tokens {
+                *tokens.entry(token).or_default() += count;
+            }
+        }
+
+        let mut top_tokens: Vec<CountEntry> = tokens
+            .into_iter()
+            .map(|(value, count)| CountEntry {
+                value: value.to_string(),
+                count,
+            })
+            .collect();
+        top_tokens.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        top_tokens.truncate(TOP_TOKENS);
+
+        DashboardSnapshot {
+            at: chrono::DateTime::from_timestamp(now, 0).unwrap_or_default(),
+            clicks_per_second,
+            clicks_last_minute,
+            top_tokens,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/live",
+    tag = "admin",
+    responses(
+        (status = 101, description = "WebSocket pushing a DashboardSnapshot as a JSON text message every second"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn live_dashboard(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| push_snapshots(socket, state))
+}
+
+async fn push_snapshots(mut socket: WebSocket, state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(PUSH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let Ok(snapshot) = serde_json::to_string(&state.dashboard.snapshot()) else {
+                    return;
+                };
+                if socket.send(Message::Text(snapshot)).await.is_err() {
+                    return;
+                }
+            }
+            // The feed is one way, anything but a close or a dropped connection is ignored
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_drops_old_seconds() {
+        let window = RollingWindow::new();
+        window.record_at(1_000, "old");
+        for _ in 0..3 {
+            window.record_at(1_059, "abc");
+        }
+        window.record_at(1_060, "xyz");
+
+        let snapshot = window.snapshot_at(1_060);
+        assert_eq!(snapshot.clicks_per_second, 3);
+        assert_eq!(snapshot.clicks_last_minute, 4);
+        assert_eq!(snapshot.top_tokens[0].value, "abc");
+        assert_eq!(snapshot.top_tokens.len(), 2);
+
+        // Second 1_000 is overwritten once the ring comes round again
+        window.record_at(1_120, "new");
+        assert_eq!(window.snapshot_at(1_120).clicks_last_minute, 1);
+    }
+}
@@ -0,0 +1,276 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{AnyPool, Row};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use url::{Host, Url};
+use utoipa::ToSchema;
+
+use crate::models::{CreateDestinationEntryRequest, DestinationEntry, DestinationListQuery};
+use crate::storage;
+use crate::{AppError, AppState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DestinationList {
+    Block,
+    Allow,
+}
+
+impl DestinationList {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DestinationList::Block => "block",
+            DestinationList::Allow => "allow",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "block" => Some(DestinationList::Block),
+            "allow" => Some(DestinationList::Allow),
+            _ => None,
+        }
+    }
+}
+
+// Lowercased host without a trailing dot. Unlike custom domains, single labels are fine
+// here so a whole TLD like "zip" can be listed.
+fn normalize_host(raw: &str) -> Option<String> {
+    match Host::parse(raw.trim().trim_end_matches('.')).ok()? {
+        Host::Domain(domain) => Some(domain.to_lowercase()),
+        Host::Ipv4(ip) => Some(ip.to_string()),
+        Host::Ipv6(ip) => Some(format!("[{}]", ip)),
+    }
+}
+
+// The host itself followed by each parent domain, so an entry also covers its subdomains
+fn candidates(host: &str) -> impl Iterator<Item = &str> {
+    let is_ip = host.starts_with('[') || host.parse::<std::net::Ipv4Addr>().is_ok();
+    std::iter::successors(Some(host), move |host| {
+        if is_ip {
+            None
+        } else {
+            host.split_once('.').map(|(_, parent)| parent)
+        }
+    })
+}
+
+#[derive(Debug, Default)]
+struct Lists {
+    blocked: HashSet<String>,
+    allowed: HashSet<String>,
+}
+
+// In-memory copy of destination_lists, consulted on every create and update (and on
+// redirects when enabled) without a database round trip
+#[derive(Clone, Default)]
+pub struct DestinationPolicy {
+    lists: Arc<RwLock<Lists>>,
+}
+
+impl DestinationPolicy {
+    pub async fn load(db: &AnyPool) -> Result<Self, sqlx::Error> {
+        let policy = Self::default();
+        policy.reload(db).await?;
+        Ok(policy)
+    }
+
+    pub async fn reload(&self, db: &AnyPool) -> Result<(), sqlx::Error> {
+        let rows = sqlx::query("SELECT hostname, list FROM destination_lists")
+            .fetch_all(db)
+            .await?;
+
+        let mut lists = Lists::default();
+        for row in rows {
+            let hostname: String = row.get("hostname");
+            match DestinationList::parse(&row.get::<String, _>("list")) {
+                Some(DestinationList::Block) => lists.blocked.insert(hostname),
+                Some(DestinationList::Allow) => lists.allowed.insert(hostname),
+                None => false,
+            };
+        }
+        *self.lists.write().unwrap() = lists;
+        Ok(())
+    }
+
+    // Changes made through another instance sharing the database show up after one interval
+    pub fn spawn_refresh(&self, db: AnyPool, interval: Duration) {
+        let policy = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval.max(Duration::from_secs(1)));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = policy.reload(&db).await {
+                    eprintln!("⚠️  Failed to reload destination lists: {}", e);
+                }
+            }
+        });
+    }
+
+    // Blocked hosts are always refused; while the allowlist is non-empty, so is everything not on it
+    pub fn check(&self, url: &Url) -> Result<(), String> {
+        let Some(host) = url.host_str().and_then(normalize_host) else {
+            return Ok(());
+        };
+        let lists = self.lists.read().unwrap();
+        if candidates(&host).any(|candidate| lists.blocked.contains(candidate)) {
+            return Err(format!("Destination {} is blocked", host));
+        }
+        if !lists.allowed.is_empty() && !candidates(&host).any(|candidate| lists.allowed.contains(candidate)) {
+            return Err(format!("Destination {} is not on the allowlist", host));
+        }
+        Ok(())
+    }
+}
+
+fn entry_from_row(row: &sqlx::any::AnyRow) -> DestinationEntry {
+    DestinationEntry {
+        hostname: row.get("hostname"),
+        list: DestinationList::parse(&row.get::<String, _>("list")).unwrap_or(DestinationList::Block),
+        reason: row.get("reason"),
+        created_at: storage::get_ts(row, "created_at"),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/destinations",
+    tag = "admin",
+    request_body = CreateDestinationEntryRequest,
+    responses(
+        (status = 201, description = "Host added to the list", body = DestinationEntry),
+        (status = 400, description = "Invalid hostname", body = ErrorResponse),
+        (status = 409, description = "Host is already listed", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn create_entry(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<CreateDestinationEntryRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let hostname = normalize_host(&payload.hostname)
+        .ok_or_else(|| AppError::BadRequest(format!("Invalid hostname: {}", payload.hostname)))?;
+
+    let entry = DestinationEntry {
+        hostname,
+        list: payload.list,
+        reason: payload.reason,
+        created_at: storage::now(),
+    };
+
+    sqlx::query("INSERT INTO destination_lists (hostname, list, reason, created_at) VALUES ($1, $2, $3, $4)")
+        .bind(&entry.hostname)
+        .bind(entry.list.as_str())
+        .bind(&entry.reason)
+        .bind(storage::ts(entry.created_at))
+        .execute(&state.db)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+                AppError::Conflict(format!("{} is already listed, delete it first to move it", entry.hostname))
+            }
+            _ => AppError::DatabaseError(e.to_string()),
+        })?;
+
+    state
+        .destinations
+        .reload(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/destinations",
+    tag = "admin",
+    params(DestinationListQuery),
+    responses(
+        (status = 200, description = "Listed hosts", body = [DestinationEntry]),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn list_entries(
+    Query(query): Query<DestinationListQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let rows = match query.list {
+        Some(list) => sqlx::query("SELECT * FROM destination_lists WHERE list = $1 ORDER BY hostname")
+            .bind(list.as_str())
+            .fetch_all(&state.db)
+            .await,
+        None => sqlx::query("SELECT * FROM destination_lists ORDER BY hostname")
+            .fetch_all(&state.db)
+            .await,
+    }
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(rows.iter().map(entry_from_row).collect::<Vec<_>>()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/destinations/{hostname}",
+    tag = "admin",
+    params(("hostname" = String, Path, description = "Listed host")),
+    responses(
+        (status = 204, description = "Host removed from its list"),
+        (status = 404, description = "Host is not listed", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn delete_entry(
+    Path(hostname): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let hostname = normalize_host(&hostname).unwrap_or(hostname);
+    let result = sqlx::query("DELETE FROM destination_lists WHERE hostname = $1")
+        .bind(&hostname)
+        .execute(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Host is not listed".into()));
+    }
+
+    state
+        .destinations
+        .reload(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lists_cover_subdomains() {
+        let policy = DestinationPolicy::default();
+        let url = |raw: &str| Url::parse(raw).unwrap();
+        {
+            let mut lists = policy.lists.write().unwrap();
+            lists.blocked.insert("evil.example".into());
+            lists.blocked.insert("zip".into());
+        }
+
+        assert!(policy.check(&url("https://login.evil.example/x")).is_err());
+        assert!(policy.check(&url("https://EVIL.example./")).is_err());
+        assert!(policy.check(&url("https://files.zip")).is_err());
+        assert!(policy.check(&url("https://notevil.example")).is_ok());
+
+        policy.lists.write().unwrap().allowed.insert("example.com".into());
+        assert!(policy.check(&url("https://docs.example.com")).is_ok());
+        assert!(policy.check(&url("https://example.org")).is_err());
+        assert!(policy.check(&url("http://93.184.216.34/")).is_err());
+    }
+}
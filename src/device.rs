@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Coarse visitor platform, enough to send people to the right app store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Device {
+    Ios,
+    Android,
+    Desktop,
+}
+
+impl Device {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Device::Ios => "ios",
+            Device::Android => "android",
+            Device::Desktop => "desktop",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ios" => Some(Device::Ios),
+            "android" => Some(Device::Android),
+            "desktop" => Some(Device::Desktop),
+            _ => None,
+        }
+    }
+
+    // Substring checks rather than a full UA parser, mobile platforms announce themselves
+    // reliably and everything else (including bots and missing headers) counts as desktop
+    pub fn detect(user_agent: Option<&str>) -> Self {
+        let Some(user_agent) = user_agent else {
+            return Device::Desktop;
+        };
+        if user_agent.contains("Android") {
+            Device::Android
+        } else if ["iPhone", "iPad", "iPod"].iter().any(|marker| user_agent.contains(marker)) {
+            Device::Ios
+        } else {
+            Device::Desktop
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_device() {
+        let iphone = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 Mobile/15E148";
+        let android = "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 Chrome/124.0 Mobile Safari/537.36";
+        let mac = "Mozilla/5.0 (Macintosh; Intel Mac OS X 14_4) AppleWebKit/605.1.15 Version/17.4 Safari/605.1.15";
+
+        assert_eq!(Device::detect(Some(iphone)), Device::Ios);
+        assert_eq!(Device::detect(Some(android)), Device::Android);
+        assert_eq!(Device::detect(Some(mac)), Device::Desktop);
+        assert_eq!(Device::detect(None), Device::Desktop);
+        assert_eq!(Device::parse(Device::Android.as_str()), Some(Device::Android));
+    }
+}
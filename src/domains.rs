@@ -0,0 +1,316 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json},
+};
+use moka::sync::Cache;
+use sqlx::{AnyPool, Row};
+use std::sync::Arc;
+use std::time::Duration;
+use url::Host;
+use uuid::Uuid;
+
+use crate::models::{CreateDomainRequest, DomainInfo, UpdateDomainRequest};
+use crate::{pages, storage};
+use crate::{AppError, AppState};
+
+const MAX_CACHED_HOSTS: u64 = 1_000;
+
+// Lowercased hostname without port, or None if it is not a valid DNS name
+pub fn normalize_hostname(raw: &str) -> Option<String> {
+    match Host::parse(raw.trim().trim_end_matches('.')).ok()? {
+        Host::Domain(domain) if domain.contains('.') => Some(domain.to_lowercase()),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Domain {
+    pub hostname: String,
+    pub fallback_url: Option<String>,
+}
+
+// Remembers which Host headers are registered custom domains, so cached redirects do not
+// need a database round trip just to pick the namespace
+#[derive(Clone)]
+pub struct DomainResolver {
+    hosts: Cache<String, Option<Domain>>,
+}
+
+impl DomainResolver {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            hosts: Cache::builder()
+                .max_capacity(MAX_CACHED_HOSTS)
+                .time_to_live(ttl.max(Duration::from_secs(1)))
+                .build(),
+        }
+    }
+
+    // The custom domain a request came in on, None for the default domain
+    pub async fn resolve(&self, db: &AnyPool, headers: &HeaderMap) -> Result<Option<String>, AppError> {
+        Ok(self.lookup(db, headers).await?.map(|domain| domain.hostname))
+    }
+
+    pub async fn lookup(&self, db: &AnyPool, headers: &HeaderMap) -> Result<Option<Domain>, AppError> {
+        let Some(host) = headers
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.rsplit_once(':').map_or(value, |(host, _)| host))
+            .and_then(normalize_hostname)
+        else {
+            return Ok(None);
+        };
+
+        if let Some(domain) = self.hosts.get(&host) {
+            return Ok(domain);
+        }
+        let domain = sqlx::query("SELECT fallback_url FROM domains WHERE hostname = $1")
+            .bind(&host)
+            .fetch_optional(db)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+            .map(|row| Domain {
+                hostname: host.clone(),
+                fallback_url: row.get("fallback_url"),
+            });
+        self.hosts.insert(host, domain.clone());
+        Ok(domain)
+    }
+
+    fn invalidate(&self, hostname: &str) {
+        self.hosts.invalidate(hostname);
+    }
+}
+
+// Links may only be created on domains that have been registered
+pub async fn ensure_exists(db: &AnyPool, hostname: &str) -> Result<(), AppError> {
+    let found = sqlx::query("SELECT id FROM domains WHERE hostname = $1")
+        .bind(hostname)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    match found {
+        Some(_) => Ok(()),
+        None => Err(AppError::BadRequest(format!("Unknown domain: {}", hostname))),
+    }
+}
+
+fn domain_from_row(row: &sqlx::any::AnyRow) -> DomainInfo {
+    DomainInfo {
+        id: row.get("id"),
+        hostname: row.get("hostname"),
+        fallback_url: row.get("fallback_url"),
+        created_at: storage::get_ts(row, "created_at"),
+        link_count: row.get("link_count"),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/domains",
+    tag = "admin",
+    request_body = CreateDomainRequest,
+    responses(
+        (status = 201, description = "Domain registered", body = DomainInfo),
+        (status = 400, description = "Invalid hostname", body = ErrorResponse),
+        (status = 409, description = "Domain already registered", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn create_domain(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<CreateDomainRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let hostname = normalize_hostname(&payload.hostname)
+        .ok_or_else(|| AppError::BadRequest("hostname must be a fully qualified domain name".into()))?;
+    let fallback_url = payload.fallback_url.filter(|url| !url.is_empty());
+    check_fallback_url(fallback_url.as_deref())?;
+
+    let domain = DomainInfo {
+        id: Uuid::new_v4().to_string(),
+        hostname,
+        fallback_url,
+        created_at: storage::now(),
+        link_count: 0,
+    };
+
+    sqlx::query("INSERT INTO domains (id, hostname, fallback_url, created_at) VALUES ($1, $2, $3, $4)")
+        .bind(&domain.id)
+        .bind(&domain.hostname)
+        .bind(&domain.fallback_url)
+        .bind(storage::ts(domain.created_at))
+        .execute(&state.db)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+                AppError::Conflict("Domain already registered".into())
+            }
+            _ => AppError::DatabaseError(e.to_string()),
+        })?;
+
+    state.domains.invalidate(&domain.hostname);
+    Ok((StatusCode::CREATED, Json(domain)))
+}
+
+fn check_fallback_url(url: Option<&str>) -> Result<(), AppError> {
+    match url {
+        Some(url) if !pages::is_redirect_url(url) => Err(AppError::BadRequest("fallback_url must be an http(s) URL".into())),
+        _ => Ok(()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/domains",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Registered domains", body = [DomainInfo]),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn list_domains(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT d.id, d.hostname, d.fallback_url, d.created_at, COUNT(u.id) AS link_count
+        FROM domains d LEFT JOIN urls u ON u.domain = d.hostname
+        GROUP BY d.id, d.hostname, d.fallback_url, d.created_at
+        ORDER BY d.hostname
+        "#
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(rows.iter().map(domain_from_row).collect::<Vec<_>>()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/domains/{hostname}",
+    tag = "admin",
+    params(("hostname" = String, Path, description = "Custom domain hostname")),
+    responses(
+        (status = 200, description = "Domain details", body = DomainInfo),
+        (status = 404, description = "Domain not found", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn get_domain(
+    Path(hostname): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(fetch_domain(&state.db, &hostname.to_lowercase()).await?))
+}
+
+async fn fetch_domain(db: &AnyPool, hostname: &str) -> Result<DomainInfo, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT d.id, d.hostname, d.fallback_url, d.created_at, COUNT(u.id) AS link_count
+        FROM domains d LEFT JOIN urls u ON u.domain = d.hostname
+        WHERE d.hostname = $1
+        GROUP BY d.id, d.hostname, d.fallback_url, d.created_at
+        "#
+    )
+    .bind(hostname)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Domain not found".into()))?;
+
+    Ok(domain_from_row(&row))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/domains/{hostname}",
+    tag = "admin",
+    params(("hostname" = String, Path, description = "Custom domain hostname")),
+    request_body = UpdateDomainRequest,
+    responses(
+        (status = 200, description = "Domain updated", body = DomainInfo),
+        (status = 400, description = "Invalid fallback URL", body = ErrorResponse),
+        (status = 404, description = "Domain not found", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn update_domain(
+    Path(hostname): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<UpdateDomainRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let hostname = hostname.to_lowercase();
+    if let Some(fallback_url) = payload.fallback_url {
+        let fallback_url = fallback_url.filter(|url| !url.is_empty());
+        check_fallback_url(fallback_url.as_deref())?;
+        let result = sqlx::query("UPDATE domains SET fallback_url = $1 WHERE hostname = $2")
+            .bind(&fallback_url)
+            .bind(&hostname)
+            .execute(&state.db)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Domain not found".into()));
+        }
+        state.domains.invalidate(&hostname);
+    }
+
+    Ok(Json(fetch_domain(&state.db, &hostname).await?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/domains/{hostname}",
+    tag = "admin",
+    params(("hostname" = String, Path, description = "Custom domain hostname")),
+    responses(
+        (status = 204, description = "Domain removed"),
+        (status = 404, description = "Domain not found", body = ErrorResponse),
+        (status = 409, description = "Domain still has links", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn delete_domain(
+    Path(hostname): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let hostname = hostname.to_lowercase();
+
+    // Refuse rather than cascade, dropping a domain should never silently delete links
+    let in_use = sqlx::query("SELECT id FROM urls WHERE domain = $1 LIMIT 1")
+        .bind(&hostname)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if in_use.is_some() {
+        return Err(AppError::Conflict("Domain still has links, delete and purge them first".into()));
+    }
+
+    let result = sqlx::query("DELETE FROM domains WHERE hostname = $1")
+        .bind(&hostname)
+        .execute(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Domain not found".into()));
+    }
+
+    state.domains.invalidate(&hostname);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_hostname() {
+        assert_eq!(normalize_hostname("Go.MyCompany.com").as_deref(), Some("go.mycompany.com"));
+        assert_eq!(normalize_hostname("links.other.com.").as_deref(), Some("links.other.com"));
+        assert!(normalize_hostname("localhost").is_none());
+        assert!(normalize_hostname("10.0.0.1").is_none());
+        assert!(normalize_hostname("bad host.com").is_none());
+    }
+}
@@ -0,0 +1,202 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use chrono::SecondsFormat;
+use futures::TryStreamExt;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::auth::Caller;
+use crate::base_url::BaseUrl;
+use crate::models::{FileFormat, ExportQuery, ListUrlsQuery, UrlInfo};
+use crate::storage::SqlBuilder;
+use crate::{load_tags, order_clause, push_url_filters, url_info_from_row, AppError, AppState};
+
+// Rows are read from the cursor and written out in chunks, so memory stays flat however
+// many links match. Each chunk costs one extra query for its tags.
+const CHUNK_SIZE: usize = 500;
+const CSV_HEADER: &str =
+    "token,short_url,original_url,title,created_at,expires_at,click_count,max_clicks,domain,tags,starts_at\n";
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_row(url: &UrlInfo) -> String {
+    let fields = [
+        csv_field(&url.token),
+        csv_field(&url.short_url),
+        csv_field(&url.original_url),
+        csv_field(url.title.as_deref().unwrap_or_default()),
+        url.created_at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        url.expires_at
+            .map(|expires_at| expires_at.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            .unwrap_or_default(),
+        url.click_count.to_string(),
+        url.max_clicks.map(|max| max.to_string()).unwrap_or_default(),
+        csv_field(url.domain.as_deref().unwrap_or_default()),
+        csv_field(&url.tags.join(" ")),
+        url.starts_at
+            .map(|starts_at| starts_at.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            .unwrap_or_default(),
+    ];
+    format!("{}\n", fields.join(","))
+}
+
+fn render_chunk(format: FileFormat, urls: &[UrlInfo]) -> String {
+    urls.iter()
+        .map(|url| match format {
+            FileFormat::Csv => csv_row(url),
+            FileFormat::Ndjson => format!("{}\n", serde_json::to_string(url).unwrap_or_default()),
+        })
+        .collect()
+}
+
+async fn write_export(
+    state: Arc<AppState>,
+    base: BaseUrl,
+    mut query: SqlBuilder,
+    format: FileFormat,
+    sender: mpsc::Sender<Result<Bytes, std::io::Error>>,
+) -> Result<(), AppError> {
+    let mut rows = query.build().fetch(state.links.reader());
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+
+    loop {
+        let row = rows
+            .try_next()
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if let Some(row) = &row {
+            chunk.push(url_info_from_row(&base, row));
+        }
+
+        if chunk.len() == CHUNK_SIZE || (row.is_none() && !chunk.is_empty()) {
+            load_tags(state.links.reader(), &mut chunk).await?;
+            let body = render_chunk(format, &chunk);
+            chunk.clear();
+            if sender.send(Ok(Bytes::from(body))).await.is_err() {
+                // Client went away, stop reading
+                return Ok(());
+            }
+        }
+
+        if row.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/urls/export",
+    tag = "urls",
+    params(ExportQuery, ListUrlsQuery),
+    responses(
+        (status = 200, description = "All matching links, streamed", content_type = ["text/csv", "application/x-ndjson"]),
+    )
+)]
+pub async fn export_urls(
+    Query(export): Query<ExportQuery>,
+    Query(filters): Query<ListUrlsQuery>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+) -> Result<impl IntoResponse, AppError> {
+    let mut query = SqlBuilder::new("SELECT * FROM urls");
+    push_url_filters(&mut query, &filters, &caller);
+    query.push(order_clause(&filters));
+
+    let (sender, mut receiver) = mpsc::channel(4);
+    if let FileFormat::Csv = export.format {
+        sender
+            .send(Ok(Bytes::from_static(CSV_HEADER.as_bytes())))
+            .await
+            .expect("receiver is held below");
+    }
+
+    tokio::spawn(async move {
+        let failed = sender.clone();
+        if let Err(e) = write_export(state, base, query, export.format, sender).await {
+            eprintln!("⚠️  Export failed: {:?}", e);
+            // Abort the response body so the client does not mistake a partial file for a full one
+            let _ = failed.send(Err(std::io::Error::other("export failed"))).await;
+        }
+    });
+
+    // The body may be polled again after it ended (compression does), a closed receiver keeps
+    // answering None where unfold would panic
+    let stream = futures::stream::poll_fn(move |cx| receiver.poll_recv(cx));
+    let body = Body::from_stream(stream);
+    let (content_type, extension) = match export.format {
+        FileFormat::Csv => ("text/csv; charset=utf-8", "csv"),
+        FileFormat::Ndjson => ("application/x-ndjson", "ndjson"),
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"quickurl-export.{}\"", extension),
+            ),
+        ],
+        body,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_csv_row_quotes_special_fields() {
+        let url = UrlInfo {
+            id: "id".into(),
+            token: "abc123".into(),
+            original_url: "https://example.com/?a=1,2".into(),
+            raw_url: None,
+            display_url: "https://example.com/?a=1,2".into(),
+            short_url: "https://qurl.example/abc123".into(),
+            title: Some("Say \"hi\"".into()),
+            description: None,
+            image_url: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            starts_at: None,
+            expires_at: Some(Utc::now()),
+            click_count: 7,
+            bot_clicks: 2,
+            unique_clicks: 5,
+            max_clicks: None,
+            domain: None,
+            org_id: None,
+            campaign_id: None,
+            forward_query: false,
+            template: false,
+            tags: vec!["a".into(), "b".into()],
+            notes: None,
+            metadata: None,
+            is_active: true,
+            flagged: false,
+            flag_reason: None,
+            warned: false,
+            takedown: None,
+            last_checked_at: None,
+            health_status: None,
+        };
+
+        let row = csv_row(&url);
+        assert!(row.starts_with("abc123,https://qurl.example/abc123,\"https://example.com/?a=1,2\",\"Say \"\"hi\"\"\","));
+        assert!(row.ends_with(",7,,,a b,\n"));
+        assert_eq!(row.matches(',').count(), CSV_HEADER.matches(',').count() + 1);
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::Context;
+use maxminddb::{geoip2, Reader};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+// Where a click came from, as far as the GeoLite2 database knows
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub country: Option<String>,
+    pub city: Option<String>,
+}
+
+// Offline GeoIP lookups against a local MaxMind City database, read into memory once at
+// startup. Without a configured database every lookup comes back empty.
+#[derive(Clone, Default)]
+pub struct GeoIp {
+    reader: Option<Arc<Reader<Vec<u8>>>>,
+}
+
+impl GeoIp {
+    pub fn open(path: Option<&str>) -> anyhow::Result<Self> {
+        let Some(path) = path.filter(|path| !path.is_empty()) else {
+            return Ok(Self::default());
+        };
+        let reader = Reader::open_readfile(Path::new(path))
+            .with_context(|| format!("failed to open GeoIP database {}", path))?;
+        Ok(Self {
+            reader: Some(Arc::new(reader)),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.reader.is_some()
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> Location {
+        let Some(reader) = &self.reader else {
+            return Location::default();
+        };
+        // Private and unknown addresses are simply not in the database
+        let Ok(record) = reader.lookup::<geoip2::City>(ip) else {
+            return Location::default();
+        };
+
+        Location {
+            country: record.country.and_then(|country| country.iso_code).map(String::from),
+            city: record
+                .city
+                .and_then(|city| city.names)
+                .and_then(|names| names.get("en").map(|name| name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_lookup_is_empty() {
+        let geo = GeoIp::open(None).unwrap();
+
+        assert!(!geo.is_enabled());
+        assert_eq!(geo.lookup("203.0.113.7".parse().unwrap()), Location::default());
+        assert!(GeoIp::open(Some("/nonexistent/GeoLite2-City.mmdb")).is_err());
+    }
+}
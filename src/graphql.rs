@@ -0,0 +1,359 @@
+use async_graphql::{
+    http::GraphiQLSource, ComplexObject, Context, EmptyMutation, EmptySubscription, Enum, ErrorExtensions,
+    InputObject, Json, Object, Result, Schema, SimpleObject,
+};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::{Html, IntoResponse},
+    Extension,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::Row;
+use std::sync::Arc;
+
+use crate::auth::{self, Caller, Permissions, Scope};
+use crate::base_url::BaseUrl;
+use crate::campaigns;
+use crate::models::{
+    CampaignInfo, CampaignStatsQuery, CampaignStatsResponse, ListUrlsQuery, SortField, SortOrder, UrlInfo,
+    UrlStatsResponse,
+};
+use crate::stats::{self, DEFAULT_STATS_DAYS};
+use crate::storage::{self, SqlBuilder};
+use crate::versioning::API_PREFIX;
+use crate::{
+    find_urls, load_tags, push_owner_filter, url_info_from_row, Access, AppError, AppState, DEFAULT_PER_PAGE,
+    MAX_PER_PAGE,
+};
+
+// Every selected field counts one, lists count their children once per requested item
+const MAX_COMPLEXITY: usize = 20_000;
+const MAX_DEPTH: usize = 10;
+const DEFAULT_CLICKS: i64 = 20;
+const MAX_CLICKS: i64 = 500;
+
+pub type QuickUrlSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+pub fn schema(state: Arc<AppState>) -> QuickUrlSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(state)
+        .limit_complexity(MAX_COMPLEXITY)
+        .limit_depth(MAX_DEPTH)
+        .finish()
+}
+
+// The caller of the request, resolved once before the query runs
+struct Session {
+    caller: Caller,
+    permissions: Permissions,
+    base: BaseUrl,
+}
+
+// Same message as the problem details of the REST API, the code and status go in the extensions
+impl From<AppError> for async_graphql::Error {
+    fn from(error: AppError) -> Self {
+        let (status, code, detail) = error.parts();
+        if status.is_server_error() {
+            eprintln!("❌ GraphQL query failed: {}", detail);
+        }
+        async_graphql::Error::new(detail).extend_with(|_, extensions| {
+            extensions.set("code", code);
+            extensions.set("status", status.as_u16());
+        })
+    }
+}
+
+fn database_error(e: sqlx::Error) -> AppError {
+    AppError::DatabaseError(e.to_string())
+}
+
+// State and caller of the request, once the caller is known to hold the scope
+fn session<'a>(ctx: &Context<'a>, scope: Scope) -> Result<(&'a Arc<AppState>, &'a Session), AppError> {
+    let (state, session) = (ctx.data_unchecked::<Arc<AppState>>(), ctx.data_unchecked::<Session>());
+    auth::require_anonymous_scope(state, &session.caller, scope)?;
+    session.permissions.require(scope)?;
+    Ok((state, session))
+}
+
+fn check_page(first: i64, offset: i64) -> Result<(), AppError> {
+    if !(1..=MAX_PER_PAGE).contains(&first) {
+        return Err(AppError::BadRequest(format!("first must be between 1 and {}", MAX_PER_PAGE)));
+    }
+    if offset < 0 {
+        return Err(AppError::BadRequest("offset must not be negative".into()));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Enum)]
+pub enum LinkOrder {
+    #[default]
+    Newest,
+    Oldest,
+    MostClicked,
+    LeastClicked,
+}
+
+// The filters of GET /urls, every one given must match
+#[derive(Debug, Default, InputObject)]
+pub struct LinkFilter {
+    // Case-insensitive part of the title
+    pub search: Option<String>,
+    pub tag: Option<String>,
+    pub org_id: Option<String>,
+    pub campaign_id: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub expires_before: Option<DateTime<Utc>>,
+}
+
+impl LinkFilter {
+    fn into_query(self, order: LinkOrder) -> ListUrlsQuery {
+        let (sort, order) = match order {
+            LinkOrder::Newest => (SortField::CreatedAt, SortOrder::Desc),
+            LinkOrder::Oldest => (SortField::CreatedAt, SortOrder::Asc),
+            LinkOrder::MostClicked => (SortField::ClickCount, SortOrder::Desc),
+            LinkOrder::LeastClicked => (SortField::ClickCount, SortOrder::Asc),
+        };
+        ListUrlsQuery {
+            sort,
+            order,
+            created_after: self.created_after,
+            expires_before: self.expires_before,
+            q: self.search,
+            tag: self.tag,
+            org_id: self.org_id,
+            campaign_id: self.campaign_id,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct LinkPage {
+    // Matching links over all pages
+    pub total: i64,
+    pub links: Vec<UrlInfo>,
+}
+
+async fn link_page(ctx: &Context<'_>, query: ListUrlsQuery, first: i64, offset: i64) -> Result<LinkPage, AppError> {
+    check_page(first, offset)?;
+    let (state, session) = session(ctx, Scope::LinksRead)?;
+    let (total, links) = find_urls(state, &query, &session.caller, &session.base, first, offset).await?;
+    Ok(LinkPage { total, links })
+}
+
+// One click event, who made it is only kept as a hash and not exposed
+#[derive(Debug, SimpleObject)]
+pub struct Click {
+    pub clicked_at: DateTime<Utc>,
+    pub referrer: Option<String>,
+    pub user_agent: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub variant: Option<String>,
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    // Links the caller can see, newest first unless ordered otherwise
+    #[graphql(complexity = "first.max(0) as usize * child_complexity")]
+    async fn links(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] filter: LinkFilter,
+        #[graphql(default)] order_by: LinkOrder,
+        #[graphql(default_with = "DEFAULT_PER_PAGE")] first: i64,
+        #[graphql(default)] offset: i64,
+    ) -> Result<LinkPage> {
+        Ok(link_page(ctx, filter.into_query(order_by), first, offset).await?)
+    }
+
+    async fn link(&self, ctx: &Context<'_>, token: String) -> Result<Option<UrlInfo>> {
+        let (state, session) = session(ctx, Scope::LinksRead)?;
+        let mut lookup = SqlBuilder::new("SELECT * FROM urls WHERE token = ");
+        lookup.push_bind(token).push(" AND deleted_at IS NULL");
+        push_owner_filter(&mut lookup, &session.caller, Access::Read);
+        let Some(row) = lookup
+            .build()
+            .fetch_optional(state.links.reader())
+            .await
+            .map_err(database_error)?
+        else {
+            return Ok(None);
+        };
+
+        let mut link = url_info_from_row(&session.base, &row);
+        load_tags(state.links.reader(), std::slice::from_mut(&mut link)).await?;
+        Ok(Some(link))
+    }
+
+    // Campaigns have owners, so like GET /campaigns this needs a credential
+    async fn campaigns(&self, ctx: &Context<'_>) -> Result<Vec<CampaignInfo>> {
+        let (state, session) = session(ctx, Scope::LinksRead)?;
+        if session.caller == Caller::Anonymous {
+            return Err(AppError::Unauthorized("Missing bearer token".into()).into());
+        }
+        Ok(campaigns::visible_campaigns(state.links.reader(), &session.caller).await?)
+    }
+
+    async fn campaign(&self, ctx: &Context<'_>, id: String) -> Result<Option<CampaignInfo>> {
+        let (state, session) = session(ctx, Scope::LinksRead)?;
+        match campaigns::find_campaign(state.links.reader(), &id, &session.caller).await {
+            Ok(campaign) => Ok(Some(campaign)),
+            Err(AppError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[ComplexObject]
+impl UrlInfo {
+    async fn metadata(&self) -> Option<Json<serde_json::Map<String, serde_json::Value>>> {
+        self.metadata.clone().map(Json)
+    }
+
+    // None when the link is in no campaign or one the caller cannot see
+    async fn campaign(&self, ctx: &Context<'_>) -> Result<Option<CampaignInfo>> {
+        let Some(campaign_id) = &self.campaign_id else {
+            return Ok(None);
+        };
+        let (state, session) = session(ctx, Scope::LinksRead)?;
+        match campaigns::find_campaign(state.links.reader(), campaign_id, &session.caller).await {
+            Ok(campaign) => Ok(Some(campaign)),
+            Err(AppError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Most recent first
+    #[graphql(complexity = "last.max(0) as usize * child_complexity")]
+    async fn clicks(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "DEFAULT_CLICKS")] last: i64,
+    ) -> Result<Vec<Click>> {
+        if !(1..=MAX_CLICKS).contains(&last) {
+            return Err(AppError::BadRequest(format!("last must be between 1 and {}", MAX_CLICKS)).into());
+        }
+        let (state, _) = session(ctx, Scope::StatsRead)?;
+        let rows = sqlx::query(
+            r#"
+            SELECT clicked_at, referrer, user_agent, country, city, variant FROM clicks
+            WHERE url_id = $1
+            ORDER BY clicked_at DESC, id DESC
+            LIMIT $2
+            "#
+        )
+        .bind(&self.id)
+        .bind(last)
+        .fetch_all(state.links.reader())
+        .await
+        .map_err(database_error)?;
+
+        Ok(rows
+            .iter()
+            .map(|row| Click {
+                clicked_at: storage::get_ts(row, "clicked_at"),
+                referrer: row.get("referrer"),
+                user_agent: row.get("user_agent"),
+                country: row.get("country"),
+                city: row.get("city"),
+                variant: row.get("variant"),
+            })
+            .collect())
+    }
+
+    // Same figures as GET /urls/{token}/stats
+    async fn stats(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "DEFAULT_STATS_DAYS")] days: i64,
+    ) -> Result<UrlStatsResponse> {
+        let (state, _) = session(ctx, Scope::StatsRead)?;
+        Ok(stats::url_stats(state.links.reader(), &self.id, self.token.clone(), days).await?)
+    }
+}
+
+#[ComplexObject]
+impl CampaignInfo {
+    #[graphql(complexity = "first.max(0) as usize * child_complexity")]
+    async fn links(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] order_by: LinkOrder,
+        #[graphql(default_with = "DEFAULT_PER_PAGE")] first: i64,
+        #[graphql(default)] offset: i64,
+    ) -> Result<LinkPage> {
+        let filter = LinkFilter {
+            campaign_id: Some(self.id.clone()),
+            ..Default::default()
+        };
+        Ok(link_page(ctx, filter.into_query(order_by), first, offset).await?)
+    }
+
+    // Same figures as GET /campaigns/{id}/stats, the last 30 days unless given
+    async fn stats(
+        &self,
+        ctx: &Context<'_>,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<CampaignStatsResponse> {
+        let (state, session) = session(ctx, Scope::StatsRead)?;
+        let (from, to) = campaigns::date_range(&CampaignStatsQuery { from, to }, Utc::now().date_naive())?;
+        let campaign = CampaignInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            created_at: self.created_at,
+            link_count: self.link_count,
+        };
+        Ok(campaigns::campaign_stats(state.links.reader(), &session.base, campaign, from, to).await?)
+    }
+}
+
+// Anonymous callers see what the public REST routes show them, a credential needs the scopes
+// of the fields it selects
+pub async fn execute(
+    State(state): State<Arc<AppState>>,
+    Extension(schema): Extension<QuickUrlSchema>,
+    headers: HeaderMap,
+    base: BaseUrl,
+    request: GraphQLRequest,
+) -> Result<GraphQLResponse, AppError> {
+    let (caller, permissions) = auth::resolve_caller(&state, auth::bearer_token(&headers)).await?;
+    let request = request.into_inner().data(Session { caller, permissions, base });
+    Ok(schema.execute(request).await.into())
+}
+
+pub async fn graphiql() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint(&format!("{}/graphql", API_PREFIX)).finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_maps_to_list_query() {
+        let filter = LinkFilter {
+            search: Some("launch".into()),
+            campaign_id: Some("c1".into()),
+            ..Default::default()
+        };
+        let query = filter.into_query(LinkOrder::MostClicked);
+        assert!(matches!(query.sort, SortField::ClickCount));
+        assert!(matches!(query.order, SortOrder::Desc));
+        assert_eq!(query.q.as_deref(), Some("launch"));
+        assert_eq!(query.campaign_id.as_deref(), Some("c1"));
+        assert!(!query.deleted);
+
+        assert!(check_page(DEFAULT_PER_PAGE, 0).is_ok());
+        assert!(check_page(0, 0).is_err());
+        assert!(check_page(MAX_PER_PAGE + 1, 0).is_err());
+        assert!(check_page(10, -1).is_err());
+    }
+}
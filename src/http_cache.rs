@@ -0,0 +1,93 @@
+use axum::http::{header, HeaderMap, HeaderValue};
+use chrono::{DateTime, Utc};
+
+use crate::cache::CachedLink;
+use crate::models::UrlInfo;
+
+// Link details change with every click as well, so the click counts are part of the tag
+pub fn etag(url: &UrlInfo) -> String {
+    format!("W/\"{}-{}-{}\"", url.updated_at.timestamp_micros(), url.click_count, url.bot_clicks)
+}
+
+// If-None-Match with weak comparison, which is all a GET needs
+pub fn not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+// Limited links have to see every click and targeted or split ones answer differently per
+// visitor, so neither may be stored. Plain links may be cached up to max_age_secs, but never
+// past their expiry if they have one.
+pub fn redirect_policy(link: &CachedLink, max_age_secs: u64, now: DateTime<Utc>) -> HeaderValue {
+    if link.max_clicks.is_some() || !link.rules.is_empty() || !link.variants.is_empty() {
+        return HeaderValue::from_static("private, no-store");
+    }
+    let remaining = match link.expires_at {
+        Some(expires_at) => u64::try_from((expires_at - now).num_seconds()).unwrap_or(0),
+        None => u64::MAX,
+    };
+    public(max_age_secs.min(remaining))
+}
+
+pub fn public(max_age_secs: u64) -> HeaderValue {
+    match max_age_secs {
+        0 => HeaderValue::from_static("no-cache"),
+        max_age => HeaderValue::from_str(&format!("public, max-age={}", max_age)).expect("digits are a valid header value"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(max_clicks: Option<i64>, expires_in: chrono::Duration) -> CachedLink {
+        CachedLink {
+            id: "id".into(),
+            original_url: "https://example.com".into(),
+            starts_at: None,
+            expires_at: Some(Utc::now() + expires_in),
+            max_clicks,
+            domain: None,
+            rules: Vec::new(),
+            variants: Vec::new(),
+            sticky_variants: false,
+            flagged: false,
+            warned: false,
+            takedown: None,
+            active: true,
+            dead: false,
+            forward_query: false,
+            template: false,
+        }
+    }
+
+    #[test]
+    fn test_redirect_policy() {
+        let now = Utc::now();
+        let day = chrono::Duration::days(1);
+        assert_eq!(redirect_policy(&link(None, day), 300, now), "public, max-age=300");
+        assert_eq!(redirect_policy(&link(None, day), 0, now), "no-cache");
+        assert_eq!(redirect_policy(&link(Some(5), day), 300, now), "private, no-store");
+        // Shortened to the expiry, which is a minute away
+        let policy = redirect_policy(&link(None, chrono::Duration::seconds(61)), 300, now);
+        assert!(policy == "public, max-age=60" || policy == "public, max-age=61");
+        let permanent = CachedLink { expires_at: None, ..link(None, day) };
+        assert_eq!(redirect_policy(&permanent, 300, now), "public, max-age=300");
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let mut headers = HeaderMap::new();
+        assert!(!not_modified(&headers, "W/\"1-2\""));
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"0-0\", W/\"1-2\""));
+        assert!(not_modified(&headers, "W/\"1-2\""));
+        assert!(!not_modified(&headers, "W/\"1-3\""));
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("*"));
+        assert!(not_modified(&headers, "W/\"1-3\""));
+    }
+}
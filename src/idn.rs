@@ -0,0 +1,162 @@
+use url::{Position, Url};
+
+// Flag reason of links whose host imitates another domain with lookalike letters
+pub const HOMOGRAPH_REASON: &str = "HOMOGRAPH";
+
+// Domains phishing links most often imitate, compared after mapping lookalikes to ASCII
+const POPULAR_DOMAINS: &[&str] = &[
+    "amazon.com",
+    "apple.com",
+    "bankofamerica.com",
+    "binance.com",
+    "booking.com",
+    "chase.com",
+    "coinbase.com",
+    "dropbox.com",
+    "ebay.com",
+    "facebook.com",
+    "github.com",
+    "gmail.com",
+    "google.com",
+    "icloud.com",
+    "instagram.com",
+    "linkedin.com",
+    "microsoft.com",
+    "netflix.com",
+    "office.com",
+    "outlook.com",
+    "paypal.com",
+    "spotify.com",
+    "telegram.org",
+    "twitter.com",
+    "wellsfargo.com",
+    "whatsapp.com",
+    "wikipedia.org",
+    "yahoo.com",
+    "youtube.com",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+}
+
+fn script(c: char) -> Option<Script> {
+    match c {
+        'a'..='z' | '\u{00c0}'..='\u{024f}' => Some(Script::Latin),
+        '\u{0370}'..='\u{03ff}' => Some(Script::Greek),
+        '\u{0400}'..='\u{052f}' => Some(Script::Cyrillic),
+        _ => None,
+    }
+}
+
+// Latin, Greek and Cyrillic letters in one label, which no real word needs
+fn mixed_script(label: &str) -> bool {
+    let mut scripts = label.chars().filter_map(script);
+    let Some(first) = scripts.next() else {
+        return false;
+    };
+    scripts.any(|script| script != first)
+}
+
+// The ASCII letter a Greek, Cyrillic or accented Latin lowercase letter is easily taken for
+fn confusable(c: char) -> char {
+    match c {
+        'а' | 'α' | 'ɑ' | 'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+        'с' | 'ϲ' | 'ç' => 'c',
+        'ԁ' => 'd',
+        'е' | 'ё' | 'è' | 'é' | 'ê' | 'ë' => 'e',
+        'ɡ' => 'g',
+        'һ' => 'h',
+        'і' | 'ι' | 'ı' | 'ì' | 'í' | 'î' | 'ï' => 'i',
+        'ј' | 'ϳ' => 'j',
+        'κ' | 'к' => 'k',
+        'ӏ' | 'ℓ' => 'l',
+        'ո' => 'n',
+        'о' | 'ο' | 'σ' | 'ò' | 'ó' | 'ô' | 'õ' | 'ö' => 'o',
+        'р' | 'ρ' => 'p',
+        'ԛ' => 'q',
+        'ѕ' => 's',
+        'τ' | 'т' => 't',
+        'υ' | 'ս' | 'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'ν' | 'ѵ' => 'v',
+        'ԝ' | 'ω' => 'w',
+        'х' | 'χ' => 'x',
+        'у' | 'γ' | 'ý' | 'ÿ' => 'y',
+        'ᴢ' => 'z',
+        c => c,
+    }
+}
+
+// The host in Unicode when it has internationalized labels, None for plain ASCII hosts
+fn unicode_host(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    if !host.split('.').any(|label| label.starts_with("xn--")) {
+        return None;
+    }
+    // Empty when the punycode does not decode
+    Some(url::quirks::domain_to_unicode(host)).filter(|unicode| !unicode.is_empty())
+}
+
+// Mixed-script hosts and hosts that read like one of the popular domains, or a subdomain of one
+pub fn is_homograph(url: &Url) -> bool {
+    let Some(unicode) = unicode_host(url) else {
+        return false;
+    };
+    if unicode.split('.').any(mixed_script) {
+        return true;
+    }
+    let skeleton: String = unicode.chars().map(confusable).collect();
+    POPULAR_DOMAINS
+        .iter()
+        .any(|domain| skeleton == *domain || skeleton.ends_with(&format!(".{}", domain)))
+}
+
+// What a new or changed destination is flagged for on its own, before any Safe Browsing lookup
+pub fn flag_reason(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    is_homograph(&url).then(|| HOMOGRAPH_REASON.to_string())
+}
+
+// Destinations are stored with punycode hosts, people read them in Unicode. Homographs stay in
+// punycode, which is what gives them away.
+pub fn display_url(url: &str) -> String {
+    let Ok(parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    match unicode_host(&parsed).filter(|_| !is_homograph(&parsed)) {
+        Some(unicode) => format!("{}{}{}", &parsed[..Position::BeforeHost], unicode, &parsed[Position::AfterHost..]),
+        None => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_url_decodes_punycode() {
+        assert_eq!(display_url("https://xn--bcher-kva.de/a?b=1"), "https://bücher.de/a?b=1");
+        assert_eq!(display_url("https://example.com/"), "https://example.com/");
+        assert_eq!(display_url("not a url"), "not a url");
+
+        let stored = Url::parse("https://bücher.de/").unwrap().to_string();
+        assert_eq!(stored, "https://xn--bcher-kva.de/");
+        // Cyrillic "а" in paypal
+        let homograph = Url::parse("https://pаypal.com/login").unwrap().to_string();
+        assert_eq!(display_url(&homograph), homograph);
+    }
+
+    #[test]
+    fn test_flags_homographs() {
+        // All Cyrillic "аррӏе", mixed Latin and Cyrillic, Greek omicron in a subdomain
+        for lookalike in ["https://аррӏе.com", "https://gооgle.com/", "https://login.gοogle.com/"] {
+            assert_eq!(flag_reason(lookalike).as_deref(), Some(HOMOGRAPH_REASON), "{}", lookalike);
+        }
+        for fine in ["https://google.com", "https://bücher.de", "https://яндекс.рф", "https://παράδειγμα.δοκιμή"] {
+            assert_eq!(flag_reason(fine), None, "{}", fine);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::auth::Caller;
+use crate::models::{CreateUrlRequest, FileFormat, ImportQuery};
+use crate::base_url::BaseUrl;
+use crate::{create_many, AppError, AppState};
+
+// Uploads are read into memory whole, this keeps a single request bounded
+pub const MAX_UPLOAD_BYTES: usize = 16 * 1024 * 1024;
+
+// Column names follow what link shorteners commonly export, unknown columns are ignored
+#[derive(Debug, Deserialize)]
+struct ImportRow {
+    url: String,
+    title: Option<String>,
+    custom_alias: Option<String>,
+    starts_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+    notes: Option<String>,
+}
+
+impl From<ImportRow> for CreateUrlRequest {
+    fn from(row: ImportRow) -> Self {
+        CreateUrlRequest {
+            url: row.url,
+            title: row.title,
+            starts_at: row.starts_at,
+            expires_at: row.expires_at.map(Some),
+            max_clicks: None,
+            domain: None,
+            tags: Vec::new(),
+            custom_alias: row.custom_alias,
+            notes: row.notes,
+            metadata: None,
+            campaign_id: None,
+            forward_query: false,
+            template: false,
+        }
+    }
+}
+
+fn detect_format(query: &ImportQuery, headers: &HeaderMap) -> FileFormat {
+    if let Some(format) = query.format {
+        return format;
+    }
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if content_type.contains("ndjson") || content_type.contains("json") {
+        FileFormat::Ndjson
+    } else {
+        FileFormat::Csv
+    }
+}
+
+fn parse_csv(body: &str) -> Result<Vec<Result<CreateUrlRequest, String>>, AppError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body.as_bytes());
+
+    let has_url_column = reader
+        .headers()
+        .map_err(|e| AppError::BadRequest(format!("Invalid CSV header: {}", e)))?
+        .iter()
+        .any(|column| column == "url");
+    if !has_url_column {
+        return Err(AppError::BadRequest("CSV header must include a url column".into()));
+    }
+
+    Ok(reader
+        .deserialize::<ImportRow>()
+        .map(|row| row.map(Into::into).map_err(|e| format!("Invalid row: {}", e)))
+        .collect())
+}
+
+fn parse_ndjson(body: &str) -> Vec<Result<CreateUrlRequest, String>> {
+    body.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str::<ImportRow>(line)
+                .map(Into::into)
+                .map_err(|e| format!("Invalid row: {}", e))
+        })
+        .collect()
+}
+
+// Result indexes refer to data rows, starting at 0 and not counting the CSV header
+#[utoipa::path(
+    post,
+    path = "/urls/import",
+    tag = "urls",
+    params(ImportQuery),
+    request_body(content = String, description = "CSV with a header row or NDJSON with columns url, title, custom_alias, starts_at, expires_at, notes", content_type = "text/csv"),
+    responses(
+        (status = 200, description = "Per-row results", body = BatchShortenResponse),
+        (status = 400, description = "Unreadable upload or too many rows", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn import_urls(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ImportQuery>,
+    caller: Caller,
+    base: BaseUrl,
+    headers: HeaderMap,
+    body: String,
+) -> Result<impl IntoResponse, AppError> {
+    let rows = match detect_format(&query, &headers) {
+        FileFormat::Csv => parse_csv(&body)?,
+        FileFormat::Ndjson => parse_ndjson(&body),
+    };
+
+    let max = state.config.max_import_rows;
+    if rows.is_empty() || rows.len() > max {
+        return Err(AppError::BadRequest(format!(
+            "Import must contain between 1 and {} rows",
+            max
+        )));
+    }
+
+    Ok(Json(create_many(&state, &caller, &base, rows).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_rows() {
+        let rows = parse_csv(
+            "url,title,custom_alias,expires_at,clicks\n\
+             https://example.com/a,Launch,launch,2030-01-01T00:00:00Z,12\n\
+             https://example.com/b,,,,\n\
+             https://example.com/c,x,,not-a-date,\n",
+        )
+        .unwrap();
+
+        assert_eq!(rows.len(), 3);
+        let first = rows[0].as_ref().unwrap();
+        assert_eq!(first.custom_alias.as_deref(), Some("launch"));
+        assert!(first.expires_at.is_some());
+        let second = rows[1].as_ref().unwrap();
+        assert_eq!(second.title, None);
+        assert_eq!(second.custom_alias, None);
+        assert!(rows[2].is_err());
+
+        assert!(parse_csv("link,title\nhttps://example.com,x\n").is_err());
+    }
+}
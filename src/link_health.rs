@@ -0,0 +1,233 @@
+use axum::{extract::State, response::{IntoResponse, Json}};
+use futures::StreamExt;
+use reqwest::{Method, StatusCode};
+use sqlx::Row;
+use std::collections::{HashMap, HashSet};
+use std::error::Error as _;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::models::LinkCheckReport;
+use crate::{scheduler, storage, validation, AppError, AppState};
+
+const PAGE_SIZE: i64 = 200;
+const MAX_CONCURRENT_CHECKS: usize = 8;
+const MAX_REDIRECTS: usize = 5;
+// Contains "bot", so checks of other short links end up in bot_clicks
+const USER_AGENT: &str = "QuickURL-LinkCheckBot/1.0";
+
+pub const HEALTHY: &str = "ok";
+const WAYBACK_PREFIX: &str = "https://web.archive.org/web/";
+
+// Gone for good rather than briefly down, timeouts and server errors often pass on their own
+pub fn is_dead(status: &str) -> bool {
+    matches!(status, "404" | "410" | "connection_error" | "ssl_error")
+}
+
+// Where visitors of a dead link go instead: the latest Wayback Machine snapshot, or the
+// configured URL with {url} replaced by the encoded destination
+pub fn fallback_url(fallback: &str, destination: &str) -> String {
+    if fallback == "wayback" {
+        return format!("{}{}", WAYBACK_PREFIX, destination);
+    }
+    let encoded: String = url::form_urlencoded::byte_serialize(destination.as_bytes()).collect();
+    fallback.replace("{url}", &encoded)
+}
+
+fn client(config: &Config) -> anyhow::Result<reqwest::Client> {
+    Ok(validation::public_only(reqwest::Client::builder(), config.allow_private_destinations)
+        .timeout(Duration::from_secs(config.link_check_timeout_secs))
+        .redirect(validation::redirect_policy(config.max_url_length, config.allow_private_destinations, MAX_REDIRECTS))
+        .user_agent(USER_AGENT)
+        .build()?)
+}
+
+// What health_status records: "ok" when the destination answers, the status code of an error
+// answer such as "404", otherwise "timeout", "ssl_error" or "connection_error"
+pub async fn check(client: &reqwest::Client, destination: &str) -> String {
+    let mut answer = client.request(Method::HEAD, destination).send().await;
+    // Plenty of servers refuse or mishandle HEAD, anything but a clear "gone" gets a GET as well.
+    // Only the headers are read, the body is dropped unread.
+    let retry = match &answer {
+        Ok(response) => response.status().as_u16() >= 400 && !matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::GONE),
+        Err(e) => !e.is_timeout(),
+    };
+    if retry {
+        answer = client.request(Method::GET, destination).send().await;
+    }
+    match answer {
+        Ok(response) if response.status().as_u16() < 400 => HEALTHY.to_string(),
+        Ok(response) => response.status().as_u16().to_string(),
+        Err(e) => failure(&e).to_string(),
+    }
+}
+
+fn failure(e: &reqwest::Error) -> &'static str {
+    if e.is_timeout() {
+        return "timeout";
+    }
+    // TLS errors only show up in the message of some underlying source
+    let mut source = e.source();
+    while let Some(cause) = source {
+        let message = cause.to_string().to_ascii_lowercase();
+        if ["certificate", "tls", "ssl", "handshake"].iter().any(|marker| message.contains(marker)) {
+            return "ssl_error";
+        }
+        source = cause.source();
+    }
+    "connection_error"
+}
+
+// Checks the destination of every live link, each distinct destination once per page. Only a
+// change of status moves updated_at, every check moves last_checked_at.
+pub async fn run_checks(state: &AppState) -> anyhow::Result<LinkCheckReport> {
+    let client = client(&state.config)?;
+    let mut report = LinkCheckReport::default();
+    let now = storage::now();
+    let mut after = String::new();
+    loop {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, token, original_url, health_status FROM urls
+            WHERE deleted_at IS NULL AND (expires_at IS NULL OR expires_at > $1) AND id > $2
+                AND (LOWER(original_url) LIKE 'http://%' OR LOWER(original_url) LIKE 'https://%')
+            ORDER BY id LIMIT $3
+            "#
+        )
+        .bind(storage::ts(now))
+        .bind(&after)
+        .bind(PAGE_SIZE)
+        .fetch_all(&state.db)
+        .await?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        after = last.get("id");
+
+        let destinations: HashSet<String> = rows.iter().map(|row| row.get("original_url")).collect();
+        let statuses: HashMap<String, String> = futures::stream::iter(destinations)
+            .map(|destination| {
+                let client = &client;
+                async move {
+                    let status = check(client, &destination).await;
+                    (destination, status)
+                }
+            })
+            .buffer_unordered(MAX_CONCURRENT_CHECKS)
+            .collect()
+            .await;
+
+        let checked_at = storage::ts(storage::now());
+        for row in &rows {
+            let status = &statuses[&row.get::<String, _>("original_url")];
+            let changed = row.get::<Option<String>, _>("health_status").as_ref() != Some(status);
+            let mut update = sqlx::query(if changed {
+                "UPDATE urls SET last_checked_at = $1, health_status = $2, updated_at = $1 WHERE id = $3"
+            } else {
+                "UPDATE urls SET last_checked_at = $1, health_status = $2 WHERE id = $3"
+            });
+            update = update.bind(&checked_at).bind(status).bind(row.get::<String, _>("id"));
+            update.execute(&state.db).await?;
+
+            report.checked += 1;
+            if status != HEALTHY {
+                report.broken += 1;
+            }
+            if changed {
+                report.changed += 1;
+                state.links.invalidate(&row.get::<String, _>("token")).await;
+            }
+        }
+    }
+    Ok(report)
+}
+
+pub fn spawn(state: Arc<AppState>) {
+    let interval_secs = state.config.link_check_interval_secs;
+    scheduler::spawn(state, "link_check", interval_secs, |state| async move {
+        match run_checks(&state).await {
+            Ok(report) if report.changed > 0 => println!(
+                "🩺 Link check found {} of {} destinations broken, {} changed",
+                report.broken, report.checked, report.changed
+            ),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("❌ Link check failed: {}", e);
+                return Err(e.to_string());
+            }
+        }
+        Ok(())
+    });
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/check-links",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Destinations of all live links checked", body = LinkCheckReport),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn trigger_checks(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let report = run_checks(&state)
+        .await
+        .map_err(|e| AppError::InternalError(e.to_string()))?;
+
+    Ok(Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // Answers HEAD with `head` and anything else with `get`, one connection per request
+    async fn serve(head: &'static str, get: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let read = socket.read(&mut request).await.unwrap_or_default();
+                let status = if request[..read].starts_with(b"HEAD") { head } else { get };
+                let response = format!("HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n", status);
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{}/", address)
+    }
+
+    #[test]
+    fn test_fallback_url() {
+        let destination = "https://example.com/a?b=1";
+        assert_eq!(fallback_url("wayback", destination), "https://web.archive.org/web/https://example.com/a?b=1");
+        assert_eq!(
+            fallback_url("https://gone.example/?from={url}", destination),
+            "https://gone.example/?from=https%3A%2F%2Fexample.com%2Fa%3Fb%3D1"
+        );
+        assert!(is_dead("404") && is_dead("connection_error"));
+        assert!(!is_dead("ok") && !is_dead("503") && !is_dead("timeout"));
+    }
+
+    #[tokio::test]
+    async fn test_check_statuses() {
+        let config = Config { allow_private_destinations: true, link_check_timeout_secs: 2, ..Config::default() };
+        let client = client(&config).unwrap();
+
+        assert_eq!(check(&client, &serve("200 OK", "200 OK").await).await, "ok");
+        assert_eq!(check(&client, &serve("404 Not Found", "200 OK").await).await, "404");
+        // Refused HEAD, the GET decides
+        assert_eq!(check(&client, &serve("405 Method Not Allowed", "200 OK").await).await, "ok");
+        assert_eq!(check(&client, &serve("405 Method Not Allowed", "503 Service Unavailable").await).await, "503");
+
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        assert_eq!(check(&client, &format!("http://{}/", closed)).await, "connection_error");
+    }
+}
@@ -0,0 +1,320 @@
+use sqlx::{AnyPool, Row};
+
+use crate::cache::{CachedLink, LinkCache};
+use crate::shared_cache::SharedCache;
+use crate::resolver::{self, Schedule};
+use crate::{aliases, link_health, reports, rules, storage, telemetry, variants, AppError};
+
+// How a click on a limited link was counted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Counted {
+    // click_count is already updated, with the destination stored at that moment
+    InDatabase(String),
+    // Counted in Redis, the click buffer adds it to click_count
+    Shared,
+    LimitReached,
+    // Switched off after it was cached, answered with the disabled page
+    Disabled,
+}
+
+// The redirect path's access to links, and the one place their caches are consulted: the local
+// cache, then Redis when configured. Lookups read from the replica when one is configured; a
+// token it does not know yet is looked up again on the primary so a link works right after it
+// was created. Writes always go to the primary.
+#[derive(Clone)]
+pub struct LinkStore {
+    primary: AnyPool,
+    replica: Option<AnyPool>,
+    cache: LinkCache,
+    shared: Option<SharedCache>,
+}
+
+impl LinkStore {
+    pub fn new(primary: AnyPool, replica: Option<AnyPool>, cache: LinkCache, shared: Option<SharedCache>) -> Self {
+        if let Some(shared) = &shared {
+            shared.spawn_listener(cache.clone());
+        }
+        Self { primary, replica, cache, shared }
+    }
+
+    // Pool for reads that can lag behind writes, the primary when there is no replica
+    pub fn reader(&self) -> &AnyPool {
+        self.replica.as_ref().unwrap_or(&self.primary)
+    }
+
+    pub fn has_replica(&self) -> bool {
+        self.replica.is_some()
+    }
+
+    pub async fn resolve(&self, token: &str) -> Result<CachedLink, AppError> {
+        if let Some(link) = self.cache.get(token) {
+            return Ok(link);
+        }
+        if let Some(link) = self.shared_get(token).await {
+            self.cache.insert(token.to_string(), link.clone());
+            return Ok(link);
+        }
+
+        let mut link = load(self.reader(), token).await?;
+        if link.is_none() && self.replica.is_some() {
+            link = load(&self.primary, token).await?;
+        }
+        let link = link.ok_or(AppError::UrlNotFound)?;
+        self.cache.insert(token.to_string(), link.clone());
+        if let Some(shared) = &self.shared {
+            shared.insert(token, &link).await;
+        }
+        Ok(link)
+    }
+
+    // Token of the link an alias belongs to, looked up again on the primary like tokens
+    pub async fn resolve_alias(&self, alias: &str) -> Result<Option<String>, AppError> {
+        let mut token = aliases::canonical_token(self.reader(), alias).await?;
+        if token.is_none() && self.replica.is_some() {
+            token = aliases::canonical_token(&self.primary, alias).await?;
+        }
+        Ok(token)
+    }
+
+    async fn shared_get(&self, token: &str) -> Option<CachedLink> {
+        self.shared.as_ref()?.get(token).await
+    }
+
+    // Limited links need an exact count. With Redis the shared counter decides, otherwise one
+    // statement checks that the link is still live with clicks left, counts the click and returns
+    // its current destination, so expiry, deletion and the limit cannot race the count. Only a
+    // refused click takes a second query to tell why. While Redis is unavailable the database is
+    // used, clicks counted there are missing from the counter.
+    pub async fn count_click(&self, token: &str, max_clicks: i64) -> Result<Counted, AppError> {
+        if let Some(shared) = &self.shared {
+            if let Some(count) = shared.increment_clicks(token, || self.stored_clicks(token)).await? {
+                return Ok(if count > max_clicks { Counted::LimitReached } else { Counted::Shared });
+            }
+        }
+
+        let now = storage::ts(storage::now());
+        let increment = storage::retry_busy(|| {
+            sqlx::query(
+                r#"
+                UPDATE urls SET click_count = click_count + 1
+                WHERE token = $1 AND deleted_at IS NULL AND is_active = 1 AND takedown IS NULL
+                    AND (max_clicks IS NULL OR click_count < max_clicks)
+                    AND (starts_at IS NULL OR starts_at <= $2) AND (expires_at IS NULL OR expires_at > $2)
+                RETURNING original_url
+                "#
+            )
+            .bind(token)
+            .bind(&now)
+            .fetch_optional(&self.primary)
+        });
+        let counted = telemetry::timed("increment_click_count", increment)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        match counted {
+            Some(row) => {
+                let original_url: String = row.get("original_url");
+                Ok(Counted::InDatabase(original_url))
+            }
+            None => {
+                // The link changed after we cached it, or ran out of clicks
+                self.invalidate(token).await;
+                self.refusal(token).await
+            }
+        }
+    }
+
+    async fn refusal(&self, token: &str) -> Result<Counted, AppError> {
+        let row = sqlx::query("SELECT starts_at, expires_at, deleted_at, takedown, is_active FROM urls WHERE token = $1")
+            .bind(token)
+            .fetch_optional(&self.primary)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        let Some(row) = row else {
+            return Err(AppError::UrlNotFound);
+        };
+        if row.get::<Option<String>, _>("deleted_at").is_some() {
+            return Err(AppError::UrlDeleted);
+        }
+        let starts_at = storage::get_opt_ts(&row, "starts_at");
+        let expires_at = storage::get_opt_ts(&row, "expires_at");
+        // In the order the redirect checks them
+        match resolver::schedule(starts_at, expires_at, storage::now()) {
+            Schedule::Pending => return Err(AppError::UrlNotFound),
+            Schedule::Expired => return Err(AppError::UrlExpired),
+            Schedule::Live => {}
+        }
+        if let Some(takedown) = row.get::<Option<String>, _>("takedown").as_deref().and_then(reports::Takedown::parse) {
+            return Err(takedown.error());
+        }
+        if row.get::<i64, _>("is_active") == 0 {
+            return Ok(Counted::Disabled);
+        }
+        Ok(Counted::LimitReached)
+    }
+
+    // Whether a limited link has no clicks left, without counting one
+    pub async fn limit_reached(&self, token: &str, max_clicks: i64) -> Result<bool, AppError> {
+        if let Some(shared) = &self.shared {
+            if let Some(count) = shared.clicks(token).await {
+                return Ok(count >= max_clicks);
+            }
+        }
+        Ok(self.stored_clicks(token).await? >= max_clicks)
+    }
+
+    async fn stored_clicks(&self, token: &str) -> Result<i64, AppError> {
+        let row = sqlx::query("SELECT click_count FROM urls WHERE token = $1")
+            .bind(token)
+            .fetch_optional(&self.primary)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        Ok(row.map_or(0, |row| row.get("click_count")))
+    }
+
+    pub async fn invalidate(&self, token: &str) {
+        self.cache.invalidate(token);
+        if let Some(shared) = &self.shared {
+            shared.invalidate(token).await;
+        }
+    }
+
+    // Whether Redis answered the last command, None without Redis
+    pub fn shared_healthy(&self) -> Option<bool> {
+        self.shared.as_ref().map(SharedCache::is_healthy)
+    }
+
+    // Approximate number of cached links, None when the cache is disabled
+    pub fn cached_count(&self) -> Option<u64> {
+        self.cache.entry_count()
+    }
+}
+
+// Deleted links are an error rather than None, they are gone on every pool
+async fn load(db: &AnyPool, token: &str) -> Result<Option<CachedLink>, AppError> {
+    let lookup = sqlx::query(
+        r#"
+        SELECT id, original_url, starts_at, expires_at, max_clicks, domain, sticky_variants, flag_reason, takedown,
+            health_status, forward_query, template, warned, is_active, deleted_at
+        FROM urls WHERE token = $1
+        "#
+    )
+    .bind(token)
+    .fetch_optional(db);
+    let Some(row) = telemetry::timed("lookup_url", lookup)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    else {
+        return Ok(None);
+    };
+    // Not cached, so a restore takes effect on the next request
+    if row.get::<Option<String>, _>("deleted_at").is_some() {
+        return Err(AppError::UrlDeleted);
+    }
+
+    let id: String = row.get("id");
+    Ok(Some(CachedLink {
+        rules: rules::load(db, &id).await?,
+        variants: variants::load(db, &id).await?,
+        sticky_variants: row.get::<i64, _>("sticky_variants") != 0,
+        flagged: row.get::<Option<String>, _>("flag_reason").is_some(),
+        warned: row.get::<i64, _>("warned") != 0,
+        takedown: row.get::<Option<String>, _>("takedown").as_deref().and_then(reports::Takedown::parse),
+        active: row.get::<i64, _>("is_active") != 0,
+        dead: row.get::<Option<String>, _>("health_status").as_deref().is_some_and(link_health::is_dead),
+        forward_query: row.get::<i64, _>("forward_query") != 0,
+        template: row.get::<i64, _>("template") != 0,
+        id,
+        original_url: row.get("original_url"),
+        starts_at: storage::get_opt_ts(&row, "starts_at"),
+        expires_at: storage::get_opt_ts(&row, "expires_at"),
+        max_clicks: row.get("max_clicks"),
+        domain: row.get("domain"),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn insert_link(db: &AnyPool, token: &str) {
+        let now = storage::now();
+        sqlx::query(
+            r#"
+            INSERT INTO urls (id, token, original_url, created_at, expires_at, click_count, max_clicks)
+            VALUES ($1, $2, $3, $4, $5, 0, 1)
+            "#,
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(token)
+        .bind("https://example.com")
+        .bind(storage::ts(now))
+        .bind(storage::ts(now + chrono::Duration::days(1)))
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replica_miss_falls_back_to_primary() {
+        let replicas = storage::test_pools().await.unwrap();
+        for ((primary, _), (replica, _)) in storage::test_pools().await.unwrap().into_iter().zip(replicas) {
+            let links = LinkStore::new(primary.clone(), Some(replica), LinkCache::new(10, Duration::from_secs(60)), None);
+
+            insert_link(&primary, "fresh1").await;
+            assert_eq!(links.resolve("fresh1").await.unwrap().original_url, "https://example.com");
+            assert!(matches!(links.resolve("nope12").await, Err(AppError::UrlNotFound)));
+
+            assert!(!links.limit_reached("fresh1", 1).await.unwrap());
+            assert_eq!(links.count_click("fresh1", 1).await.unwrap(), Counted::InDatabase("https://example.com".into()));
+            assert!(links.limit_reached("fresh1", 1).await.unwrap());
+            assert_eq!(links.count_click("fresh1", 1).await.unwrap(), Counted::LimitReached);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_count_click_checks_the_stored_link() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let links = LinkStore::new(db.clone(), None, LinkCache::new(10, Duration::from_secs(60)), None);
+            insert_link(&db, "edited").await;
+            insert_link(&db, "lapsed").await;
+            links.resolve("edited").await.unwrap();
+
+            // The cached link is stale, the click counts for what the database has now
+            sqlx::query("UPDATE urls SET original_url = 'https://example.org', max_clicks = 5 WHERE token = 'edited'")
+                .execute(&db)
+                .await
+                .unwrap();
+            assert_eq!(links.count_click("edited", 5).await.unwrap(), Counted::InDatabase("https://example.org".into()));
+
+            sqlx::query("UPDATE urls SET expires_at = $1 WHERE token = 'lapsed'")
+                .bind(storage::ts(storage::now() - chrono::Duration::seconds(1)))
+                .execute(&db)
+                .await
+                .unwrap();
+            assert!(matches!(links.count_click("lapsed", 1).await, Err(AppError::UrlExpired)));
+            sqlx::query("UPDATE urls SET deleted_at = $1 WHERE token = 'edited'")
+                .bind(storage::ts(storage::now()))
+                .execute(&db)
+                .await
+                .unwrap();
+            assert!(matches!(links.count_click("edited", 5).await, Err(AppError::UrlDeleted)));
+            assert!(matches!(links.count_click("nope12", 1).await, Err(AppError::UrlNotFound)));
+
+            // Switched off or taken down since it was cached, the click is not counted
+            insert_link(&db, "offln1").await;
+            insert_link(&db, "takdn1").await;
+            sqlx::query("UPDATE urls SET is_active = 0 WHERE token = 'offln1'").execute(&db).await.unwrap();
+            sqlx::query("UPDATE urls SET takedown = 'legal' WHERE token = 'takdn1'").execute(&db).await.unwrap();
+            assert_eq!(links.count_click("offln1", 5).await.unwrap(), Counted::Disabled);
+            assert!(matches!(links.count_click("takdn1", 5).await, Err(AppError::LegalTakedown)));
+            let clicks: i64 = sqlx::query("SELECT CAST(SUM(click_count) AS BIGINT) AS clicks FROM urls WHERE token IN ('offln1', 'takdn1')")
+                .fetch_one(&db)
+                .await
+                .unwrap()
+                .get("clicks");
+            assert_eq!(clicks, 0);
+        }
+    }
+}
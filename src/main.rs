@@ -1,68 +1,549 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{ConnectInfo, DefaultBodyLimit, Path, Query, RawQuery, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware,
     response::{IntoResponse, Json, Redirect},
-    routing::{get, post},
-    Router,
+    routing::{delete, get, patch, post, put},
+    Extension, Router,
 };
-use sqlx::{sqlite::SqlitePool, Row};
+use clap::Parser;
+use metrics_exporter_prometheus::PrometheusHandle;
+use sha2::Digest;
+use sqlx::{any::AnyRow, AnyConnection, AnyPool, Connection, Row};
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::CorsLayer;
 use uuid::Uuid;
 
+mod admin_ui;
+mod aliases;
+mod audit;
+mod auth;
+mod base_url;
+mod bio;
+mod bots;
+mod broker;
+mod bulk;
+mod cache;
+mod campaigns;
+mod cleanup;
+mod cli;
+mod clicks;
+mod config;
+mod dashboard;
+mod destinations;
+mod device;
+mod domains;
+mod export;
+mod geo;
+mod graphql;
+mod http_cache;
+mod idn;
+mod import;
+mod link_health;
+mod links;
 mod models;
-mod token;
+mod normalize;
+mod oidc;
+mod openapi;
+mod orgs;
+mod outbox;
+mod pages;
+mod preview;
+mod qr;
+mod quota;
+mod ratelimit;
+mod referrers;
+mod reports;
+mod request_id;
+mod rollup;
+mod rules;
+mod safebrowsing;
+mod scheduler;
+mod search;
+mod shared_cache;
+mod site;
+mod stats;
+mod stream;
+mod telemetry;
+mod templates;
+mod titles;
+mod users;
+mod validation;
+mod variants;
+mod versioning;
+mod webhooks;
 
+use quickurl_core::{profanity, reserved, resolver, scaling, sequence, snowflake, storage, token, SignedLink, Signer};
+
+use auth::{Caller, Scope};
+use audit::AuditAction;
+use base_url::BaseUrl;
+use broker::EventPublisher;
+use cache::{CachedLink, LinkCache};
+use links::{Counted, LinkStore};
+use clicks::{Click, ClickRecorder};
+use cli::{Cli, Command};
+use config::{Config, TokenMode, UnsafeLinkAction};
+use dashboard::RollingWindow;
+use destinations::DestinationPolicy;
+use domains::DomainResolver;
+use geo::GeoIp;
 use models::*;
+use normalize::Normalizer;
+use oidc::Oidc;
+use pages::Pages;
+use ratelimit::RateLimiter;
+use request_id::RequestContext;
+use resolver::Schedule;
+use profanity::WordFilter;
+use reserved::ReservedTokens;
+use safebrowsing::SafeBrowsing;
+use shared_cache::SharedCache;
+use site::SiteFiles;
+use scaling::TokenScaling;
+use sequence::Sequence;
+use snowflake::Snowflake;
+use storage::{Backend, SqlBuilder};
+use stream::ClickStream;
+use titles::TitleFetcher;
 use token::TokenGenerator;
+use webhooks::{WebhookDispatcher, WebhookEvent};
 
 #[derive(Clone)]
 pub struct AppState {
-    db: SqlitePool,
+    db: AnyPool,
+    backend: Backend,
+    config: Config,
+    metrics: PrometheusHandle,
     token_gen: TokenGenerator,
+    // Set in snowflake token mode, random tokens otherwise
+    snowflake: Option<Snowflake>,
+    sequence: Option<Sequence>,
+    token_scaling: TokenScaling,
+    reserved: ReservedTokens,
+    words: WordFilter,
+    admin_key_hash: Option<String>,
+    jwt_secret: Vec<u8>,
+    // Set when signing_secret is configured
+    signer: Option<Signer>,
+    // Set when oidc_issuer is configured
+    oidc: Option<Oidc>,
+    links: LinkStore,
+    clicks: ClickRecorder,
+    domains: DomainResolver,
+    geoip: GeoIp,
+    webhooks: WebhookDispatcher,
+    events: EventPublisher,
+    click_stream: ClickStream,
+    dashboard: RollingWindow,
+    destinations: DestinationPolicy,
+    safe_browsing: SafeBrowsing,
+    titles: TitleFetcher,
+    pages: Pages,
+    site: SiteFiles,
+    normalizer: Normalizer,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let config = Config::load()?;
+    match cli.command {
+        None | Some(Command::Serve) => serve(config).await,
+        Some(_) => cli::run(cli, &config).await,
+    }
+}
+
+async fn serve(config: Config) -> anyhow::Result<()> {
     println!("🚀 Starting QuickURL API server...");
 
-    // Initialize database
-let database_url = "sqlite:quickurl.db";
-let db = SqlitePool::connect(database_url).await?;
-    
-    // Run migrations
-    sqlx::migrate!("./migrations").run(&db).await?;
+    let _tracer_provider = telemetry::install_tracing(&config)?;
+    if let Some(endpoint) = config.otlp_endpoint.as_deref().filter(|endpoint| !endpoint.is_empty()) {
+        println!("🔭 Exporting traces to {}", endpoint);
+    }
 
-    let state = AppState {
-        db,
-        token_gen: TokenGenerator::new(),
+    // Initialize database and run migrations for the selected backend
+    let (db, backend) = storage::connect(&config.database()).await?;
+    println!("🗄️  Using {:?} database", backend);
+    let replica = storage::connect_replica(&config.database(), backend).await?;
+    if replica.is_some() {
+        println!("🗄️  Reading links, listings and stats from the replica");
+    }
+
+    // Admin key is only used to manage API keys, it is never stored in the database
+    let admin_key_hash = config
+        .admin_key
+        .as_deref()
+        .filter(|key| !key.is_empty())
+        .map(auth::hash_key);
+    if admin_key_hash.is_none() {
+        println!("⚠️  No admin key configured, POST /keys is disabled");
+    }
+
+    let jwt_secret = match config.jwt_secret.as_deref().filter(|secret| !secret.is_empty()) {
+        Some(secret) => secret.as_bytes().to_vec(),
+        None => {
+            println!("⚠️  No JWT secret configured, user sessions will not survive a restart");
+            rand::random::<[u8; 32]>().to_vec()
+        }
     };
 
-    // Build the application with routes
-    let app = Router::new()
-        .route("/", get(health_check))
-        .route("/health", get(health_check))
+    let normalizer = Normalizer::new(&config);
+    let signer = config.signing_secret.as_deref().filter(|secret| !secret.is_empty()).map(Signer::new);
+    if signer.is_some() {
+        println!("✍️  Redirecting signed links minted with the signing secret");
+    }
+
+    let oidc = Oidc::from_config(&config)?;
+    if let Some(issuer) = config.oidc_issuer.as_deref().filter(|_| oidc.is_some()) {
+        println!("🔑 Signing in through OIDC provider {}", issuer);
+    }
+
+    let geoip = GeoIp::open(config.geoip_database.as_deref())?;
+    if !geoip.is_enabled() {
+        println!("⚠️  No GeoIP database configured, clicks are recorded without location");
+    }
+
+    let bind_address = config.bind_address();
+    let write_limiter = Arc::new(RateLimiter::new(config.write_rate_limit_per_minute));
+    let redirect_limiter = Arc::new(RateLimiter::new(config.redirect_rate_limit_per_minute));
+    let clicks = ClickRecorder::spawn(
+        db.clone(),
+        Duration::from_millis(config.click_flush_interval_ms),
+        config.click_buffer_size,
+        Duration::from_secs(config.unique_click_window_secs),
+    );
+    let webhooks = WebhookDispatcher::new(db.clone(), &config)?;
+    webhooks.spawn();
+    let events = EventPublisher::spawn(&config, db.clone())?;
+    if events.is_enabled() {
+        println!("📨 Publishing click events to the broker on {}", config.event_topic);
+    }
+    let destinations = DestinationPolicy::load(&db).await?;
+    destinations.spawn_refresh(db.clone(), Duration::from_secs(config.redirect_cache_ttl_secs));
+    let safe_browsing = SafeBrowsing::new(&config)?;
+    let titles = TitleFetcher::spawn(db.clone(), &config)?;
+    let pages = Pages::load(&config)?;
+    let site = SiteFiles::load(&config)?;
+    if config.fetch_titles {
+        println!("🏷️  Fetching titles of links created without one");
+    }
+    let shared_cache = SharedCache::connect(&config).await?;
+    if shared_cache.is_some() {
+        println!("🧩 Sharing cached links and click limits through Redis");
+    }
+    let links = LinkStore::new(
+        db.clone(),
+        replica,
+        LinkCache::new(
+            config.redirect_cache_capacity,
+            Duration::from_secs(config.redirect_cache_ttl_secs),
+        ),
+        shared_cache,
+    );
+    let snowflake = match config.token_mode {
+        TokenMode::Snowflake => {
+            let snowflake = Snowflake::start(&db, config.instance_id)
+                .await?
+                .scrambled(config.token_scramble_key.as_deref().map(scramble_key));
+            println!("❄️  Minting snowflake tokens as worker {}", snowflake.worker());
+            Some(snowflake)
+        }
+        TokenMode::Random | TokenMode::Sequential | TokenMode::Hash => None,
+    };
+    // Only random tokens collide by chance, hash tokens would change with the number of links
+    let odds = if config.token_mode == TokenMode::Random { config.token_collision_odds } else { 0 };
+    let token_scaling = TokenScaling::start(&db, odds).await?;
+    let sequence = (config.token_mode == TokenMode::Sequential).then(|| {
+        println!("🔢 Minting sequential tokens");
+        Sequence::new(db.clone()).scrambled(config.token_scramble_key.as_deref().map(scramble_key))
+    });
+    let state = Arc::new(AppState {
+        db,
+        backend,
+        clicks,
+        webhooks,
+        events,
+        click_stream: ClickStream::new(),
+        dashboard: RollingWindow::new(),
+        destinations,
+        safe_browsing,
+        titles,
+        pages,
+        site,
+        metrics: telemetry::install()?,
+        token_gen: TokenGenerator::with_length(config.token_length)
+            .alphabet(config.token_alphabet.as_deref())
+            .exclude_ambiguous(config.exclude_ambiguous_chars)
+            .lowercase(config.case_insensitive_tokens),
+        snowflake,
+        sequence,
+        token_scaling,
+        reserved: ReservedTokens::new(&config.reserved_tokens, &config.reserved_prefixes),
+        words: WordFilter::new(config.token_word_filter, &config.token_blocked_words),
+        links,
+        domains: DomainResolver::new(Duration::from_secs(config.redirect_cache_ttl_secs)),
+        config,
+        admin_key_hash,
+        jwt_secret,
+        signer,
+        normalizer,
+        oidc,
+        geoip,
+    });
+
+    cleanup::spawn(state.clone());
+    safebrowsing::spawn(state.clone());
+    link_health::spawn(state.clone());
+    rollup::spawn(state.clone());
+
+    // Mutating routes require an API key, redirects and lookups stay public
+    let protected = Router::new()
         .route("/shorten", post(create_short_url))
+        .route("/shorten/batch", post(create_short_urls_batch))
+        .route("/urls/bulk", post(bulk::bulk_update))
+        .route(
+            "/urls/import",
+            post(import::import_urls).layer(DefaultBodyLimit::max(import::MAX_UPLOAD_BYTES)),
+        )
+        .route("/urls/:token", patch(update_url).delete(delete_url))
+        .route("/urls/:token/restore", post(restore_url))
+        .route("/urls/:token/disable", post(disable_url))
+        .route("/urls/:token/enable", post(enable_url))
+        .route("/urls/:token/rules", put(rules::put_rules))
+        .route("/urls/:token/variants", put(variants::put_variants))
+        .route("/urls/:token/aliases", post(aliases::add_alias))
+        .route("/urls/:token/aliases/:alias", delete(aliases::delete_alias))
+        .route("/urls/:token/transfer", post(orgs::transfer_url))
+        .route("/orgs", post(orgs::create_org).get(orgs::list_orgs))
+        .route("/orgs/:id/members", get(orgs::list_members).put(orgs::put_member))
+        .route("/orgs/:id/members/:user_id", delete(orgs::delete_member))
+        .route("/campaigns", post(campaigns::create_campaign).get(campaigns::list_campaigns))
+        .route("/campaigns/:id", delete(campaigns::delete_campaign))
+        .route("/pages", post(bio::create_page).get(bio::list_pages))
+        .route("/pages/:slug", get(bio::get_page).patch(bio::update_page).delete(bio::delete_page))
+        .route("/webhooks", post(webhooks::create_webhook).get(webhooks::list_webhooks))
+        .route("/webhooks/:id", delete(webhooks::delete_webhook))
+        .route("/webhooks/:id/deliveries", get(webhooks::list_deliveries))
+        .route_layer(middleware::from_fn(auth::enforce_role))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_api_key))
+        .route_layer(middleware::from_fn_with_state((write_limiter.clone(), state.clone()), ratelimit::rate_limit));
+
+    // Any role may export or erase its own account
+    let own_account = Router::new()
+        .route("/users/me", delete(users::delete_account))
+        .route("/users/me/export", get(users::export_account))
+        .route("/users/me/usage", get(quota::get_usage))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_api_key))
+        .route_layer(middleware::from_fn_with_state((write_limiter.clone(), state.clone()), ratelimit::rate_limit));
+
+    let accounts = Router::new()
+        .route("/auth/register", post(users::register))
+        .route("/auth/login", post(users::login))
+        .route_layer(middleware::from_fn_with_state((write_limiter.clone(), state.clone()), ratelimit::rate_limit));
+
+    // Browser redirects, the callback is registered with the identity provider as it is
+    let oidc = Router::new()
+        .route("/auth/oidc/login", get(oidc::login))
+        .route("/auth/oidc/callback", get(oidc::callback))
+        .route_layer(middleware::from_fn_with_state((write_limiter.clone(), state.clone()), ratelimit::rate_limit));
+
+    // Resolves its own caller, the key may come as a query parameter and anonymous use is optional
+    let bookmarklet = Router::new()
+        .route("/shorten", get(shorten_from_query))
+        .route_layer(middleware::from_fn_with_state((write_limiter.clone(), state.clone()), ratelimit::rate_limit));
+
+    // Anyone can report a link, the write limit keeps the queue from being flooded
+    let reports = Router::new()
+        .route("/report/:token", post(reports::create_report))
+        .route_layer(middleware::from_fn_with_state((write_limiter, state.clone()), ratelimit::rate_limit));
+
+    let redirects = Router::new()
+        .route("/:token", get(redirect_url).post(continue_redirect))
+        .route("/:token/", get(redirect_trailing_slash))
+        .route("/p/:token", get(preview::get_preview))
+        .route("/:token/oembed", get(preview::get_oembed))
+        .route("/:token/*path", get(redirect_path).post(continue_redirect_path))
+        .route_layer(middleware::from_fn_with_state((redirect_limiter, state.clone()), ratelimit::rate_limit));
+
+    let admin = Router::new()
+        .route("/keys", post(auth::create_api_key).get(auth::list_api_keys))
+        .route("/keys/:id/rotate", post(auth::rotate_api_key))
+        .route("/admin/users/:id/role", put(users::set_user_role))
+        .route("/admin/cleanup", post(cleanup::trigger_cleanup))
+        .route("/admin/purge", post(cleanup::purge_deleted))
+        .route("/admin/rescan", post(safebrowsing::trigger_rescan))
+        .route("/admin/check-links", post(link_health::trigger_checks))
+        .route("/admin/jobs", get(scheduler::list_jobs))
+        .route("/admin/reports", get(reports::list_reports))
+        .route("/admin/reports/:token/resolve", post(reports::resolve_reports))
+        .route("/events", get(stream::stream_all_clicks))
+        .route("/admin/live", get(dashboard::live_dashboard))
+        .route("/admin/destinations", post(destinations::create_entry).get(destinations::list_entries))
+        .route("/admin/destinations/:hostname", delete(destinations::delete_entry))
+        .route("/domains", post(domains::create_domain).get(domains::list_domains))
+        .route(
+            "/domains/:hostname",
+            get(domains::get_domain).patch(domains::update_domain).delete(domains::delete_domain),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_admin_key));
+
+    // Public, but a presented key must have the scope
+    let lookups = Router::new()
         .route("/urls", get(list_urls))
+        .route("/urls/export", get(export::export_urls))
+        .route("/urls/search", get(search::search_urls))
         .route("/urls/:token", get(get_url_info))
-        .route("/urls/:token", axum::routing::delete(delete_url))
-        .route("/:token", get(redirect_url))
+        .route("/urls/:token/history", get(audit::get_history))
+        .route("/urls/:token/rules", get(rules::get_rules))
+        .route("/urls/:token/variants", get(variants::get_variants))
+        .route("/urls/:token/aliases", get(aliases::list_aliases))
+        .route("/urls/:token/qr", get(qr::get_qr_code))
+        .route_layer(middleware::from_fn_with_state((state.clone(), Scope::LinksRead), auth::require_scope));
+
+    let analytics = Router::new()
+        .route("/urls/:token/stats", get(stats::get_url_stats))
+        .route("/urls/:token/stats/geo", get(stats::get_geo_stats))
+        .route("/urls/:token/stats/referrers", get(referrers::get_referrer_stats))
+        .route("/urls/:token/stream", get(stream::stream_url_clicks))
+        .route("/campaigns/:id/stats", get(campaigns::get_campaign_stats))
+        .route("/stats/top", get(stats::get_top_links))
+        .route_layer(middleware::from_fn_with_state((state.clone(), Scope::StatsRead), auth::require_scope));
+
+    // Fields check the scopes they need, so the whole schema sits behind one route
+    let graphql = Router::new()
+        .route("/graphql", get(graphql::graphiql).post(graphql::execute))
+        .layer(Extension(graphql::schema(state.clone())));
+
+    // JSON endpoints are versioned, redirects and health checks stay at the root
+    let api = Router::new()
+        .merge(lookups)
+        .merge(analytics)
+        .merge(graphql)
+        .merge(protected)
+        .merge(own_account)
+        .merge(accounts)
+        .merge(bookmarklet)
+        .merge(reports)
+        .merge(admin);
+
+    // Build the application with routes
+    let mut app = Router::new()
+        .route("/", get(health_check))
+        .route("/health", get(health_check))
+        .route("/healthz", get(health_check))
+        .route("/readyz", get(readiness_check))
+        .route("/robots.txt", get(site::robots_txt))
+        .route("/favicon.ico", get(site::favicon))
+        .route("/.well-known/security.txt", get(site::security_txt))
+        .route("/metrics", get(telemetry::metrics_handler))
+        .route("/openapi.json", get(openapi::openapi_json))
+        .route("/docs", get(openapi::swagger_ui))
+        .route("/admin", get(admin_ui::index))
+        .route("/admin/assets/*path", get(admin_ui::asset))
+        .merge(redirects)
+        .merge(oidc)
+        .nest(versioning::API_PREFIX, api.clone());
+    // The paths from before versioning answer like their successors until legacy_api_paths is off
+    if state.config.legacy_api_paths {
+        app = app.merge(api.layer(middleware::from_fn(versioning::deprecated)));
+    }
+    let app = app
+        // Skips small bodies, images and event streams, large listings and exports shrink a lot
+        .layer(CompressionLayer::new())
+        .layer(middleware::from_fn(telemetry::track_http))
+        .layer(middleware::from_fn(request_id::propagate))
         .layer(CorsLayer::permissive())
-        .with_state(Arc::new(state));
+        .with_state(state);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
-    println!("📡 Server running on http://0.0.0.0:3000");
-    println!("📚 API Endpoints:");
-    println!("  POST /shorten - Create short URL");
-    println!("  GET  /urls - List all URLs");
+    let listener = tokio::net::TcpListener::bind(&bind_address).await?;
+    println!("📡 Server running on http://{}", bind_address);
+    println!("📚 API Endpoints (JSON ones under {}, redirects and probes at the root):", versioning::API_PREFIX);
+    println!("  GET  /healthz - Liveness, the process is up");
+    println!("  GET  /readyz - Readiness, checks the database and migrations (503 when unavailable)");
+    println!("  GET  /metrics - Prometheus metrics");
+    println!("  GET  /robots.txt, /favicon.ico, /.well-known/security.txt - Files crawlers and browsers look for");
+    println!("  GET  /openapi.json - OpenAPI document");
+    println!("  GET  /docs - Interactive API docs");
+    println!("  GET  /admin - Web UI for managing links, signs in with an API key, admin key or user token");
+    println!("  POST /auth/register - Create a user account");
+    println!("  POST /auth/login - Exchange email and password for a bearer token");
+    println!("  GET  /auth/oidc/login - Sign in through the OIDC provider (?format=json for the token without the web UI)");
+    println!("  GET  /users/me/export - Download the account's links and clicks as a zip");
+    println!("  DELETE /users/me - Erase the account with its links and clicks");
+    println!("  GET  /users/me/usage - Links, daily links and custom aliases used against the quotas");
+    println!("  POST /keys, GET /keys - Create API keys with a role and optional scopes, list them with their last use (admin)");
+    println!("  POST /keys/:id/rotate - Replace a key's secret, the old one keeps working for a grace period (admin)");
+    println!("  PUT  /admin/users/:id/role - Give an account the admin, editor or read_only role (admin)");
+    println!("  POST /admin/cleanup - Remove expired links now (admin)");
+    println!("  POST /admin/purge - Permanently remove deleted links (?older_than_days) (admin)");
+    println!("  POST /admin/rescan - Check live links against Safe Browsing now (admin)");
+    println!("  POST /admin/check-links - Check that the destinations of live links still answer (admin)");
+    println!("  GET  /admin/jobs - Background jobs with their next and last run (admin)");
+    println!("  GET  /admin/reports - Abuse report queue (?status=open|resolved|all) (admin)");
+    println!("  POST /admin/reports/:token/resolve - Disable (410), take down for legal reasons (451) or clear a reported link (admin)");
+    println!("  GET  /events - Live click stream for every link over Server-Sent Events (admin)");
+    println!("  GET  /admin/live - WebSocket feed of clicks per second and top tokens of the last minute (admin)");
+    println!("  POST /admin/destinations, GET /admin/destinations, DELETE /admin/destinations/:hostname - Destination block and allow lists (admin)");
+    println!("  POST /domains, GET /domains[/:hostname], PATCH|DELETE /domains/:hostname - Custom domains (admin)");
+    println!("  POST /shorten - Create short URL, optionally with custom_alias, starts_at, max_clicks or a custom domain (?dedupe) (auth)");
+    println!("  GET  /shorten?url= - Create short URL and return it as plain text, for bookmarklets and curl (?format=json, custom_alias, dedupe, key)");
+    println!("  POST /shorten/batch - Create many short URLs at once (auth)");
+    println!("  POST /urls/bulk - Delete, set expires_at or add a tag on many links in one transaction (auth)");
+    println!("  POST /urls/import - Create links from a CSV or NDJSON upload (auth)");
+    println!("  GET  /urls - List URLs, scoped to the caller's account and organizations (?page, per_page, sort, order, created_after, expires_before, q, tag, org_id, campaign_id, deleted)");
+    println!("  GET  /urls/search - Full-text search over titles and destinations, ranked and highlighted (?q, limit)");
+    println!("  GET  /urls/export - Download links as CSV or NDJSON (?format, same filters as /urls)");
     println!("  GET  /urls/:token - Get URL info");
-    println!("  DELETE /urls/:token - Delete URL");
-    println!("  GET  /:token - Redirect to original URL");
+    println!("  GET  /urls/:token/stats - Click analytics (?days)");
+    println!("  GET  /stats/top - Most clicked links among the caller's, trending widget (?period=24h|7d|30d, limit)");
+    println!("  GET  /urls/:token/stats/geo - Clicks per country and top cities");
+    println!("  GET  /urls/:token/stats/referrers - Clicks per referring domain, direct and email (?days)");
+    println!("  GET  /urls/:token/stream - Live clicks over Server-Sent Events");
+    println!("  POST /graphql - Links, clicks, campaigns and their statistics in one query (GET opens GraphiQL)");
+    println!("  GET  /urls/:token/history - Who created, changed or deleted the link (auth)");
+    println!("  GET  /urls/:token/qr - QR code (?format=png|svg, size, ec=L|M|Q|H)");
+    println!("  PATCH /urls/:token - Update URL, title, schedule, expiry or tags (auth)");
+    println!("  DELETE /urls/:token - Delete URL, restorable until purged (auth)");
+    println!("  POST /urls/:token/restore - Restore a deleted URL (auth)");
+    println!("  POST /urls/:token/disable, POST /urls/:token/enable - Switch a link off and on again, keeping its stats (auth)");
+    println!("  GET  /urls/:token/rules, PUT /urls/:token/rules - Per-country and per-device destination overrides (PUT needs auth)");
+    println!("  GET  /urls/:token/variants, PUT /urls/:token/variants - Weighted A/B split destinations (PUT needs auth)");
+    println!("  GET  /urls/:token/aliases, POST /urls/:token/aliases, DELETE /urls/:token/aliases/:alias - More tokens for the same link and statistics (POST and DELETE need auth)");
+    println!("  POST /urls/:token/transfer - Move a link between the caller's account and an organization (auth)");
+    println!("  POST /orgs, GET /orgs - Create organizations and list the caller's (auth)");
+    println!("  GET  /orgs/:id/members, PUT /orgs/:id/members, DELETE /orgs/:id/members/:user_id - Members and their admin, editor or viewer roles (auth)");
+    println!("  POST /campaigns, GET /campaigns, DELETE /campaigns/:id - Group links with campaign_id for roll-up statistics (auth)");
+    println!("  POST /pages, GET /pages[/:slug], PATCH|DELETE /pages/:slug - Link-in-bio pages, public at /@slug (auth)");
+    println!("  GET  /campaigns/:id/stats - Clicks, unique visitors and per-link breakdown of a campaign (?from, to) (auth)");
+    println!("  POST /webhooks, GET /webhooks, DELETE /webhooks/:id - Signed event notifications (auth)");
+    println!("  GET  /webhooks/:id/deliveries - Recent delivery attempts of a webhook (auth)");
+    println!("  POST /report/:token - Report an abusive link");
+    println!("  GET  /:token - Redirect to original URL, scoped by Host for custom domains (also /:token/)");
+    println!("  GET  /@:slug - Link-in-bio page");
+    println!("  POST /:token - Continue to a flagged link from its warning page");
+    println!("  GET  /p/:token or /:token+ - Preview destination before following");
+    println!("  GET  /:token/oembed - oEmbed description of a link, the preview page carries Open Graph tags");
+    println!("  GET  /:token/*path - Redirect a template link with its placeholders filled from the path");
+    println!("  GET  /:payload/:signature - Redirect a signed link minted with the signing_secret");
 
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
     Ok(())
 }
 
+// Liveness only, it never touches dependencies so a slow database does not get the process restarted
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "service",
+    responses(
+        (status = 200, description = "Service is up, / and /health answer the same", body = HealthResponse),
+    ),
+)]
 async fn health_check() -> impl IntoResponse {
     Json(HealthResponse {
         status: "healthy".to_string(),
@@ -71,180 +552,1931 @@ async fn health_check() -> impl IntoResponse {
     })
 }
 
-async fn create_short_url(
-    State(state): State<Arc<AppState>>,
-    Json(payload): Json<CreateUrlRequest>,
-) -> Result<impl IntoResponse, AppError> {
-    // Validate URL
-    if !payload.url.starts_with("http://") && !payload.url.starts_with("https://") {
-        return Err(AppError::BadRequest("URL must start with http:// or https://".into()));
+const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
+
+async fn ping_database(db: &AnyPool) -> DatabaseCheck {
+    let started = Instant::now();
+    let ping = tokio::time::timeout(READINESS_TIMEOUT, sqlx::query("SELECT 1").execute(db)).await;
+    match ping {
+        Ok(Ok(_)) => DatabaseCheck {
+            ok: true,
+            latency_ms: Some(started.elapsed().as_secs_f64() * 1000.0),
+            error: None,
+        },
+        Ok(Err(e)) => DatabaseCheck { ok: false, latency_ms: None, error: Some(e.to_string()) },
+        Err(_) => DatabaseCheck { ok: false, latency_ms: None, error: Some("timed out".into()) },
     }
+}
 
-    // Generate unique token
-    let token = state.token_gen.generate();
-    let id = Uuid::new_v4().to_string();
-    let created_at = chrono::Utc::now();
-    let expires_at = payload.expires_at.unwrap_or_else(|| {
-        chrono::Utc::now() + chrono::Duration::days(30) // Default 30 days
-    });
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "service",
+    responses(
+        (status = 200, description = "Database reachable and migrations applied", body = ReadinessResponse),
+        (status = 503, description = "Database or replica unreachable, or behind this release", body = ReadinessResponse),
+    ),
+)]
+async fn readiness_check(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let database = ping_database(&state.db).await;
+    let replica = match state.links.has_replica() {
+        true => Some(ping_database(state.links.reader()).await),
+        false => None,
+    };
+
+    let applied = match database.ok {
+        true => storage::applied_migration(&state.db).await.ok().flatten(),
+        false => None,
+    };
+    let latest = storage::latest_migration(state.backend);
+    let migrations = MigrationCheck {
+        up_to_date: applied.is_some() && applied >= latest,
+        applied,
+        latest,
+    };
+    let cache = CacheCheck {
+        enabled: state.links.cached_count().is_some(),
+        entries: state.links.cached_count().unwrap_or(0),
+        redis: state.links.shared_healthy(),
+    };
+
+    let ready = database.ok && migrations.up_to_date && replica.as_ref().is_none_or(|replica| replica.ok);
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let body = ReadinessResponse {
+        status: if ready { "ready" } else { "unavailable" }.to_string(),
+        database,
+        replica,
+        migrations,
+        cache,
+    };
+    (status, Json(body))
+}
+
+// Shared by everything that stores a destination, so links, rules and variants accept exactly the same URLs
+fn validate_url(state: &AppState, url: &str) -> Result<(), AppError> {
+    let url = validation::validate_link_destination(&state.config, url).map_err(AppError::BadRequest)?;
+    state.destinations.check(&url).map_err(AppError::BadRequest)
+}
+
+fn validate_schedule(
+    starts_at: Option<chrono::DateTime<chrono::Utc>>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<(), AppError> {
+    if starts_at.zip(expires_at).is_some_and(|(starts_at, expires_at)| starts_at >= expires_at) {
+        return Err(AppError::BadRequest("starts_at must be before expires_at".into()));
+    }
+    Ok(())
+}
+
+fn validate_max_clicks(max_clicks: Option<i64>) -> Result<(), AppError> {
+    if max_clicks.is_some_and(|max| max < 1) {
+        return Err(AppError::BadRequest("max_clicks must be at least 1".into()));
+    }
+    Ok(())
+}
+
+const MAX_NOTES_LENGTH: usize = 2000;
+const MAX_METADATA_BYTES: usize = 4096;
+
+fn validate_notes(notes: Option<&str>) -> Result<(), AppError> {
+    if notes.is_some_and(|notes| notes.chars().count() > MAX_NOTES_LENGTH) {
+        return Err(AppError::BadRequest(format!("notes must be at most {} characters", MAX_NOTES_LENGTH)));
+    }
+    Ok(())
+}
+
+// Stored as JSON text, the size limit keeps one link from carrying a document
+fn metadata_text(metadata: Option<&serde_json::Map<String, serde_json::Value>>) -> Result<Option<String>, AppError> {
+    let Some(metadata) = metadata else {
+        return Ok(None);
+    };
+    let text = serde_json::to_string(metadata).map_err(|e| AppError::InternalError(e.to_string()))?;
+    if text.len() > MAX_METADATA_BYTES {
+        return Err(AppError::BadRequest(format!("metadata must be at most {} bytes of JSON", MAX_METADATA_BYTES)));
+    }
+    Ok(Some(text))
+}
+
+const MIN_ALIAS_LENGTH: usize = 3;
+const MAX_ALIAS_LENGTH: usize = 64;
+
+fn validate_alias(config: &Config, reserved: &ReservedTokens, alias: String) -> Result<String, AppError> {
+    let valid_chars = alias
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !(MIN_ALIAS_LENGTH..=MAX_ALIAS_LENGTH).contains(&alias.len()) || !valid_chars {
+        return Err(AppError::BadRequest(format!(
+            "custom_alias must be {} to {} letters, digits, '-' or '_'",
+            MIN_ALIAS_LENGTH, MAX_ALIAS_LENGTH
+        )));
+    }
+    // Stored lowercase so that every spelling of it finds the link
+    let alias = if config.case_insensitive_tokens { alias.to_ascii_lowercase() } else { alias };
+    if reserved.contains(&alias) {
+        return Err(AppError::BadRequest(format!("custom_alias {} is reserved", alias)));
+    }
+    Ok(alias)
+}
+
+// The spelling to retry a token with that was not found as typed, with case_insensitive_tokens.
+// Tokens created before the option was turned on can be mixed case, so the exact one goes first.
+pub fn folded_token(config: &Config, token: &str) -> Option<String> {
+    config.case_insensitive_tokens.then(|| resolver::folded(token)).flatten()
+}
+
+// The link and its own token, which an alias or a differently cased spelling resolves to
+async fn resolve_token(state: &AppState, token: String) -> Result<(String, CachedLink), AppError> {
+    let folded = folded_token(&state.config, &token);
+    for spelling in std::iter::once(token).chain(folded) {
+        match state.links.resolve(&spelling).await {
+            Err(AppError::UrlNotFound) => {}
+            result => return result.map(|link| (spelling, link)),
+        }
+        if let Some(canonical) = state.links.resolve_alias(&spelling).await? {
+            return state.links.resolve(&canonical).await.map(|link| (canonical, link));
+        }
+    }
+    Err(AppError::UrlNotFound)
+}
+
+// New token of the configured length plus `extra` in the configured mode
+async fn generate_token(state: &AppState, url: &str, domain: Option<&str>, extra: usize) -> Result<String, AppError> {
+    // Snowflake and sequential ids never repeat, so a collision can only be a custom alias and a
+    // longer token would not help
+    if let Some(snowflake) = &state.snowflake {
+        loop {
+            let token = snowflake.next_token(&state.token_gen);
+            if usable_token(state, &token) {
+                return Ok(token);
+            }
+        }
+    }
+    if let Some(sequence) = &state.sequence {
+        loop {
+            let token = sequence
+                .next_token(&state.token_gen)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            if usable_token(state, &token) {
+                return Ok(token);
+            }
+        }
+    }
+    // An unusable hash token gets longer, drawing another one would give a different token per request
+    if state.config.token_mode == TokenMode::Hash {
+        // Config validation makes sure the key is set in this mode
+        let key = state.config.token_scramble_key.as_deref().unwrap_or_default();
+        let mut generator = state.token_gen.extended(extra);
+        let destination = normalize::canonicalize(url).unwrap_or_else(|| url.to_string());
+        let input = format!("{}\n{}", domain.unwrap_or_default(), destination);
+        loop {
+            let token = generator.hashed_with_key(key.as_bytes(), &input);
+            if usable_token(state, &token) {
+                return Ok(token);
+            }
+            generator = generator.extended(1);
+        }
+    }
+    let generator = state.token_scaling.generator(&state.token_gen).extended(extra);
+    loop {
+        let token = generator.generate();
+        if usable_token(state, &token) {
+            return Ok(token);
+        }
+    }
+}
+
+// Generated tokens skip reserved words and whatever the word filter blocks
+fn usable_token(state: &AppState, token: &str) -> bool {
+    !state.reserved.contains(token) && !state.words.matches(token)
+}
+
+// The scramble key is any string, the permutation needs 64 bits of it
+fn scramble_key(key: &str) -> u64 {
+    let digest = sha2::Sha256::digest(key.as_bytes());
+    u64::from_be_bytes(digest[..8].try_into().expect("a SHA-256 digest has 32 bytes"))
+}
+
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LENGTH: usize = 50;
+
+// Tags are case-insensitive slugs, duplicates collapse and the result is sorted
+fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, AppError> {
+    let mut normalized: Vec<String> = tags.iter().map(|tag| tag.trim().to_lowercase()).collect();
+    normalized.sort();
+    normalized.dedup();
+
+    if normalized.len() > MAX_TAGS {
+        return Err(AppError::BadRequest(format!("A link can have at most {} tags", MAX_TAGS)));
+    }
+    for tag in &normalized {
+        let valid_chars = tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+        if tag.is_empty() || tag.len() > MAX_TAG_LENGTH || !valid_chars {
+            return Err(AppError::BadRequest(format!(
+                "Invalid tag {:?}: use up to {} letters, digits, '-', '_', '.' or ':'",
+                tag, MAX_TAG_LENGTH
+            )));
+        }
+    }
+    Ok(normalized)
+}
+
+// The form a destination is stored in. Templates are kept as they are, parsing would escape
+// the braces of their placeholders.
+fn normalized_destination(state: &AppState, url: &str, template: bool) -> String {
+    match template {
+        true => url.to_string(),
+        false => state.normalizer.normalize(url).unwrap_or_else(|| url.to_string()),
+    }
+}
+
+// Validates a create request and assigns id, token and timestamps without touching the database
+async fn prepare_url(state: &AppState, base: &BaseUrl, payload: CreateUrlRequest) -> Result<CreateUrlResponse, AppError> {
+    validate_url(state, &payload.url)?;
+    if payload.template {
+        templates::validate(&payload.url)?;
+    }
+    let original_url = normalized_destination(state, &payload.url, payload.template);
+    validate_max_clicks(payload.max_clicks)?;
+    validate_notes(payload.notes.as_deref())?;
+    metadata_text(payload.metadata.as_ref())?;
+    let tags = normalize_tags(payload.tags)?;
+    let domain = payload
+        .domain
+        .map(|domain| {
+            domains::normalize_hostname(&domain)
+                .ok_or_else(|| AppError::BadRequest(format!("Invalid domain: {}", domain)))
+        })
+        .transpose()?;
+
+    let token = match payload.custom_alias {
+        Some(alias) => validate_alias(&state.config, &state.reserved, alias)?,
+        None => generate_token(state, &original_url, domain.as_deref(), 0).await?,
+    };
+    let created_at = storage::now();
+    let expires_at = match payload.expires_at {
+        Some(expires_at) => expires_at,
+        None => (state.config.default_ttl_days > 0)
+            .then(|| created_at + chrono::Duration::days(state.config.default_ttl_days)),
+    };
+    validate_schedule(payload.starts_at, expires_at)?;
+
+    Ok(CreateUrlResponse {
+        id: Uuid::new_v4().to_string(),
+        short_url: base.short_url(domain.as_deref(), &token),
+        token,
+        original_url,
+        raw_url: Some(payload.url),
+        title: payload.title,
+        created_at,
+        starts_at: payload.starts_at,
+        expires_at,
+        click_count: 0,
+        max_clicks: payload.max_clicks,
+        domain,
+        tags,
+        notes: payload.notes,
+        metadata: payload.metadata,
+        campaign_id: payload.campaign_id,
+        forward_query: payload.forward_query,
+        template: payload.template,
+    })
+}
 
-    // Insert into database
-    sqlx::query(
+async fn insert_url(
+    conn: &mut AnyConnection,
+    url: &CreateUrlResponse,
+    caller: &Caller,
+    normalized_url: Option<&str>,
+    custom_alias: bool,
+) -> Result<(), AppError> {
+    // The unique index on token cannot see aliases
+    if aliases::is_alias(conn, &url.token).await? {
+        return Err(AppError::Conflict(format!("Token {} is already in use", url.token)));
+    }
+    // Lookalike hosts are let through but flagged, like destinations Safe Browsing lists later on
+    let flag_reason = idn::flag_reason(&url.original_url);
+    let insert = sqlx::query(
         r#"
-        INSERT INTO urls (id, token, original_url, title, created_at, expires_at, click_count)
-        VALUES (?, ?, ?, ?, ?, ?, 0)
+        INSERT INTO urls (id, token, original_url, title, created_at, updated_at, starts_at, expires_at, click_count, max_clicks, user_id, domain, normalized_url, notes, metadata, custom_alias, campaign_id, forward_query, template, raw_url, flagged_at, flag_reason)
+        VALUES ($1, $2, $3, $4, $5, $5, $6, $7, 0, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)
         "#
     )
-    .bind(&id)
-    .bind(&token)
-    .bind(&payload.url)
-    .bind(&payload.title)
-    .bind(created_at)
-    .bind(expires_at)
-    .execute(&state.db)
+    .bind(&url.id)
+    .bind(&url.token)
+    .bind(&url.original_url)
+    .bind(&url.title)
+    .bind(storage::ts(url.created_at))
+    .bind(url.starts_at.map(storage::ts))
+    .bind(url.expires_at.map(storage::ts))
+    .bind(url.max_clicks)
+    .bind(caller.user_id())
+    .bind(&url.domain)
+    .bind(normalized_url)
+    .bind(&url.notes)
+    .bind(metadata_text(url.metadata.as_ref())?)
+    .bind(i64::from(custom_alias))
+    .bind(&url.campaign_id)
+    .bind(i64::from(url.forward_query))
+    .bind(i64::from(url.template))
+    .bind(&url.raw_url)
+    .bind(flag_reason.as_ref().map(|_| storage::ts(url.created_at)))
+    .bind(&flag_reason)
+    .execute(&mut *conn);
+
+    telemetry::timed("insert_url", insert)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+                AppError::Conflict(format!("Token {} is already in use", url.token))
+            }
+            _ => AppError::DatabaseError(e.to_string()),
+        })?;
+    insert_tags(conn, &url.id, &url.tags).await?;
+    audit::record(conn, &url.id, &url.token, AuditAction::Create, caller, audit::snapshot(url)).await?;
+
+    metrics::counter!(telemetry::URLS_CREATED_TOTAL).increment(1);
+    Ok(())
+}
+
+async fn insert_tags(conn: &mut AnyConnection, url_id: &str, tags: &[String]) -> Result<(), AppError> {
+    for tag in tags {
+        sqlx::query("INSERT INTO url_tags (url_id, tag) VALUES ($1, $2)")
+            .bind(url_id)
+            .bind(tag)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+// Fills in tags for already loaded links with a single query
+async fn load_tags<'c>(db: impl sqlx::Executor<'c, Database = sqlx::Any>, urls: &mut [UrlInfo]) -> Result<(), AppError> {
+    if urls.is_empty() {
+        return Ok(());
+    }
+
+    let mut query = SqlBuilder::new("SELECT url_id, tag FROM url_tags WHERE url_id IN (");
+    for (i, url) in urls.iter().enumerate() {
+        if i > 0 {
+            query.push(", ");
+        }
+        query.push_bind(url.id.clone());
+    }
+    query.push(") ORDER BY tag");
+
+    let rows = telemetry::timed("load_tags", query.build().fetch_all(db))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for row in rows {
+        tags.entry(row.get("url_id")).or_default().push(row.get("tag"));
+    }
+    for url in urls {
+        url.tags = tags.remove(&url.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+const MAX_TOKEN_ATTEMPTS: usize = 5;
+// Attempts at the configured length before each further retry adds a character
+const ATTEMPTS_BEFORE_ESCALATING: usize = 3;
+
+// Id of a live (not deleted) link
+async fn find_url_id(db: &AnyPool, token: &str) -> Result<String, AppError> {
+    sqlx::query("SELECT id FROM urls WHERE token = $1 AND deleted_at IS NULL")
+        .bind(token)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .map(|row| row.get("id"))
+        .ok_or(AppError::UrlNotFound)
+}
+
+// Whether a link or an alias has the token
+async fn token_exists(conn: &mut AnyConnection, token: &str) -> Result<bool, AppError> {
+    let link = sqlx::query("SELECT id FROM urls WHERE token = $1")
+        .bind(token)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(link.is_some() || aliases::is_alias(conn, token).await?)
+}
+
+// A custom alias stored lowercase under case_insensitive_tokens, when a mixed-case token or alias
+// from before the option folds to it. The exact match resolves first, so the alias could only be
+// reached by the spellings that one does not take.
+async fn folded_token_exists(state: &AppState, conn: &mut AnyConnection, alias: &str) -> Result<bool, AppError> {
+    if !state.config.case_insensitive_tokens {
+        return Ok(false);
+    }
+    let taken = sqlx::query(
+        "SELECT token FROM urls WHERE LOWER(token) = $1 UNION ALL SELECT token FROM link_aliases WHERE LOWER(token) = $1",
+    )
+    .bind(alias)
+    .fetch_optional(&mut *conn)
     .await
     .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(taken.is_some())
+}
 
-    let response = CreateUrlResponse {
-        id,
-        token: token.clone(),
-        original_url: payload.url,
-        short_url: format!("http://localhost:3000/{}", token),
-        title: payload.title,
-        created_at,
-        expires_at,
-        click_count: 0,
+// Checks the caller's quotas, then inserts inside a savepoint, so a failed attempt leaves the
+// caller's transaction usable. A generated token that collides is replaced and retried; any
+// other conflict, including a taken custom alias or a dedupe race, is returned to the caller.
+async fn insert_unique(
+    state: &AppState,
+    base: &BaseUrl,
+    conn: &mut AnyConnection,
+    url: &mut CreateUrlResponse,
+    generated: bool,
+    caller: &Caller,
+    normalized_url: Option<&str>,
+) -> Result<(), AppError> {
+    quota::check(conn, &state.config, caller, !generated).await?;
+    if !generated && folded_token_exists(state, conn, &url.token).await? {
+        return Err(AppError::Conflict(format!("Token {} is already in use", url.token)));
+    }
+    for attempt in 1..=MAX_TOKEN_ATTEMPTS {
+        let mut savepoint = conn
+            .begin()
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let error = match insert_url(&mut savepoint, url, caller, normalized_url, !generated).await {
+            Ok(()) => {
+                // Announced by the commit that creates the link, never by one that is rolled back
+                let data = serde_json::to_value(&*url).unwrap_or_default();
+                webhooks::enqueue(&mut savepoint, WebhookEvent::LinkCreated, &url.id, &data)
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+                state.token_scaling.added();
+                return savepoint
+                    .commit()
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()));
+            }
+            Err(e) => e,
+        };
+        savepoint
+            .rollback()
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let collided = generated && matches!(error, AppError::Conflict(_)) && token_exists(conn, &url.token).await?;
+        if !collided {
+            return Err(error);
+        }
+
+        metrics::counter!(telemetry::TOKEN_COLLISIONS_TOTAL).increment(1);
+        // The same destination always hashes to the same token, only a longer one can differ
+        let extra = match state.config.token_mode {
+            TokenMode::Hash => attempt,
+            _ => attempt.saturating_sub(ATTEMPTS_BEFORE_ESCALATING - 1),
+        };
+        url.token = generate_token(state, &url.original_url, url.domain.as_deref(), extra).await?;
+        url.short_url = base.short_url(url.domain.as_deref(), &url.token);
+    }
+
+    Err(AppError::InternalError("Could not generate a unique token, please retry".into()))
+}
+
+// Live link the same owner previously created with dedupe for this destination and domain.
+// Dead ones give up their normalized_url so a fresh link can take over the unique slot.
+async fn find_deduped_url(
+    state: &AppState,
+    base: &BaseUrl,
+    owner: Option<&str>,
+    url: &CreateUrlResponse,
+    normalized_url: &str,
+) -> Result<Option<UrlInfo>, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT * FROM urls
+        WHERE normalized_url = $1 AND COALESCE(user_id, '') = $2 AND COALESCE(domain, '') = $3
+        "#
+    )
+    .bind(normalized_url)
+    .bind(owner.unwrap_or_default())
+    .bind(url.domain.clone().unwrap_or_default())
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let Some(row) = row else {
+        return Ok(None);
     };
+    let mut existing = url_info_from_row(base, &row);
+    load_tags(&state.db, std::slice::from_mut(&mut existing)).await?;
+    let exhausted = existing.max_clicks.is_some_and(|max| existing.click_count >= max);
+    if existing.expires_at.is_none_or(|expires_at| expires_at > chrono::Utc::now()) && !exhausted {
+        return Ok(Some(existing));
+    }
+
+    sqlx::query("UPDATE urls SET normalized_url = NULL WHERE id = $1")
+        .bind(&existing.id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(None)
+}
 
-    Ok((StatusCode::CREATED, Json(response)))
+// Runs only once the link is committed, a rolled back insert never has its destination fetched
+// Also for links with a title, the description and image of their preview come from there too
+fn fetch_card(state: &AppState, url: &CreateUrlResponse) {
+    state.titles.enqueue(&url.id, &url.original_url);
 }
 
-async fn list_urls(
+#[utoipa::path(
+    post,
+    path = "/shorten",
+    tag = "urls",
+    params(CreateUrlOptions),
+    request_body = CreateUrlRequest,
+    responses(
+        (status = 201, description = "Short URL created", body = CreateUrlResponse),
+        (status = 200, description = "Existing link returned by dedupe", body = UrlInfo),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Quota of the user account exceeded", body = ErrorResponse),
+        (status = 409, description = "Conflicting concurrent update", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+async fn create_short_url(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+    Query(options): Query<CreateUrlOptions>,
+    Json(payload): Json<CreateUrlRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let dedupe = options.dedupe.unwrap_or(state.config.dedupe_by_default);
+    shorten(&state, &caller, &base, dedupe, payload).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/shorten",
+    tag = "urls",
+    params(ShortenQuery),
+    responses(
+        (status = 201, description = "Short URL created, as text or a CreateUrlResponse with format=json", body = String, content_type = "text/plain"),
+        (status = 200, description = "Existing link returned by dedupe", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security((), ("api_key" = []), ("user_token" = []))
+)]
+async fn shorten_from_query(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    base: BaseUrl,
+    Query(query): Query<ShortenQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let key = query.key.as_deref().or(auth::bearer_token(&headers));
+    let (caller, permissions) = auth::resolve_caller(&state, key).await?;
+    if caller != Caller::Anonymous {
+        permissions.require(Scope::LinksWrite)?;
+    }
+    if caller == Caller::Anonymous && !state.config.anonymous_get_shorten {
+        return Err(AppError::Unauthorized("Missing bearer token or key parameter".into()));
+    }
+
+    let payload = CreateUrlRequest {
+        url: query.url,
+        title: None,
+        starts_at: None,
+        expires_at: None,
+        max_clicks: None,
+        domain: None,
+        tags: Vec::new(),
+        custom_alias: query.custom_alias,
+        notes: None,
+        metadata: None,
+        campaign_id: None,
+        forward_query: false,
+        template: false,
+    };
+    let dedupe = query.dedupe.unwrap_or(state.config.dedupe_by_default);
+    let shortened = shorten(&state, &caller, &base, dedupe, payload).await?;
+    if query.format == ShortenFormat::Json {
+        return Ok(shortened.into_response());
+    }
+    Ok((
+        shortened.status(),
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        format!("{}\n", shortened.short_url()),
+    )
+        .into_response())
+}
+
+// A new link, or the live one dedupe found for the same destination
+enum Shortened {
+    Created(CreateUrlResponse),
+    Existing(UrlInfo),
+}
+
+impl Shortened {
+    fn status(&self) -> StatusCode {
+        match self {
+            Shortened::Created(_) => StatusCode::CREATED,
+            Shortened::Existing(_) => StatusCode::OK,
+        }
+    }
+
+    fn short_url(&self) -> &str {
+        match self {
+            Shortened::Created(url) => &url.short_url,
+            Shortened::Existing(url) => &url.short_url,
+        }
+    }
+}
+
+impl IntoResponse for Shortened {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.status();
+        match self {
+            Shortened::Created(url) => (status, Json(url)).into_response(),
+            Shortened::Existing(url) => (status, Json(url)).into_response(),
+        }
+    }
+}
+
+async fn shorten(
+    state: &AppState,
+    caller: &Caller,
+    base: &BaseUrl,
+    dedupe: bool,
+    payload: CreateUrlRequest,
+) -> Result<Shortened, AppError> {
+    let generated = payload.custom_alias.is_none();
+    // A hash token already taken by the same destination is that link, not a new one
+    let dedupe = dedupe || (generated && state.config.token_mode == TokenMode::Hash);
+    let mut url = prepare_url(state, base, payload).await?;
+    state.safe_browsing.check(&url.original_url).await?;
+    let owner = caller.user_id();
+    if let Some(domain) = &url.domain {
+        domains::ensure_exists(&state.db, domain).await?;
+    }
+    if let Some(campaign_id) = &url.campaign_id {
+        campaigns::ensure_visible(&state.db, campaign_id, caller).await?;
+    }
+
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if !dedupe {
+        insert_unique(state, base, &mut tx, &mut url, generated, caller, None).await?;
+        tx.commit()
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        fetch_card(state, &url);
+        return Ok(Shortened::Created(url));
+    }
+
+    let normalized_url = normalize::canonicalize(&url.original_url)
+        .ok_or_else(|| AppError::BadRequest("URL cannot be parsed".into()))?;
+    if let Some(existing) = find_deduped_url(state, base, owner, &url, &normalized_url).await? {
+        return Ok(Shortened::Existing(existing));
+    }
+
+    match insert_unique(state, base, &mut tx, &mut url, generated, caller, Some(&normalized_url)).await {
+        Ok(()) => {
+            tx.commit()
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            fetch_card(state, &url);
+            Ok(Shortened::Created(url))
+        }
+        // Another request inserted the same destination between our lookup and insert
+        Err(AppError::Conflict(_)) => {
+            tx.rollback()
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            find_deduped_url(state, base, owner, &url, &normalized_url)
+                .await?
+                .map(Shortened::Existing)
+                .ok_or_else(|| AppError::Conflict("Concurrent update, please retry".into()))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/shorten/batch",
+    tag = "urls",
+    request_body = BatchShortenRequest,
+    responses(
+        (status = 200, description = "Per-item results", body = BatchShortenResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+async fn create_short_urls_batch(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+    Json(payload): Json<BatchShortenRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    let rows = sqlx::query("SELECT * FROM urls ORDER BY created_at DESC")
-        .fetch_all(&state.db)
+    let max = state.config.max_batch_size;
+    if payload.urls.is_empty() || payload.urls.len() > max {
+        return Err(AppError::BadRequest(format!(
+            "Batch must contain between 1 and {} URLs",
+            max
+        )));
+    }
+
+    let items = payload.urls.into_iter().map(Ok).collect();
+    Ok(Json(create_many(&state, &caller, &base, items).await?))
+}
+
+async fn create_one(
+    state: &AppState,
+    conn: &mut AnyConnection,
+    caller: &Caller,
+    base: &BaseUrl,
+    payload: CreateUrlRequest,
+) -> Result<CreateUrlResponse, AppError> {
+    let generated = payload.custom_alias.is_none();
+    let mut url = prepare_url(state, base, payload).await?;
+    state.safe_browsing.check(&url.original_url).await?;
+    if let Some(domain) = &url.domain {
+        domains::ensure_exists(&state.db, domain).await?;
+    }
+    if let Some(campaign_id) = &url.campaign_id {
+        campaigns::ensure_visible(&state.db, campaign_id, caller).await?;
+    }
+
+    insert_unique(state, base, conn, &mut url, generated, caller, None).await?;
+    Ok(url)
+}
+
+// Shared by batch and import: invalid or conflicting items are reported individually
+// and everything else is inserted in one transaction
+async fn create_many(
+    state: &AppState,
+    caller: &Caller,
+    base: &BaseUrl,
+    items: Vec<Result<CreateUrlRequest, String>>,
+) -> Result<BatchShortenResponse, AppError> {
+    // Taking more sequential ids inside the transaction would wait for its own write lock on SQLite
+    if let Some(sequence) = &state.sequence {
+        sequence
+            .prefetch(items.len() + MAX_TOKEN_ATTEMPTS)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    }
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let mut results = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+        let outcome = match item {
+            Ok(payload) => create_one(state, &mut tx, caller, base, payload).await,
+            Err(error) => Err(AppError::BadRequest(error)),
+        };
+        let result = match outcome {
+            Ok(url) => BatchItemResult::Created { index, url: Box::new(url) },
+            Err(AppError::BadRequest(error) | AppError::Conflict(error) | AppError::QuotaExceeded(error)) => {
+                BatchItemResult::Error { index, error }
+            }
+            Err(e) => return Err(e),
+        };
+        results.push(result);
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let mut created = 0;
+    for result in &results {
+        if let BatchItemResult::Created { url, .. } = result {
+            fetch_card(state, url);
+            created += 1;
+        }
+    }
+
+    Ok(BatchShortenResponse {
+        created,
+        failed: results.len() - created,
+        results,
+    })
+}
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+// Users only see their own links and those of their organizations, where viewers may read but
+// not change them. Anonymous callers only see unowned links.
+fn push_owner_filter(builder: &mut SqlBuilder, caller: &Caller, access: Access) {
+    match caller {
+        Caller::User(user_id) => {
+            builder
+                .push(" AND (user_id = ")
+                .push_bind(user_id.clone())
+                .push(" OR org_id IN (SELECT org_id FROM organization_members WHERE user_id = ")
+                .push_bind(user_id.clone());
+            if access == Access::Write {
+                builder.push(" AND role IN ('admin', 'editor')");
+            }
+            builder.push("))");
+        }
+        Caller::Anonymous => {
+            builder.push(" AND user_id IS NULL AND org_id IS NULL");
+        }
+        Caller::Admin | Caller::ApiKey(_) => {}
+    }
+}
+
+// Shared WHERE clause for the list query and its total count
+fn push_url_filters(builder: &mut SqlBuilder, query: &ListUrlsQuery, caller: &Caller) {
+    builder.push(if query.deleted {
+        " WHERE deleted_at IS NOT NULL"
+    } else {
+        " WHERE deleted_at IS NULL"
+    });
+    push_owner_filter(builder, caller, Access::Read);
+
+    if let Some(org_id) = query.org_id.as_deref().filter(|org_id| !org_id.is_empty()) {
+        builder.push(" AND org_id = ").push_bind(org_id.to_string());
+    }
+    if let Some(campaign_id) = query.campaign_id.as_deref().filter(|campaign_id| !campaign_id.is_empty()) {
+        builder.push(" AND campaign_id = ").push_bind(campaign_id.to_string());
+    }
+    if let Some(created_after) = query.created_after {
+        builder.push(" AND created_at > ").push_bind(storage::ts(created_after));
+    }
+    if let Some(expires_before) = query.expires_before {
+        builder.push(" AND expires_at < ").push_bind(storage::ts(expires_before));
+    }
+    if let Some(q) = query.q.as_deref().filter(|q| !q.is_empty()) {
+        // LIKE is case-sensitive on Postgres, lower both sides for identical results
+        builder
+            .push(" AND LOWER(title) LIKE ")
+            .push_bind(format!("%{}%", q.to_lowercase()));
+    }
+    if let Some(tag) = query.tag.as_deref().filter(|tag| !tag.is_empty()) {
+        builder
+            .push(" AND id IN (SELECT url_id FROM url_tags WHERE tag = ")
+            .push_bind(tag.trim().to_lowercase())
+            .push(")");
+    }
+    match query.health {
+        Some(HealthFilter::Ok) => builder.push(" AND health_status = ").push_bind(link_health::HEALTHY),
+        Some(HealthFilter::Broken) => builder.push(" AND health_status <> ").push_bind(link_health::HEALTHY),
+        Some(HealthFilter::Unchecked) => builder.push(" AND health_status IS NULL"),
+        None => builder,
+    };
+}
+
+// Ties on the sort column are broken by id so pages never overlap
+fn order_clause(query: &ListUrlsQuery) -> String {
+    let sort_column = match query.sort {
+        SortField::CreatedAt => "created_at",
+        SortField::ClickCount => "click_count",
+    };
+    let sort_order = match query.order {
+        SortOrder::Asc => "ASC",
+        SortOrder::Desc => "DESC",
+    };
+    format!(" ORDER BY {} {}, id {}", sort_column, sort_order, sort_order)
+}
+
+fn url_info_from_row(base: &BaseUrl, row: &AnyRow) -> UrlInfo {
+    let token: String = row.get("token");
+    let domain: Option<String> = row.get("domain");
+    let flag_reason: Option<String> = row.get("flag_reason");
+    let takedown: Option<String> = row.get("takedown");
+    let metadata: Option<String> = row.get("metadata");
+    let original_url: String = row.get("original_url");
+    UrlInfo {
+        id: row.get("id"),
+        short_url: base.short_url(domain.as_deref(), &token),
+        token,
+        display_url: idn::display_url(&original_url),
+        original_url,
+        raw_url: row.get("raw_url"),
+        title: row.get("title"),
+        description: row.get("description"),
+        image_url: row.get("image_url"),
+        created_at: storage::get_ts(row, "created_at"),
+        updated_at: storage::get_opt_ts(row, "updated_at").unwrap_or_else(|| storage::get_ts(row, "created_at")),
+        starts_at: storage::get_opt_ts(row, "starts_at"),
+        expires_at: storage::get_opt_ts(row, "expires_at"),
+        click_count: row.get("click_count"),
+        bot_clicks: row.get("bot_clicks"),
+        unique_clicks: row.get("unique_clicks"),
+        max_clicks: row.get("max_clicks"),
+        domain,
+        org_id: row.get("org_id"),
+        campaign_id: row.get("campaign_id"),
+        forward_query: row.get::<i64, _>("forward_query") != 0,
+        template: row.get::<i64, _>("template") != 0,
+        tags: Vec::new(),
+        notes: row.get("notes"),
+        metadata: metadata.and_then(|metadata| serde_json::from_str(&metadata).ok()),
+        is_active: row.get::<i64, _>("is_active") != 0,
+        flagged: flag_reason.is_some(),
+        flag_reason,
+        warned: row.get::<i64, _>("warned") != 0,
+        takedown: takedown.as_deref().and_then(reports::Takedown::parse),
+        last_checked_at: storage::get_opt_ts(row, "last_checked_at"),
+        health_status: row.get("health_status"),
+    }
+}
+
+// A page of the links matching the filters and how many match in total
+async fn find_urls(
+    state: &AppState,
+    query: &ListUrlsQuery,
+    caller: &Caller,
+    base: &BaseUrl,
+    limit: i64,
+    offset: i64,
+) -> Result<(i64, Vec<UrlInfo>), AppError> {
+    let mut count_query = SqlBuilder::new("SELECT COUNT(*) AS total FROM urls");
+    push_url_filters(&mut count_query, query, caller);
+    let total: i64 = telemetry::timed("count_urls", count_query.build().fetch_one(state.links.reader()))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .get("total");
+
+    let mut list_query = SqlBuilder::new("SELECT * FROM urls");
+    push_url_filters(&mut list_query, query, caller);
+    list_query
+        .push(order_clause(query))
+        .push(" LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    let rows = telemetry::timed("list_urls", list_query.build().fetch_all(state.links.reader()))
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-    let urls: Vec<UrlInfo> = rows
+    let mut urls: Vec<UrlInfo> = rows
         .into_iter()
-        .map(|row| UrlInfo {
-            id: row.get("id"),
-            token: row.get("token"),
-            original_url: row.get("original_url"),
-            short_url: format!("http://localhost:3000/{}", row.get::<String, _>("token")),
-            title: row.get("title"),
-            created_at: row.get("created_at"),
-            expires_at: row.get("expires_at"),
-            click_count: row.get("click_count"),
-        })
+        .map(|row| url_info_from_row(base, &row))
         .collect();
+    load_tags(state.links.reader(), &mut urls).await?;
+    Ok((total, urls))
+}
+
+#[utoipa::path(
+    get,
+    path = "/urls",
+    tag = "urls",
+    params(ListUrlsQuery),
+    responses(
+        (status = 200, description = "Page of URLs", body = ListUrlsResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+    ),
+)]
+async fn list_urls(
+    Query(query): Query<ListUrlsQuery>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+) -> Result<impl IntoResponse, AppError> {
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE);
+    if page < 1 {
+        return Err(AppError::BadRequest("page must be at least 1".into()));
+    }
+    if !(1..=MAX_PER_PAGE).contains(&per_page) {
+        return Err(AppError::BadRequest(format!(
+            "per_page must be between 1 and {}",
+            MAX_PER_PAGE
+        )));
+    }
 
-    Ok(Json(ListUrlsResponse { urls }))
+    let offset = (page - 1)
+        .checked_mul(per_page)
+        .ok_or_else(|| AppError::BadRequest("page is too large".into()))?;
+    let (total, urls) = find_urls(&state, &query, &caller, &base, per_page, offset).await?;
+    Ok(Json(ListUrlsResponse {
+        urls,
+        total,
+        page,
+        per_page,
+        total_pages: (total + per_page - 1) / per_page,
+    }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/urls/{token}",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 200, description = "URL details, with an ETag", body = UrlInfo),
+        (status = 304, description = "Unchanged since the ETag in If-None-Match"),
+        (status = 404, description = "URL not found, or not the caller's to read", body = ErrorResponse),
+    ),
+)]
 async fn get_url_info(
     Path(token): Path<String>,
     State(state): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, AppError> {
-    let row = sqlx::query("SELECT * FROM urls WHERE token = ?")
-        .bind(&token)
+    caller: Caller,
+    base: BaseUrl,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    // Notes and metadata are the owner's, other callers get the same 404 as for a missing token
+    let mut lookup = SqlBuilder::new("SELECT * FROM urls WHERE token = ");
+    lookup.push_bind(token).push(" AND deleted_at IS NULL");
+    push_owner_filter(&mut lookup, &caller, Access::Read);
+    let row = lookup
+        .build()
         .fetch_optional(&state.db)
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-    match row {
-        Some(row) => {
-            let url_info = UrlInfo {
-                id: row.get("id"),
-                token: row.get("token"),
-                original_url: row.get("original_url"),
-                short_url: format!("http://localhost:3000/{}", token),
-                title: row.get("title"),
-                created_at: row.get("created_at"),
-                expires_at: row.get("expires_at"),
-                click_count: row.get("click_count"),
-            };
-            Ok(Json(url_info))
+    let mut url = match row {
+        Some(row) => url_info_from_row(&base, &row),
+        None => return Err(AppError::UrlNotFound),
+    };
+    load_tags(&state.db, std::slice::from_mut(&mut url)).await?;
+
+    // Caches may keep a copy but have to check back, the answer is a cheap 304 when unchanged
+    let etag = http_cache::etag(&url);
+    let cache_headers = [(header::ETAG, etag.clone()), (header::CACHE_CONTROL, "no-cache".to_string())];
+    if http_cache::not_modified(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+    Ok((cache_headers, Json(url)).into_response())
+}
+
+#[utoipa::path(
+    patch,
+    path = "/urls/{token}",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    request_body = UpdateUrlRequest,
+    responses(
+        (status = 200, description = "Updated URL", body = UrlInfo),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+async fn update_url(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+    Json(payload): Json<UpdateUrlRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut update = SqlBuilder::new("UPDATE urls SET ");
+    let mut changes = 0;
+    let mut set = |update: &mut SqlBuilder, column: &str| {
+        if changes > 0 {
+            update.push(", ");
+        }
+        changes += 1;
+        update.push(format!("{} = ", column));
+    };
+
+    if let Some(url) = payload.url {
+        validate_url(&state, &url)?;
+        state.safe_browsing.check(&url).await?;
+        // Template URLs stay as they are, whether the link is one may come from the request
+        let template = match payload.template {
+            Some(template) => template,
+            None => sqlx::query("SELECT template FROM urls WHERE token = $1")
+                .bind(&token)
+                .fetch_optional(&state.db)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?
+                .is_some_and(|row| row.get::<i64, _>("template") != 0),
+        };
+        let original_url = normalized_destination(&state, &url, template);
+        let flag_reason = idn::flag_reason(&original_url);
+        set(&mut update, "flagged_at");
+        update.push_bind(flag_reason.as_ref().map(|_| storage::ts(storage::now())));
+        set(&mut update, "flag_reason");
+        update.push_bind(flag_reason);
+        set(&mut update, "original_url");
+        update.push_bind(original_url);
+        set(&mut update, "raw_url");
+        update.push_bind(url);
+        // The new destination just passed the check, and has not been checked for health yet
+        for column in ["last_checked_at", "health_status"] {
+            set(&mut update, column);
+            update.push_bind(None::<String>);
+        }
+    }
+    if let Some(title) = payload.title {
+        set(&mut update, "title");
+        update.push_bind(title);
+    }
+    if let Some(starts_at) = payload.starts_at {
+        set(&mut update, "starts_at");
+        update.push_bind(starts_at.map(storage::ts));
+    }
+    if let Some(expires_at) = payload.expires_at {
+        set(&mut update, "expires_at");
+        update.push_bind(expires_at.map(storage::ts));
+    }
+    if let Some(max_clicks) = payload.max_clicks {
+        validate_max_clicks(max_clicks)?;
+        set(&mut update, "max_clicks");
+        update.push_bind(max_clicks);
+    }
+    if let Some(notes) = payload.notes {
+        validate_notes(notes.as_deref())?;
+        set(&mut update, "notes");
+        update.push_bind(notes);
+    }
+    if let Some(metadata) = payload.metadata {
+        let metadata = metadata_text(metadata.as_ref())?;
+        set(&mut update, "metadata");
+        update.push_bind(metadata);
+    }
+    if let Some(campaign_id) = payload.campaign_id {
+        if let Some(campaign_id) = &campaign_id {
+            campaigns::ensure_visible(&state.db, campaign_id, &caller).await?;
         }
-        None => Err(AppError::NotFound("URL not found".into())),
+        set(&mut update, "campaign_id");
+        update.push_bind(campaign_id);
     }
+    if let Some(forward_query) = payload.forward_query {
+        set(&mut update, "forward_query");
+        update.push_bind(i64::from(forward_query));
+    }
+    if let Some(template) = payload.template {
+        set(&mut update, "template");
+        update.push_bind(i64::from(template));
+    }
+
+    let tags = payload.tags.map(normalize_tags).transpose()?;
+    if changes == 0 && tags.is_none() {
+        return Err(AppError::BadRequest("No fields to update".into()));
+    }
+    // Changing only the tags moves updated_at as well, and with it the ETag
+    update
+        .push(if changes > 0 { ", updated_at = " } else { "updated_at = " })
+        .push_bind(storage::ts(storage::now()));
+
+    let mut lookup = SqlBuilder::new("SELECT * FROM urls WHERE token = ");
+    lookup.push_bind(token.clone()).push(" AND deleted_at IS NULL");
+    push_owner_filter(&mut lookup, &caller, Access::Write);
+
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let mut old = lookup
+        .build()
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .map(|row| url_info_from_row(&base, &row))
+        .ok_or(AppError::UrlNotFound)?;
+    load_tags(&mut *tx, std::slice::from_mut(&mut old)).await?;
+    let id = old.id.clone();
+
+    update.push(" WHERE id = ").push_bind(id.clone());
+    update
+        .build()
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if let Some(tags) = tags {
+        sqlx::query("DELETE FROM url_tags WHERE url_id = $1")
+            .bind(&id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        insert_tags(&mut tx, &id, &tags).await?;
+    }
+
+    let mut updated = sqlx::query("SELECT * FROM urls WHERE id = $1")
+        .bind(&id)
+        .fetch_one(&mut *tx)
+        .await
+        .map(|row| url_info_from_row(&base, &row))
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    load_tags(&mut *tx, std::slice::from_mut(&mut updated)).await?;
+    // Checked on the stored result, the request may change only one side of the window
+    validate_schedule(updated.starts_at, updated.expires_at)?;
+    if updated.template {
+        templates::validate(&updated.original_url)?;
+    }
+    audit::record(&mut tx, &id, &token, AuditAction::Update, &caller, audit::diff(&old, &updated)).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    state.links.invalidate(&token).await;
+
+    Ok(Json(updated))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/urls/{token}",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 204, description = "URL deleted, it can be restored until purged"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
 async fn delete_url(
     Path(token): Path<String>,
     State(state): State<Arc<AppState>>,
+    caller: Caller,
 ) -> Result<impl IntoResponse, AppError> {
-    let result = sqlx::query("DELETE FROM urls WHERE token = ?")
-        .bind(&token)
-        .execute(&state.db)
+    set_deleted(&state, &token, &caller, true)
+        .await?
+        .ok_or(AppError::UrlNotFound)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Tombstones or restores a link and logs it, None when no link in that state was found.
+// Deleting drops normalized_url so the link no longer holds its dedupe slot.
+async fn set_deleted(state: &AppState, token: &str, caller: &Caller, deleted: bool) -> Result<Option<()>, AppError> {
+    let mut lookup = SqlBuilder::new("SELECT id FROM urls WHERE token = ");
+    lookup.push_bind(token.to_string()).push(if deleted {
+        " AND deleted_at IS NULL"
+    } else {
+        " AND deleted_at IS NOT NULL"
+    });
+    push_owner_filter(&mut lookup, caller, Access::Write);
+
+    let mut tx = state
+        .db
+        .begin()
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-    if result.rows_affected() == 0 {
-        return Err(AppError::NotFound("URL not found".into()));
+    let id: Option<String> = lookup
+        .build()
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .map(|row| row.get("id"));
+    let Some(id) = id else {
+        return Ok(None);
+    };
+
+    tombstone(&mut tx, &id, token, caller, deleted).await?;
+    if deleted {
+        webhooks::enqueue(&mut tx, WebhookEvent::LinkDeleted, &id, &serde_json::json!({ "token": token }))
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
     }
 
-    Ok(StatusCode::NO_CONTENT)
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    state.links.invalidate(token).await;
+    Ok(Some(()))
 }
 
-async fn redirect_url(
+async fn tombstone(conn: &mut AnyConnection, id: &str, token: &str, caller: &Caller, deleted: bool) -> Result<(), AppError> {
+    let (update, action) = if deleted {
+        ("UPDATE urls SET normalized_url = NULL, deleted_at = $1, updated_at = $3 WHERE id = $2", AuditAction::Delete)
+    } else {
+        ("UPDATE urls SET deleted_at = $1, updated_at = $3 WHERE id = $2", AuditAction::Restore)
+    };
+    let now = storage::ts(storage::now());
+    sqlx::query(update)
+        .bind(deleted.then(|| now.clone()))
+        .bind(id)
+        .bind(&now)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    audit::record(conn, id, token, action, caller, serde_json::json!({})).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/urls/{token}/restore",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 200, description = "Restored URL", body = UrlInfo),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "No deleted URL with this token", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+async fn restore_url(
     Path(token): Path<String>,
     State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
 ) -> Result<impl IntoResponse, AppError> {
-    // Get URL and check if exists and not expired
-    let row = sqlx::query("SELECT * FROM urls WHERE token = ?")
-        .bind(&token)
-        .fetch_optional(&state.db)
+    set_deleted(&state, &token, &caller, false)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No deleted URL with this token".into()))?;
+
+    get_url_info(Path(token), State(state), caller, base, HeaderMap::new()).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/urls/{token}/disable",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 200, description = "Disabled URL, its redirect answers 410 until it is enabled again", body = UrlInfo),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+async fn disable_url(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+) -> Result<impl IntoResponse, AppError> {
+    set_active(&state, &token, &caller, false).await?;
+    get_url_info(Path(token), State(state), caller, base, HeaderMap::new()).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/urls/{token}/enable",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 200, description = "Enabled URL, redirecting again", body = UrlInfo),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+async fn enable_url(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+) -> Result<impl IntoResponse, AppError> {
+    set_active(&state, &token, &caller, true).await?;
+    get_url_info(Path(token), State(state), caller, base, HeaderMap::new()).await
+}
+
+// Switches a link off or back on and logs it, unlike a delete the link keeps its token, dedupe
+// slot and stats. Nothing changes when it already is in that state.
+async fn set_active(state: &AppState, token: &str, caller: &Caller, active: bool) -> Result<(), AppError> {
+    let mut lookup = SqlBuilder::new("SELECT id, is_active FROM urls WHERE token = ");
+    lookup.push_bind(token.to_string()).push(" AND deleted_at IS NULL");
+    push_owner_filter(&mut lookup, caller, Access::Write);
+
+    let mut tx = state
+        .db
+        .begin()
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-    match row {
-        Some(row) => {
-            let expires_at: chrono::DateTime<chrono::Utc> = row.get("expires_at");
-            
-            // Check if expired
-            if chrono::Utc::now() > expires_at {
-                return Err(AppError::Gone("URL has expired".into()));
+    let row = lookup
+        .build()
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or(AppError::UrlNotFound)?;
+    let id: String = row.get("id");
+    let was_active = row.get::<i64, _>("is_active") != 0;
+    if was_active == active {
+        return Ok(());
+    }
+
+    sqlx::query("UPDATE urls SET is_active = $1, updated_at = $2 WHERE id = $3")
+        .bind(i64::from(active))
+        .bind(storage::ts(storage::now()))
+        .bind(&id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let changes = audit::diff(&serde_json::json!({ "is_active": was_active }), &serde_json::json!({ "is_active": active }));
+    audit::record(&mut tx, &id, token, AuditAction::Update, caller, changes).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    state.links.invalidate(token).await;
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/{token}",
+    tag = "redirects",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 308, description = "Redirect to the original URL, with the query string appended for links with forward_query. HEAD gets the same answer without counting a click, unless count_head_requests is set"),
+        (status = 307, description = "Redirect chosen by the link's targeting rules or A/B split, or the dead_link_fallback"),
+        (status = 200, description = "HTML warning page for links a moderator marked with \"warn\", or flagged unsafe with unsafe_link_action = \"warn\". Its continue button posts back to the same URL"),
+        (status = 403, description = "Destination is blocked, with check_destinations_on_redirect, or flagged unsafe, with unsafe_link_action = \"disable\"", body = ErrorResponse),
+        (status = 302, description = "Unknown token, with a fallback_url for the domain or globally, or expired or used up link, when not_found_page or expired_link_page is a URL"),
+        (status = 404, description = "URL not found, an HTML not_found_page when set", body = ErrorResponse),
+        (status = 410, description = "URL deleted, expired, disabled or click limit reached, disabled links answer with the disabled_link_page and expired or used up links with an HTML expired_link_page when set", body = ErrorResponse),
+        (status = 451, description = "Taken down for legal reasons", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+)]
+async fn redirect_url(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    RawQuery(query): RawQuery,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    // Link-in-bio pages share the namespace, tokens never start with @
+    let response = match token.strip_prefix('@') {
+        Some(slug) => bio::show_page(&state, slug, &headers).await,
+        None => follow(state.clone(), token.clone(), addr, query, method, headers.clone(), false).await,
+    };
+    match response {
+        Err(error) => error_page(&state, &token, &headers, error).await,
+        response => response,
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/{token}",
+    tag = "redirects",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 303, description = "Visitor confirmed the warning page, redirect to the destination"),
+        (status = 403, description = "Destination is blocked or flagged unsafe, with unsafe_link_action = \"disable\"", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+        (status = 410, description = "URL deleted, expired, disabled or click limit reached", body = ErrorResponse),
+        (status = 451, description = "Taken down for legal reasons", body = ErrorResponse),
+    ),
+)]
+async fn continue_redirect(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    RawQuery(query): RawQuery,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    let mut response = match follow(state.clone(), token.clone(), addr, query, method, headers.clone(), true).await {
+        Err(error) => error_page(&state, &token, &headers, error).await?,
+        response => response?,
+    };
+    // 307 and 308 would make the browser post the form on to the destination
+    if matches!(response.status(), StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT) {
+        *response.status_mut() = StatusCode::SEE_OTHER;
+        response.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    }
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/{token}/{path}",
+    tag = "redirects",
+    params(
+        ("token" = String, Path, description = "Token of a template link, or the payload of a signed link: its destination URL in unpadded base64url"),
+        ("path" = String, Path, description = "Segments filling the template's placeholders in order, or the signature of a signed link: the first 16 bytes of the payload's HMAC-SHA256 with the signing_secret in unpadded base64url"),
+    ),
+    responses(
+        (status = 308, description = "Redirect to the signed destination, or to the template's destination with its placeholders filled from the path and the query string"),
+        (status = 307, description = "Template link redirected by its targeting rules or A/B split"),
+        (status = 200, description = "HTML warning page for a flagged template link"),
+        (status = 400, description = "Signed destination is not a URL the server accepts", body = ErrorResponse),
+        (status = 403, description = "Destination is blocked", body = ErrorResponse),
+        (status = 404, description = "Neither a valid signature nor a template link with a value for each placeholder", body = ErrorResponse),
+        (status = 410, description = "Template link deleted, expired, disabled or click limit reached", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+)]
+async fn redirect_path(
+    Path((token, path)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    connect_info: ConnectInfo<SocketAddr>,
+    query: RawQuery,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    let signed = state.signer.as_ref().filter(|_| !path.contains('/'));
+    if let Some(link) = signed.and_then(|signer| signer.verify(&token, &path)) {
+        return redirect_signed(&state, &link);
+    }
+    let path = Path(format!("{}/{}", token, path));
+    redirect_url(path, State(state), connect_info, query, method, headers).await
+}
+
+// The warning page of a flagged template link posts back to its own URL
+async fn continue_redirect_path(
+    Path((token, path)): Path<(String, String)>,
+    state: State<Arc<AppState>>,
+    connect_info: ConnectInfo<SocketAddr>,
+    query: RawQuery,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    continue_redirect(Path(format!("{}/{}", token, path)), state, connect_info, query, method, headers).await
+}
+
+// Nothing is stored for signed links, so there is no link to look up, schedule or count clicks of
+fn redirect_signed(state: &AppState, link: &SignedLink) -> Result<axum::response::Response, AppError> {
+    let config = &state.config;
+    // Cached no longer than it is valid, like expiring links
+    let remaining = match link.expires_at {
+        Some(expires_at) => u64::try_from((expires_at - storage::now()).num_seconds()).map_err(|_| AppError::UrlExpired)?,
+        None => u64::MAX,
+    };
+    let url = validation::validate_link_destination(config, &link.destination).map_err(AppError::BadRequest)?;
+    state.destinations.check(&url).map_err(AppError::Forbidden)?;
+
+    metrics::counter!(telemetry::REDIRECTS_TOTAL).increment(1);
+    Ok(redirect(url.as_str(), false, http_cache::public(config.redirect_max_age_secs.min(remaining))))
+}
+
+// The operator's fallback_url, not_found_page or expired_link_page in place of the problem document, if set
+async fn error_page(
+    state: &AppState,
+    token: &str,
+    headers: &HeaderMap,
+    error: AppError,
+) -> Result<axum::response::Response, AppError> {
+    let page = match error {
+        AppError::UrlNotFound => {
+            let domain = state.domains.lookup(&state.db, headers).await?;
+            state.pages.not_found(token, domain.as_ref().and_then(|domain| domain.fallback_url.as_deref()))
+        }
+        AppError::UrlExpired | AppError::ClickLimitReached => state.pages.expired(token),
+        _ => None,
+    };
+    page.ok_or(error)
+}
+
+// Visitors coming from the warning page have `confirmed` it and are redirected like anyone else
+async fn follow(
+    state: Arc<AppState>,
+    token: String,
+    addr: SocketAddr,
+    query: Option<String>,
+    method: Method,
+    headers: HeaderMap,
+    confirmed: bool,
+) -> Result<axum::response::Response, AppError> {
+    // Template links take the rest of the path, /docs/intro is the docs link filled with "intro"
+    let (token, path) = match token.split_once('/') {
+        Some((token, path)) => (token.to_string(), path.to_string()),
+        None => (token, String::new()),
+    };
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+
+    // bit.ly style preview: appending + to a short link shows where it goes
+    if let Some(token) = token.strip_suffix('+').filter(|_| segments.is_empty()) {
+        return preview::get_preview(Path(token.to_string()), State(state), headers)
+            .await
+            .map(IntoResponse::into_response);
+    }
+
+    let (token, link) = resolve_token(&state, token).await?;
+
+    // Tokens are unique across all domains, a token only resolves on the Host it was created for
+    if link.domain != state.domains.resolve(&state.db, &headers).await? {
+        return Err(AppError::UrlNotFound);
+    }
+    if !link.template && !segments.is_empty() {
+        return Err(AppError::UrlNotFound);
+    }
+
+    // Scheduled links look like unknown tokens until they go live
+    let now = chrono::Utc::now();
+    match resolver::schedule(link.starts_at, link.expires_at, now) {
+        Schedule::Pending => return Err(AppError::UrlNotFound),
+        Schedule::Expired => return Err(AppError::UrlExpired),
+        Schedule::Live => {}
+    }
+    if let Some(takedown) = link.takedown {
+        return Err(takedown.error());
+    }
+    if !link.active {
+        return Ok(state.pages.disabled());
+    }
+    if link.flagged && state.config.unsafe_link_action == UnsafeLinkAction::Disable {
+        return Err(AppError::UrlUnsafe);
+    }
+
+    let ip = if state.config.privacy_mode { stats::anonymize_ip(addr.ip()) } else { addr.ip() };
+    let ip_hash = stats::hash_ip(&state.config.ip_hash_salt, ip);
+    let location = state.geoip.lookup(ip);
+    let user_agent = headers.get(header::USER_AGENT).and_then(|value| value.to_str().ok());
+    let device = device::Device::detect(user_agent);
+    // Targeting rules take precedence, the split only shares out the remaining traffic
+    let mut variant = None;
+    let mut destination = match rules::pick(&link.rules, &location, device) {
+        Some(url) => url.to_string(),
+        None => match variants::pick(&link.variants, link.sticky_variants.then_some(ip_hash.as_str())) {
+            Some(chosen) => {
+                variant = Some(chosen.name.clone());
+                chosen.url.clone()
             }
+            None => link.original_url.clone(),
+        },
+    };
+    if link.template {
+        destination = templates::fill(&destination, &segments, query.as_deref()).ok_or(AppError::UrlNotFound)?;
+        // The visitor supplied part of it, so it is checked like a new link's destination
+        let url = validation::validate_link_destination(&state.config, &destination).map_err(|_| AppError::UrlNotFound)?;
+        state.destinations.check(&url).map_err(AppError::Forbidden)?;
+    }
+    let own_destination = destination == link.original_url;
 
-            let original_url: String = row.get("original_url");
+    // Checked before counting, a refused redirect is not a click
+    if state.config.check_destinations_on_redirect {
+        if let Ok(url) = url::Url::parse(&destination) {
+            state.destinations.check(&url).map_err(AppError::Forbidden)?;
+        }
+    }
 
-            // Increment click count
-            sqlx::query("UPDATE urls SET click_count = click_count + 1 WHERE token = ?")
-                .bind(&token)
-                .execute(&state.db)
-                .await
-                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let mut cache_control = http_cache::redirect_policy(&link, state.config.redirect_max_age_secs, now);
+    // Targeted and split links answer differently per visitor, so browsers must not cache the redirect
+    let mut temporary = !link.rules.is_empty() || !link.variants.is_empty();
 
-            Ok(Redirect::permanent(&original_url))
+    // Only the link's own URL is health checked, rules and variants keep their destinations.
+    // The detour is never cached, the destination may come back.
+    let fallback = state.config.dead_link_fallback.as_deref().filter(|fallback| !fallback.is_empty());
+    if let Some(fallback) = fallback.filter(|_| link.dead && destination == link.original_url) {
+        destination = link_health::fallback_url(fallback, &destination);
+        cache_control = HeaderValue::from_static("no-cache");
+        temporary = true;
+    } else if let Some(query) = query.as_deref().filter(|query| link.forward_query && !query.is_empty()) {
+        destination = normalize::merge_query(&destination, query);
+    }
+
+    // Nothing is counted until the visitor confirms, the page shows where they would be sent
+    let warn = link.warned || (link.flagged && state.config.unsafe_link_action == UnsafeLinkAction::Warn);
+    if warn && !confirmed {
+        return Ok(state.pages.warning(&destination));
+    }
+
+    // Link checkers probe with HEAD and previews are fetched by bots, they get the same answer
+    // but only add to bot_clicks, neither using up a click limit nor showing up in stats
+    let bot = (method == Method::HEAD && !state.config.count_head_requests)
+        || (!state.config.count_bot_clicks && bots::is_bot(user_agent));
+    if bot {
+        if let Some(max_clicks) = link.max_clicks {
+            if state.links.limit_reached(&token, max_clicks).await? {
+                return Err(AppError::ClickLimitReached);
+            }
         }
-        None => Err(AppError::NotFound("URL not found".into())),
+        let mut click = Click::new(link.id, &headers, ip_hash, location, false);
+        click.bot = true;
+        state.clicks.record(click);
+        return Ok(redirect(&destination, temporary, cache_control));
     }
+
+    // Limited links are counted right away, everything else goes through the click buffer
+    let counted = match link.max_clicks {
+        Some(max_clicks) => match state.links.count_click(&token, max_clicks).await? {
+            Counted::LimitReached => return Err(AppError::ClickLimitReached),
+            Counted::Disabled => return Ok(state.pages.disabled()),
+            Counted::InDatabase(current) => {
+                // Edited after we cached it, the click was counted for the stored destination
+                if own_destination && current != link.original_url {
+                    destination = match query.as_deref().filter(|query| link.forward_query && !query.is_empty()) {
+                        Some(query) => normalize::merge_query(&current, query),
+                        None => current,
+                    };
+                    cache_control = HeaderValue::from_static("no-cache");
+                }
+                true
+            }
+            Counted::Shared => false,
+        },
+        None => false,
+    };
+
+    let visitor_hash = stats::visitor_hash(&state.config.ip_hash_salt, ip, user_agent, now.date_naive());
+    let mut click = Click::new(link.id, &headers, ip_hash, location, counted);
+    click.variant = variant;
+    click.visitor_hash = Some(visitor_hash);
+    if state.config.privacy_mode {
+        click.user_agent = None;
+        if clicks::do_not_track(&headers) {
+            click.forget_visitor();
+        }
+    }
+    state.dashboard.record(&token);
+    let sampled = state.webhooks.sample_click();
+    if sampled || state.click_stream.is_watched() || state.events.is_enabled() {
+        let event = ClickEvent {
+            token: token.clone(),
+            clicked_at: click.clicked_at,
+            referrer: click.referrer.clone(),
+            country: click.country.clone(),
+            city: click.city.clone(),
+            device,
+            variant: click.variant.clone(),
+        };
+        click.sampled = sampled;
+        click.publish = state.events.is_enabled();
+        if sampled || click.publish {
+            click.event = Some(event.clone());
+        }
+        state.click_stream.publish(click.url_id.clone(), event);
+    }
+    state.clicks.record(click);
+
+    metrics::counter!(telemetry::REDIRECTS_TOTAL).increment(1);
+    Ok(redirect(&destination, temporary, cache_control))
 }
 
+// /AbC123/ is a common typo of /AbC123, from hand-typed links and some chat apps
+async fn redirect_trailing_slash(
+    path: Path<String>,
+    State(state): State<Arc<AppState>>,
+    connect_info: ConnectInfo<SocketAddr>,
+    query: RawQuery,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    if !state.config.ignore_trailing_slash {
+        return Err(AppError::UrlNotFound);
+    }
+    redirect_url(path, State(state), connect_info, query, method, headers).await
+}
+
+fn redirect(destination: &str, temporary: bool, cache_control: HeaderValue) -> axum::response::Response {
+    // Links stored before destinations were normalized, and templates, can hold Unicode hosts
+    // and paths, a Location header has to be ASCII
+    let encoded = (!destination.is_ascii())
+        .then(|| url::Url::parse(destination).ok())
+        .flatten()
+        .map(String::from);
+    let destination = encoded.as_deref().unwrap_or(destination);
+    let mut response = if temporary {
+        Redirect::temporary(destination).into_response()
+    } else {
+        Redirect::permanent(destination).into_response()
+    };
+    response.headers_mut().insert(header::CACHE_CONTROL, cache_control);
+    response
+}
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
 #[derive(Debug)]
 pub enum AppError {
     DatabaseError(String),
     NotFound(String),
     BadRequest(String),
-    Gone(String),
+    Conflict(String),
+    Unauthorized(String),
+    Forbidden(String),
+    TooManyRequests(String),
+    QuotaExceeded(String),
+    InternalError(String),
+    // Link states that clients special-case get their own code instead of a free-form message
+    UrlNotFound,
+    UrlDeleted,
+    UrlExpired,
+    ClickLimitReached,
+    UrlDisabled,
+    UrlInactive,
+    UrlUnsafe,
+    LegalTakedown,
+}
+
+impl AppError {
+    // Status, stable machine-readable code and human-readable detail of the problem
+    fn parts(self) -> (StatusCode, &'static str, String) {
+        match self {
+            AppError::DatabaseError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, "database_error", msg),
+            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, "not_found", msg),
+            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, "invalid_request", msg),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, "conflict", msg),
+            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, "unauthorized", msg),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, "forbidden", msg),
+            AppError::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, "rate_limited", msg),
+            AppError::QuotaExceeded(msg) => (StatusCode::FORBIDDEN, "quota_exceeded", msg),
+            AppError::InternalError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error", msg),
+            AppError::UrlNotFound => (StatusCode::NOT_FOUND, "token_not_found", "URL not found".into()),
+            AppError::UrlDeleted => (StatusCode::GONE, "url_deleted", "URL has been deleted".into()),
+            AppError::UrlExpired => (StatusCode::GONE, "url_expired", "URL has expired".into()),
+            AppError::ClickLimitReached => {
+                (StatusCode::GONE, "click_limit_reached", "URL has reached its click limit".into())
+            }
+            AppError::UrlDisabled => (StatusCode::GONE, "url_disabled", "URL has been disabled for abuse".into()),
+            AppError::UrlInactive => (StatusCode::GONE, "url_inactive", "URL is switched off".into()),
+            AppError::UrlUnsafe => (
+                StatusCode::FORBIDDEN,
+                "url_unsafe",
+                "URL has been disabled because its destination is unsafe".into(),
+            ),
+            AppError::LegalTakedown => (
+                StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+                "unavailable_for_legal_reasons",
+                "URL is unavailable for legal reasons".into(),
+            ),
+        }
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
-        let (status, error_message) = match self {
-            AppError::DatabaseError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::Gone(msg) => (StatusCode::GONE, msg),
+        let (status, code, error_message) = self.parts();
+        if status == StatusCode::NOT_FOUND {
+            metrics::counter!(telemetry::NOT_FOUND_TOTAL).increment(1);
+        }
+
+        let context = request_id::current();
+        // Server errors are the ones users report, the id in the body finds the matching log line
+        if status.is_server_error() {
+            match &context {
+                Some(RequestContext { request_id, trace_id: Some(trace_id) }) => {
+                    eprintln!("❌ Request {} (trace {}) failed: {}", request_id, trace_id, error_message)
+                }
+                Some(RequestContext { request_id, trace_id: None }) => {
+                    eprintln!("❌ Request {} failed: {}", request_id, error_message)
+                }
+                None => eprintln!("❌ Request failed: {}", error_message),
+            }
+        }
+
+        // RFC 7807 problem details, `code` tells problems with the same status apart
+        let body = ErrorResponse {
+            problem_type: "about:blank".into(),
+            title: status.canonical_reason().unwrap_or_default().into(),
+            status: status.as_u16(),
+            detail: error_message,
+            code: code.into(),
+            request_id: context.map(|context| context.request_id),
         };
+        // A cached 404 would hide a link created right after, errors are never stored
+        (
+            status,
+            [(header::CONTENT_TYPE, PROBLEM_JSON), (header::CACHE_CONTROL, "no-store")],
+            Json(body),
+        )
+            .into_response()
+    }
+}
 
-        let body = Json(serde_json::json!({
-            "error": error_message
-        }));
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        (status, body).into_response()
+    // axum cannot list its routes, so this reads them from the router built in main
+    #[test]
+    fn test_route_segments_are_reserved() {
+        let reserved = ReservedTokens::new(&[], &[]);
+        let source = include_str!("main.rs");
+        let paths = source
+            .split(".route(")
+            .skip(1)
+            .filter_map(|call| call.trim_start().strip_prefix('"')?.split('"').next())
+            .filter(|path| path.starts_with('/'))
+            .chain([versioning::API_PREFIX]);
+
+        let mut checked = 0;
+        for path in paths {
+            // Segments with a dot are no valid token, their stem is what could be taken
+            let segment = path.trim_start_matches('/').split('/').next().unwrap_or_default();
+            let stem = segment.split('.').next().unwrap_or_default();
+            if stem.is_empty() || stem.starts_with(':') {
+                continue;
+            }
+            assert!(reserved.contains(stem), "{} is routed but not reserved", stem);
+            checked += 1;
+        }
+        assert!(checked > 50);
     }
 }
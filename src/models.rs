@@ -1,45 +1,1024 @@
-use chrono::{DateTime, Utc};
+use async_graphql::SimpleObject;
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::auth::{Role, Scope};
+use crate::destinations::DestinationList;
+use crate::device::Device;
+use crate::orgs::OrgRole;
+use crate::reports::{ModerationAction, ReportReason, ReportStatus, Takedown};
+use crate::webhooks::WebhookEvent;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CreateUrlRequest {
     pub url: String,
     pub title: Option<String>,
-    pub expires_at: Option<DateTime<Utc>>,
+    // The link does not resolve before this time
+    pub starts_at: Option<DateTime<Utc>>,
+    // Omitted takes default_ttl_days, null never expires
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    pub max_clicks: Option<i64>,
+    // Registered custom domain to serve the link from, the default domain when omitted
+    pub domain: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Used as the token instead of a generated one
+    pub custom_alias: Option<String>,
+    // Free-form text for whoever manages the link, never shown on the preview page
+    pub notes: Option<String>,
+    // Any JSON object, e.g. ticket or campaign ids
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
+    // Campaign whose statistics the link counts towards
+    pub campaign_id: Option<String>,
+    // Append the query string of the short URL, e.g. ad click ids, to the destination
+    #[serde(default)]
+    pub forward_query: bool,
+    // The URL has {placeholders} filled from the path and query of the short URL, e.g. /docs/intro
+    #[serde(default)]
+    pub template: bool,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CreateUrlOptions {
+    pub dedupe: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ShortenFormat {
+    // Just the short URL and a newline
+    #[default]
+    Text,
+    Json,
+}
+
+// GET /shorten for bookmarklets and shell one-liners, which cannot easily send a JSON body
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ShortenQuery {
+    pub url: String,
+    pub custom_alias: Option<String>,
+    #[serde(default)]
+    pub format: ShortenFormat,
+    pub dedupe: Option<bool>,
+    // API key or user token for callers that cannot set an Authorization header
+    pub key: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+// Distinguishes a missing field (None) from an explicit null (Some(None)) in request bodies
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct UpdateUrlRequest {
+    pub url: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub title: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub starts_at: Option<Option<DateTime<Utc>>>,
+    // null makes the link permanent
+    #[serde(default, deserialize_with = "double_option")]
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub max_clicks: Option<Option<i64>>,
+    // Replaces the whole tag set when present
+    pub tags: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub notes: Option<Option<String>>,
+    // Replaces the whole object, null removes it
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<Option<serde_json::Map<String, serde_json::Value>>>,
+    // null takes the link out of its campaign
+    #[serde(default, deserialize_with = "double_option")]
+    pub campaign_id: Option<Option<String>>,
+    pub forward_query: Option<bool>,
+    pub template: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct CreateUrlResponse {
     pub id: String,
     pub token: String,
     pub original_url: String,
+    // The URL as submitted, original_url is its normalized form
+    pub raw_url: Option<String>,
     pub short_url: String,
     pub title: Option<String>,
     pub created_at: DateTime<Utc>,
-    pub expires_at: DateTime<Utc>,
+    pub starts_at: Option<DateTime<Utc>>,
+    // None for links that never expire
+    pub expires_at: Option<DateTime<Utc>>,
     pub click_count: i64,
+    pub max_clicks: Option<i64>,
+    pub domain: Option<String>,
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
+    pub campaign_id: Option<String>,
+    pub forward_query: bool,
+    pub template: bool,
+}
+
+// Sends matching visitors to `url` instead of the default destination. A rule needs at least
+// one condition and matches when all of its conditions do.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RedirectRule {
+    // ISO 3166 country code, e.g. "DE"
+    pub country: Option<String>,
+    pub device: Option<Device>,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RedirectRulesRequest {
+    pub rules: Vec<RedirectRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SplitVariant {
+    // Reported in click stats, unique within the link
+    pub name: String,
+    pub url: String,
+    // Relative share of traffic, 1 to 1000
+    pub weight: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SplitVariantsRequest {
+    // Send a returning visitor to the variant they saw before
+    #[serde(default)]
+    pub sticky: bool,
+    pub variants: Vec<SplitVariant>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BatchShortenRequest {
+    pub urls: Vec<CreateUrlRequest>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BatchItemResult {
+    Created { index: usize, url: Box<CreateUrlResponse> },
+    Error { index: usize, error: String },
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchShortenResponse {
+    pub created: usize,
+    pub failed: usize,
+    pub results: Vec<BatchItemResult>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum BulkOperation {
+    Delete,
+    // null removes the expiry
+    SetExpiry { expires_at: Option<DateTime<Utc>> },
+    AddTag { tag: String },
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkRequest {
+    pub tokens: Vec<String>,
+    #[serde(flatten)]
+    pub operation: BulkOperation,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BulkItemResult {
+    Done { token: String },
+    Error { token: String, error: String },
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkResponse {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BulkItemResult>,
+}
+
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+#[graphql(complex)]
 pub struct UrlInfo {
     pub id: String,
     pub token: String,
     pub original_url: String,
+    // The URL as submitted, before normalization, None for links older than that
+    pub raw_url: Option<String>,
+    // original_url with an internationalized host in Unicode, unless it is flagged as a homograph
+    pub display_url: String,
     pub short_url: String,
     pub title: Option<String>,
+    // From the destination's OpenGraph tags with fetch_titles, shown in link previews and oEmbed
+    pub description: Option<String>,
+    pub image_url: Option<String>,
     pub created_at: DateTime<Utc>,
-    pub expires_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub starts_at: Option<DateTime<Utc>>,
+    // None for links that never expire
+    pub expires_at: Option<DateTime<Utc>>,
     pub click_count: i64,
+    // Redirects served to crawlers and link previews, not part of click_count
+    pub bot_clicks: i64,
+    // Clicks minus repeats by the same visitor within unique_click_window_secs
+    pub unique_clicks: i64,
+    pub max_clicks: Option<i64>,
+    pub domain: Option<String>,
+    // Organization owning the link, None for personal and unowned links
+    pub org_id: Option<String>,
+    pub campaign_id: Option<String>,
+    // Query parameters of the short URL are passed on to the destination
+    pub forward_query: bool,
+    // original_url has {placeholders} filled in on redirect
+    pub template: bool,
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    #[graphql(skip)]
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
+    // False while the link is disabled, it answers 410 until enabled again
+    pub is_active: bool,
+    // Safe Browsing lists the destination, the reason is the threat type
+    pub flagged: bool,
+    pub flag_reason: Option<String>,
+    // A moderator asked for visitors to see a warning page before the redirect
+    pub warned: bool,
+    // Set when a moderator took the link down
+    #[graphql(skip)]
+    pub takedown: Option<Takedown>,
+    // Last destination check, "ok" or what failed, e.g. "404" or "timeout"
+    pub last_checked_at: Option<DateTime<Utc>>,
+    pub health_status: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    CreatedAt,
+    ClickCount,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthFilter {
+    Ok,
+    // The last check failed
+    Broken,
+    // Not checked yet
+    Unchecked,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListUrlsQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    #[serde(default)]
+    pub sort: SortField,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub created_after: Option<DateTime<Utc>>,
+    pub expires_before: Option<DateTime<Utc>>,
+    pub q: Option<String>,
+    pub tag: Option<String>,
+    // Only list deleted links that are waiting to be restored or purged
+    #[serde(default)]
+    pub deleted: bool,
+    // By the outcome of the last destination check
+    pub health: Option<HealthFilter>,
+    // Only links of this organization
+    pub org_id: Option<String>,
+    // Only links of this campaign
+    pub campaign_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    #[default]
+    Csv,
+    Ndjson,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: FileFormat,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery {
+    // Falls back to the request Content-Type when omitted
+    pub format: Option<FileFormat>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    // Words to find in titles and destinations, each also matches as a prefix
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    pub url: UrlInfo,
+    // Higher is a better match, title words weigh more than destination words
+    pub score: f64,
+    // HTML-escaped text with the matched words wrapped in <mark>
+    pub title_highlight: Option<String>,
+    pub url_highlight: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ListUrlsResponse {
     pub urls: Vec<UrlInfo>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+    pub total_pages: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    // Always about:blank, the problem is identified by `code`
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    // Stable identifier such as url_expired or token_not_found, unlike `detail` safe to match on
+    pub code: String,
+    // X-Request-Id of the failed request, quote it when reporting a problem
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct HealthResponse {
     pub status: String,
     pub service: String,
     pub version: String,
 }
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DatabaseCheck {
+    pub ok: bool,
+    pub latency_ms: Option<f64>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MigrationCheck {
+    // Newest version recorded in the database and the newest this binary ships
+    pub applied: Option<i64>,
+    pub latest: Option<i64>,
+    pub up_to_date: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheCheck {
+    pub enabled: bool,
+    pub entries: u64,
+    // Whether Redis answered its last command, absent without redis_url. Redirects fall back to
+    // the database, so this does not affect readiness.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redis: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    // "ready" or "unavailable"
+    pub status: String,
+    pub database: DatabaseCheck,
+    // Only present when database_read_url is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replica: Option<DatabaseCheck>,
+    pub migrations: MigrationCheck,
+    pub cache: CacheCheck,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RegisterRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserResponse {
+    pub id: String,
+    pub email: String,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetRoleRequest {
+    pub role: Role,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuotaUsage {
+    pub used: i64,
+    // None when unlimited
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UsageResponse {
+    // Live links, deleted ones free their slot
+    pub links: QuotaUsage,
+    // Links created since midnight UTC
+    pub links_today: QuotaUsage,
+    pub custom_aliases: QuotaUsage,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateOrgRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrgInfo {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    // The caller's role, None for API keys and the admin key
+    pub role: Option<OrgRole>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PutMemberRequest {
+    // Of a registered account
+    pub email: String,
+    pub role: OrgRole,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrgMember {
+    pub user_id: String,
+    pub email: String,
+    pub role: OrgRole,
+    pub added_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TransferUrlRequest {
+    // Organization to hand the link to, null takes it into the caller's personal ownership
+    pub org_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobInfo {
+    pub name: String,
+    pub next_run_at: Option<DateTime<Utc>>,
+    // Claimed by an instance and not finished yet
+    pub running: bool,
+    pub last_run_at: Option<DateTime<Utc>>,
+    // "ok" or "failed"
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+    pub last_duration_ms: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateAliasRequest {
+    pub alias: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AliasInfo {
+    pub alias: String,
+    pub short_url: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateCampaignRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+#[graphql(complex)]
+pub struct CampaignInfo {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub link_count: i64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BioPageLinkRequest {
+    pub token: String,
+    // Shown instead of the link's own title
+    pub title: Option<String>,
+    // An emoji or a few characters, or the http(s) URL of an image
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateBioPageRequest {
+    // Lowercase letters, digits, - and _, the page is served at /@slug
+    pub slug: String,
+    pub title: String,
+    pub description: Option<String>,
+    // In the order they are shown
+    #[serde(default)]
+    pub links: Vec<BioPageLinkRequest>,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct UpdateBioPageRequest {
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub description: Option<Option<String>>,
+    // Replaces all links of the page
+    pub links: Option<Vec<BioPageLinkRequest>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BioPageLinkInfo {
+    pub token: String,
+    pub short_url: String,
+    pub title: Option<String>,
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BioPageInfo {
+    pub id: String,
+    pub slug: String,
+    // Where visitors find the page
+    pub page_url: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub links: Vec<BioPageLinkInfo>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Whole UTC days, both ends included
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CampaignStatsQuery {
+    // Defaults to 30 days before `to`
+    pub from: Option<NaiveDate>,
+    // Defaults to today
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct CampaignLinkStats {
+    pub token: String,
+    pub short_url: String,
+    pub original_url: String,
+    pub clicks: i64,
+    pub unique_visitors: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct CampaignStatsResponse {
+    pub campaign: CampaignInfo,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub total_clicks: i64,
+    // Distinct visitors over all links, someone who clicked two of them counts once
+    pub unique_visitors: i64,
+    pub daily: Vec<DailyClicks>,
+    // Most clicked first
+    pub links: Vec<CampaignLinkStats>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LoginResponse {
+    pub token: String,
+    pub token_type: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateDomainRequest {
+    pub hostname: String,
+    // Where unknown tokens on the domain are sent with a 302, instead of the global fallback_url
+    pub fallback_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateDomainRequest {
+    // null goes back to the global fallback_url
+    #[serde(default, deserialize_with = "double_option")]
+    pub fallback_url: Option<Option<String>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateDestinationEntryRequest {
+    // Also covers every subdomain
+    pub hostname: String,
+    pub list: DestinationList,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DestinationEntry {
+    pub hostname: String,
+    pub list: DestinationList,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DestinationListQuery {
+    // Only entries of this list, both when left out
+    pub list: Option<DestinationList>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DomainInfo {
+    pub id: String,
+    pub hostname: String,
+    pub fallback_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub link_count: i64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    // Defaults to editor
+    pub role: Option<Role>,
+    // Limits the key to these within its role, all the role allows when left out
+    pub scopes: Option<Vec<Scope>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateApiKeyResponse {
+    pub id: String,
+    pub name: String,
+    pub key: String,
+    pub role: Role,
+    pub scopes: Option<Vec<Scope>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyInfo {
+    pub id: String,
+    pub name: String,
+    pub role: Role,
+    pub scopes: Option<Vec<Scope>>,
+    pub created_at: DateTime<Utc>,
+    // To the minute
+    pub last_used_at: Option<DateTime<Utc>>,
+    // Until then the secret replaced by the last rotation still works
+    pub previous_key_expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct RotateApiKeyRequest {
+    // How long the old secret keeps working, a day by default and 0 revokes it at once
+    pub grace_period_secs: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RotateApiKeyResponse {
+    pub id: String,
+    pub key: String,
+    pub previous_key_expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsQuery {
+    pub days: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, ToSchema)]
+pub enum TopPeriod {
+    #[default]
+    #[serde(rename = "24h")]
+    Day,
+    #[serde(rename = "7d")]
+    Week,
+    #[serde(rename = "30d")]
+    Month,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TopLinksQuery {
+    #[serde(default)]
+    pub period: TopPeriod,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TopLink {
+    pub token: String,
+    pub short_url: String,
+    pub original_url: String,
+    pub title: Option<String>,
+    pub clicks: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TopLinksResponse {
+    pub period: TopPeriod,
+    pub since: DateTime<Utc>,
+    // Most clicked first
+    pub links: Vec<TopLink>,
+}
+
+// One redirect as pushed to live dashboards and click.recorded webhooks
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ClickEvent {
+    pub token: String,
+    pub clicked_at: DateTime<Utc>,
+    pub referrer: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub device: Device,
+    pub variant: Option<String>,
+}
+
+// Rolling one minute view of redirect traffic for the live dashboard
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DashboardSnapshot {
+    pub at: DateTime<Utc>,
+    pub clicks_per_second: i64,
+    pub clicks_last_minute: i64,
+    pub top_tokens: Vec<CountEntry>,
+}
+
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct DailyClicks {
+    pub date: String,
+    pub clicks: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct CountEntry {
+    pub value: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct UrlStatsResponse {
+    pub token: String,
+    pub total_clicks: i64,
+    pub unique_visitors: i64,
+    // Visits, repeat clicks by one visitor within unique_click_window_secs count once
+    pub unique_clicks: i64,
+    // Crawlers and link previews, left out of every other figure
+    pub bot_clicks: i64,
+    pub daily: Vec<DailyClicks>,
+    pub top_referrers: Vec<CountEntry>,
+    pub top_user_agents: Vec<CountEntry>,
+    // Clicks per A/B variant served
+    pub variants: Vec<CountEntry>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReferrerStatsResponse {
+    pub token: String,
+    pub days: i64,
+    pub total_clicks: i64,
+    // Referring domains, "direct" without a Referer and "email" for webmail and mail apps
+    pub domains: Vec<CountEntry>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GeoStatsResponse {
+    pub token: String,
+    // ISO 3166 country codes, most clicks first
+    pub countries: Vec<CountEntry>,
+    pub top_cities: Vec<CountEntry>,
+    // Clicks without a known location
+    pub unknown: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+pub enum QrErrorCorrection {
+    #[serde(alias = "l")]
+    L,
+    #[default]
+    #[serde(alias = "m")]
+    M,
+    #[serde(alias = "q")]
+    Q,
+    #[serde(alias = "h")]
+    H,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct QrQuery {
+    #[serde(default)]
+    pub format: QrFormat,
+    pub size: Option<u32>,
+    #[serde(default)]
+    pub ec: QrErrorCorrection,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CleanupReport {
+    pub mode: String,
+    pub removed: u64,
+    pub archived: u64,
+    // Click events past click_retention_days
+    pub clicks_purged: u64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateReportRequest {
+    pub reason: ReportReason,
+    pub details: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateReportResponse {
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AbuseReport {
+    pub id: String,
+    pub token: String,
+    pub original_url: String,
+    pub reason: ReportReason,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub resolution: Option<ModerationAction>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReportListQuery {
+    #[serde(default)]
+    pub status: ReportStatus,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ResolveReportsRequest {
+    pub action: ModerationAction,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ModerationResult {
+    pub token: String,
+    pub takedown: Option<Takedown>,
+    pub warned: bool,
+    // Open reports closed by this decision
+    pub resolved: u64,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct LinkCheckReport {
+    pub checked: u64,
+    pub broken: u64,
+    // Links whose status differs from their previous check
+    pub changed: u64,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct RescanReport {
+    pub scanned: u64,
+    pub flagged: u64,
+    pub cleared: u64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    // Every event when left out
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    // Only events for this link, otherwise every link the caller can manage
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub token: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateWebhookResponse {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub token: Option<String>,
+    // Key for the X-QuickURL-Signature HMAC, only shown once
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub event: String,
+    pub attempts: i64,
+    pub delivered: bool,
+    // Response status of the last attempt, none when the request itself failed
+    pub status_code: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub action: String,
+    // "admin", "api_key:<id>" or "user:<id>"
+    pub actor: String,
+    // Initial values for create, old and new value per changed field for update
+    #[schema(value_type = Object)]
+    pub changes: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PurgeQuery {
+    // Only purge links deleted at least this many days ago, everything deleted when unset, at most 36500
+    pub older_than_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PurgeReport {
+    pub purged: u64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OEmbedQuery {
+    // Only json is supported, xml answers 501 as the oEmbed spec asks
+    pub format: Option<String>,
+}
+
+// oEmbed "link" response, see https://oembed.com
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OEmbedResponse {
+    pub version: &'static str,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub provider_name: &'static str,
+    pub provider_url: String,
+    // Seconds consumers may cache the response
+    pub cache_age: u64,
+}
@@ -0,0 +1,121 @@
+use std::collections::HashSet;
+use url::Url;
+
+use crate::config::Config;
+
+// Rewrites destinations into the form they are stored in. Parsing already lowercases the scheme
+// and host, drops default ports and resolves dot segments; on top of that tracking parameters
+// are dropped and, if configured, the rest sorted.
+#[derive(Debug, Clone, Default)]
+pub struct Normalizer {
+    strip: Vec<String>,
+    sort: bool,
+}
+
+impl Normalizer {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            strip: config.strip_query_params.iter().map(|param| param.trim().to_string()).collect(),
+            sort: config.sort_query_params,
+        }
+    }
+
+    fn strips(&self, name: &str) -> bool {
+        self.strip.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        })
+    }
+
+    // None when the URL does not parse. The query is only rewritten when a parameter goes or
+    // moves, re-encoding it could change what the destination receives.
+    pub fn normalize(&self, raw: &str) -> Option<String> {
+        let mut url = Url::parse(raw.trim()).ok()?;
+        let pairs: Vec<(String, String)> =
+            url.query_pairs().map(|(name, value)| (name.into_owned(), value.into_owned())).collect();
+        let mut kept: Vec<(String, String)> = pairs.iter().filter(|(name, _)| !self.strips(name)).cloned().collect();
+        if self.sort {
+            kept.sort();
+        }
+        if kept != pairs {
+            if kept.is_empty() {
+                url.set_query(None);
+            } else {
+                url.query_pairs_mut().clear().extend_pairs(kept);
+            }
+        }
+        Some(url.to_string())
+    }
+}
+
+// Canonical form used to detect duplicate destinations, of an already normalized URL.
+// Fragments never reach the server so they are not part of a link's identity.
+pub fn canonicalize(raw: &str) -> Option<String> {
+    let mut url = Url::parse(raw.trim()).ok()?;
+    url.set_fragment(None);
+    Some(url.to_string())
+}
+
+// The visitor's query parameters appended to the destination, e.g. click ids that ad platforms
+// add to the short URL. Parameters the destination already has keep the link's value.
+pub fn merge_query(destination: &str, query: &str) -> String {
+    let Ok(mut url) = Url::parse(destination) else {
+        return destination.to_string();
+    };
+    let existing: HashSet<String> = url.query_pairs().map(|(name, _)| name.into_owned()).collect();
+    let extra: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes())
+        .filter(|(name, _)| !name.is_empty() && !existing.contains(name.as_ref()))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    if extra.is_empty() {
+        return destination.to_string();
+    }
+    url.query_pairs_mut().extend_pairs(extra);
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equivalent_urls_share_canonical_form() {
+        let canonical = canonicalize("https://example.com/docs/").unwrap();
+
+        assert_eq!(canonicalize("HTTPS://Example.COM:443/docs/").unwrap(), canonical);
+        assert_eq!(canonicalize("https://example.com/a/../docs/#intro").unwrap(), canonical);
+        assert_ne!(canonicalize("https://example.com/docs").unwrap(), canonical);
+        assert!(canonicalize("not a url").is_none());
+    }
+
+    #[test]
+    fn test_normalizer_strips_and_sorts_params() {
+        let config = Config {
+            strip_query_params: vec!["fbclid".into(), "utm_*".into()],
+            ..Config::default()
+        };
+        let normalizer = Normalizer::new(&config);
+        assert_eq!(
+            normalizer.normalize("HTTP://Example.COM:80/a/./b/../c?utm_source=x&id=1&fbclid=y#top").unwrap(),
+            "http://example.com/a/c?id=1#top"
+        );
+        assert_eq!(normalizer.normalize("https://example.com/?fbclid=y").unwrap(), "https://example.com/");
+        // Untouched queries keep their encoding
+        let untouched = "https://example.com/?q=a%20b&z=1&a=2";
+        assert_eq!(normalizer.normalize(untouched).unwrap(), untouched);
+
+        let sorted = Normalizer::new(&Config { sort_query_params: true, ..config });
+        assert_eq!(sorted.normalize("https://example.com/?z=1&a=2").unwrap(), "https://example.com/?a=2&z=1");
+        assert!(sorted.normalize("not a url").is_none());
+    }
+
+    #[test]
+    fn test_merge_query_keeps_destination_params() {
+        assert_eq!(merge_query("https://example.com/a", "gclid=x1"), "https://example.com/a?gclid=x1");
+        assert_eq!(
+            merge_query("https://example.com/a?utm_source=qr#top", "utm_source=ad&fbclid=y%202"),
+            "https://example.com/a?utm_source=qr&fbclid=y+2#top"
+        );
+        assert_eq!(merge_query("https://example.com/a?b=1", "b=2&=3"), "https://example.com/a?b=1");
+    }
+}
@@ -0,0 +1,493 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Json, Redirect, Response},
+};
+use chrono::Utc;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{AnyPool, Row};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::auth::Role;
+use crate::config::Config;
+use crate::models::LoginResponse;
+use crate::storage;
+use crate::token::TokenGenerator;
+use crate::users;
+use crate::{AppError, AppState};
+
+const CALLBACK_PATH: &str = "/auth/oidc/callback";
+const COOKIE_NAME: &str = "quickurl_oidc";
+// Time the user has at the provider to sign in
+const LOGIN_TTL_SECS: i64 = 600;
+const NONCE_LENGTH: usize = 32;
+const FETCH_TIMEOUT_SECS: u64 = 10;
+// Discovery document and signing keys, refetched sooner when a token names an unknown key
+const PROVIDER_TTL_SECS: u64 = 3600;
+
+#[derive(Debug, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+struct Provider {
+    discovery: Discovery,
+    keys: JwkSet,
+}
+
+// Signs accounts in through an OpenID Connect provider with the authorization code flow. The
+// provider's endpoints and keys are discovered from oidc_issuer and cached.
+#[derive(Clone)]
+pub struct Oidc {
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    scopes: String,
+    redirect_url: String,
+    role_claim: Option<String>,
+    admin_values: Vec<String>,
+    read_only_values: Vec<String>,
+    client: reqwest::Client,
+    provider: moka::sync::Cache<(), Arc<Provider>>,
+}
+
+// Carried through the provider in the state parameter, signed with the session secret. The
+// nonce also goes into a cookie, so a callback only completes in the browser that started it.
+#[derive(Debug, Serialize, Deserialize)]
+struct LoginState {
+    nonce: String,
+    json: bool,
+    exp: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginQuery {
+    // Answer the callback with the token as JSON instead of signing in to the web UI
+    #[serde(default)]
+    format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+async fn fetch_json<T: DeserializeOwned>(request: reqwest::RequestBuilder) -> Result<T, String> {
+    let response = request.send().await.and_then(|response| response.error_for_status()).map_err(|e| e.to_string())?;
+    let body = response.bytes().await.map_err(|e| e.to_string())?;
+    serde_json::from_slice(&body).map_err(|e| e.to_string())
+}
+
+impl Oidc {
+    pub fn from_config(config: &Config) -> anyhow::Result<Option<Self>> {
+        let Some(issuer) = config.oidc_issuer.as_deref().filter(|issuer| !issuer.is_empty()) else {
+            return Ok(None);
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+            .build()?;
+        Ok(Some(Self {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id: config.oidc_client_id.clone(),
+            client_secret: config.oidc_client_secret.clone(),
+            scopes: config.oidc_scopes.clone(),
+            redirect_url: format!("{}{}", config.base_url.trim_end_matches('/'), CALLBACK_PATH),
+            role_claim: config.oidc_role_claim.clone().filter(|claim| !claim.is_empty()),
+            admin_values: config.oidc_admin_values.clone(),
+            read_only_values: config.oidc_read_only_values.clone(),
+            client,
+            provider: moka::sync::Cache::builder()
+                .max_capacity(1)
+                .time_to_live(Duration::from_secs(PROVIDER_TTL_SECS))
+                .build(),
+        }))
+    }
+
+    async fn provider(&self, refresh: bool) -> Result<Arc<Provider>, AppError> {
+        if !refresh {
+            if let Some(provider) = self.provider.get(&()) {
+                return Ok(provider);
+            }
+        }
+        let unavailable = |e: String| AppError::InternalError(format!("OIDC provider unavailable: {}", e));
+        let discovery: Discovery = fetch_json(self.client.get(format!("{}/.well-known/openid-configuration", self.issuer)))
+            .await
+            .map_err(unavailable)?;
+        let keys: JwkSet = fetch_json(self.client.get(&discovery.jwks_uri)).await.map_err(unavailable)?;
+
+        let provider = Arc::new(Provider { discovery, keys });
+        self.provider.insert((), provider.clone());
+        Ok(provider)
+    }
+
+    // Checks signature, issuer, audience, expiry and nonce of an ID token and returns its claims.
+    // Providers may sign with the client secret (HS256) or a published key.
+    async fn verify_id_token(&self, id_token: &str, nonce: &str) -> Result<Value, AppError> {
+        let invalid = |detail: &str| AppError::Unauthorized(format!("Invalid ID token: {}", detail));
+        let header = jsonwebtoken::decode_header(id_token).map_err(|e| invalid(&e.to_string()))?;
+
+        let mut provider = self.provider(false).await?;
+        let key = match header.alg {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                let secret = self.client_secret.as_deref().ok_or_else(|| invalid("signed with a client secret that is not configured"))?;
+                DecodingKey::from_secret(secret.as_bytes())
+            }
+            _ => {
+                let kid = header.kid.as_deref().ok_or_else(|| invalid("no key id"))?;
+                if provider.keys.find(kid).is_none() {
+                    // The provider may have rotated its keys since they were cached
+                    provider = self.provider(true).await?;
+                }
+                let jwk = provider.keys.find(kid).ok_or_else(|| invalid("unknown signing key"))?;
+                DecodingKey::from_jwk(jwk).map_err(|e| invalid(&e.to_string()))?
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_issuer(&[&provider.discovery.issuer]);
+        let claims = jsonwebtoken::decode::<Value>(id_token, &key, &validation)
+            .map_err(|e| invalid(&e.to_string()))?
+            .claims;
+        if claims["nonce"].as_str() != Some(nonce) {
+            return Err(invalid("nonce mismatch"));
+        }
+        Ok(claims)
+    }
+
+    // The first role whose configured values the role claim names, editor when none match.
+    // None without oidc_role_claim, accounts then keep the role they have.
+    fn role(&self, claims: &Value) -> Option<Role> {
+        let path = self.role_claim.as_deref()?;
+        let claim = path.split('.').try_fold(claims, |value, key| value.get(key));
+        let values: Vec<&str> = match claim {
+            Some(Value::String(value)) => value.split_whitespace().collect(),
+            Some(Value::Array(values)) => values.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let named = |configured: &[String]| values.iter().any(|value| configured.iter().any(|c| c == value));
+        Some(if named(&self.admin_values) {
+            Role::Admin
+        } else if named(&self.read_only_values) {
+            Role::ReadOnly
+        } else {
+            Role::Editor
+        })
+    }
+}
+
+fn enabled(state: &AppState) -> Result<&Oidc, AppError> {
+    state
+        .oidc
+        .as_ref()
+        .ok_or_else(|| AppError::NotFound("OIDC sign-in is not configured".into()))
+}
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| pair.trim().strip_prefix(name)?.strip_prefix('='))
+}
+
+// Finds the account by provider subject, then by verified email, and creates one otherwise.
+// Accounts created here have no password and can only sign in through the provider. Only an
+// email the provider says it verified links an account, and only one not linked yet.
+async fn find_or_create_user(db: &AnyPool, issuer: &str, claims: &Value, role: Option<Role>) -> Result<String, AppError> {
+    let database_error = |e: sqlx::Error| AppError::DatabaseError(e.to_string());
+    let subject = claims["sub"]
+        .as_str()
+        .ok_or_else(|| AppError::Unauthorized("Invalid ID token: no subject".into()))?;
+    let email = claims["email"].as_str().map(|email| email.trim().to_lowercase());
+    let email_verified = claims["email_verified"].as_bool().unwrap_or(false);
+
+    let mut tx = db.begin().await.map_err(database_error)?;
+    let mut user_id: Option<String> = sqlx::query("SELECT id FROM users WHERE oidc_issuer = $1 AND oidc_subject = $2")
+        .bind(issuer)
+        .bind(subject)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(database_error)?
+        .map(|row| row.get("id"));
+
+    if let (None, Some(email), true) = (&user_id, &email, email_verified) {
+        let existing = sqlx::query("SELECT id, oidc_subject FROM users WHERE email = $1")
+            .bind(email)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        if let Some(row) = existing {
+            if row.get::<Option<String>, _>("oidc_subject").is_some() {
+                return Err(AppError::Conflict("The account with this email is linked to another sign-in".into()));
+            }
+            let id: String = row.get("id");
+            sqlx::query("UPDATE users SET oidc_issuer = $1, oidc_subject = $2 WHERE id = $3 AND oidc_subject IS NULL")
+                .bind(issuer)
+                .bind(subject)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await
+                .map_err(database_error)?;
+            user_id = Some(id);
+        }
+    }
+
+    let user_id = match user_id {
+        Some(id) => id,
+        None => {
+            let email = email
+                .filter(|_| email_verified)
+                .ok_or_else(|| AppError::BadRequest("The provider did not share a verified email address".into()))?;
+            let id = Uuid::new_v4().to_string();
+            sqlx::query(
+                r#"
+                INSERT INTO users (id, email, password_hash, created_at, oidc_issuer, oidc_subject)
+                VALUES ($1, $2, '', $3, $4, $5)
+                "#
+            )
+            .bind(&id)
+            .bind(&email)
+            .bind(storage::ts(storage::now()))
+            .bind(issuer)
+            .bind(subject)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+            id
+        }
+    };
+
+    if let Some(role) = role {
+        sqlx::query("UPDATE users SET role = $1 WHERE id = $2")
+            .bind(role.as_str())
+            .bind(&user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+    }
+    tx.commit().await.map_err(database_error)?;
+    Ok(user_id)
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/oidc/login",
+    tag = "users",
+    params(("format" = Option<String>, Query, description = "json to get the token as JSON from the callback instead of signing in to the web UI")),
+    responses(
+        (status = 303, description = "Redirect to the provider's sign-in page"),
+        (status = 404, description = "OIDC sign-in is not configured", body = ErrorResponse),
+    )
+)]
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LoginQuery>,
+) -> Result<Response, AppError> {
+    let oidc = enabled(&state)?;
+    let provider = oidc.provider(false).await?;
+
+    let nonce = TokenGenerator::with_length(NONCE_LENGTH).generate();
+    let login_state = LoginState {
+        nonce: nonce.clone(),
+        json: query.format.as_deref() == Some("json"),
+        exp: Utc::now().timestamp() + LOGIN_TTL_SECS,
+    };
+    let signed_state = jsonwebtoken::encode(&Header::default(), &login_state, &EncodingKey::from_secret(&state.jwt_secret))
+        .map_err(|e| AppError::InternalError(e.to_string()))?;
+
+    let mut target = url::Url::parse(&provider.discovery.authorization_endpoint)
+        .map_err(|e| AppError::InternalError(format!("Invalid authorization endpoint: {}", e)))?;
+    target
+        .query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &oidc.client_id)
+        .append_pair("redirect_uri", &oidc.redirect_url)
+        .append_pair("scope", &oidc.scopes)
+        .append_pair("state", &signed_state)
+        .append_pair("nonce", &nonce);
+
+    let secure = if oidc.redirect_url.starts_with("https://") { "; Secure" } else { "" };
+    let cookie = format!(
+        "{}={}; Path=/auth/oidc; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        COOKIE_NAME, nonce, LOGIN_TTL_SECS, secure
+    );
+    Ok(([(header::SET_COOKIE, cookie)], Redirect::to(target.as_str())).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/oidc/callback",
+    tag = "users",
+    responses(
+        (status = 303, description = "Signed in, redirect to the web UI with the bearer token"),
+        (status = 200, description = "Bearer token, when the login asked for format=json", body = LoginResponse),
+        (status = 401, description = "Sign-in failed or was not started in this browser", body = ErrorResponse),
+    )
+)]
+pub async fn callback(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CallbackQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let oidc = enabled(&state)?;
+    if let Some(error) = query.error {
+        let detail = query.error_description.unwrap_or(error);
+        return Err(AppError::Unauthorized(format!("Sign-in failed: {}", detail)));
+    }
+    let (Some(code), Some(signed_state)) = (query.code, query.state) else {
+        return Err(AppError::BadRequest("code and state are required".into()));
+    };
+
+    let login_state = jsonwebtoken::decode::<LoginState>(
+        &signed_state,
+        &DecodingKey::from_secret(&state.jwt_secret),
+        &Validation::default(),
+    )
+    .map_err(|_| AppError::Unauthorized("Sign-in expired, please start again".into()))?
+    .claims;
+    if cookie(&headers, COOKIE_NAME) != Some(login_state.nonce.as_str()) {
+        return Err(AppError::Unauthorized("Sign-in was started in another browser".into()));
+    }
+
+    let provider = oidc.provider(false).await?;
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("redirect_uri", oidc.redirect_url.as_str()),
+        ("client_id", oidc.client_id.as_str()),
+    ];
+    if let Some(secret) = &oidc.client_secret {
+        form.push(("client_secret", secret));
+    }
+    let tokens: TokenResponse = fetch_json(oidc.client.post(&provider.discovery.token_endpoint).form(&form))
+        .await
+        .map_err(|e| AppError::Unauthorized(format!("Code exchange failed: {}", e)))?;
+
+    let claims = oidc.verify_id_token(&tokens.id_token, &login_state.nonce).await?;
+    let user_id = find_or_create_user(&state.db, &provider.discovery.issuer, &claims, oidc.role(&claims)).await?;
+
+    let expires_at = Utc::now() + chrono::Duration::hours(state.config.jwt_ttl_hours);
+    let token = users::issue_token(&state.jwt_secret, &user_id, expires_at)?;
+    let clear_cookie = format!("{}=; Path=/auth/oidc; Max-Age=0", COOKIE_NAME);
+    if login_state.json {
+        let response = LoginResponse { token, token_type: "Bearer".into(), expires_at };
+        return Ok(([(header::SET_COOKIE, clear_cookie)], Json(response)).into_response());
+    }
+    // In the fragment, so the token never reaches server logs
+    let target = format!("/admin#token={}", token);
+    Ok(([(header::SET_COOKIE, clear_cookie)], Redirect::to(&target)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn oidc(role_claim: Option<&str>) -> Oidc {
+        let config = Config {
+            oidc_issuer: Some("https://id.example/realms/main/".into()),
+            oidc_client_id: "quickurl".into(),
+            oidc_client_secret: Some("client-secret".into()),
+            oidc_role_claim: role_claim.map(String::from),
+            oidc_admin_values: vec!["quickurl-admin".into()],
+            oidc_read_only_values: vec!["auditors".into()],
+            ..Config::default()
+        };
+        Oidc::from_config(&config).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_role_from_claims() {
+        let claims = json!({ "realm_access": { "roles": ["offline_access", "quickurl-admin"] }, "groups": "auditors staff" });
+        assert_eq!(oidc(Some("realm_access.roles")).role(&claims), Some(Role::Admin));
+        assert_eq!(oidc(Some("groups")).role(&claims), Some(Role::ReadOnly));
+        assert_eq!(oidc(Some("missing.claim")).role(&claims), Some(Role::Editor));
+        assert_eq!(oidc(None).role(&claims), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "theme=dark; quickurl_oidc=abc".parse().unwrap());
+        assert_eq!(cookie(&headers, COOKIE_NAME), Some("abc"));
+        assert_eq!(cookie(&headers, "quickurl"), None);
+    }
+
+    #[tokio::test]
+    async fn test_verify_id_token_and_accounts() {
+        let oidc = oidc(Some("roles"));
+        oidc.provider.insert((), Arc::new(Provider {
+            discovery: Discovery {
+                issuer: oidc.issuer.clone(),
+                authorization_endpoint: String::new(),
+                token_endpoint: String::new(),
+                jwks_uri: String::new(),
+            },
+            keys: JwkSet { keys: Vec::new() },
+        }));
+        let sign = |claims: Value| {
+            jsonwebtoken::encode(&Header::default(), &claims, &EncodingKey::from_secret(b"client-secret")).unwrap()
+        };
+        let exp = Utc::now().timestamp() + 60;
+        let claims = json!({
+            "iss": oidc.issuer, "aud": "quickurl", "exp": exp, "nonce": "n1",
+            "sub": "s1", "email": "Ann@Example.com", "email_verified": true, "roles": ["quickurl-admin"],
+        });
+        let verified = oidc.verify_id_token(&sign(claims.clone()), "n1").await.unwrap();
+        assert!(oidc.verify_id_token(&sign(claims.clone()), "n2").await.is_err());
+        let mut foreign = claims.clone();
+        foreign["aud"] = json!("other-client");
+        assert!(oidc.verify_id_token(&sign(foreign), "n1").await.is_err());
+
+        // An existing password account is linked by email, and found by subject afterwards
+        for (db, _) in storage::test_pools().await.unwrap() {
+            sqlx::query("INSERT INTO users (id, email, password_hash, created_at) VALUES ('u1', 'ann@example.com', 'x', $1)")
+                .bind(storage::ts(storage::now()))
+                .execute(&db)
+                .await
+                .unwrap();
+            let role = oidc.role(&verified);
+            assert_eq!(find_or_create_user(&db, &oidc.issuer, &verified, role).await.unwrap(), "u1");
+            let renamed = json!({ "sub": "s1", "email": "ann@new.example", "email_verified": true });
+            assert_eq!(find_or_create_user(&db, &oidc.issuer, &renamed, None).await.unwrap(), "u1");
+            let stored: String = sqlx::query("SELECT role FROM users WHERE id = 'u1'").fetch_one(&db).await.unwrap().get("role");
+            assert_eq!(stored, "admin");
+
+            let unverified = json!({ "sub": "s2", "email": "bob@example.com", "email_verified": false });
+            assert!(matches!(find_or_create_user(&db, &oidc.issuer, &unverified, None).await, Err(AppError::BadRequest(_))));
+            let created = json!({ "sub": "s3", "email": "cy@example.com", "email_verified": true });
+            assert_ne!(find_or_create_user(&db, &oidc.issuer, &created, None).await.unwrap(), "u1");
+
+            // Without email_verified the email is not trusted, neither to link nor to create an account
+            sqlx::query("INSERT INTO users (id, email, password_hash, created_at) VALUES ('u2', 'dee@example.com', 'x', $1)")
+                .bind(storage::ts(storage::now()))
+                .execute(&db)
+                .await
+                .unwrap();
+            let unclaimed = json!({ "sub": "s4", "email": "dee@example.com" });
+            assert!(matches!(find_or_create_user(&db, &oidc.issuer, &unclaimed, None).await, Err(AppError::BadRequest(_))));
+            let subject: Option<String> =
+                sqlx::query("SELECT oidc_subject FROM users WHERE id = 'u2'").fetch_one(&db).await.unwrap().get("oidc_subject");
+            assert_eq!(subject, None);
+            // An account linked already keeps its subject
+            let other = json!({ "sub": "s5", "email": "ann@example.com", "email_verified": true });
+            assert!(matches!(find_or_create_user(&db, &oidc.issuer, &other, None).await, Err(AppError::Conflict(_))));
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use axum::response::{Html, IntoResponse, Json};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::RefOr;
+use utoipa::{Modify, OpenApi};
+
+use crate::models::*;
+use crate::versioning::{API_PREFIX, ROOT_PATHS};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "QuickURL",
+        description = "URL shortener with click analytics",
+        license(name = "MIT")
+    ),
+    paths(
+        crate::health_check,
+        crate::readiness_check,
+        crate::create_short_url,
+        crate::shorten_from_query,
+        crate::create_short_urls_batch,
+        crate::bulk::bulk_update,
+        crate::import::import_urls,
+        crate::list_urls,
+        crate::export::export_urls,
+        crate::search::search_urls,
+        crate::get_url_info,
+        crate::update_url,
+        crate::delete_url,
+        crate::restore_url,
+        crate::disable_url,
+        crate::enable_url,
+        crate::audit::get_history,
+        crate::rules::get_rules,
+        crate::rules::put_rules,
+        crate::variants::get_variants,
+        crate::variants::put_variants,
+        crate::aliases::list_aliases,
+        crate::aliases::add_alias,
+        crate::aliases::delete_alias,
+        crate::orgs::transfer_url,
+        crate::redirect_url,
+        crate::continue_redirect,
+        crate::redirect_path,
+        crate::preview::get_preview,
+        crate::preview::get_oembed,
+        crate::stats::get_url_stats,
+        crate::stats::get_geo_stats,
+        crate::referrers::get_referrer_stats,
+        crate::stats::get_top_links,
+        crate::stream::stream_url_clicks,
+        crate::stream::stream_all_clicks,
+        crate::dashboard::live_dashboard,
+        crate::qr::get_qr_code,
+        crate::users::register,
+        crate::users::login,
+        crate::oidc::login,
+        crate::oidc::callback,
+        crate::users::export_account,
+        crate::users::delete_account,
+        crate::quota::get_usage,
+        crate::orgs::create_org,
+        crate::orgs::list_orgs,
+        crate::orgs::list_members,
+        crate::orgs::put_member,
+        crate::orgs::delete_member,
+        crate::campaigns::create_campaign,
+        crate::campaigns::list_campaigns,
+        crate::campaigns::delete_campaign,
+        crate::campaigns::get_campaign_stats,
+        crate::bio::create_page,
+        crate::bio::list_pages,
+        crate::bio::get_page,
+        crate::bio::update_page,
+        crate::bio::delete_page,
+        crate::webhooks::create_webhook,
+        crate::webhooks::list_webhooks,
+        crate::webhooks::delete_webhook,
+        crate::webhooks::list_deliveries,
+        crate::auth::create_api_key,
+        crate::auth::list_api_keys,
+        crate::auth::rotate_api_key,
+        crate::users::set_user_role,
+        crate::domains::create_domain,
+        crate::domains::list_domains,
+        crate::domains::get_domain,
+        crate::domains::update_domain,
+        crate::domains::delete_domain,
+        crate::destinations::create_entry,
+        crate::destinations::list_entries,
+        crate::destinations::delete_entry,
+        crate::cleanup::trigger_cleanup,
+        crate::cleanup::purge_deleted,
+        crate::safebrowsing::trigger_rescan,
+        crate::link_health::trigger_checks,
+        crate::scheduler::list_jobs,
+        crate::reports::create_report,
+        crate::reports::list_reports,
+        crate::reports::resolve_reports,
+        crate::telemetry::metrics_handler,
+    ),
+    components(schemas(
+        CreateUrlRequest,
+        CreateUrlResponse,
+        ShortenFormat,
+        UpdateUrlRequest,
+        UrlInfo,
+        RedirectRule,
+        crate::device::Device,
+        RedirectRulesRequest,
+        SplitVariant,
+        SplitVariantsRequest,
+        BatchShortenRequest,
+        BatchShortenResponse,
+        BatchItemResult,
+        ListUrlsResponse,
+        SearchResult,
+        SearchResponse,
+        FileFormat,
+        SortField,
+        SortOrder,
+        HealthFilter,
+        UrlStatsResponse,
+        DailyClicks,
+        ClickEvent,
+        DashboardSnapshot,
+        CountEntry,
+        GeoStatsResponse,
+        ReferrerStatsResponse,
+        TopPeriod,
+        TopLink,
+        TopLinksResponse,
+        QrFormat,
+        QrErrorCorrection,
+        RegisterRequest,
+        UserResponse,
+        QuotaUsage,
+        UsageResponse,
+        CreateOrgRequest,
+        OrgInfo,
+        BulkOperation,
+        BulkRequest,
+        BulkItemResult,
+        BulkResponse,
+        CreateAliasRequest,
+        AliasInfo,
+        CreateCampaignRequest,
+        CampaignInfo,
+        BioPageLinkRequest,
+        CreateBioPageRequest,
+        UpdateBioPageRequest,
+        BioPageLinkInfo,
+        BioPageInfo,
+        CampaignLinkStats,
+        CampaignStatsResponse,
+        crate::orgs::OrgRole,
+        PutMemberRequest,
+        OrgMember,
+        TransferUrlRequest,
+        LoginRequest,
+        LoginResponse,
+        crate::auth::Role,
+        SetRoleRequest,
+        CreateApiKeyRequest,
+        CreateApiKeyResponse,
+        crate::auth::Scope,
+        ApiKeyInfo,
+        RotateApiKeyRequest,
+        RotateApiKeyResponse,
+        CreateWebhookRequest,
+        CreateWebhookResponse,
+        Webhook,
+        crate::webhooks::WebhookEvent,
+        WebhookDelivery,
+        CreateDomainRequest,
+        UpdateDomainRequest,
+        DomainInfo,
+        CreateDestinationEntryRequest,
+        DestinationEntry,
+        crate::destinations::DestinationList,
+        CleanupReport,
+        RescanReport,
+        LinkCheckReport,
+        JobInfo,
+        CreateReportRequest,
+        CreateReportResponse,
+        AbuseReport,
+        ResolveReportsRequest,
+        ModerationResult,
+        crate::reports::ReportReason,
+        crate::reports::ModerationAction,
+        crate::reports::Takedown,
+        PurgeReport,
+        OEmbedResponse,
+        AuditEntry,
+        HealthResponse,
+        ReadinessResponse,
+        DatabaseCheck,
+        MigrationCheck,
+        CacheCheck,
+        ErrorResponse,
+    )),
+    modifiers(&SecuritySchemes, &ProblemResponses, &VersionedPaths),
+    tags(
+        (name = "urls", description = "Create, inspect and manage short URLs"),
+        (name = "redirects", description = "Public short link resolution"),
+        (name = "users", description = "Accounts and login"),
+        (name = "orgs", description = "Organizations that share ownership of links"),
+        (name = "campaigns", description = "Groups of links with combined statistics"),
+        (name = "pages", description = "Link-in-bio pages listing a user's short links"),
+        (name = "webhooks", description = "Signed notifications about link and click events"),
+        (name = "admin", description = "Operations that require the admin key"),
+        (name = "service", description = "Health and monitoring"),
+    )
+)]
+pub struct ApiDoc;
+
+// All credentials travel as `Authorization: Bearer ...`, they only differ in what they unlock
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        for name in ["api_key", "user_token", "admin_key"] {
+            components.add_security_scheme(
+                name,
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+// Errors are sent as application/problem+json, the annotations only say which responses carry one
+struct ProblemResponses;
+
+impl Modify for ProblemResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let responses = openapi
+            .paths
+            .paths
+            .values_mut()
+            .flat_map(|item| item.operations.values_mut())
+            .flat_map(|operation| operation.responses.responses.values_mut());
+        for response in responses {
+            let RefOr::T(response) = response else { continue };
+            let is_problem = response.content.get("application/json").is_some_and(|content| {
+                matches!(&content.schema, RefOr::Ref(schema) if schema.ref_location.ends_with("/ErrorResponse"))
+            });
+            if is_problem {
+                if let Some(content) = response.content.shift_remove("application/json") {
+                    response.content.insert("application/problem+json".into(), content);
+                }
+            }
+        }
+    }
+}
+
+// Handlers are annotated with the path inside the API, the router nests them under the prefix
+struct VersionedPaths;
+
+impl Modify for VersionedPaths {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi.paths.paths = std::mem::take(&mut openapi.paths.paths)
+            .into_iter()
+            .map(|(path, item)| match ROOT_PATHS.contains(&path.as_str()) {
+                true => (path, item),
+                false => (format!("{}{}", API_PREFIX, path), item),
+            })
+            .collect();
+    }
+}
+
+// Swagger UI assets come from a CDN so the binary does not have to bundle them
+const SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>QuickURL API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
+
+pub async fn openapi_json() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+pub async fn swagger_ui() -> Html<&'static str> {
+    Html(SWAGGER_UI_HTML)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_covers_routes() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let paths = doc["paths"].as_object().unwrap();
+
+        for path in ["/api/v1/shorten", "/api/v1/shorten/batch", "/api/v1/urls", "/api/v1/urls/{token}", "/{token}", "/api/v1/keys"] {
+            assert!(paths.contains_key(path), "{} missing", path);
+        }
+        // Only the JSON endpoints move under the prefix
+        assert!(!paths.contains_key("/urls"));
+        assert!(paths.contains_key("/healthz"));
+        assert!(paths["/api/v1/urls/{token}"]["patch"]["security"].is_array());
+        assert!(paths["/api/v1/shorten"]["get"]["responses"]["201"]["content"]["text/plain"].is_object());
+        assert!(doc["components"]["securitySchemes"]["admin_key"].is_object());
+        assert!(paths["/{token}"]["get"]["responses"]["410"]["content"]["application/problem+json"].is_object());
+    }
+}
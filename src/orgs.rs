@@ -0,0 +1,525 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{AnyPool, Row};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::audit::{self, AuditAction};
+use crate::auth::Caller;
+use crate::base_url::BaseUrl;
+use crate::models::{CreateOrgRequest, OrgInfo, OrgMember, PutMemberRequest, TransferUrlRequest};
+use crate::storage::{self, SqlBuilder};
+use crate::{load_tags, push_owner_filter, url_info_from_row, Access, AppError, AppState};
+
+const MAX_NAME_LENGTH: usize = 100;
+
+// Viewers see the organization's links, editors also create, change and delete them, admins
+// also manage the members
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OrgRole {
+    Viewer,
+    Editor,
+    Admin,
+}
+
+impl OrgRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrgRole::Viewer => "viewer",
+            OrgRole::Editor => "editor",
+            OrgRole::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "viewer" => Some(OrgRole::Viewer),
+            "editor" => Some(OrgRole::Editor),
+            "admin" => Some(OrgRole::Admin),
+            _ => None,
+        }
+    }
+}
+
+fn database_error(e: sqlx::Error) -> AppError {
+    AppError::DatabaseError(e.to_string())
+}
+
+async fn member_role(db: &AnyPool, org_id: &str, user_id: &str) -> Result<Option<OrgRole>, AppError> {
+    Ok(sqlx::query("SELECT role FROM organization_members WHERE org_id = $1 AND user_id = $2")
+        .bind(org_id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+        .map_err(database_error)?
+        .and_then(|row| OrgRole::parse(&row.get::<String, _>("role"))))
+}
+
+// API keys and the admin key act on every organization like they do on every link. For
+// everyone else an organization they are not a member of does not exist.
+async fn require_role(db: &AnyPool, org_id: &str, caller: &Caller, needed: OrgRole) -> Result<Option<OrgRole>, AppError> {
+    let exists = sqlx::query("SELECT id FROM organizations WHERE id = $1")
+        .bind(org_id)
+        .fetch_optional(db)
+        .await
+        .map_err(database_error)?
+        .is_some();
+    let not_found = || AppError::NotFound("Organization not found".into());
+    if !exists {
+        return Err(not_found());
+    }
+
+    match caller {
+        Caller::Admin | Caller::ApiKey(_) => Ok(None),
+        Caller::Anonymous => Err(not_found()),
+        Caller::User(user_id) => match member_role(db, org_id, user_id).await? {
+            Some(role) if role >= needed => Ok(Some(role)),
+            Some(_) => Err(AppError::Forbidden(format!("Requires the {} role in this organization", needed.as_str()))),
+            None => Err(not_found()),
+        },
+    }
+}
+
+// An account about to be erased must not leave members behind without an admin. The last member
+// of an organization may go, nobody is left to manage it for.
+pub async fn ensure_handed_over(db: &AnyPool, user_id: &str) -> Result<(), AppError> {
+    let stranded = sqlx::query(
+        r#"
+        SELECT org_id FROM organization_members m
+        WHERE m.user_id = $1 AND m.role = 'admin'
+            AND NOT EXISTS (
+                SELECT 1 FROM organization_members o WHERE o.org_id = m.org_id AND o.user_id <> $1 AND o.role = 'admin'
+            )
+            AND EXISTS (SELECT 1 FROM organization_members o WHERE o.org_id = m.org_id AND o.user_id <> $1)
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+    .map_err(database_error)?;
+    if let Some(row) = stranded {
+        return Err(AppError::Conflict(format!(
+            "Make another member an admin of organization {} first",
+            row.get::<String, _>("org_id")
+        )));
+    }
+    Ok(())
+}
+
+// Keeps every organization manageable by at least one of its members
+async fn ensure_other_admin(db: &AnyPool, org_id: &str, user_id: &str) -> Result<(), AppError> {
+    let others: i64 = sqlx::query(
+        "SELECT COUNT(*) AS admins FROM organization_members WHERE org_id = $1 AND user_id <> $2 AND role = 'admin'"
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+    .map_err(database_error)?
+    .get("admins");
+    if others == 0 {
+        return Err(AppError::Conflict("An organization needs at least one admin".into()));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/orgs",
+    tag = "orgs",
+    request_body = CreateOrgRequest,
+    responses(
+        (status = 201, description = "Organization created, the caller is its first admin", body = OrgInfo),
+        (status = 400, description = "Invalid name", body = ErrorResponse),
+        (status = 403, description = "Not a user account", body = ErrorResponse),
+    ),
+    security(("user_token" = []))
+)]
+pub async fn create_org(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<CreateOrgRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = caller
+        .user_id()
+        .ok_or_else(|| AppError::Forbidden("Organizations are created by user accounts".into()))?;
+    let name = payload.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AppError::BadRequest(format!("name must be 1 to {} characters", MAX_NAME_LENGTH)));
+    }
+
+    let org = OrgInfo {
+        id: Uuid::new_v4().to_string(),
+        name,
+        created_at: storage::now(),
+        role: Some(OrgRole::Admin),
+    };
+    let mut tx = state.db.begin().await.map_err(database_error)?;
+    sqlx::query("INSERT INTO organizations (id, name, created_at) VALUES ($1, $2, $3)")
+        .bind(&org.id)
+        .bind(&org.name)
+        .bind(storage::ts(org.created_at))
+        .execute(&mut *tx)
+        .await
+        .map_err(database_error)?;
+    sqlx::query("INSERT INTO organization_members (org_id, user_id, role, created_at) VALUES ($1, $2, $3, $4)")
+        .bind(&org.id)
+        .bind(user_id)
+        .bind(OrgRole::Admin.as_str())
+        .bind(storage::ts(org.created_at))
+        .execute(&mut *tx)
+        .await
+        .map_err(database_error)?;
+    tx.commit().await.map_err(database_error)?;
+
+    Ok((StatusCode::CREATED, Json(org)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/orgs",
+    tag = "orgs",
+    responses(
+        (status = 200, description = "Organizations the caller belongs to, every one for API keys and the admin key", body = [OrgInfo]),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn list_orgs(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    let rows = match &caller {
+        Caller::User(user_id) => sqlx::query(
+            r#"
+            SELECT o.id, o.name, o.created_at, m.role FROM organizations o
+            JOIN organization_members m ON m.org_id = o.id
+            WHERE m.user_id = $1
+            ORDER BY o.name, o.id
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&state.db)
+        .await,
+        _ => sqlx::query("SELECT id, name, created_at, NULL AS role FROM organizations ORDER BY name, id")
+            .fetch_all(&state.db)
+            .await,
+    }
+    .map_err(database_error)?;
+
+    let orgs: Vec<OrgInfo> = rows
+        .iter()
+        .map(|row| OrgInfo {
+            id: row.get("id"),
+            name: row.get("name"),
+            created_at: storage::get_ts(row, "created_at"),
+            role: row.get::<Option<String>, _>("role").as_deref().and_then(OrgRole::parse),
+        })
+        .collect();
+    Ok(Json(orgs))
+}
+
+#[utoipa::path(
+    get,
+    path = "/orgs/{id}/members",
+    tag = "orgs",
+    params(("id" = String, Path, description = "Organization id")),
+    responses(
+        (status = 200, description = "Members and their roles", body = [OrgMember]),
+        (status = 404, description = "Organization not found", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn list_members(
+    Path(org_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    require_role(&state.db, &org_id, &caller, OrgRole::Viewer).await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT m.user_id, u.email, m.role, m.created_at FROM organization_members m
+        JOIN users u ON u.id = m.user_id
+        WHERE m.org_id = $1
+        ORDER BY u.email
+        "#
+    )
+    .bind(&org_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(database_error)?;
+
+    let members: Vec<OrgMember> = rows
+        .iter()
+        .filter_map(|row| {
+            Some(OrgMember {
+                user_id: row.get("user_id"),
+                email: row.get("email"),
+                role: OrgRole::parse(&row.get::<String, _>("role"))?,
+                added_at: storage::get_ts(row, "created_at"),
+            })
+        })
+        .collect();
+    Ok(Json(members))
+}
+
+#[utoipa::path(
+    put,
+    path = "/orgs/{id}/members",
+    tag = "orgs",
+    params(("id" = String, Path, description = "Organization id")),
+    request_body = PutMemberRequest,
+    responses(
+        (status = 200, description = "Member added or role changed", body = OrgMember),
+        (status = 400, description = "No account with that email", body = ErrorResponse),
+        (status = 403, description = "Requires the admin role", body = ErrorResponse),
+        (status = 404, description = "Organization not found", body = ErrorResponse),
+        (status = 409, description = "Would leave the organization without an admin", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn put_member(
+    Path(org_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<PutMemberRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    require_role(&state.db, &org_id, &caller, OrgRole::Admin).await?;
+
+    let email = payload.email.trim().to_lowercase();
+    let user_id: String = sqlx::query("SELECT id FROM users WHERE email = $1")
+        .bind(&email)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(database_error)?
+        .map(|row| row.get("id"))
+        .ok_or_else(|| AppError::BadRequest(format!("No account is registered for {}", email)))?;
+
+    let current = member_role(&state.db, &org_id, &user_id).await?;
+    if current == Some(OrgRole::Admin) && payload.role != OrgRole::Admin {
+        ensure_other_admin(&state.db, &org_id, &user_id).await?;
+    }
+
+    let added_at = storage::now();
+    if current.is_some() {
+        sqlx::query("UPDATE organization_members SET role = $1 WHERE org_id = $2 AND user_id = $3")
+            .bind(payload.role.as_str())
+            .bind(&org_id)
+            .bind(&user_id)
+            .execute(&state.db)
+            .await
+            .map_err(database_error)?;
+    } else {
+        sqlx::query("INSERT INTO organization_members (org_id, user_id, role, created_at) VALUES ($1, $2, $3, $4)")
+            .bind(&org_id)
+            .bind(&user_id)
+            .bind(payload.role.as_str())
+            .bind(storage::ts(added_at))
+            .execute(&state.db)
+            .await
+            .map_err(database_error)?;
+    }
+
+    let added_at: chrono::DateTime<chrono::Utc> =
+        sqlx::query("SELECT created_at FROM organization_members WHERE org_id = $1 AND user_id = $2")
+            .bind(&org_id)
+            .bind(&user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(database_error)?
+            .map_or(added_at, |row| storage::get_ts(&row, "created_at"));
+    Ok(Json(OrgMember { user_id, email, role: payload.role, added_at }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/orgs/{id}/members/{user_id}",
+    tag = "orgs",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("user_id" = String, Path, description = "Member to remove, members may remove themselves"),
+    ),
+    responses(
+        (status = 204, description = "Member removed"),
+        (status = 403, description = "Requires the admin role", body = ErrorResponse),
+        (status = 404, description = "Organization or member not found", body = ErrorResponse),
+        (status = 409, description = "Would leave the organization without an admin", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn delete_member(
+    Path((org_id, user_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    let leaving = caller.user_id() == Some(user_id.as_str());
+    let needed = if leaving { OrgRole::Viewer } else { OrgRole::Admin };
+    require_role(&state.db, &org_id, &caller, needed).await?;
+
+    match member_role(&state.db, &org_id, &user_id).await? {
+        None => return Err(AppError::NotFound("Member not found".into())),
+        Some(OrgRole::Admin) => ensure_other_admin(&state.db, &org_id, &user_id).await?,
+        Some(_) => {}
+    }
+    sqlx::query("DELETE FROM organization_members WHERE org_id = $1 AND user_id = $2")
+        .bind(&org_id)
+        .bind(&user_id)
+        .execute(&state.db)
+        .await
+        .map_err(database_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/urls/{token}/transfer",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    request_body = TransferUrlRequest,
+    responses(
+        (status = 200, description = "Link moved to its new owner", body = UrlInfo),
+        (status = 400, description = "Only user accounts can own links personally", body = ErrorResponse),
+        (status = 403, description = "Requires the editor role in the organization", body = ErrorResponse),
+        (status = 404, description = "URL or organization not found", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn transfer_url(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+    Json(payload): Json<TransferUrlRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    // Taking a link over needs write access to it as well as to where it goes
+    let (user_id, org_id) = match payload.org_id {
+        Some(org_id) => {
+            require_role(&state.db, &org_id, &caller, OrgRole::Editor).await?;
+            (None, Some(org_id))
+        }
+        None => {
+            let user_id = caller
+                .user_id()
+                .ok_or_else(|| AppError::BadRequest("Only user accounts can own links personally".into()))?;
+            (Some(user_id.to_string()), None)
+        }
+    };
+
+    let mut lookup = SqlBuilder::new("SELECT * FROM urls WHERE token = ");
+    lookup.push_bind(token.clone()).push(" AND deleted_at IS NULL");
+    push_owner_filter(&mut lookup, &caller, Access::Write);
+
+    let mut tx = state.db.begin().await.map_err(database_error)?;
+    let mut old = lookup
+        .build()
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(database_error)?
+        .map(|row| url_info_from_row(&base, &row))
+        .ok_or(AppError::UrlNotFound)?;
+    load_tags(&mut *tx, std::slice::from_mut(&mut old)).await?;
+
+    // Dedupe is scoped to the owner, the link gives up its slot rather than clash in the new one
+    sqlx::query("UPDATE urls SET user_id = $1, org_id = $2, normalized_url = NULL, updated_at = $3 WHERE id = $4")
+        .bind(&user_id)
+        .bind(&org_id)
+        .bind(storage::ts(storage::now()))
+        .bind(&old.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(database_error)?;
+
+    let mut updated = sqlx::query("SELECT * FROM urls WHERE id = $1")
+        .bind(&old.id)
+        .fetch_one(&mut *tx)
+        .await
+        .map(|row| url_info_from_row(&base, &row))
+        .map_err(database_error)?;
+    updated.tags = old.tags.clone();
+    audit::record(&mut tx, &old.id, &token, AuditAction::Update, &caller, audit::diff(&old, &updated)).await?;
+    tx.commit().await.map_err(database_error)?;
+    state.links.invalidate(&token).await;
+
+    Ok(Json(updated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_member_roles_scope_links() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let now = storage::ts(storage::now());
+            for user in ["admin", "editor", "viewer", "outsider"] {
+                sqlx::query("INSERT INTO users (id, email, password_hash, created_at) VALUES ($1, $1, 'x', $2)")
+                    .bind(user)
+                    .bind(&now)
+                    .execute(&db)
+                    .await
+                    .unwrap();
+            }
+            sqlx::query("INSERT INTO organizations (id, name, created_at) VALUES ('o1', 'Team', $1)")
+                .bind(&now)
+                .execute(&db)
+                .await
+                .unwrap();
+            for (user, role) in [("admin", "admin"), ("editor", "editor"), ("viewer", "viewer")] {
+                sqlx::query("INSERT INTO organization_members (org_id, user_id, role, created_at) VALUES ('o1', $1, $2, $3)")
+                    .bind(user)
+                    .bind(role)
+                    .bind(&now)
+                    .execute(&db)
+                    .await
+                    .unwrap();
+            }
+            sqlx::query(
+                r#"
+                INSERT INTO urls (id, token, original_url, created_at, click_count, org_id)
+                VALUES ('u1', 'team', 'https://example.com', $1, 0, 'o1')
+                "#
+            )
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+
+            let visible = |user: &'static str, access: Access| {
+                let db = db.clone();
+                async move {
+                    let mut lookup = SqlBuilder::new("SELECT id FROM urls WHERE token = 'team'");
+                    push_owner_filter(&mut lookup, &Caller::User(user.into()), access);
+                    lookup.build().fetch_optional(&db).await.unwrap().is_some()
+                }
+            };
+            assert!(visible("viewer", Access::Read).await && !visible("viewer", Access::Write).await);
+            assert!(visible("editor", Access::Write).await);
+            assert!(!visible("outsider", Access::Read).await);
+
+            let viewer = Caller::User("viewer".into());
+            assert_eq!(require_role(&db, "o1", &viewer, OrgRole::Viewer).await.unwrap(), Some(OrgRole::Viewer));
+            assert!(matches!(require_role(&db, "o1", &viewer, OrgRole::Editor).await, Err(AppError::Forbidden(_))));
+            let outsider = Caller::User("outsider".into());
+            assert!(matches!(require_role(&db, "o1", &outsider, OrgRole::Viewer).await, Err(AppError::NotFound(_))));
+            assert!(require_role(&db, "o1", &Caller::Admin, OrgRole::Admin).await.is_ok());
+
+            // The only admin can not step down or erase their account, once there is a second one they can
+            assert!(matches!(ensure_other_admin(&db, "o1", "admin").await, Err(AppError::Conflict(_))));
+            assert!(matches!(ensure_handed_over(&db, "admin").await, Err(AppError::Conflict(_))));
+            assert!(ensure_handed_over(&db, "viewer").await.is_ok());
+            sqlx::query("UPDATE organization_members SET role = 'admin' WHERE user_id = 'editor'")
+                .execute(&db)
+                .await
+                .unwrap();
+            assert!(ensure_other_admin(&db, "o1", "admin").await.is_ok());
+            assert!(ensure_handed_over(&db, "admin").await.is_ok());
+        }
+    }
+}
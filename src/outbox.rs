@@ -0,0 +1,144 @@
+use chrono::{DateTime, Utc};
+use sqlx::{AnyConnection, AnyPool, Row};
+use std::time::Duration;
+
+use crate::storage::{self, SqlBuilder};
+
+// Each topic has its own dispatcher
+pub const WEBHOOKS: &str = "webhooks";
+pub const EVENTS: &str = "events";
+
+#[derive(Debug)]
+pub struct Entry {
+    pub id: String,
+    pub payload: String,
+    // Including the attempt it was just claimed for
+    pub attempts: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+fn later(delay: Duration) -> String {
+    storage::ts(storage::now() + chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::hours(1)))
+}
+
+// Runs in the caller's transaction, the entry is due straight away once it commits
+pub async fn push(conn: &mut AnyConnection, topic: &str, id: &str, payload: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO outbox (id, topic, payload, created_at, next_attempt_at) VALUES ($1, $2, $3, $4, $4)")
+        .bind(id)
+        .bind(topic)
+        .bind(payload)
+        .bind(storage::ts(storage::now()))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+// Up to `limit` due entries of the topic, oldest first. No other dispatcher gets them for
+// `lease`, after that an attempt that never finished, say because the process died, is
+// handed out again.
+pub async fn claim(db: &AnyPool, topic: &str, limit: usize, lease: Duration) -> Result<Vec<Entry>, sqlx::Error> {
+    let until = later(lease);
+    let now = storage::ts(storage::now());
+    // The outer check makes a concurrent claim of the same entry on Postgres match nothing
+    let rows = storage::retry_busy(|| {
+        sqlx::query(
+            r#"
+            UPDATE outbox SET attempts = attempts + 1, next_attempt_at = $1
+            WHERE next_attempt_at <= $2 AND id IN (
+                SELECT id FROM outbox WHERE topic = $3 AND next_attempt_at <= $2 ORDER BY created_at LIMIT $4
+            )
+            RETURNING id, payload, attempts, created_at
+            "#
+        )
+        .bind(&until)
+        .bind(&now)
+        .bind(topic)
+        .bind(limit as i64)
+        .fetch_all(db)
+    })
+    .await?;
+
+    let mut entries: Vec<Entry> = rows
+        .iter()
+        .map(|row| Entry {
+            id: row.get("id"),
+            payload: row.get("payload"),
+            attempts: row.get::<i64, _>("attempts").try_into().unwrap_or(u32::MAX),
+            created_at: storage::get_ts(row, "created_at"),
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.created_at);
+    Ok(entries)
+}
+
+fn push_ids(query: &mut SqlBuilder, ids: &[String]) {
+    query.push(" WHERE id IN (");
+    for (i, id) in ids.iter().enumerate() {
+        if i > 0 {
+            query.push(", ");
+        }
+        query.push_bind(id.clone());
+    }
+    query.push(")");
+}
+
+// Delivered, or given up on
+pub async fn finish(db: &AnyPool, ids: &[String]) -> Result<(), sqlx::Error> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    storage::retry_busy(|| async {
+        let mut query = SqlBuilder::new("DELETE FROM outbox");
+        push_ids(&mut query, ids);
+        query.build().execute(db).await
+    })
+    .await?;
+    Ok(())
+}
+
+// Makes failed entries due again after the delay
+pub async fn postpone(db: &AnyPool, ids: &[String], delay: Duration) -> Result<(), sqlx::Error> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let due = later(delay);
+    storage::retry_busy(|| async {
+        let mut query = SqlBuilder::new("UPDATE outbox SET next_attempt_at = ");
+        query.push_bind(due.clone());
+        push_ids(&mut query, ids);
+        query.build().execute(db).await
+    })
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_claimed_entries_wait_for_their_lease() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let mut tx = db.begin().await.unwrap();
+            push(&mut tx, WEBHOOKS, "a", "{}").await.unwrap();
+            push(&mut tx, EVENTS, "b", "{}").await.unwrap();
+            tx.commit().await.unwrap();
+
+            let claimed = claim(&db, WEBHOOKS, 10, Duration::from_secs(60)).await.unwrap();
+            assert_eq!(claimed.len(), 1);
+            assert_eq!((claimed[0].id.as_str(), claimed[0].attempts), ("a", 1));
+            // Leased to the first claim, and other topics are left alone
+            assert!(claim(&db, WEBHOOKS, 10, Duration::from_secs(60)).await.unwrap().is_empty());
+
+            postpone(&db, &["a".into()], Duration::ZERO).await.unwrap();
+            let retried = claim(&db, WEBHOOKS, 10, Duration::ZERO).await.unwrap();
+            assert_eq!(retried[0].attempts, 2);
+            // A lease that ran out is claimed again
+            assert_eq!(claim(&db, WEBHOOKS, 10, Duration::ZERO).await.unwrap().len(), 1);
+
+            finish(&db, &["a".into()]).await.unwrap();
+            assert!(claim(&db, WEBHOOKS, 10, Duration::ZERO).await.unwrap().is_empty());
+            assert_eq!(claim(&db, EVENTS, 10, Duration::ZERO).await.unwrap()[0].id, "b");
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use anyhow::Context;
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+
+use crate::config::Config;
+use crate::preview::escape_html;
+
+const DISABLED_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <meta name="robots" content="noindex">
+  <title>Link disabled</title>
+  <style>
+    body { font-family: system-ui, sans-serif; max-width: 40rem; margin: 4rem auto; padding: 0 1rem; color: #222; }
+  </style>
+</head>
+<body>
+  <h1>This link is disabled</h1>
+  <p>Its owner or a moderator has switched it off for now. It may work again later.</p>
+</body>
+</html>
+"#;
+
+// {destination} is replaced with the escaped URL. The form has no action, so it posts back to
+// the short link with its query string.
+const WARNING_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <meta name="robots" content="noindex">
+  <title>Warning: suspicious link</title>
+  <style>
+    body { font-family: system-ui, sans-serif; max-width: 40rem; margin: 4rem auto; padding: 0 1rem; color: #222; }
+    code { display: block; padding: 0.75rem; background: #f4f4f4; overflow-wrap: anywhere; }
+    button { padding: 0.5rem 1rem; }
+  </style>
+</head>
+<body>
+  <h1>This link may be unsafe</h1>
+  <p>It was flagged as suspicious, it could lead to phishing, malware or other harmful content. It goes to:</p>
+  <code>{destination}</code>
+  <p>Only continue if you trust where it leads.</p>
+  <form method="post">
+    <button type="submit">Continue anyway</button>
+  </form>
+</body>
+</html>
+"#;
+
+// http(s) only, {token} may appear anywhere in it
+pub fn is_redirect_url(url: &str) -> bool {
+    (url.starts_with("http://") || url.starts_with("https://")) && url::Url::parse(&url.replace("{token}", "token")).is_ok()
+}
+
+// 302 to the URL with the token filled in. Never cached, the token may be taken later.
+pub fn redirect(url: &str, token: &str) -> Response {
+    let encoded: String = url::form_urlencoded::byte_serialize(token.as_bytes()).collect();
+    let mut response = Redirect::to(&url.replace("{token}", &encoded)).into_response();
+    *response.status_mut() = StatusCode::FOUND;
+    response.headers_mut().insert(header::CACHE_CONTROL, header::HeaderValue::from_static("no-store"));
+    response
+}
+
+// An operator's not_found_page or expired_link_page
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Page {
+    Html(String),
+    Redirect(String),
+}
+
+impl Page {
+    fn load(option: &str, value: Option<&str>) -> anyhow::Result<Option<Self>> {
+        let Some(value) = value.filter(|value| !value.is_empty()) else {
+            return Ok(None);
+        };
+        if value.starts_with("http://") || value.starts_with("https://") {
+            anyhow::ensure!(is_redirect_url(value), "{} is not a valid URL", option);
+            return Ok(Some(Page::Redirect(value.to_string())));
+        }
+        let html = std::fs::read_to_string(value).with_context(|| format!("Could not read {} {}", option, value))?;
+        Ok(Some(Page::Html(html)))
+    }
+
+    // Neither is cached, the token may be taken or the link extended later
+    fn respond(&self, status: StatusCode, token: &str) -> Response {
+        match self {
+            Page::Html(html) => (
+                status,
+                [(header::CONTENT_TYPE, "text/html; charset=utf-8"), (header::CACHE_CONTROL, "no-store")],
+                html.replace("{token}", &escape_html(token)),
+            )
+                .into_response(),
+            Page::Redirect(url) => redirect(url, token),
+        }
+    }
+}
+
+// Pages the redirect route answers visitors with instead of a problem document, read once at start
+#[derive(Debug, Clone)]
+pub struct Pages {
+    disabled: String,
+    not_found: Option<Page>,
+    expired: Option<Page>,
+    fallback: Option<String>,
+}
+
+impl Pages {
+    pub fn load(config: &Config) -> anyhow::Result<Self> {
+        let disabled = match config.disabled_link_page.as_deref().filter(|path| !path.is_empty()) {
+            Some(path) => std::fs::read_to_string(path).with_context(|| format!("Could not read disabled_link_page {}", path))?,
+            None => DISABLED_PAGE.to_string(),
+        };
+        let fallback = config.fallback_url.clone().filter(|url| !url.is_empty());
+        if let Some(url) = &fallback {
+            anyhow::ensure!(is_redirect_url(url), "fallback_url must be an http(s) URL");
+        }
+        Ok(Self {
+            disabled,
+            fallback,
+            not_found: Page::load("not_found_page", config.not_found_page.as_deref())?,
+            expired: Page::load("expired_link_page", config.expired_link_page.as_deref())?,
+        })
+    }
+
+    // The custom domain's fallback URL, then the global one, then the not_found_page. None leaves
+    // the answer to the usual problem document.
+    pub fn not_found(&self, token: &str, domain_fallback: Option<&str>) -> Option<Response> {
+        match domain_fallback.or(self.fallback.as_deref()) {
+            Some(url) => Some(redirect(url, token)),
+            None => self.not_found.as_ref().map(|page| page.respond(StatusCode::NOT_FOUND, token)),
+        }
+    }
+
+    // Expired links and links that used up their click limit
+    pub fn expired(&self, token: &str) -> Option<Response> {
+        self.expired.as_ref().map(|page| page.respond(StatusCode::GONE, token))
+    }
+
+    // Never cached, the link can be enabled again any time
+    pub fn disabled(&self) -> Response {
+        (
+            StatusCode::GONE,
+            [(header::CONTENT_TYPE, "text/html; charset=utf-8"), (header::CACHE_CONTROL, "no-store")],
+            self.disabled.clone(),
+        )
+            .into_response()
+    }
+
+    // Shown instead of redirecting to a flagged link, until the visitor confirms
+    pub fn warning(&self, destination: &str) -> Response {
+        (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "text/html; charset=utf-8"),
+                (header::CACHE_CONTROL, "no-store"),
+                (header::HeaderName::from_static("x-robots-tag"), "noindex"),
+            ],
+            WARNING_PAGE.replace("{destination}", &escape_html(destination)),
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_page_from_file() {
+        let response = Pages::load(&Config::default()).unwrap().disabled();
+        assert_eq!(response.status(), StatusCode::GONE);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+
+        let path = std::env::temp_dir().join(format!("quickurl-disabled-{}.html", std::process::id()));
+        std::fs::write(&path, "<p>Paused</p>").unwrap();
+        let config = Config {
+            disabled_link_page: Some(path.to_string_lossy().into_owned()),
+            ..Config::default()
+        };
+        assert_eq!(Pages::load(&config).unwrap().disabled, "<p>Paused</p>");
+        std::fs::remove_file(&path).unwrap();
+
+        let missing = Config {
+            disabled_link_page: Some("/nonexistent/disabled.html".into()),
+            ..Config::default()
+        };
+        assert!(Pages::load(&missing).is_err());
+    }
+
+    #[test]
+    fn test_not_found_and_expired_pages() {
+        let pages = Pages::load(&Config::default()).unwrap();
+        assert!(pages.not_found("abc", None).is_none());
+        assert!(pages.expired("abc").is_none());
+
+        let path = std::env::temp_dir().join(format!("quickurl-not-found-{}.html", std::process::id()));
+        std::fs::write(&path, "<p>No {token} here</p>").unwrap();
+        let config = Config {
+            not_found_page: Some(path.to_string_lossy().into_owned()),
+            expired_link_page: Some("https://example.com/expired?t={token}".into()),
+            ..Config::default()
+        };
+        let pages = Pages::load(&config).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(pages.not_found, Some(Page::Html("<p>No {token} here</p>".into())));
+        assert_eq!(pages.not_found("x", None).unwrap().status(), StatusCode::NOT_FOUND);
+
+        // Fallback URLs win over the page, a custom domain's over the global one
+        let fallback = Pages { fallback: Some("https://example.com/".into()), ..pages };
+        assert_eq!(fallback.not_found("x", None).unwrap().headers()[header::LOCATION], "https://example.com/");
+        let domain = fallback.not_found("x", Some("https://go.example.com/?t={token}")).unwrap();
+        assert_eq!(domain.headers()[header::LOCATION], "https://go.example.com/?t=x");
+        assert!(!is_redirect_url("ftp://example.com/"));
+
+        let expired = fallback.expired("a b").unwrap();
+        assert_eq!(expired.status(), StatusCode::FOUND);
+        assert_eq!(expired.headers()[header::LOCATION], "https://example.com/expired?t=a+b");
+
+        let invalid = Config {
+            expired_link_page: Some("https://exa mple.com/".into()),
+            ..Config::default()
+        };
+        assert!(Pages::load(&invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_warning_page_escapes_destination() {
+        let response = Pages::load(&Config::default()).unwrap().warning("https://example.com/?a=1&b=<script>");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("https://example.com/?a=1&amp;b=&lt;script&gt;"));
+        assert!(body.contains(r#"<form method="post">"#));
+    }
+}
@@ -0,0 +1,270 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Json, Response},
+};
+use sqlx::{any::AnyRow, AnyPool, Row};
+use std::sync::Arc;
+
+use crate::base_url::BaseUrl;
+use crate::models::{OEmbedQuery, OEmbedResponse};
+use crate::reports::Takedown;
+use crate::storage;
+use crate::{folded_token, AppError, AppState};
+
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const PROVIDER_NAME: &str = "QuickURL";
+// How long unfurlers may keep the oEmbed answer, titles rarely change
+const OEMBED_CACHE_AGE_SECS: u64 = 3600;
+
+// What the page shows about the link, the card fields come from the destination's own tags
+struct Preview<'a> {
+    short_url: &'a str,
+    original_url: &'a str,
+    title: Option<&'a str>,
+    description: Option<&'a str>,
+    image_url: Option<&'a str>,
+    created: &'a str,
+}
+
+// Open Graph and Twitter card tags, so the preview unfurls like the destination would in chat
+// apps without them following the link
+fn card_tags(preview: &Preview) -> String {
+    let mut tags = vec![
+        ("og:type", "website".to_string()),
+        ("og:site_name", PROVIDER_NAME.to_string()),
+        ("og:url", preview.short_url.to_string()),
+        ("og:title", preview.title.unwrap_or(preview.short_url).to_string()),
+    ];
+    if let Some(description) = preview.description {
+        tags.push(("og:description", description.to_string()));
+    }
+    if let Some(image_url) = preview.image_url {
+        tags.push(("og:image", image_url.to_string()));
+    }
+    let card = if preview.image_url.is_some() { "summary_large_image" } else { "summary" };
+    tags.push(("twitter:card", card.to_string()));
+
+    let mut html: Vec<String> = tags
+        .iter()
+        .map(|(key, content)| {
+            // Open Graph uses property, Twitter's own tags go by name
+            let attribute = if key.starts_with("og:") { "property" } else { "name" };
+            format!(r#"<meta {}="{}" content="{}">"#, attribute, key, escape_html(content))
+        })
+        .collect();
+    html.push(format!(
+        r#"<link rel="alternate" type="application/json+oembed" href="{}/oembed">"#,
+        escape_html(preview.short_url)
+    ));
+    html.join("\n  ")
+}
+
+fn render(preview: &Preview) -> String {
+    let title = preview
+        .title
+        .map(|title| format!("<h1>{}</h1>", escape_html(title)))
+        .unwrap_or_default();
+    let description = preview
+        .description
+        .map(|description| format!("<p>{}</p>", escape_html(description)))
+        .unwrap_or_default();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <meta name="robots" content="noindex">
+  <title>Link preview</title>
+  {card}
+  <style>
+    body {{ font-family: system-ui, sans-serif; max-width: 40rem; margin: 4rem auto; padding: 0 1rem; color: #222; }}
+    .destination {{ word-break: break-all; padding: 0.75rem; background: #f4f4f4; border-radius: 4px; }}
+    .meta {{ color: #666; font-size: 0.9rem; }}
+    a.continue {{ display: inline-block; margin-top: 1.5rem; padding: 0.6rem 1.2rem; background: #2563eb; color: #fff; border-radius: 4px; text-decoration: none; }}
+  </style>
+</head>
+<body>
+  {title}
+  {description}
+  <p>This short link leads to:</p>
+  <p class="destination">{destination}</p>
+  <p class="meta">{short_url} &middot; created {created}</p>
+  <a class="continue" href="{short_url}" rel="noreferrer">Continue</a>
+</body>
+</html>
+"#,
+        card = card_tags(preview),
+        title = title,
+        description = description,
+        destination = escape_html(preview.original_url),
+        short_url = escape_html(preview.short_url),
+        created = escape_html(preview.created),
+    )
+}
+
+async fn find_preview(db: &AnyPool, token: &str) -> Result<Option<AnyRow>, AppError> {
+    sqlx::query("SELECT token, original_url, title, description, image_url, created_at, starts_at, expires_at, domain, takedown, is_active, deleted_at FROM urls WHERE token = $1 OR id IN (SELECT url_id FROM link_aliases WHERE token = $1)")
+        .bind(token)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))
+}
+
+// Shows where a link leads without following it or counting a click
+#[utoipa::path(
+    get,
+    path = "/p/{token}",
+    tag = "redirects",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 200, description = "HTML preview of the destination", content_type = "text/html"),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+        (status = 410, description = "URL deleted, expired or disabled", body = ErrorResponse),
+        (status = 451, description = "Taken down for legal reasons", body = ErrorResponse),
+    )
+)]
+pub async fn get_preview(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let row = find_live(&state, &token, &headers).await?;
+    let domain: Option<String> = row.get("domain");
+    let title: Option<String> = row.get("title");
+    let description: Option<String> = row.get("description");
+    let image_url: Option<String> = row.get("image_url");
+    let created = storage::get_ts(&row, "created_at").format("%Y-%m-%d").to_string();
+    Ok(Html(render(&Preview {
+        short_url: &BaseUrl::from_headers(&state.config, &headers).short_url(domain.as_deref(), &row.get::<String, _>("token")),
+        original_url: &row.get::<String, _>("original_url"),
+        title: title.as_deref(),
+        description: description.as_deref(),
+        image_url: image_url.as_deref(),
+        created: &created,
+    })))
+}
+
+// Describes a link for chat apps and CMSs that support oEmbed discovery, without following it
+#[utoipa::path(
+    get,
+    path = "/{token}/oembed",
+    tag = "redirects",
+    params(("token" = String, Path, description = "Short URL token"), OEmbedQuery),
+    responses(
+        (status = 200, description = "oEmbed link response", body = OEmbedResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+        (status = 410, description = "URL deleted, expired or disabled", body = ErrorResponse),
+        (status = 451, description = "Taken down for legal reasons", body = ErrorResponse),
+        (status = 501, description = "Format other than json requested"),
+    )
+)]
+pub async fn get_oembed(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<OEmbedQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if query.format.as_deref().is_some_and(|format| format != "json") {
+        return Ok(StatusCode::NOT_IMPLEMENTED.into_response());
+    }
+    let row = find_live(&state, &token, &headers).await?;
+    let domain: Option<String> = row.get("domain");
+    let oembed = OEmbedResponse {
+        version: "1.0",
+        kind: "link",
+        title: row.get("title"),
+        provider_name: PROVIDER_NAME,
+        provider_url: BaseUrl::from_headers(&state.config, &headers).short_url(domain.as_deref(), ""),
+        cache_age: OEMBED_CACHE_AGE_SECS,
+    };
+    let cache_control = format!("public, max-age={}", OEMBED_CACHE_AGE_SECS);
+    Ok(([(header::CACHE_CONTROL, cache_control)], Json(oembed)).into_response())
+}
+
+// The link behind a token or alias if it would redirect on this host, checked like the redirect
+async fn find_live(state: &AppState, token: &str, headers: &HeaderMap) -> Result<AnyRow, AppError> {
+    let mut row = find_preview(&state.db, token).await?;
+    if let (None, Some(folded)) = (&row, folded_token(&state.config, token)) {
+        row = find_preview(&state.db, &folded).await?;
+    }
+    let row = row.ok_or(AppError::UrlNotFound)?;
+
+    let domain: Option<String> = row.get("domain");
+    if domain != state.domains.resolve(&state.db, headers).await? {
+        return Err(AppError::UrlNotFound);
+    }
+    if storage::get_opt_ts(&row, "starts_at").is_some_and(|starts_at| chrono::Utc::now() < starts_at) {
+        return Err(AppError::UrlNotFound);
+    }
+    if row.get::<Option<String>, _>("deleted_at").is_some() {
+        return Err(AppError::UrlDeleted);
+    }
+    if storage::get_opt_ts(&row, "expires_at").is_some_and(|expires_at| chrono::Utc::now() > expires_at) {
+        return Err(AppError::UrlExpired);
+    }
+    if let Some(takedown) = row.get::<Option<String>, _>("takedown").as_deref().and_then(Takedown::parse) {
+        return Err(takedown.error());
+    }
+    if row.get::<i64, _>("is_active") == 0 {
+        return Err(AppError::UrlInactive);
+    }
+    Ok(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_escapes_link_fields() {
+        let page = render(&Preview {
+            short_url: "https://qurl.example/abc123",
+            original_url: "https://example.com/?a=1&b=<script>",
+            title: Some("\"Quarterly\" <report>"),
+            description: Some("Numbers & <b>more</b>"),
+            image_url: Some("https://example.com/cover.png?a=1&b=2"),
+            created: "2025-08-21",
+        });
+
+        assert!(page.contains("https://example.com/?a=1&amp;b=&lt;script&gt;"));
+        assert!(page.contains("<h1>&quot;Quarterly&quot; &lt;report&gt;</h1>"));
+        assert!(page.contains(r#"href="https://qurl.example/abc123""#));
+        assert!(!page.contains("<script>"));
+
+        assert!(page.contains(r#"<meta property="og:title" content="&quot;Quarterly&quot; &lt;report&gt;">"#));
+        assert!(page.contains(r#"<meta property="og:description" content="Numbers &amp; &lt;b&gt;more&lt;/b&gt;">"#));
+        assert!(page.contains(r#"<meta property="og:image" content="https://example.com/cover.png?a=1&amp;b=2">"#));
+        assert!(page.contains(r#"<meta name="twitter:card" content="summary_large_image">"#));
+        assert!(page.contains(r#"href="https://qurl.example/abc123/oembed""#));
+
+        // Untitled links are named by their short URL, the destination stays out of the card
+        let card = card_tags(&Preview {
+            short_url: "https://qurl.example/abc123",
+            original_url: "https://example.com/secret",
+            title: None,
+            description: None,
+            image_url: None,
+            created: "2025-08-21",
+        });
+        assert!(card.contains(r#"<meta property="og:title" content="https://qurl.example/abc123">"#));
+        assert!(card.contains(r#"<meta name="twitter:card" content="summary">"#));
+        assert!(!card.contains("example.com"));
+    }
+}
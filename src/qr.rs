@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use qrcode::{render::svg, Color, EcLevel, QrCode};
+use sqlx::Row;
+use std::sync::Arc;
+
+use crate::base_url::BaseUrl;
+use crate::models::{QrErrorCorrection, QrFormat, QrQuery};
+use crate::{AppError, AppState};
+
+const DEFAULT_SIZE: u32 = 256;
+const MIN_SIZE: u32 = 64;
+const MAX_SIZE: u32 = 2048;
+// The QR spec asks for a 4 module light border around the code
+const QUIET_ZONE: usize = 4;
+
+fn ec_level(ec: QrErrorCorrection) -> EcLevel {
+    match ec {
+        QrErrorCorrection::L => EcLevel::L,
+        QrErrorCorrection::M => EcLevel::M,
+        QrErrorCorrection::Q => EcLevel::Q,
+        QrErrorCorrection::H => EcLevel::H,
+    }
+}
+
+// Renders an 8-bit grayscale PNG at least `size` pixels wide, using whole pixels per module
+pub fn render_png(code: &QrCode, size: u32) -> Result<Vec<u8>, png::EncodingError> {
+    let modules = code.width() + 2 * QUIET_ZONE;
+    let scale = (size as usize).div_ceil(modules).max(1);
+    let width = modules * scale;
+    let colors = code.to_colors();
+
+    let mut pixels = vec![255u8; width * width];
+    for (i, color) in colors.iter().enumerate() {
+        if *color == Color::Light {
+            continue;
+        }
+        let x0 = (i % code.width() + QUIET_ZONE) * scale;
+        let y0 = (i / code.width() + QUIET_ZONE) * scale;
+        for y in y0..y0 + scale {
+            pixels[y * width + x0..y * width + x0 + scale].fill(0);
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width as u32, width as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels)?;
+    Ok(out)
+}
+
+pub fn render_svg(code: &QrCode, size: u32) -> String {
+    code.render::<svg::Color>()
+        .min_dimensions(size, size)
+        .quiet_zone(true)
+        .build()
+}
+
+#[utoipa::path(
+    get,
+    path = "/urls/{token}/qr",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token"), QrQuery),
+    responses(
+        (status = 200, description = "QR code encoding the short URL", content_type = ["image/png", "image/svg+xml"]),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_qr_code(
+    Path(token): Path<String>,
+    Query(query): Query<QrQuery>,
+    State(state): State<Arc<AppState>>,
+    base: BaseUrl,
+) -> Result<impl IntoResponse, AppError> {
+    let size = query.size.unwrap_or(DEFAULT_SIZE);
+    if !(MIN_SIZE..=MAX_SIZE).contains(&size) {
+        return Err(AppError::BadRequest(format!(
+            "size must be between {} and {}",
+            MIN_SIZE, MAX_SIZE
+        )));
+    }
+
+    let domain: Option<String> = sqlx::query("SELECT domain FROM urls WHERE token = $1 AND deleted_at IS NULL")
+        .bind(&token)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .map(|row| row.get("domain"))
+        .ok_or(AppError::UrlNotFound)?;
+
+    let short_url = base.short_url(domain.as_deref(), &token);
+    let code = QrCode::with_error_correction_level(short_url.as_bytes(), ec_level(query.ec))
+        .map_err(|e| AppError::BadRequest(format!("Cannot encode QR code: {}", e)))?;
+
+    let response = match query.format {
+        QrFormat::Png => {
+            let png = render_png(&code, size)
+                .map_err(|e| AppError::InternalError(format!("PNG encoding failed: {}", e)))?;
+            ([(header::CONTENT_TYPE, "image/png")], png).into_response()
+        }
+        QrFormat::Svg => {
+            ([(header::CONTENT_TYPE, "image/svg+xml")], render_svg(&code, size)).into_response()
+        }
+    };
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_is_at_least_requested_size() {
+        let code = QrCode::new(b"http://localhost:3000/abc123").unwrap();
+        let png = render_png(&code, 256).unwrap();
+
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let width = u32::from_be_bytes(png[16..20].try_into().unwrap());
+        assert!(width >= 256);
+    }
+}
@@ -0,0 +1,177 @@
+use axum::{extract::State, response::{IntoResponse, Json}};
+use chrono::{NaiveTime, TimeZone, Utc};
+use sqlx::{AnyConnection, Row};
+use std::sync::Arc;
+
+use crate::auth::Caller;
+use crate::config::Config;
+use crate::models::{QuotaUsage, UsageResponse};
+use crate::storage;
+use crate::{AppError, AppState};
+
+struct Counts {
+    links: i64,
+    links_today: i64,
+    custom_aliases: i64,
+}
+
+// Live links and custom aliases free their slot when deleted. The daily count starts at
+// midnight UTC and includes links deleted since, so creating and deleting does not reset it.
+async fn counts(conn: &mut AnyConnection, user_id: &str) -> Result<Counts, AppError> {
+    let today = Utc.from_utc_datetime(&Utc::now().date_naive().and_time(NaiveTime::MIN));
+    let row = sqlx::query(
+        r#"
+        SELECT
+            COUNT(CASE WHEN deleted_at IS NULL THEN 1 END) AS links,
+            COUNT(CASE WHEN created_at >= $2 THEN 1 END) AS links_today,
+            COUNT(CASE WHEN deleted_at IS NULL AND custom_alias = 1 THEN 1 END) AS custom_aliases
+        FROM urls WHERE user_id = $1
+        "#
+    )
+    .bind(user_id)
+    .bind(storage::ts(today))
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Counts {
+        links: row.get("links"),
+        links_today: row.get("links_today"),
+        custom_aliases: row.get("custom_aliases"),
+    })
+}
+
+// 0 means unlimited
+fn limit(configured: u64) -> Option<i64> {
+    (configured > 0).then(|| i64::try_from(configured).unwrap_or(i64::MAX))
+}
+
+// Quotas only apply to user accounts, API keys and the admin key act for the whole service.
+// Runs in the transaction of the insert, so links created earlier in a batch are counted.
+pub async fn check(conn: &mut AnyConnection, config: &Config, caller: &Caller, custom_alias: bool) -> Result<(), AppError> {
+    let Some(user_id) = caller.user_id() else {
+        return Ok(());
+    };
+    let limited = [config.max_links_per_user, config.max_links_per_user_per_day, config.max_custom_aliases_per_user]
+        .iter()
+        .any(|&configured| configured > 0);
+    if !limited {
+        return Ok(());
+    }
+    // Locks the user's row until the insert commits, so concurrent requests of one account count
+    // one after the other instead of all passing on the same count. A no-op write works on both
+    // backends: a row lock on Postgres, the write lock on SQLite.
+    sqlx::query("UPDATE users SET id = id WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let counts = counts(conn, user_id).await?;
+    let exceeded = |used: i64, configured: u64| limit(configured).is_some_and(|limit| used >= limit);
+
+    if exceeded(counts.links, config.max_links_per_user) {
+        return Err(AppError::QuotaExceeded(format!(
+            "Link quota of {} reached, delete links to create new ones",
+            config.max_links_per_user
+        )));
+    }
+    if exceeded(counts.links_today, config.max_links_per_user_per_day) {
+        return Err(AppError::QuotaExceeded(format!(
+            "Daily quota of {} new links reached, try again tomorrow",
+            config.max_links_per_user_per_day
+        )));
+    }
+    if custom_alias && exceeded(counts.custom_aliases, config.max_custom_aliases_per_user) {
+        return Err(AppError::QuotaExceeded(format!(
+            "Custom alias quota of {} reached, leave out custom_alias for a generated token",
+            config.max_custom_aliases_per_user
+        )));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/me/usage",
+    tag = "users",
+    responses(
+        (status = 200, description = "Consumption of each quota", body = UsageResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 403, description = "Not a user account", body = ErrorResponse),
+    ),
+    security(("user_token" = []))
+)]
+pub async fn get_usage(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = caller
+        .user_id()
+        .ok_or_else(|| AppError::Forbidden("Only user accounts have quotas".into()))?;
+    let mut conn = state
+        .db
+        .acquire()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let counts = counts(&mut conn, user_id).await?;
+    let config = &state.config;
+
+    Ok(Json(UsageResponse {
+        links: QuotaUsage { used: counts.links, limit: limit(config.max_links_per_user) },
+        links_today: QuotaUsage { used: counts.links_today, limit: limit(config.max_links_per_user_per_day) },
+        custom_aliases: QuotaUsage { used: counts.custom_aliases, limit: limit(config.max_custom_aliases_per_user) },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_counts_users_links() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let mut conn = db.acquire().await.unwrap();
+            let now = storage::ts(Utc::now());
+            let yesterday = storage::ts(Utc::now() - chrono::Duration::days(1));
+            sqlx::query("INSERT INTO users (id, email, password_hash, created_at) VALUES ('u1', 'u1', 'x', $1)")
+                .bind(&now)
+                .execute(&mut *conn)
+                .await
+                .unwrap();
+            // One link from yesterday, one custom alias from today and one deleted today
+            for (id, created_at, custom_alias, deleted_at) in [
+                ("a", &yesterday, 0, None),
+                ("b", &now, 1, None),
+                ("c", &now, 1, Some(&now)),
+            ] {
+                sqlx::query(
+                    r#"
+                    INSERT INTO urls (id, token, original_url, created_at, expires_at, click_count, user_id, custom_alias, deleted_at)
+                    VALUES ($1, $1, 'https://example.com', $2, NULL, 0, 'u1', $3, $4)
+                    "#
+                )
+                .bind(id)
+                .bind(created_at)
+                .bind(custom_alias)
+                .bind(deleted_at)
+                .execute(&mut *conn)
+                .await
+                .unwrap();
+            }
+
+            let user = Caller::User("u1".into());
+            let config = |links, per_day, aliases| Config {
+                max_links_per_user: links,
+                max_links_per_user_per_day: per_day,
+                max_custom_aliases_per_user: aliases,
+                ..Config::default()
+            };
+            assert!(check(&mut conn, &config(3, 3, 2), &user, true).await.is_ok());
+            assert!(matches!(check(&mut conn, &config(2, 0, 0), &user, false).await, Err(AppError::QuotaExceeded(_))));
+            assert!(matches!(check(&mut conn, &config(0, 2, 0), &user, false).await, Err(AppError::QuotaExceeded(_))));
+            assert!(check(&mut conn, &config(0, 0, 1), &user, false).await.is_ok());
+            assert!(matches!(check(&mut conn, &config(0, 0, 1), &user, true).await, Err(AppError::QuotaExceeded(_))));
+            assert!(check(&mut conn, &config(1, 1, 1), &Caller::Admin, true).await.is_ok());
+        }
+    }
+}
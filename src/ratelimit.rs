@@ -0,0 +1,185 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::auth::{self, Caller};
+use crate::{AppError, AppState};
+
+// Above this many tracked clients, buckets that refilled completely are dropped
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset_secs: u64,
+}
+
+// Token bucket per client: holds up to `per_minute` requests and refills continuously
+pub struct RateLimiter {
+    per_minute: u32,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.per_minute > 0
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+
+    pub fn check(&self, key: &str) -> Decision {
+        self.check_at(key, Instant::now())
+    }
+
+    pub fn check_at(&self, key: &str, now: Instant) -> Decision {
+        let capacity = self.per_minute as f64;
+        let refill = self.refill_per_sec();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * refill < capacity
+            });
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill).min(capacity);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        // When blocked, reset is the wait for the next token, otherwise the wait until full
+        let missing = if allowed { capacity - bucket.tokens } else { 1.0 - bucket.tokens };
+        Decision {
+            allowed,
+            limit: self.per_minute,
+            remaining: bucket.tokens.floor() as u32,
+            reset_secs: (missing / refill).ceil() as u64,
+        }
+    }
+}
+
+// Callers with a working credential share one bucket wherever they connect from, everyone else,
+// including a bearer value that resolves to nothing, is keyed by IP
+fn client_key(req: &Request, caller: Option<&Caller>) -> String {
+    match caller {
+        Some(Caller::Admin) => return "admin".into(),
+        Some(Caller::ApiKey(id)) => return format!("key:{}", id),
+        Some(Caller::User(id)) => return format!("user:{}", id),
+        Some(Caller::Anonymous) | None => {}
+    }
+
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| format!("ip:{}", addr.ip()))
+        .unwrap_or_else(|| "ip:unknown".into())
+}
+
+fn set_headers(headers: &mut HeaderMap, decision: &Decision) {
+    headers.insert("x-ratelimit-limit", HeaderValue::from(decision.limit));
+    headers.insert("x-ratelimit-remaining", HeaderValue::from(decision.remaining));
+    headers.insert("x-ratelimit-reset", HeaderValue::from(decision.reset_secs));
+}
+
+pub async fn rate_limit(
+    State((limiter, state)): State<(Arc<RateLimiter>, Arc<AppState>)>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    if !limiter.is_enabled() {
+        return next.run(req).await;
+    }
+
+    // Resolved once here, the auth layers inside reuse it. A failed credential is left for them to refuse.
+    let caller = match auth::bearer_token(req.headers()) {
+        Some(key) => auth::resolve_caller(&state, Some(key)).await.ok(),
+        None => None,
+    };
+    let decision = limiter.check(&client_key(&req, caller.as_ref().map(|(caller, _)| caller)));
+    if let Some((caller, permissions)) = caller {
+        req.extensions_mut().insert(caller);
+        req.extensions_mut().insert(permissions);
+    }
+    let mut response = if decision.allowed {
+        next.run(req).await
+    } else {
+        let mut response = AppError::TooManyRequests("Rate limit exceeded".into()).into_response();
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(decision.reset_secs));
+        response
+    };
+
+    set_headers(response.headers_mut(), &decision);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_bucket_blocks_then_refills() {
+        let limiter = RateLimiter::new(60);
+        let start = Instant::now();
+
+        for _ in 0..60 {
+            assert!(limiter.check_at("ip:1", start).allowed);
+        }
+        let blocked = limiter.check_at("ip:1", start);
+        assert!(!blocked.allowed);
+        assert_eq!(blocked.reset_secs, 1);
+
+        // Other clients have their own bucket
+        assert!(limiter.check_at("ip:2", start).allowed);
+
+        // 60/min refills one token per second
+        let later = limiter.check_at("ip:1", start + Duration::from_secs(1));
+        assert!(later.allowed);
+        assert_eq!(later.remaining, 0);
+    }
+
+    #[test]
+    fn test_unresolved_bearers_are_keyed_by_ip() {
+        let mut req = Request::builder()
+            .header(header::AUTHORIZATION, "Bearer made-up")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        req.extensions_mut().insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 4000))));
+
+        assert_eq!(client_key(&req, None), "ip:192.0.2.1");
+        assert_eq!(client_key(&req, Some(&Caller::Anonymous)), "ip:192.0.2.1");
+        assert_eq!(client_key(&req, Some(&Caller::ApiKey("k1".into()))), "key:k1");
+        assert_eq!(client_key(&req, Some(&Caller::User("u1".into()))), "user:u1");
+    }
+}
@@ -0,0 +1,146 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Json},
+};
+use sqlx::Row;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::models::{CountEntry, ReferrerStatsResponse, StatsQuery};
+use crate::stats::{DEFAULT_STATS_DAYS, MAX_STATS_DAYS};
+use crate::{find_url_id, storage, AppError, AppState};
+
+const DIRECT: &str = "direct";
+const EMAIL: &str = "email";
+const OTHER: &str = "other";
+
+// Webmail front ends and mail apps, clicks from newsletters mostly arrive through one of these
+const EMAIL_HOSTS: &[&str] = &[
+    "mail.google.com",
+    "com.google.android.gm",
+    "outlook.live.com",
+    "outlook.office.com",
+    "outlook.office365.com",
+    "mail.yahoo.com",
+    "mail.aol.com",
+    "mail.proton.me",
+    "app.fastmail.com",
+];
+
+// Link shorteners and alternative hostnames of the same network
+const ALIASES: &[(&str, &str)] = &[
+    ("t.co", "twitter.com"),
+    ("x.com", "twitter.com"),
+    ("lnkd.in", "linkedin.com"),
+    ("youtu.be", "youtube.com"),
+    ("old.reddit.com", "reddit.com"),
+    ("out.reddit.com", "reddit.com"),
+    ("com.reddit.frontpage", "reddit.com"),
+];
+
+// Subdomains that only mark the mobile site or an outbound link redirector
+const STRIPPED_PREFIXES: &[&str] = &["www.", "m.", "mobile.", "l.", "lm."];
+
+// The channel of a Referer: "direct" without one, "email" for webmail and mail apps, otherwise
+// the referring domain without www. and similar prefixes, e.g. "twitter.com" for t.co
+pub fn channel(referrer: Option<&str>) -> String {
+    let Some(referrer) = referrer.map(str::trim).filter(|referrer| !referrer.is_empty()) else {
+        return DIRECT.to_string();
+    };
+    // android-app://com.google.android.gm/ names the app in the host
+    let Some(host) = url::Url::parse(referrer)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.trim_end_matches('.').to_ascii_lowercase()))
+    else {
+        return OTHER.to_string();
+    };
+
+    if EMAIL_HOSTS.contains(&host.as_str()) || host.starts_with("webmail.") {
+        return EMAIL.to_string();
+    }
+    let mut domain = host.as_str();
+    while let Some(rest) = STRIPPED_PREFIXES.iter().find_map(|prefix| domain.strip_prefix(prefix)) {
+        if !rest.contains('.') {
+            break;
+        }
+        domain = rest;
+    }
+    ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == domain)
+        .map_or(domain, |(_, canonical)| canonical)
+        .to_string()
+}
+
+#[utoipa::path(
+    get,
+    path = "/urls/{token}/stats/referrers",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token"), StatsQuery),
+    responses(
+        (status = 200, description = "Clicks per referring domain, most first", body = ReferrerStatsResponse),
+        (status = 400, description = "Invalid number of days", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_referrer_stats(
+    Path(token): Path<String>,
+    Query(query): Query<StatsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let days = query.days.unwrap_or(DEFAULT_STATS_DAYS);
+    if !(1..=MAX_STATS_DAYS).contains(&days) {
+        return Err(AppError::BadRequest(format!(
+            "days must be between 1 and {}",
+            MAX_STATS_DAYS
+        )));
+    }
+
+    let url_id = find_url_id(state.links.reader(), &token).await?;
+    let since = storage::now() - chrono::Duration::days(days);
+    // Full referrers are grouped by the database first, there are far fewer of them than clicks
+    let rows = sqlx::query(
+        "SELECT referrer, COUNT(*) AS clicks FROM clicks WHERE url_id = $1 AND clicked_at >= $2 GROUP BY referrer"
+    )
+    .bind(&url_id)
+    .bind(storage::ts(since))
+    .fetch_all(state.links.reader())
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let mut channels: HashMap<String, i64> = HashMap::new();
+    for row in &rows {
+        let referrer: Option<String> = row.get("referrer");
+        *channels.entry(channel(referrer.as_deref())).or_insert(0) += row.get::<i64, _>("clicks");
+    }
+    let total_clicks = channels.values().sum();
+    let mut domains: Vec<CountEntry> = channels
+        .into_iter()
+        .map(|(value, count)| CountEntry { value, count })
+        .collect();
+    domains.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+
+    Ok(Json(ReferrerStatsResponse { token, days, total_clicks, domains }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_groups_referrers() {
+        assert_eq!(channel(None), "direct");
+        assert_eq!(channel(Some("  ")), "direct");
+        assert_eq!(channel(Some("https://t.co/abc123")), "twitter.com");
+        assert_eq!(channel(Some("https://www.Twitter.com/home")), "twitter.com");
+        assert_eq!(channel(Some("https://l.facebook.com/l.php?u=x")), "facebook.com");
+        assert_eq!(channel(Some("https://m.reddit.com/r/rust")), "reddit.com");
+        assert_eq!(channel(Some("https://news.ycombinator.com/item?id=1")), "news.ycombinator.com");
+        assert_eq!(channel(Some("https://mail.google.com/mail/u/0/")), "email");
+        assert_eq!(channel(Some("android-app://com.google.android.gm/")), "email");
+        assert_eq!(channel(Some("https://webmail.example.org/")), "email");
+        // A bare "m.com" keeps its prefix rather than turning into "com"
+        assert_eq!(channel(Some("https://m.com/")), "m.com");
+        assert_eq!(channel(Some("not a url")), "other");
+    }
+}
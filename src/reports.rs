@@ -0,0 +1,344 @@
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{any::AnyRow, Row};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::audit::{self, AuditAction};
+use crate::auth::Caller;
+use crate::models::{
+    AbuseReport, CreateReportRequest, CreateReportResponse, ModerationResult, ReportListQuery,
+    ResolveReportsRequest,
+};
+use crate::storage::{self, SqlBuilder};
+use crate::{find_url_id, stats, AppError, AppState};
+
+const MAX_DETAILS_LENGTH: usize = 2000;
+const MAX_LISTED_REPORTS: i64 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportReason {
+    Phishing,
+    Malware,
+    Spam,
+    Illegal,
+    Other,
+}
+
+impl ReportReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReportReason::Phishing => "phishing",
+            ReportReason::Malware => "malware",
+            ReportReason::Spam => "spam",
+            ReportReason::Illegal => "illegal",
+            ReportReason::Other => "other",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "phishing" => Some(ReportReason::Phishing),
+            "malware" => Some(ReportReason::Malware),
+            "spam" => Some(ReportReason::Spam),
+            "illegal" => Some(ReportReason::Illegal),
+            "other" => Some(ReportReason::Other),
+            _ => None,
+        }
+    }
+}
+
+// How a moderator took a link down, which decides what visitors get instead of the redirect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Takedown {
+    Abuse,
+    Legal,
+}
+
+impl Takedown {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Takedown::Abuse => "abuse",
+            Takedown::Legal => "legal",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "abuse" => Some(Takedown::Abuse),
+            "legal" => Some(Takedown::Legal),
+            _ => None,
+        }
+    }
+
+    pub fn error(self) -> AppError {
+        match self {
+            Takedown::Abuse => AppError::UrlDisabled,
+            Takedown::Legal => AppError::LegalTakedown,
+        }
+    }
+}
+
+// "disable" answers 410, "legal" 451, "warn" keeps the link online behind a warning page and
+// "clear" keeps (or puts back) the link online as it was
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    Disable,
+    Legal,
+    Warn,
+    Clear,
+}
+
+impl ModerationAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ModerationAction::Disable => "disable",
+            ModerationAction::Legal => "legal",
+            ModerationAction::Warn => "warn",
+            ModerationAction::Clear => "clear",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "disable" => Some(ModerationAction::Disable),
+            "legal" => Some(ModerationAction::Legal),
+            "warn" => Some(ModerationAction::Warn),
+            "clear" => Some(ModerationAction::Clear),
+            _ => None,
+        }
+    }
+
+    fn takedown(self) -> Option<Takedown> {
+        match self {
+            ModerationAction::Disable => Some(Takedown::Abuse),
+            ModerationAction::Legal => Some(Takedown::Legal),
+            ModerationAction::Warn | ModerationAction::Clear => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+    #[default]
+    Open,
+    Resolved,
+    All,
+}
+
+fn report_from_row(row: &AnyRow) -> AbuseReport {
+    AbuseReport {
+        id: row.get("id"),
+        token: row.get("token"),
+        original_url: row.get("original_url"),
+        reason: ReportReason::parse(&row.get::<String, _>("reason")).unwrap_or(ReportReason::Other),
+        details: row.get("details"),
+        created_at: storage::get_ts(row, "created_at"),
+        resolved_at: storage::get_opt_ts(row, "resolved_at"),
+        resolution: row.get::<Option<String>, _>("resolution").as_deref().and_then(ModerationAction::parse),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/report/{token}",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    request_body = CreateReportRequest,
+    responses(
+        (status = 202, description = "Report queued for moderation", body = CreateReportResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+)]
+pub async fn create_report(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(payload): Json<CreateReportRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let details = payload.details.map(|details| details.trim().to_string()).filter(|details| !details.is_empty());
+    if details.as_ref().is_some_and(|details| details.len() > MAX_DETAILS_LENGTH) {
+        return Err(AppError::BadRequest(format!("details must be at most {} bytes", MAX_DETAILS_LENGTH)));
+    }
+
+    let url_id = find_url_id(&state.db, &token).await?;
+    let ip = if state.config.privacy_mode { stats::anonymize_ip(addr.ip()) } else { addr.ip() };
+    let reporter = stats::hash_ip(&state.config.ip_hash_salt, ip);
+
+    // Reporting the same link again while the first report is open just returns it
+    let existing = sqlx::query("SELECT id FROM abuse_reports WHERE url_id = $1 AND reporter = $2 AND resolved_at IS NULL")
+        .bind(&url_id)
+        .bind(&reporter)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if let Some(row) = existing {
+        return Ok((StatusCode::ACCEPTED, Json(CreateReportResponse { id: row.get("id") })));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO abuse_reports (id, url_id, reason, details, reporter, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#
+    )
+    .bind(&id)
+    .bind(&url_id)
+    .bind(payload.reason.as_str())
+    .bind(details)
+    .bind(reporter)
+    .bind(storage::ts(storage::now()))
+    .execute(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok((StatusCode::ACCEPTED, Json(CreateReportResponse { id })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/reports",
+    tag = "admin",
+    params(ReportListQuery),
+    responses(
+        (status = 200, description = "Reports, oldest first", body = [AbuseReport]),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn list_reports(
+    Query(query): Query<ReportListQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut list = SqlBuilder::new(
+        r#"
+        SELECT r.id, r.reason, r.details, r.created_at, r.resolved_at, r.resolution, u.token, u.original_url
+        FROM abuse_reports r JOIN urls u ON u.id = r.url_id
+        "#
+    );
+    match query.status {
+        ReportStatus::Open => {
+            list.push(" WHERE r.resolved_at IS NULL");
+        }
+        ReportStatus::Resolved => {
+            list.push(" WHERE r.resolved_at IS NOT NULL");
+        }
+        ReportStatus::All => {}
+    }
+    list.push(" ORDER BY r.created_at, r.id LIMIT ").push_bind(MAX_LISTED_REPORTS);
+
+    let reports = list
+        .build()
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .iter()
+        .map(report_from_row)
+        .collect::<Vec<_>>();
+
+    Ok(Json(reports))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/reports/{token}/resolve",
+    tag = "admin",
+    params(("token" = String, Path, description = "Short URL token")),
+    request_body = ResolveReportsRequest,
+    responses(
+        (status = 200, description = "Decision applied to the link and its open reports", body = ModerationResult),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn resolve_reports(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<ResolveReportsRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let takedown = payload.action.takedown();
+    // Each decision replaces the previous one, a takedown or clear also lifts a warning
+    let warned = payload.action == ModerationAction::Warn;
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Deleted links are included, a takedown has to survive the owner restoring the link
+    let row = sqlx::query("SELECT id, takedown, warned FROM urls WHERE token = $1")
+        .bind(&token)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or(AppError::UrlNotFound)?;
+    let url_id: String = row.get("id");
+    let old = row.get::<Option<String>, _>("takedown").as_deref().and_then(Takedown::parse);
+    let was_warned = row.get::<i64, _>("warned") != 0;
+
+    sqlx::query("UPDATE urls SET takedown = $1, warned = $2, updated_at = $3 WHERE id = $4")
+        .bind(takedown.map(Takedown::as_str))
+        .bind(i64::from(warned))
+        .bind(storage::ts(storage::now()))
+        .bind(&url_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let resolved = sqlx::query("UPDATE abuse_reports SET resolved_at = $1, resolution = $2 WHERE url_id = $3 AND resolved_at IS NULL")
+        .bind(storage::ts(storage::now()))
+        .bind(payload.action.as_str())
+        .bind(&url_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .rows_affected();
+
+    if old != takedown || was_warned != warned {
+        let changes = audit::diff(
+            &serde_json::json!({ "takedown": old, "warned": was_warned }),
+            &serde_json::json!({ "takedown": takedown, "warned": warned }),
+        );
+        audit::record(&mut tx, &url_id, &token, AuditAction::Update, &caller, changes).await?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    state.links.invalidate(&token).await;
+
+    Ok(Json(ModerationResult { token, takedown, warned, resolved }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_actions_map_to_takedowns() {
+        assert_eq!(ModerationAction::Disable.takedown(), Some(Takedown::Abuse));
+        assert_eq!(ModerationAction::Legal.takedown(), Some(Takedown::Legal));
+        assert_eq!(ModerationAction::Warn.takedown(), None);
+        assert_eq!(ModerationAction::Clear.takedown(), None);
+        assert!(matches!(Takedown::Legal.error(), AppError::LegalTakedown));
+        for action in [ModerationAction::Disable, ModerationAction::Legal, ModerationAction::Warn, ModerationAction::Clear] {
+            assert_eq!(ModerationAction::parse(action.as_str()), Some(action));
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+const TRACEPARENT_HEADER: &str = "traceparent";
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+// Identifies the request being handled in log lines and error bodies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestContext {
+    pub request_id: String,
+    // W3C trace id from an incoming traceparent header
+    pub trace_id: Option<String>,
+}
+
+tokio::task_local! {
+    static CONTEXT: RequestContext;
+}
+
+// Context of the request whose handler is running, None in background tasks
+pub fn current() -> Option<RequestContext> {
+    CONTEXT.try_with(RequestContext::clone).ok()
+}
+
+fn is_hex(value: &str, length: usize) -> bool {
+    value.len() == length && value.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+// version-traceid-parentid-flags, e.g. 00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01.
+// All-zero ids and version ff are invalid, later versions may append fields.
+fn parse_traceparent(value: &str) -> Option<String> {
+    let mut parts = value.trim().split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let parent_id = parts.next()?;
+    let flags = parts.next()?;
+    let valid = is_hex(version, 2)
+        && version != "ff"
+        && (version != "00" || parts.next().is_none())
+        && is_hex(trace_id, 32)
+        && trace_id.bytes().any(|b| b != b'0')
+        && is_hex(parent_id, 16)
+        && parent_id.bytes().any(|b| b != b'0')
+        && is_hex(flags, 2);
+    valid.then(|| trace_id.to_string())
+}
+
+// Client supplied ids are kept when they are short and printable, so they are safe to log
+fn valid_request_id(value: &str) -> bool {
+    !value.is_empty() && value.len() <= MAX_REQUEST_ID_LENGTH && value.bytes().all(|b| b.is_ascii_graphic())
+}
+
+// Takes X-Request-Id from the client (or the trace id of its traceparent) and otherwise makes
+// one up, runs the request with it in scope and echoes it on the response
+pub async fn propagate(req: Request, next: Next) -> Response {
+    let headers = req.headers();
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let trace_id = header(TRACEPARENT_HEADER).and_then(parse_traceparent);
+    let request_id = header(REQUEST_ID_HEADER)
+        .filter(|id| valid_request_id(id))
+        .map(String::from)
+        .or_else(|| trace_id.clone())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let context = RequestContext { request_id: request_id.clone(), trace_id };
+    let mut response = CONTEXT.scope(context, next.run(req)).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_traceparent() {
+        assert_eq!(
+            parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").as_deref(),
+            Some("4bf92f3577b34da6a3ce929d0e0e4736")
+        );
+        assert!(parse_traceparent("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_some());
+        assert!(parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_none());
+        assert!(parse_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none());
+        assert!(parse_traceparent("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").is_none());
+        assert!(parse_traceparent("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01").is_none());
+        assert!(!valid_request_id("has space"));
+        assert!(valid_request_id("req-1234"));
+    }
+}
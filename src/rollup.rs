@@ -0,0 +1,218 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sqlx::{AnyPool, Row};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::models::DailyClicks;
+use crate::storage::{self, SqlBuilder};
+use crate::{scheduler, AppError, AppState};
+
+// Clicks reach the database in batches, a day is only rolled up once it has been over this long
+const SETTLE_MINUTES: i64 = 60;
+
+// Which links a daily series adds up, both bind the id as $1
+#[derive(Debug, Clone, Copy)]
+pub enum Links<'a> {
+    Url(&'a str),
+    Campaign(&'a str),
+}
+
+impl<'a> Links<'a> {
+    fn condition(self) -> &'static str {
+        match self {
+            Links::Url(_) => "url_id = $1",
+            Links::Campaign(_) => "url_id IN (SELECT id FROM urls WHERE campaign_id = $1 AND deleted_at IS NULL)",
+        }
+    }
+
+    fn id(self) -> &'a str {
+        match self {
+            Links::Url(id) | Links::Campaign(id) => id,
+        }
+    }
+}
+
+// The first day without aggregates, None before anything was rolled up. Earlier days are
+// answered from click_stats_daily, this one and later from the click events.
+pub async fn first_open_day(db: &AnyPool) -> Result<Option<NaiveDate>, sqlx::Error> {
+    let last: Option<String> = sqlx::query("SELECT MAX(date) AS date FROM click_stats_daily")
+        .fetch_one(db)
+        .await?
+        .get("date");
+    Ok(last
+        .and_then(|date| date.parse::<NaiveDate>().ok())
+        .map(|date| date + Duration::days(1)))
+}
+
+// Aggregates the days after the last rolled up one that are over, returns how many link-days
+// were added. Instances running it at the same time skip what the other one inserted.
+pub async fn roll_up(db: &AnyPool, until: NaiveDate) -> Result<u64, sqlx::Error> {
+    let since = first_open_day(db).await?.map(storage::day_start).unwrap_or_default();
+    Ok(sqlx::query(
+        r#"
+        INSERT INTO click_stats_daily (url_id, date, clicks, unique_visitors)
+        SELECT url_id, substr(clicked_at, 1, 10), COUNT(*), COUNT(DISTINCT ip_hash) FROM clicks
+        WHERE clicked_at >= $1 AND clicked_at < $2
+        GROUP BY url_id, substr(clicked_at, 1, 10)
+        ON CONFLICT (url_id, date) DO NOTHING
+        "#
+    )
+    .bind(since)
+    .bind(storage::day_start(until))
+    .execute(db)
+    .await?
+    .rows_affected())
+}
+
+pub async fn run_rollup(db: &AnyPool) -> Result<u64, sqlx::Error> {
+    let settled = storage::now() - Duration::minutes(SETTLE_MINUTES);
+    roll_up(db, settled.date_naive()).await
+}
+
+// Clicks per day from `from` to `to` inclusive, days without clicks are left out
+pub async fn daily_clicks(
+    db: &AnyPool,
+    links: Links<'_>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<DailyClicks>, AppError> {
+    let database_error = |e: sqlx::Error| AppError::DatabaseError(e.to_string());
+    let open = first_open_day(db).await.map_err(database_error)?;
+    let mut days = BTreeMap::new();
+
+    if let Some(open) = open.filter(|open| *open > from) {
+        // SUM is NUMERIC on Postgres, the cast keeps it an integer on both backends
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT date, CAST(SUM(clicks) AS BIGINT) AS clicks FROM click_stats_daily
+            WHERE {} AND date >= $2 AND date < $3 AND date <= $4
+            GROUP BY date
+            "#,
+            links.condition()
+        ))
+        .bind(links.id())
+        .bind(from.to_string())
+        .bind(open.to_string())
+        .bind(to.to_string())
+        .fetch_all(db)
+        .await
+        .map_err(database_error)?;
+        for row in rows {
+            days.insert(row.get::<String, _>("date"), row.get::<i64, _>("clicks"));
+        }
+    }
+
+    // Timestamps are stored as RFC 3339 text, so the first 10 chars are the UTC date
+    let since = open.map_or(from, |open| open.max(from));
+    let rows = sqlx::query(&format!(
+        r#"
+        SELECT substr(clicked_at, 1, 10) AS date, COUNT(*) AS clicks FROM clicks
+        WHERE {} AND clicked_at >= $2 AND clicked_at < $3
+        GROUP BY date
+        "#,
+        links.condition()
+    ))
+    .bind(links.id())
+    .bind(storage::day_start(since))
+    .bind(storage::day_start(to + Duration::days(1)))
+    .fetch_all(db)
+    .await
+    .map_err(database_error)?;
+    for row in rows {
+        *days.entry(row.get::<String, _>("date")).or_insert(0) += row.get::<i64, _>("clicks");
+    }
+
+    Ok(days.into_iter().map(|(date, clicks)| DailyClicks { date, clicks }).collect())
+}
+
+// A `(url_id, clicks)` table of every click since the instant, one row per click or per
+// rolled up day. Days that lie wholly inside the period come from the aggregates where
+// they exist, the partial first day and the open days from the events.
+pub fn push_clicks_since(builder: &mut SqlBuilder, since: DateTime<Utc>, open: Option<NaiveDate>) {
+    let first_full = since.date_naive() + Duration::days(1);
+    let open = open.map_or(first_full, |open| open.max(first_full));
+    builder
+        .push("(SELECT url_id, clicks FROM click_stats_daily WHERE date >= ")
+        .push_bind(first_full.to_string())
+        .push(" AND date < ")
+        .push_bind(open.to_string())
+        .push(" UNION ALL SELECT url_id, 1 AS clicks FROM clicks WHERE clicked_at >= ")
+        .push_bind(storage::ts(since))
+        .push(" AND (clicked_at < ")
+        .push_bind(storage::day_start(first_full))
+        .push(" OR clicked_at >= ")
+        .push_bind(storage::day_start(open))
+        .push("))");
+}
+
+pub fn spawn(state: Arc<AppState>) {
+    let interval_secs = state.config.stats_rollup_interval_secs;
+    scheduler::spawn(state, "stats_rollup", interval_secs, |state| async move {
+        match run_rollup(&state.db).await {
+            Ok(0) => {}
+            Ok(rows) => println!("📊 Rolled up {} link-days of clicks", rows),
+            Err(e) => {
+                eprintln!("❌ Stats rollup failed: {}", e);
+                return Err(e.to_string());
+            }
+        }
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rollup_serves_purged_days() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            sqlx::query("INSERT INTO urls (id, token, original_url, created_at, click_count) VALUES ('u1', 'abc', 'https://example.com', '2024-05-01T00:00:00Z', 4)")
+                .execute(&db)
+                .await
+                .unwrap();
+            for (clicked_at, ip_hash) in [
+                ("2024-05-01T08:00:00Z", "a"),
+                ("2024-05-01T09:00:00Z", "a"),
+                ("2024-05-02T10:00:00Z", "b"),
+                ("2024-05-03T11:00:00Z", "c"),
+            ] {
+                sqlx::query("INSERT INTO clicks (url_id, clicked_at, ip_hash) VALUES ('u1', $1, $2)")
+                    .bind(clicked_at)
+                    .bind(ip_hash)
+                    .execute(&db)
+                    .await
+                    .unwrap();
+            }
+
+            let day = |date: &str| date.parse::<NaiveDate>().unwrap();
+            assert_eq!(first_open_day(&db).await.unwrap(), None);
+            assert_eq!(roll_up(&db, day("2024-05-03")).await.unwrap(), 2);
+            assert_eq!(roll_up(&db, day("2024-05-03")).await.unwrap(), 0);
+            assert_eq!(first_open_day(&db).await.unwrap(), Some(day("2024-05-03")));
+            let unique: i64 = sqlx::query("SELECT unique_visitors FROM click_stats_daily WHERE date = '2024-05-01'")
+                .fetch_one(&db)
+                .await
+                .unwrap()
+                .get("unique_visitors");
+            assert_eq!(unique, 1);
+
+            // Rolled up events can go, the series still has them
+            sqlx::query("DELETE FROM clicks WHERE clicked_at < '2024-05-03'").execute(&db).await.unwrap();
+            let daily = daily_clicks(&db, Links::Url("u1"), day("2024-05-01"), day("2024-05-31")).await.unwrap();
+            let daily: Vec<(&str, i64)> = daily.iter().map(|entry| (entry.date.as_str(), entry.clicks)).collect();
+            assert_eq!(daily, [("2024-05-01", 2), ("2024-05-02", 1), ("2024-05-03", 1)]);
+
+            let later = daily_clicks(&db, Links::Url("u1"), day("2024-05-02"), day("2024-05-02")).await.unwrap();
+            assert_eq!(later.len(), 1);
+
+            // The partial first day only has its purged events, the 2nd comes from the aggregates
+            let since = storage::parse_ts("2024-05-01T08:30:00Z");
+            let mut total = SqlBuilder::new("SELECT CAST(SUM(clicks) AS BIGINT) AS clicks FROM ");
+            push_clicks_since(&mut total, since, first_open_day(&db).await.unwrap());
+            total.push(" t");
+            let clicks: i64 = total.build().fetch_one(&db).await.unwrap().get("clicks");
+            assert_eq!(clicks, 2);
+        }
+    }
+}
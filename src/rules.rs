@@ -0,0 +1,204 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Json},
+};
+use sqlx::{AnyConnection, Row};
+use std::sync::Arc;
+
+use crate::audit::{self, AuditAction};
+use crate::auth::Caller;
+use crate::device::Device;
+use crate::geo::Location;
+use crate::models::{RedirectRule, RedirectRulesRequest};
+use crate::storage::SqlBuilder;
+use crate::{find_url_id, push_owner_filter, validate_url, Access, AppError, AppState};
+
+const MAX_RULES: usize = 50;
+
+fn matches(rule: &RedirectRule, location: &Location, device: Device) -> bool {
+    let country = rule
+        .country
+        .as_deref()
+        .is_none_or(|country| location.country.as_deref() == Some(country));
+    country && rule.device.is_none_or(|wanted| wanted == device)
+}
+
+// Checked in order, the first rule matching the visitor wins, otherwise the link's own URL
+pub fn pick<'a>(rules: &'a [RedirectRule], location: &Location, device: Device) -> Option<&'a str> {
+    rules
+        .iter()
+        .find(|rule| matches(rule, location, device))
+        .map(|rule| rule.url.as_str())
+}
+
+fn normalize_rules(state: &AppState, rules: Vec<RedirectRule>) -> Result<Vec<RedirectRule>, AppError> {
+    if rules.len() > MAX_RULES {
+        return Err(AppError::BadRequest(format!("A link can have at most {} rules", MAX_RULES)));
+    }
+    rules
+        .into_iter()
+        .map(|rule| {
+            if rule.country.is_none() && rule.device.is_none() {
+                return Err(AppError::BadRequest("Each rule needs a country or a device".into()));
+            }
+            let country = rule.country.map(|country| country.trim().to_uppercase());
+            if let Some(country) = &country {
+                if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+                    return Err(AppError::BadRequest(format!(
+                        "Invalid country {:?}: use a two letter ISO 3166 code",
+                        country
+                    )));
+                }
+            }
+            validate_url(state, &rule.url)?;
+            Ok(RedirectRule {
+                country,
+                device: rule.device,
+                url: rule.url,
+            })
+        })
+        .collect()
+}
+
+pub async fn load(db: impl sqlx::Executor<'_, Database = sqlx::Any>, url_id: &str) -> Result<Vec<RedirectRule>, AppError> {
+    let rows = sqlx::query("SELECT country, device, destination FROM redirect_rules WHERE url_id = $1 ORDER BY position")
+        .bind(url_id)
+        .fetch_all(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| RedirectRule {
+            country: row.get("country"),
+            device: row.get::<Option<String>, _>("device").as_deref().and_then(Device::parse),
+            url: row.get("destination"),
+        })
+        .collect())
+}
+
+async fn replace(conn: &mut AnyConnection, url_id: &str, rules: &[RedirectRule]) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM redirect_rules WHERE url_id = $1")
+        .bind(url_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    for (position, rule) in rules.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO redirect_rules (url_id, position, country, device, destination) VALUES ($1, $2, $3, $4, $5)"
+        )
+            .bind(url_id)
+            .bind(position as i64)
+            .bind(&rule.country)
+            .bind(rule.device.map(Device::as_str))
+            .bind(&rule.url)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/urls/{token}/rules",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 200, description = "Destination overrides in evaluation order", body = RedirectRulesRequest),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_rules(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let url_id = find_url_id(&state.db, &token).await?;
+    let rules = load(&state.db, &url_id).await?;
+    Ok(Json(RedirectRulesRequest { rules }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/urls/{token}/rules",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    request_body = RedirectRulesRequest,
+    responses(
+        (status = 200, description = "Rules replaced", body = RedirectRulesRequest),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn put_rules(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<RedirectRulesRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let rules = normalize_rules(&state, payload.rules)?;
+
+    let mut lookup = SqlBuilder::new("SELECT id FROM urls WHERE token = ");
+    lookup.push_bind(token.clone()).push(" AND deleted_at IS NULL");
+    push_owner_filter(&mut lookup, &caller, Access::Write);
+
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let url_id: String = lookup
+        .build()
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .map(|row| row.get("id"))
+        .ok_or(AppError::UrlNotFound)?;
+
+    let old = RedirectRulesRequest {
+        rules: load(&mut *tx, &url_id).await?,
+    };
+    replace(&mut tx, &url_id, &rules).await?;
+    let new = RedirectRulesRequest { rules };
+    audit::record(&mut tx, &url_id, &token, AuditAction::Update, &caller, audit::diff(&old, &new)).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    state.links.invalidate(&token).await;
+
+    Ok(Json(new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rule = |country: Option<&str>, device: Option<Device>, url: &str| RedirectRule {
+            country: country.map(String::from),
+            device,
+            url: url.into(),
+        };
+        let rules = vec![
+            rule(Some("DE"), Some(Device::Ios), "https://apps.apple.com/de/app"),
+            rule(Some("DE"), None, "https://example.de"),
+            rule(None, Some(Device::Android), "https://play.google.com/store/apps"),
+        ];
+        let visitor = |country: Option<&str>| Location {
+            country: country.map(String::from),
+            city: None,
+        };
+
+        assert_eq!(pick(&rules, &visitor(Some("DE")), Device::Ios), Some("https://apps.apple.com/de/app"));
+        assert_eq!(pick(&rules, &visitor(Some("DE")), Device::Android), Some("https://example.de"));
+        assert_eq!(pick(&rules, &visitor(None), Device::Android), Some("https://play.google.com/store/apps"));
+        assert_eq!(pick(&rules, &visitor(Some("FR")), Device::Desktop), None);
+    }
+}
@@ -0,0 +1,346 @@
+use axum::{extract::State, response::{IntoResponse, Json}};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+use crate::config::Config;
+use crate::models::RescanReport;
+use crate::{idn, scheduler, storage};
+use crate::{AppError, AppState};
+
+const LOOKUP_URL: &str = "https://safebrowsing.googleapis.com/v4/threatMatches:find";
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+// Most URLs the Lookup API accepts in one request, also the rescan page size
+const MAX_URLS_PER_LOOKUP: usize = 500;
+const THREAT_TYPES: &[&str] = &["MALWARE", "SOCIAL_ENGINEERING", "UNWANTED_SOFTWARE", "POTENTIALLY_HARMFUL_APPLICATION"];
+// Flag reason for matches from the local list, which carries no threat type
+const LOCAL_LIST_REASON: &str = "LOCAL_LIST";
+
+// Host suffix / path prefix combinations the Safe Browsing lists are keyed by, e.g.
+// http://a.b.c/1/2.html?param=1 gives a.b.c/1/2.html?param=1, a.b.c/1/2.html, a.b.c/, a.b.c/1/,
+// then the same paths on b.c
+pub fn expressions(url: &Url) -> Vec<String> {
+    let Some(host) = url.host_str() else {
+        return Vec::new();
+    };
+    let host = host.trim_end_matches('.');
+
+    let mut hosts = vec![host.to_string()];
+    if host.parse::<IpAddr>().is_err() && !host.starts_with('[') {
+        // Up to four suffixes built from the last five labels, never the bare TLD
+        let labels: Vec<&str> = host.split('.').collect();
+        let first = labels.len().saturating_sub(5).max(1);
+        for start in first..labels.len().saturating_sub(1) {
+            hosts.push(labels[start..].join("."));
+        }
+    }
+
+    let path = url.path();
+    let mut paths = Vec::new();
+    if let Some(query) = url.query() {
+        paths.push(format!("{}?{}", path, query));
+    }
+    paths.push(path.to_string());
+    // The root and up to three directories below it
+    let mut prefix = String::from("/");
+    paths.push(prefix.clone());
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    for segment in segments[..segments.len() - 1].iter().take(3) {
+        prefix.push_str(segment);
+        prefix.push('/');
+        paths.push(prefix.clone());
+    }
+
+    let mut seen = HashSet::new();
+    hosts
+        .iter()
+        .flat_map(|host| paths.iter().map(move |path| format!("{}{}", host, path)))
+        .filter(|expression| seen.insert(expression.clone()))
+        .collect()
+}
+
+// One hex encoded prefix (4 to 32 bytes of the SHA-256 of an expression) per line, # starts a comment
+fn parse_prefixes(contents: &str) -> anyhow::Result<HashSet<Vec<u8>>> {
+    let mut prefixes = HashSet::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let prefix = hex::decode(line)
+            .ok()
+            .filter(|prefix| (4..=32).contains(&prefix.len()))
+            .ok_or_else(|| anyhow::anyhow!("line {}: expected 8 to 64 hex characters", number + 1))?;
+        prefixes.insert(prefix);
+    }
+    Ok(prefixes)
+}
+
+#[derive(Clone)]
+pub struct SafeBrowsing {
+    client: reqwest::Client,
+    api_key: Option<String>,
+    prefixes: Arc<HashSet<Vec<u8>>>,
+    prefix_lengths: BTreeSet<usize>,
+}
+
+impl SafeBrowsing {
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        let prefixes = match config.safe_browsing_prefix_file.as_deref() {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|e| anyhow::anyhow!("failed to read safe_browsing_prefix_file {}: {}", path, e))?;
+                parse_prefixes(&contents).map_err(|e| anyhow::anyhow!("invalid safe_browsing_prefix_file {}: {}", path, e))?
+            }
+            None => HashSet::new(),
+        };
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(LOOKUP_TIMEOUT).build()?,
+            api_key: config.safe_browsing_api_key.clone().filter(|key| !key.is_empty()),
+            prefix_lengths: prefixes.iter().map(Vec::len).collect(),
+            prefixes: Arc::new(prefixes),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.api_key.is_some() || !self.prefixes.is_empty()
+    }
+
+    fn local_match(&self, url: &Url) -> bool {
+        expressions(url).iter().any(|expression| {
+            let digest = Sha256::digest(expression.as_bytes());
+            self.prefix_lengths
+                .iter()
+                .any(|&length| self.prefixes.contains(&digest[..length]))
+        })
+    }
+
+    // Threat type of every listed URL, clean ones are left out
+    pub async fn lookup(&self, urls: &[String]) -> anyhow::Result<HashMap<String, String>> {
+        let mut listed = HashMap::new();
+        let mut remaining = Vec::new();
+        for url in urls {
+            if Url::parse(url).is_ok_and(|parsed| self.local_match(&parsed)) {
+                listed.insert(url.clone(), LOCAL_LIST_REASON.to_string());
+            } else {
+                remaining.push(url.as_str());
+            }
+        }
+
+        let Some(api_key) = &self.api_key else {
+            return Ok(listed);
+        };
+        for chunk in remaining.chunks(MAX_URLS_PER_LOOKUP) {
+            for (url, threat) in self.lookup_api(api_key, chunk).await? {
+                listed.entry(url).or_insert(threat);
+            }
+        }
+        Ok(listed)
+    }
+
+    async fn lookup_api(&self, api_key: &str, urls: &[&str]) -> anyhow::Result<Vec<(String, String)>> {
+        let body = json!({
+            "client": { "clientId": "quickurl", "clientVersion": env!("CARGO_PKG_VERSION") },
+            "threatInfo": {
+                "threatTypes": THREAT_TYPES,
+                "platformTypes": ["ANY_PLATFORM"],
+                "threatEntryTypes": ["URL"],
+                "threatEntries": urls.iter().map(|url| json!({ "url": url })).collect::<Vec<_>>(),
+            },
+        });
+        // Sent as a header rather than ?key= so it does not end up in error messages
+        let response = self
+            .client
+            .post(LOOKUP_URL)
+            .header("X-Goog-Api-Key", api_key)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        let answer: Value = serde_json::from_slice(&response.bytes().await?)?;
+
+        Ok(answer["matches"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|found| {
+                let url = found["threat"]["url"].as_str()?;
+                let threat = found["threatType"].as_str()?;
+                Some((url.to_string(), threat.to_string()))
+            })
+            .collect())
+    }
+
+    // Refuses listed destinations. A failed lookup lets the link through, an outage should not
+    // stop link creation and the next rescan catches up.
+    pub async fn check(&self, url: &str) -> Result<(), AppError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        match self.lookup(&[url.to_string()]).await {
+            Ok(listed) => match listed.get(url) {
+                Some(threat) => Err(AppError::BadRequest(format!("Destination is listed as unsafe ({})", threat))),
+                None => Ok(()),
+            },
+            Err(e) => {
+                eprintln!("⚠️  Safe Browsing lookup failed: {}", e);
+                Ok(())
+            }
+        }
+    }
+}
+
+// Checks every live link again, flagging newly listed destinations and clearing flags
+// of ones that have been delisted
+pub async fn run_rescan(state: &AppState) -> anyhow::Result<RescanReport> {
+    let mut report = RescanReport::default();
+    let now = storage::now();
+    let mut after = String::new();
+    loop {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, token, original_url, flagged_at, flag_reason FROM urls
+            WHERE deleted_at IS NULL AND (expires_at IS NULL OR expires_at > $1) AND id > $2
+            ORDER BY id LIMIT $3
+            "#
+        )
+        .bind(storage::ts(now))
+        .bind(&after)
+        .bind(MAX_URLS_PER_LOOKUP as i64)
+        .fetch_all(&state.db)
+        .await?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        after = last.get("id");
+
+        let urls: Vec<String> = rows.iter().map(|row| row.get("original_url")).collect();
+        let listed = state.safe_browsing.lookup(&urls).await?;
+        for (row, url) in rows.iter().zip(&urls) {
+            report.scanned += 1;
+            let reason = listed.get(url).cloned().or_else(|| idn::flag_reason(url));
+            if reason == row.get::<Option<String>, _>("flag_reason") {
+                continue;
+            }
+
+            // A link listed under another threat type keeps its original flag time
+            let flagged_at = reason
+                .as_ref()
+                .map(|_| storage::get_opt_ts(row, "flagged_at").unwrap_or(now));
+            sqlx::query("UPDATE urls SET flagged_at = $1, flag_reason = $2, updated_at = $3 WHERE id = $4")
+                .bind(flagged_at.map(storage::ts))
+                .bind(&reason)
+                .bind(storage::ts(now))
+                .bind(row.get::<String, _>("id"))
+                .execute(&state.db)
+                .await?;
+            state.links.invalidate(&row.get::<String, _>("token")).await;
+            if reason.is_some() {
+                report.flagged += 1;
+            } else {
+                report.cleared += 1;
+            }
+        }
+    }
+    Ok(report)
+}
+
+pub fn spawn(state: Arc<AppState>) {
+    let interval_secs = state.config.safe_browsing_rescan_interval_secs;
+    if !state.safe_browsing.is_enabled() {
+        return;
+    }
+
+    scheduler::spawn(state, "safe_browsing_rescan", interval_secs, |state| async move {
+        match run_rescan(&state).await {
+            Ok(report) if report.flagged > 0 || report.cleared > 0 => println!(
+                "🛡️  Safe Browsing rescan flagged {} and cleared {} links",
+                report.flagged, report.cleared
+            ),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("❌ Safe Browsing rescan failed: {}", e);
+                return Err(e.to_string());
+            }
+        }
+        Ok(())
+    });
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/rescan",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Live links checked against Safe Browsing", body = RescanReport),
+        (status = 400, description = "Safe Browsing is not configured", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn trigger_rescan(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    if !state.safe_browsing.is_enabled() {
+        return Err(AppError::BadRequest("Safe Browsing is not configured".into()));
+    }
+    let report = run_rescan(&state)
+        .await
+        .map_err(|e| AppError::InternalError(e.to_string()))?;
+
+    Ok(Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expressions_follow_safe_browsing_order() {
+        let url = Url::parse("http://a.b.c/1/2.html?param=1").unwrap();
+        assert_eq!(
+            expressions(&url),
+            [
+                "a.b.c/1/2.html?param=1",
+                "a.b.c/1/2.html",
+                "a.b.c/",
+                "a.b.c/1/",
+                "b.c/1/2.html?param=1",
+                "b.c/1/2.html",
+                "b.c/",
+                "b.c/1/",
+            ]
+        );
+
+        let deep = Url::parse("http://a.b.c.d.e.f.g/1.html").unwrap();
+        let hosts: BTreeSet<_> = expressions(&deep)
+            .iter()
+            .map(|expression| expression.split('/').next().unwrap().to_string())
+            .collect();
+        assert_eq!(hosts, BTreeSet::from(["a.b.c.d.e.f.g", "c.d.e.f.g", "d.e.f.g", "e.f.g", "f.g"].map(String::from)));
+        assert_eq!(expressions(&Url::parse("http://1.2.3.4/").unwrap()), ["1.2.3.4/"]);
+    }
+
+    #[tokio::test]
+    async fn test_local_prefixes_match_parent_domains() {
+        let digest = hex::encode(Sha256::digest(b"evil.example/"));
+        let mut safe_browsing = SafeBrowsing::new(&Config::default()).unwrap();
+        let prefixes = parse_prefixes(&format!("# phishing kit\n{}\n", &digest[..8])).unwrap();
+        safe_browsing.prefix_lengths = prefixes.iter().map(Vec::len).collect();
+        safe_browsing.prefixes = Arc::new(prefixes);
+
+        let urls = ["https://login.evil.example/account?id=1".to_string(), "https://example.com/".to_string()];
+        let listed = safe_browsing.lookup(&urls).await.unwrap();
+        assert_eq!(listed.get(&urls[0]).map(String::as_str), Some(LOCAL_LIST_REASON));
+        assert!(!listed.contains_key(&urls[1]));
+        assert!(safe_browsing.check(&urls[0]).await.is_err());
+        assert!(parse_prefixes("abc").is_err());
+    }
+}
@@ -0,0 +1,444 @@
+use axum::{
+    extract::State,
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+use rand::Rng;
+use sqlx::{AnyPool, Row};
+use std::future::Future;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::models::JobInfo;
+use crate::storage;
+use crate::{AppError, AppState};
+
+// Background jobs that job_schedules can give a schedule
+pub const JOBS: &[&str] = &["cleanup", "stats_rollup", "link_check", "safe_browsing_rescan"];
+
+// How long a claimed run keeps other instances away when its own dies before finishing it
+const LOCK_HOURS: i64 = 6;
+// Longest sleep before the job is read again, so runs claimed elsewhere are noticed
+const POLL_SECS: i64 = 60;
+// Cron expressions that match less often than this are treated as never matching
+const SEARCH_STEPS: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(Cron),
+}
+
+// Standard five field cron: minute, hour, day of month, month and day of week (0 or 7 is
+// Sunday), each `*`, a value, a range or a comma separated list of them, optionally `/step`.
+// Like cron, a day matches either field when both day fields are restricted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn field(spec: &str, min: u32, max: u32) -> anyhow::Result<u64> {
+    let mut bits = 0;
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0)),
+            None => (part, Some(1)),
+        };
+        let step = step.ok_or_else(|| anyhow::anyhow!("invalid step in {:?}", part))?;
+        let value = |value: &str| {
+            value
+                .parse::<u32>()
+                .ok()
+                .filter(|value| (min..=max).contains(value))
+                .ok_or_else(|| anyhow::anyhow!("{:?} is not between {} and {}", value, min, max))
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (value(from)?, value(to)?),
+                // 5/15 counts from 5 to the end
+                None if part.contains('/') => (value(range)?, max),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        if from > to {
+            anyhow::bail!("invalid range {:?}", range);
+        }
+        for bit in (from..=to).step_by(step as usize) {
+            bits |= 1 << bit;
+        }
+    }
+    Ok(bits)
+}
+
+fn has(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+impl Cron {
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    // The first matching minute after the instant
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let midnight = |date: NaiveDate| date.and_hms_opt(0, 0, 0).map(|time| time.and_utc());
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        for _ in 0..SEARCH_STEPS {
+            if !has(self.months, time.month()) {
+                let (year, month) = if time.month() == 12 { (time.year() + 1, 1) } else { (time.year(), time.month() + 1) };
+                time = midnight(NaiveDate::from_ymd_opt(year, month, 1)?)?;
+            } else if !self.day_matches(time.date_naive()) {
+                time = midnight(time.date_naive().succ_opt()?)?;
+            } else if !has(self.hours, time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+impl Schedule {
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Every(interval) => Some(after + *interval),
+            Schedule::Cron(cron) => cron.next_after(after),
+        }
+    }
+}
+
+// Cron expressions, the @hourly, @daily, @weekly, @monthly and @yearly shortcuts, or
+// "@every 15m" with s, m, h or d
+impl std::str::FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let value = value.trim();
+        if let Some(every) = value.strip_prefix("@every ") {
+            let every = every.trim();
+            let (number, unit) = every.split_at(every.len().saturating_sub(1));
+            let number: i64 = number
+                .parse()
+                .ok()
+                .filter(|number| *number > 0)
+                .ok_or_else(|| anyhow::anyhow!("invalid interval {:?}", every))?;
+            let interval = match unit {
+                "s" => Duration::seconds(number),
+                "m" => Duration::minutes(number),
+                "h" => Duration::hours(number),
+                "d" => Duration::days(number),
+                _ => anyhow::bail!("interval {:?} needs a unit: s, m, h or d", every),
+            };
+            return Ok(Schedule::Every(interval));
+        }
+
+        let expression = match value {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            _ => value,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            anyhow::bail!("{:?} must have five fields: minute hour day month weekday", value);
+        };
+        let mut weekday_bits = field(weekdays, 0, 7)?;
+        if has(weekday_bits, 7) {
+            weekday_bits = (weekday_bits & !(1 << 7)) | 1;
+        }
+        Ok(Schedule::Cron(Cron {
+            minutes: field(minutes, 0, 59)?,
+            hours: field(hours, 0, 23)?,
+            days: field(days, 1, 31)?,
+            months: field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            any_day: days.starts_with('*'),
+            any_weekday: weekdays.starts_with('*'),
+        }))
+    }
+}
+
+// The job's entry in job_schedules, otherwise every interval_secs, None with neither. Comes with
+// the text it was parsed from, which is how the jobs table tells a changed schedule.
+fn schedule_of(state: &AppState, name: &str, interval_secs: u64) -> anyhow::Result<Option<(String, Schedule)>> {
+    let spec = match state.config.job_schedules.get(name) {
+        Some(expression) => expression.trim().to_string(),
+        None if interval_secs == 0 => return Ok(None),
+        None => format!("@every {}s", interval_secs),
+    };
+    let schedule = spec.parse()?;
+    Ok(Some((spec, schedule)))
+}
+
+// When the job is due next. The first instance to start registers it, and so does one started
+// with a different schedule, the old next run does not hold then. Interval jobs are due straight
+// away, cron jobs at their next match.
+async fn next_run(db: &AnyPool, name: &str, spec: &str, schedule: &Schedule) -> Result<Option<String>, sqlx::Error> {
+    let now = storage::now();
+    let first = match schedule {
+        Schedule::Every(_) => Some(now),
+        Schedule::Cron(cron) => cron.next_after(now),
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO jobs (name, schedule, next_run_at) VALUES ($1, $2, $3)
+        ON CONFLICT (name) DO UPDATE SET schedule = excluded.schedule, next_run_at = excluded.next_run_at
+        WHERE jobs.schedule IS NULL OR jobs.schedule <> excluded.schedule
+        "#
+    )
+    .bind(name)
+    .bind(spec)
+    .bind(first.map(storage::ts))
+    .execute(db)
+    .await?;
+    sqlx::query("SELECT next_run_at FROM jobs WHERE name = $1")
+        .bind(name)
+        .fetch_one(db)
+        .await
+        .map(|row| row.get("next_run_at"))
+}
+
+// Takes the run that was due at `due` and moves the job on to `following`. Only one instance
+// can match the old next_run_at, and none while an earlier run is still locked.
+async fn claim(
+    db: &AnyPool,
+    name: &str,
+    due: &str,
+    following: Option<DateTime<Utc>>,
+    instance: &str,
+    now: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let claimed = sqlx::query(
+        r#"
+        UPDATE jobs SET next_run_at = $1, locked_by = $2, locked_until = $3
+        WHERE name = $4 AND next_run_at = $5 AND (locked_until IS NULL OR locked_until <= $6)
+        "#
+    )
+    .bind(following.map(storage::ts))
+    .bind(instance)
+    .bind(storage::ts(now + Duration::hours(LOCK_HOURS)))
+    .bind(name)
+    .bind(due)
+    .bind(storage::ts(now))
+    .execute(db)
+    .await?;
+    Ok(claimed.rows_affected() == 1)
+}
+
+async fn finish(
+    db: &AnyPool,
+    name: &str,
+    instance: &str,
+    started: DateTime<Utc>,
+    result: Result<(), String>,
+) -> Result<(), sqlx::Error> {
+    let duration_ms = (storage::now() - started).num_milliseconds();
+    sqlx::query(
+        r#"
+        UPDATE jobs SET locked_by = NULL, locked_until = NULL, last_run_at = $1, last_status = $2,
+            last_error = $3, last_duration_ms = $4
+        WHERE name = $5 AND locked_by = $6
+        "#
+    )
+    .bind(storage::ts(started))
+    .bind(if result.is_ok() { "ok" } else { "failed" })
+    .bind(result.err())
+    .bind(duration_ms)
+    .bind(name)
+    .bind(instance)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+fn sleep(duration: Duration) -> tokio::time::Sleep {
+    tokio::time::sleep(duration.to_std().unwrap_or_default())
+}
+
+// Runs the job on its schedule, on one instance at a time when several share the database.
+// Due runs wait up to job_jitter_secs so instances do not all reach for them at once. Returns
+// false when the job has no schedule.
+pub fn spawn<F, Fut>(state: Arc<AppState>, name: &'static str, interval_secs: u64, run: F) -> bool
+where
+    F: Fn(Arc<AppState>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), String>> + Send + 'static,
+{
+    let (spec, schedule) = match schedule_of(&state, name, interval_secs) {
+        Ok(Some(schedule)) => schedule,
+        Ok(None) => return false,
+        Err(e) => {
+            eprintln!("❌ Job {} has an invalid schedule: {}", name, e);
+            return false;
+        }
+    };
+    let instance = Uuid::new_v4().to_string();
+    let jitter_secs = state.config.job_jitter_secs;
+
+    tokio::spawn(async move {
+        loop {
+            let due = match next_run(&state.db, name, &spec, &schedule).await {
+                Ok(Some(due)) => due,
+                Ok(None) => {
+                    sleep(Duration::seconds(POLL_SECS)).await;
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("❌ Job {} could not be scheduled: {}", name, e);
+                    sleep(Duration::seconds(POLL_SECS)).await;
+                    continue;
+                }
+            };
+            let wait = storage::parse_ts(&due) - storage::now();
+            if wait > Duration::zero() {
+                sleep(wait.min(Duration::seconds(POLL_SECS))).await;
+                continue;
+            }
+
+            let jitter = rand::thread_rng().gen_range(0..=jitter_secs);
+            sleep(Duration::seconds(jitter as i64)).await;
+            let now = storage::now();
+            match claim(&state.db, name, &due, schedule.next_after(now), &instance, now).await {
+                Ok(true) => {}
+                // Another instance has it, or is still busy with the previous run
+                Ok(false) => {
+                    sleep(Duration::seconds(POLL_SECS)).await;
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("❌ Job {} could not be claimed: {}", name, e);
+                    sleep(Duration::seconds(POLL_SECS)).await;
+                    continue;
+                }
+            }
+
+            let result = run(state.clone()).await;
+            if let Err(e) = finish(&state.db, name, &instance, now, result).await {
+                eprintln!("❌ Job {} run could not be recorded: {}", name, e);
+            }
+        }
+    });
+    true
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/jobs",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Background jobs with their next and last run", body = Vec<JobInfo>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn list_jobs(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, AppError> {
+    let now = storage::now();
+    let jobs: Vec<JobInfo> = sqlx::query("SELECT * FROM jobs ORDER BY name")
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .iter()
+        .map(|row| JobInfo {
+            name: row.get("name"),
+            next_run_at: storage::get_opt_ts(row, "next_run_at"),
+            running: storage::get_opt_ts(row, "locked_until").is_some_and(|until| until > now),
+            last_run_at: storage::get_opt_ts(row, "last_run_at"),
+            last_status: row.get("last_status"),
+            last_error: row.get("last_error"),
+            last_duration_ms: row.get("last_duration_ms"),
+        })
+        .collect();
+    Ok(Json(jobs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        storage::parse_ts(value)
+    }
+
+    fn next(expression: &str, after: &str) -> Option<DateTime<Utc>> {
+        expression.parse::<Schedule>().unwrap().next_after(at(after))
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        assert_eq!(next("*/15 * * * *", "2024-05-01T10:07:30Z"), Some(at("2024-05-01T10:15:00Z")));
+        assert_eq!(next("@daily", "2024-05-01T10:07:00Z"), Some(at("2024-05-02T00:00:00Z")));
+        assert_eq!(next("30 3 * * 1-5", "2024-05-03T04:00:00Z"), Some(at("2024-05-06T03:30:00Z")));
+        // Sunday as 7, and day of month or weekday when both are given
+        assert_eq!(next("0 0 * * 7", "2024-05-01T00:00:00Z"), Some(at("2024-05-05T00:00:00Z")));
+        assert_eq!(next("0 0 13 * 5", "2024-05-01T00:00:00Z"), Some(at("2024-05-03T00:00:00Z")));
+        assert_eq!(next("0 12 29 2 *", "2024-03-01T00:00:00Z"), Some(at("2028-02-29T12:00:00Z")));
+        assert_eq!(next("0 0 31 2 *", "2024-03-01T00:00:00Z"), None);
+        assert_eq!(next("@every 90m", "2024-05-01T10:00:00Z"), Some(at("2024-05-01T11:30:00Z")));
+
+        for invalid in ["* * * *", "60 * * * *", "*/0 * * * *", "5-1 * * * *", "@every 10", "@every 0s"] {
+            assert!(invalid.parse::<Schedule>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_only_one_instance_claims_a_run() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let schedule: Schedule = "@hourly".parse().unwrap();
+            let due = next_run(&db, "cleanup", "@hourly", &schedule).await.unwrap().unwrap();
+            // Registering the same schedule again keeps the first instance's next run
+            assert_eq!(next_run(&db, "cleanup", "@hourly", &schedule).await.unwrap().unwrap(), due);
+
+            let now = storage::parse_ts(&due);
+            let following = schedule.next_after(now);
+            assert!(claim(&db, "cleanup", &due, following, "a", now).await.unwrap());
+            assert!(!claim(&db, "cleanup", &due, following, "b", now).await.unwrap());
+
+            // The next run waits for the lock while the first one is still going
+            let next_due = storage::ts(following.unwrap());
+            assert!(!claim(&db, "cleanup", &next_due, following, "b", following.unwrap()).await.unwrap());
+            finish(&db, "cleanup", "a", now, Err("boom".into())).await.unwrap();
+            assert!(claim(&db, "cleanup", &next_due, None, "b", following.unwrap()).await.unwrap());
+
+            let status: String = sqlx::query("SELECT last_status FROM jobs WHERE name = 'cleanup'")
+                .fetch_one(&db)
+                .await
+                .unwrap()
+                .get("last_status");
+            assert_eq!(status, "failed");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_changed_schedule_resets_next_run() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let yearly: Schedule = "@yearly".parse().unwrap();
+            let due = next_run(&db, "cleanup", "@yearly", &yearly).await.unwrap().unwrap();
+            assert!(storage::parse_ts(&due) > storage::now());
+
+            // An interval job is due straight away once its schedule changes to one
+            let every = Schedule::Every(Duration::minutes(5));
+            let changed = next_run(&db, "cleanup", "@every 5m", &every).await.unwrap().unwrap();
+            assert!(storage::parse_ts(&changed) <= storage::now());
+            assert_eq!(next_run(&db, "cleanup", "@every 5m", &every).await.unwrap().unwrap(), changed);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Json},
+};
+use sqlx::Row;
+use std::sync::Arc;
+
+use crate::auth::Caller;
+use crate::base_url::BaseUrl;
+use crate::models::{SearchQuery, SearchResponse, SearchResult, UrlInfo};
+use crate::preview::escape_html;
+use crate::storage::{Backend, SqlBuilder};
+use crate::{load_tags, push_owner_filter, telemetry, url_info_from_row, Access, AppError, AppState};
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+const MAX_TERMS: usize = 8;
+
+// Same expression as the idx_urls_search index in the Postgres migration
+const DOCUMENT: &str = "setweight(to_tsvector('simple', COALESCE(title, '')), 'A') \
+    || setweight(to_tsvector('simple', regexp_replace(original_url, '[^[:alnum:]]+', ' ', 'g')), 'B')";
+
+// Runs of letters and digits, both indexes split text the same way. Anything else in the
+// query is dropped so it cannot be read as FTS5 or tsquery syntax.
+fn terms(q: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for term in q.split(|c: char| !c.is_alphanumeric()).filter(|term| !term.is_empty()) {
+        let term = term.to_lowercase();
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    terms.truncate(MAX_TERMS);
+    terms
+}
+
+// Every term has to match, as a whole word or the start of one
+fn fts5_query(terms: &[String]) -> String {
+    terms.iter().map(|term| format!("\"{}\"*", term)).collect::<Vec<_>>().join(" ")
+}
+
+fn tsquery(terms: &[String]) -> String {
+    terms.iter().map(|term| format!("{}:*", term)).collect::<Vec<_>>().join(" & ")
+}
+
+// Marks the words the terms matched in, escaping the rest so the result is safe HTML
+fn highlight(text: &str, terms: &[String]) -> String {
+    let mut highlighted = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        let word = rest.starts_with(char::is_alphanumeric);
+        let end = rest.find(|c: char| c.is_alphanumeric() != word).unwrap_or(rest.len());
+        let (run, tail) = rest.split_at(end);
+        let lower = run.to_lowercase();
+        if word && terms.iter().any(|term| lower.starts_with(term.as_str())) {
+            highlighted.push_str("<mark>");
+            highlighted.push_str(&escape_html(run));
+            highlighted.push_str("</mark>");
+        } else {
+            highlighted.push_str(&escape_html(run));
+        }
+        rest = tail;
+    }
+    highlighted
+}
+
+#[utoipa::path(
+    get,
+    path = "/urls/search",
+    tag = "urls",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Live links matching every word, best first", body = SearchResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+    ),
+)]
+pub async fn search_urls(
+    Query(query): Query<SearchQuery>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+) -> Result<impl IntoResponse, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(AppError::BadRequest(format!("limit must be between 1 and {}", MAX_LIMIT)));
+    }
+    let terms = terms(&query.q);
+    if terms.is_empty() {
+        return Err(AppError::BadRequest("q must contain at least one letter or digit".into()));
+    }
+
+    let mut search = match state.backend {
+        // bm25 is lower for better matches, titles weigh ten times the destination
+        Backend::Sqlite => {
+            let mut search = SqlBuilder::new(
+                "SELECT u.*, -bm25(urls_fts, 10.0, 1.0) AS score FROM urls_fts JOIN urls u ON u.rowid = urls_fts.rowid WHERE urls_fts MATCH ",
+            );
+            search.push_bind(fts5_query(&terms));
+            search
+        }
+        Backend::Postgres => {
+            let tsquery = tsquery(&terms);
+            let mut search = SqlBuilder::new(format!("SELECT *, ts_rank({}, to_tsquery('simple', ", DOCUMENT));
+            search
+                .push_bind(tsquery.clone())
+                .push(format!("))::float8 AS score FROM urls WHERE {} @@ to_tsquery('simple', ", DOCUMENT))
+                .push_bind(tsquery)
+                .push(")");
+            search
+        }
+    };
+    search.push(" AND deleted_at IS NULL");
+    push_owner_filter(&mut search, &caller, Access::Read);
+    search.push(" ORDER BY score DESC, id LIMIT ").push_bind(limit);
+
+    let rows = telemetry::timed("search_urls", search.build().fetch_all(state.links.reader()))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let scores: Vec<f64> = rows.iter().map(|row| row.get("score")).collect();
+    let mut urls: Vec<UrlInfo> = rows.iter().map(|row| url_info_from_row(&base, row)).collect();
+    load_tags(state.links.reader(), &mut urls).await?;
+
+    let results = urls
+        .into_iter()
+        .zip(scores)
+        .map(|(url, score)| SearchResult {
+            title_highlight: url.title.as_deref().map(|title| highlight(title, &terms)),
+            url_highlight: highlight(&url.original_url, &terms),
+            score,
+            url,
+        })
+        .collect();
+    Ok(Json(SearchResponse { results }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage;
+
+    #[test]
+    fn test_terms_and_highlight() {
+        let terms = terms("Quarterly  \"report\" OR -2024*");
+        assert_eq!(terms, ["quarterly", "report", "or", "2024"]);
+        assert_eq!(fts5_query(&terms[..2]), "\"quarterly\"* \"report\"*");
+        assert_eq!(tsquery(&terms[..2]), "quarterly:* & report:*");
+
+        let terms = vec!["rep".to_string()];
+        assert_eq!(highlight("<b>Reports</b> & more", &terms), "&lt;b&gt;<mark>Reports</mark>&lt;/b&gt; &amp; more");
+        assert_eq!(highlight("https://example.com/prep", &terms), "https://example.com/prep");
+    }
+
+    #[tokio::test]
+    async fn test_fts_index_follows_updates() {
+        for (db, backend) in storage::test_pools().await.unwrap() {
+            let now = storage::ts(storage::now());
+            for (id, title, url) in [("a", "Quarterly report", "https://example.com/q3"), ("b", "Party", "https://reports.example.org/")] {
+                sqlx::query("INSERT INTO urls (id, token, original_url, title, created_at, expires_at) VALUES ($1, $1, $2, $3, $4, $4)")
+                    .bind(id)
+                    .bind(url)
+                    .bind(title)
+                    .bind(&now)
+                    .execute(&db)
+                    .await
+                    .unwrap();
+            }
+            let search = |q: &str| {
+                let db = db.clone();
+                let terms = terms(q);
+                let (sql, q) = match backend {
+                    Backend::Sqlite => (
+                        "SELECT u.id FROM urls_fts JOIN urls u ON u.rowid = urls_fts.rowid WHERE urls_fts MATCH $1 ORDER BY bm25(urls_fts, 10.0, 1.0)".to_string(),
+                        fts5_query(&terms),
+                    ),
+                    Backend::Postgres => (
+                        format!("SELECT id FROM urls WHERE {0} @@ to_tsquery('simple', $1) ORDER BY ts_rank({0}, to_tsquery('simple', $1)) DESC", DOCUMENT),
+                        tsquery(&terms),
+                    ),
+                };
+                async move {
+                    sqlx::query(&sql)
+                        .bind(q)
+                        .fetch_all(&db)
+                        .await
+                        .unwrap()
+                        .iter()
+                        .map(|row| row.get::<String, _>("id"))
+                        .collect::<Vec<_>>()
+                }
+            };
+
+            // The title match ranks above the one in the destination
+            assert_eq!(search("report").await, ["a", "b"]);
+            assert_eq!(search("example q3").await, ["a"]);
+
+            sqlx::query("UPDATE urls SET title = 'Annual' WHERE id = 'a'").execute(&db).await.unwrap();
+            sqlx::query("DELETE FROM urls WHERE id = 'b'").execute(&db).await.unwrap();
+            assert!(search("report").await.is_empty());
+            assert_eq!(search("annual").await, ["a"]);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use futures::StreamExt;
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use redis::{AsyncCommands, Client, RedisResult};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cache::{CachedLink, LinkCache};
+use crate::config::Config;
+use crate::AppError;
+
+// Redis sits on the redirect path, a slow one must not hold redirects up for long
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(250);
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+// Links and click counters shared by all instances through Redis. Every instance keeps its own
+// LinkCache in front of it, invalidations are published so the others drop their copy as well.
+// Redis errors count as misses, the database stays the source of truth.
+#[derive(Clone)]
+pub struct SharedCache {
+    client: Client,
+    conn: ConnectionManager,
+    prefix: String,
+    // Seconds a link stays in Redis, 0 keeps only the click counters there
+    ttl_secs: u64,
+    healthy: Arc<AtomicBool>,
+}
+
+impl SharedCache {
+    pub async fn connect(config: &Config) -> anyhow::Result<Option<Self>> {
+        let Some(url) = config.redis_url.as_deref().filter(|url| !url.is_empty()) else {
+            return Ok(None);
+        };
+        let client = Client::open(url)?;
+        let manager_config = ConnectionManagerConfig::new()
+            .set_response_timeout(RESPONSE_TIMEOUT)
+            .set_connection_timeout(CONNECTION_TIMEOUT);
+        let conn = client
+            .get_connection_manager_with_config(manager_config)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to connect to Redis: {}", e))?;
+        Ok(Some(Self {
+            client,
+            conn,
+            prefix: config.redis_key_prefix.clone(),
+            ttl_secs: config.redirect_cache_ttl_secs,
+            healthy: Arc::new(AtomicBool::new(true)),
+        }))
+    }
+
+    fn link_key(&self, token: &str) -> String {
+        format!("{}link:{}", self.prefix, token)
+    }
+
+    fn counter_key(&self, token: &str) -> String {
+        format!("{}clicks:{}", self.prefix, token)
+    }
+
+    fn channel(&self) -> String {
+        format!("{}invalidate", self.prefix)
+    }
+
+    // Whether the last command got an answer
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    // Logs when Redis goes away and comes back rather than once per failed command
+    fn check<T>(&self, result: RedisResult<T>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                if !self.healthy.swap(true, Ordering::Relaxed) {
+                    println!("✅ Redis is reachable again");
+                }
+                Some(value)
+            }
+            Err(e) => {
+                if self.healthy.swap(false, Ordering::Relaxed) {
+                    eprintln!("⚠️  Redis unavailable, falling back to the database: {}", e);
+                }
+                None
+            }
+        }
+    }
+
+    pub async fn get(&self, token: &str) -> Option<CachedLink> {
+        if self.ttl_secs == 0 {
+            return None;
+        }
+        let mut conn = self.conn.clone();
+        let cached: Option<String> = self.check(conn.get(self.link_key(token)).await)?;
+        serde_json::from_str(&cached?).ok()
+    }
+
+    pub async fn insert(&self, token: &str, link: &CachedLink) {
+        if self.ttl_secs == 0 {
+            return;
+        }
+        let Ok(value) = serde_json::to_string(link) else { return };
+        let mut conn = self.conn.clone();
+        self.check(conn.set_ex::<_, _, ()>(self.link_key(token), value, self.ttl_secs).await);
+    }
+
+    pub async fn invalidate(&self, token: &str) {
+        let mut conn = self.conn.clone();
+        if self.check(conn.del::<_, ()>(self.link_key(token)).await).is_some() {
+            self.check(conn.publish::<_, _, ()>(self.channel(), token).await);
+        }
+    }
+
+    // Adds a click to the link's shared counter and returns the new total, None when Redis is
+    // unavailable. A missing counter starts from the count stored in the database.
+    pub async fn increment_clicks<F, Fut>(&self, token: &str, stored: F) -> Result<Option<i64>, AppError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<i64, AppError>>,
+    {
+        let key = self.counter_key(token);
+        let mut conn = self.conn.clone();
+        let Some(exists) = self.check(conn.exists::<_, bool>(&key).await) else {
+            return Ok(None);
+        };
+        if !exists {
+            let count = stored().await?;
+            if self.check(conn.set_nx::<_, _, ()>(&key, count).await).is_none() {
+                return Ok(None);
+            }
+        }
+        Ok(self.check(conn.incr(&key, 1).await))
+    }
+
+    // The shared count of a link's clicks, None when there is no counter yet or Redis is unavailable
+    pub async fn clicks(&self, token: &str) -> Option<i64> {
+        let mut conn = self.conn.clone();
+        self.check(conn.get(self.counter_key(token)).await)?
+    }
+
+    // Drops links from the local cache as other instances invalidate them. Messages sent while
+    // disconnected are lost, so everything cached locally is dropped after a reconnect.
+    pub fn spawn_listener(&self, local: LinkCache) {
+        let client = self.client.clone();
+        let channel = self.channel();
+        tokio::spawn(async move {
+            loop {
+                if let Ok(mut pubsub) = client.get_async_pubsub().await {
+                    if pubsub.subscribe(&channel).await.is_ok() {
+                        local.invalidate_all();
+                        let mut messages = pubsub.on_message();
+                        while let Some(message) = messages.next().await {
+                            if let Ok(token) = message.get_payload::<String>() {
+                                local.invalidate(&token);
+                            }
+                        }
+                    }
+                }
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Redis tests only run when QUICKURL_TEST_REDIS_URL points at a scratch server
+    async fn shared_cache() -> Option<SharedCache> {
+        let url = std::env::var("QUICKURL_TEST_REDIS_URL").ok()?;
+        let mut config = Config::from_toml(&format!("redis_url = \"{}\"", url)).unwrap();
+        config.redis_key_prefix = format!("quickurl-test-{}:", uuid::Uuid::new_v4());
+        SharedCache::connect(&config).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_counter_starts_from_stored_count() {
+        let Some(shared) = shared_cache().await else { return };
+
+        assert_eq!(shared.increment_clicks("abc123", || async { Ok(41) }).await.unwrap(), Some(42));
+        // Seeded once, later increments ignore the database
+        assert_eq!(shared.increment_clicks("abc123", || async { Ok(0) }).await.unwrap(), Some(43));
+        assert_eq!(shared.clicks("abc123").await, Some(43));
+        assert_eq!(shared.clicks("other1").await, None);
+        assert!(shared.get("abc123").await.is_none());
+        assert!(shared.is_healthy());
+    }
+}
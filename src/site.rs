@@ -0,0 +1,120 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::{AppError, AppState};
+
+// Short links stay crawlable, the JSON API and admin UI do not
+const ROBOTS_TXT: &str = "User-agent: *\nDisallow: /api/\nDisallow: /admin\n";
+const FAVICON: &[u8] = include_bytes!("../ui/favicon.ico");
+const FAVICON_TYPE: &str = "image/x-icon";
+// Served as they are, so browsers and crawlers may keep them for a day
+const CACHE_CONTROL: &str = "public, max-age=86400";
+
+// Files browsers and crawlers ask every host for, read once at start. Without their own
+// routes they would be looked up as tokens.
+#[derive(Debug, Clone)]
+pub struct SiteFiles {
+    robots_txt: String,
+    favicon: Vec<u8>,
+    favicon_type: &'static str,
+    security_txt: Option<String>,
+}
+
+fn read(option: &str, path: Option<&str>) -> anyhow::Result<Option<Vec<u8>>> {
+    match path.filter(|path| !path.is_empty()) {
+        Some(path) => Ok(Some(std::fs::read(path).with_context(|| format!("Could not read {} {}", option, path))?)),
+        None => Ok(None),
+    }
+}
+
+fn image_type(path: &str) -> &'static str {
+    match Path::new(path).extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("png") => "image/png",
+        Some("svg") => "image/svg+xml",
+        Some("gif") => "image/gif",
+        _ => FAVICON_TYPE,
+    }
+}
+
+impl SiteFiles {
+    pub fn load(config: &Config) -> anyhow::Result<Self> {
+        let robots_txt = match read("robots_txt_file", config.robots_txt_file.as_deref())? {
+            Some(file) => String::from_utf8(file).context("robots_txt_file must be UTF-8")?,
+            None => ROBOTS_TXT.to_string(),
+        };
+        let (favicon, favicon_type) = match read("favicon_file", config.favicon_file.as_deref())? {
+            Some(file) => (file, image_type(config.favicon_file.as_deref().unwrap_or_default())),
+            None => (FAVICON.to_vec(), FAVICON_TYPE),
+        };
+        let security_txt = read("security_txt_file", config.security_txt_file.as_deref())?
+            .map(|file| String::from_utf8(file).context("security_txt_file must be UTF-8"))
+            .transpose()?;
+        Ok(Self {
+            robots_txt,
+            favicon,
+            favicon_type,
+            security_txt,
+        })
+    }
+}
+
+fn text(body: String) -> Response {
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8"), (header::CACHE_CONTROL, CACHE_CONTROL)], body).into_response()
+}
+
+pub async fn robots_txt(State(state): State<Arc<AppState>>) -> Response {
+    text(state.site.robots_txt.clone())
+}
+
+pub async fn favicon(State(state): State<Arc<AppState>>) -> Response {
+    let site = &state.site;
+    ([(header::CONTENT_TYPE, site.favicon_type), (header::CACHE_CONTROL, CACHE_CONTROL)], site.favicon.clone()).into_response()
+}
+
+// RFC 9116, only served when the operator provides one, it has to name their contact
+pub async fn security_txt(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    match &state.site.security_txt {
+        Some(body) => Ok(text(body.clone())),
+        None => Err(AppError::NotFound("File not found".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_site_files_from_config() {
+        let site = SiteFiles::load(&Config::default()).unwrap();
+        assert!(site.robots_txt.contains("Disallow: /api/"));
+        assert_eq!((&site.favicon[..4], site.favicon_type), (&[0, 0, 1, 0][..], "image/x-icon"));
+        assert!(site.security_txt.is_none());
+
+        let dir = std::env::temp_dir();
+        let robots = dir.join(format!("quickurl-robots-{}.txt", std::process::id()));
+        let icon = dir.join(format!("quickurl-icon-{}.PNG", std::process::id()));
+        std::fs::write(&robots, "User-agent: *\nDisallow: /\n").unwrap();
+        std::fs::write(&icon, b"\x89PNG").unwrap();
+        let config = Config {
+            robots_txt_file: Some(robots.to_string_lossy().into_owned()),
+            favicon_file: Some(icon.to_string_lossy().into_owned()),
+            security_txt_file: Some("/nonexistent/security.txt".into()),
+            ..Config::default()
+        };
+        assert!(SiteFiles::load(&config).is_err());
+
+        let config = Config { security_txt_file: None, ..config };
+        let site = SiteFiles::load(&config).unwrap();
+        std::fs::remove_file(&robots).unwrap();
+        std::fs::remove_file(&icon).unwrap();
+        assert_eq!(site.robots_txt, "User-agent: *\nDisallow: /\n");
+        assert_eq!((site.favicon.as_slice(), site.favicon_type), (&b"\x89PNG"[..], "image/png"));
+    }
+}
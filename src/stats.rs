@@ -0,0 +1,277 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Json},
+};
+use chrono::NaiveDate;
+use sha2::{Digest, Sha256};
+use sqlx::{AnyPool, Row};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use crate::auth::Caller;
+use crate::base_url::BaseUrl;
+use crate::models::{
+    CountEntry, GeoStatsResponse, StatsQuery, TopLink, TopLinksQuery, TopLinksResponse, TopPeriod, UrlStatsResponse,
+};
+use crate::rollup::{self, Links};
+use crate::storage::{self, SqlBuilder};
+use crate::{find_url_id, push_owner_filter, Access, AppError, AppState};
+
+pub const DEFAULT_STATS_DAYS: i64 = 30;
+pub const MAX_STATS_DAYS: i64 = 365;
+const TOP_ENTRIES: i64 = 10;
+// More than there are ISO country codes, so every country is listed
+const MAX_COUNTRIES: i64 = 300;
+const MAX_VARIANTS: i64 = 10;
+const DEFAULT_TOP_LINKS: i64 = 20;
+const MAX_TOP_LINKS: i64 = 100;
+
+// Raw IPs are never stored, only a salted hash good enough for unique counts
+pub fn hash_ip(salt: &str, ip: IpAddr) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(ip.to_string().as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+// Privacy mode only hashes the network, the last IPv4 octet and all but 48 bits of an IPv6
+// address are dropped, so a stored hash cannot be brute-forced back to one address
+pub fn anonymize_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            IpAddr::from([a, b, c, 0])
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            IpAddr::from([segments[0], segments[1], segments[2], 0, 0, 0, 0, 0])
+        }
+    }
+}
+
+// Tells visitors apart by address and browser. The date is part of the salt, so the same
+// visitor can be recognised within a day but their visits cannot be linked across days.
+pub fn visitor_hash(salt: &str, ip: IpAddr, user_agent: Option<&str>, date: NaiveDate) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(date.to_string().as_bytes());
+    hasher.update(ip.to_string().as_bytes());
+    hasher.update(b"\n");
+    hasher.update(user_agent.unwrap_or_default().as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+async fn top_values(
+    db: &AnyPool,
+    url_id: &str,
+    column: &str,
+    limit: i64,
+) -> Result<Vec<CountEntry>, AppError> {
+    let rows = sqlx::query(&format!(
+        r#"
+        SELECT {column} AS value, COUNT(*) AS count FROM clicks
+        WHERE url_id = $1 AND {column} IS NOT NULL
+        GROUP BY {column}
+        ORDER BY count DESC, value ASC
+        LIMIT $2
+        "#
+    ))
+    .bind(url_id)
+    .bind(limit)
+    .fetch_all(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| CountEntry {
+            value: row.get("value"),
+            count: row.get("count"),
+        })
+        .collect())
+}
+
+#[utoipa::path(
+    get,
+    path = "/urls/{token}/stats",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token"), StatsQuery),
+    responses(
+        (status = 200, description = "Click analytics", body = UrlStatsResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_url_stats(
+    Path(token): Path<String>,
+    Query(query): Query<StatsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let days = query.days.unwrap_or(DEFAULT_STATS_DAYS);
+    let url_id = find_url_id(state.links.reader(), &token).await?;
+    Ok(Json(url_stats(state.links.reader(), &url_id, token, days).await?))
+}
+
+// Totals and top values over every click, the daily series over the last `days`
+pub async fn url_stats(db: &AnyPool, url_id: &str, token: String, days: i64) -> Result<UrlStatsResponse, AppError> {
+    if !(1..=MAX_STATS_DAYS).contains(&days) {
+        return Err(AppError::BadRequest(format!(
+            "days must be between 1 and {}",
+            MAX_STATS_DAYS
+        )));
+    }
+
+    let totals = sqlx::query(
+        r#"
+        SELECT COUNT(*) AS total, COUNT(DISTINCT ip_hash) AS unique_visitors,
+            (SELECT bot_clicks FROM urls WHERE id = $1) AS bot_clicks,
+            (SELECT unique_clicks FROM urls WHERE id = $1) AS unique_clicks
+        FROM clicks WHERE url_id = $1
+        "#
+    )
+    .bind(url_id)
+    .fetch_one(db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let today = chrono::Utc::now().date_naive();
+    let daily = rollup::daily_clicks(db, Links::Url(url_id), today - chrono::Duration::days(days), today).await?;
+
+    Ok(UrlStatsResponse {
+        token,
+        total_clicks: totals.get("total"),
+        unique_visitors: totals.get("unique_visitors"),
+        unique_clicks: totals.get("unique_clicks"),
+        bot_clicks: totals.get("bot_clicks"),
+        daily,
+        top_referrers: top_values(db, url_id, "referrer", TOP_ENTRIES).await?,
+        top_user_agents: top_values(db, url_id, "user_agent", TOP_ENTRIES).await?,
+        variants: top_values(db, url_id, "variant", MAX_VARIANTS).await?,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats/top",
+    tag = "urls",
+    params(TopLinksQuery),
+    responses(
+        (status = 200, description = "Most clicked links of the period among those the caller can see", body = TopLinksResponse),
+        (status = 400, description = "Invalid limit", body = ErrorResponse),
+    ),
+)]
+pub async fn get_top_links(
+    Query(query): Query<TopLinksQuery>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+) -> Result<impl IntoResponse, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_TOP_LINKS);
+    if !(1..=MAX_TOP_LINKS).contains(&limit) {
+        return Err(AppError::BadRequest(format!("limit must be between 1 and {}", MAX_TOP_LINKS)));
+    }
+    let since = storage::now()
+        - match query.period {
+            TopPeriod::Day => chrono::Duration::hours(24),
+            TopPeriod::Week => chrono::Duration::days(7),
+            TopPeriod::Month => chrono::Duration::days(30),
+        };
+    let open = rollup::first_open_day(state.links.reader())
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // SUM is NUMERIC on Postgres, the cast keeps it an integer on both backends
+    let mut top = SqlBuilder::new(
+        "SELECT u.token, u.domain, u.original_url, u.title, CAST(SUM(t.clicks) AS BIGINT) AS clicks FROM ",
+    );
+    rollup::push_clicks_since(&mut top, since, open);
+    top.push(" t JOIN urls u ON u.id = t.url_id WHERE u.deleted_at IS NULL");
+    push_owner_filter(&mut top, &caller, Access::Read);
+    top.push(" GROUP BY u.id, u.token, u.domain, u.original_url, u.title ORDER BY clicks DESC, u.token ASC LIMIT ")
+        .push_bind(limit);
+
+    let links = top
+        .build()
+        .fetch_all(state.links.reader())
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .iter()
+        .map(|row| {
+            let token: String = row.get("token");
+            let domain: Option<String> = row.get("domain");
+            TopLink {
+                short_url: base.short_url(domain.as_deref(), &token),
+                token,
+                original_url: row.get("original_url"),
+                title: row.get("title"),
+                clicks: row.get("clicks"),
+            }
+        })
+        .collect();
+
+    Ok(Json(TopLinksResponse { period: query.period, since, links }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/urls/{token}/stats/geo",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 200, description = "Clicks per country and top cities", body = GeoStatsResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_geo_stats(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let url_id = find_url_id(state.links.reader(), &token).await?;
+
+    // Clicks recorded before a GeoIP database was configured, or from unknown addresses
+    let unknown: i64 = sqlx::query("SELECT COUNT(*) AS total FROM clicks WHERE url_id = $1 AND country IS NULL")
+        .bind(&url_id)
+        .fetch_one(state.links.reader())
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .get("total");
+
+    Ok(Json(GeoStatsResponse {
+        token,
+        countries: top_values(state.links.reader(), &url_id, "country", MAX_COUNTRIES).await?,
+        top_cities: top_values(state.links.reader(), &url_id, "city", TOP_ENTRIES).await?,
+        unknown,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_ip_depends_on_salt() {
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+
+        assert_eq!(hash_ip("salt", ip), hash_ip("salt", ip));
+        assert_ne!(hash_ip("salt", ip), hash_ip("other", ip));
+        assert!(!hash_ip("salt", ip).contains("203.0.113.7"));
+    }
+
+    #[test]
+    fn test_anonymize_ip() {
+        let ip = |address: &str| address.parse::<IpAddr>().unwrap();
+
+        assert_eq!(anonymize_ip(ip("203.0.113.7")), ip("203.0.113.0"));
+        assert_eq!(anonymize_ip(ip("2001:db8:abcd:12:34::1")), ip("2001:db8:abcd::"));
+    }
+
+    #[test]
+    fn test_visitor_hash_rotates_daily() {
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let tomorrow = today.succ_opt().unwrap();
+
+        assert_eq!(visitor_hash("salt", ip, Some("Firefox"), today), visitor_hash("salt", ip, Some("Firefox"), today));
+        assert_ne!(visitor_hash("salt", ip, Some("Firefox"), today), visitor_hash("salt", ip, Some("Chrome"), today));
+        assert_ne!(visitor_hash("salt", ip, Some("Firefox"), today), visitor_hash("salt", ip, Some("Firefox"), tomorrow));
+    }
+}
@@ -0,0 +1,135 @@
+use axum::{
+    extract::{Path, State},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+};
+use futures::stream::{self, Stream};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::models::ClickEvent;
+use crate::{find_url_id, AppError, AppState};
+
+// Clicks a subscriber may fall behind by before it skips ahead
+const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug)]
+struct LiveClick {
+    url_id: String,
+    event: ClickEvent,
+}
+
+// Fans redirects out to connected dashboards. Nothing is buffered for clients that are not
+// connected, the stream only shows traffic from the moment it is opened.
+#[derive(Clone)]
+pub struct ClickStream {
+    sender: broadcast::Sender<Arc<LiveClick>>,
+}
+
+impl ClickStream {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    // Lets the redirect skip building an event nobody would receive
+    pub fn is_watched(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn publish(&self, url_id: String, event: ClickEvent) {
+        // Only fails when every subscriber has disconnected in the meantime
+        let _ = self.sender.send(Arc::new(LiveClick { url_id, event }));
+    }
+
+    // All clicks, or only those of one link
+    fn subscribe(&self, url_id: Option<String>) -> impl Stream<Item = Result<Event, axum::Error>> {
+        stream::unfold((self.sender.subscribe(), url_id), |(mut receiver, url_id)| async move {
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(click) if url_id.as_ref().is_none_or(|id| *id == click.url_id) => {
+                        Event::default().event("click").json_data(&click.event)
+                    }
+                    Ok(_) => continue,
+                    // A slow client is told how much it missed instead of being disconnected
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        Ok(Event::default().event("lagged").data(missed.to_string()))
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                };
+                return Some((event, (receiver, url_id)));
+            }
+        })
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/urls/{token}/stream",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 200, description = "Server-Sent Events: `click` with a ClickEvent per redirect, `lagged` with the number of skipped clicks", body = ClickEvent, content_type = "text/event-stream"),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+    ),
+)]
+pub async fn stream_url_clicks(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let url_id = find_url_id(&state.db, &token).await?;
+    Ok(Sse::new(state.click_stream.subscribe(Some(url_id))).keep_alive(KeepAlive::default()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Server-Sent Events for clicks on every link, same format as /urls/{token}/stream", body = ClickEvent, content_type = "text/event-stream"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn stream_all_clicks(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Sse::new(state.click_stream.subscribe(None)).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn click(token: &str) -> ClickEvent {
+        ClickEvent {
+            token: token.into(),
+            clicked_at: chrono::Utc::now(),
+            referrer: None,
+            country: None,
+            city: None,
+            device: crate::device::Device::Desktop,
+            variant: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_link_stream_only_sees_its_clicks() {
+        let clicks = ClickStream::new();
+        assert!(!clicks.is_watched());
+
+        let link = Box::pin(clicks.subscribe(Some("id-a".into())));
+        let all = Box::pin(clicks.subscribe(None));
+        assert!(clicks.is_watched());
+
+        clicks.publish("id-b".into(), click("b"));
+        clicks.publish("id-a".into(), click("a"));
+
+        let first_for_link = format!("{:?}", link.take(1).collect::<Vec<_>>().await);
+        let all = format!("{:?}", all.take(2).collect::<Vec<_>>().await);
+        assert!(first_for_link.contains("\\\"token\\\":\\\"a\\\"") && !first_for_link.contains("\\\"token\\\":\\\"b\\\""));
+        assert!(all.contains("\\\"token\\\":\\\"b\\\"") && all.contains("\\\"token\\\":\\\"a\\\""));
+    }
+}
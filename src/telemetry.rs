@@ -0,0 +1,191 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::{FutureExt, SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{global, Context, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::AppState;
+
+const LATENCY_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+pub const REDIRECTS_TOTAL: &str = "quickurl_redirects_total";
+pub const URLS_CREATED_TOTAL: &str = "quickurl_urls_created_total";
+pub const NOT_FOUND_TOTAL: &str = "quickurl_not_found_total";
+pub const TOKEN_COLLISIONS_TOTAL: &str = "quickurl_token_collisions_total";
+pub const CLICKS_DROPPED_TOTAL: &str = "quickurl_clicks_dropped_total";
+pub const REDIRECT_CACHE_TOTAL: &str = "quickurl_redirect_cache_requests_total";
+pub const WEBHOOK_DELIVERIES_TOTAL: &str = "quickurl_webhook_deliveries_total";
+pub const EVENTS_PUBLISHED_TOTAL: &str = "quickurl_events_published_total";
+const HTTP_REQUESTS_TOTAL: &str = "quickurl_http_requests_total";
+const HTTP_REQUEST_DURATION: &str = "quickurl_http_request_duration_seconds";
+const HTTP_IN_FLIGHT: &str = "quickurl_http_requests_in_flight";
+const DB_QUERY_DURATION: &str = "quickurl_db_query_duration_seconds";
+const TRACER_NAME: &str = "quickurl";
+
+pub fn install() -> anyhow::Result<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_duration_seconds".into()), LATENCY_BUCKETS)?
+        .install_recorder()?;
+
+    // Without a listener the exporter relies on us to drain histogram data periodically
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(UPKEEP_INTERVAL).await;
+            upkeep.run_upkeep();
+        }
+    });
+
+    Ok(handle)
+}
+
+// Exports spans over OTLP/HTTP when otlp_endpoint is set, until then the global tracer is a no-op.
+// Keep the provider alive for as long as spans should be exported.
+pub fn install_tracing(config: &Config) -> anyhow::Result<Option<TracerProvider>> {
+    let Some(endpoint) = config.otlp_endpoint.as_deref().filter(|endpoint| !endpoint.is_empty()) else {
+        return Ok(None);
+    };
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new("service.name", config.otlp_service_name.clone())]))
+        .build();
+    global::set_tracer_provider(provider.clone());
+    Ok(Some(provider))
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+// Continues the caller's trace when it sent a traceparent header
+fn remote_parent(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+// Records how long a database call took under the given query name, and traces it as a child
+// span of the request it runs for
+pub async fn timed<F: Future>(query: &'static str, future: F) -> F::Output {
+    let parent = Context::current();
+    let started = Instant::now();
+    let output = if parent.has_active_span() {
+        let tracer = global::tracer(TRACER_NAME);
+        let span = tracer
+            .span_builder(query)
+            .with_kind(SpanKind::Client)
+            .with_attributes([KeyValue::new("db.operation.name", query)])
+            .start_with_context(&tracer, &parent);
+        let context = parent.with_span(span);
+        let output = future.with_context(context.clone()).await;
+        context.span().end();
+        output
+    } else {
+        future.await
+    };
+    metrics::histogram!(DB_QUERY_DURATION, "query" => query).record(started.elapsed().as_secs_f64());
+    output
+}
+
+pub async fn track_http(req: Request, next: Next) -> Response {
+    // Label by route pattern rather than raw path to keep cardinality bounded
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".into());
+    let method = req.method().to_string();
+
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder(format!("{} {}", method, route))
+        .with_kind(SpanKind::Server)
+        .with_attributes([
+            KeyValue::new("http.request.method", method.clone()),
+            KeyValue::new("http.route", route.clone()),
+            KeyValue::new("url.path", req.uri().path().to_string()),
+        ])
+        .start_with_context(&tracer, &remote_parent(req.headers()));
+    let context = Context::current_with_span(span);
+
+    let in_flight = metrics::gauge!(HTTP_IN_FLIGHT);
+    in_flight.increment(1);
+    let started = Instant::now();
+
+    let response = next.run(req).with_context(context.clone()).await;
+
+    in_flight.decrement(1);
+    let span = context.span();
+    span.set_attribute(KeyValue::new("http.response.status_code", i64::from(response.status().as_u16())));
+    if response.status().is_server_error() {
+        span.set_status(Status::error(response.status().to_string()));
+    }
+    span.end();
+    let status = response.status().as_u16().to_string();
+    metrics::counter!(HTTP_REQUESTS_TOTAL, "method" => method.clone(), "route" => route.clone(), "status" => status)
+        .increment(1);
+    metrics::histogram!(HTTP_REQUEST_DURATION, "method" => method, "route" => route)
+        .record(started.elapsed().as_secs_f64());
+
+    response
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "service",
+    responses(
+        (status = 200, description = "Prometheus text exposition", body = String, content_type = "text/plain"),
+    ),
+)]
+pub async fn metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_remote_parent_from_traceparent() {
+        let mut headers = HeaderMap::new();
+        assert!(!remote_parent(&headers).span().span_context().is_valid());
+
+        headers.insert(
+            "traceparent",
+            HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        );
+        let parent = remote_parent(&headers);
+        let span = parent.span();
+        assert!(span.span_context().is_remote());
+        assert_eq!(span.span_context().trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+    }
+}
@@ -0,0 +1,276 @@
+use futures::StreamExt;
+use sqlx::AnyPool;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::config::Config;
+use crate::{storage, validation};
+
+// Enough for the <head> of nearly every page, the rest is never read
+const MAX_PAGE_BYTES: usize = 256 * 1024;
+const MAX_TITLE_LENGTH: usize = 300;
+const MAX_DESCRIPTION_LENGTH: usize = 500;
+const MAX_REDIRECTS: usize = 5;
+const MAX_CONCURRENT_FETCHES: usize = 8;
+const QUEUE_SIZE: usize = 1000;
+// Contains "bot", so our own fetches of short links end up in bot_clicks
+const USER_AGENT: &str = "QuickURL-TitleBot/1.0";
+
+struct Job {
+    url_id: String,
+    destination: String,
+}
+
+// What a link preview shows of the destination page
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Card {
+    title: Option<String>,
+    description: Option<String>,
+    // Absolute http(s) URL
+    image_url: Option<String>,
+}
+
+// Fills in the title of links created without one from the destination's OpenGraph or <title>
+// tag, and their description and image from its OpenGraph or Twitter card tags. Fetches run in
+// the background after the link is committed, so creating a link never waits for the
+// destination; a full queue or a failed fetch just leaves the fields empty.
+#[derive(Clone)]
+pub struct TitleFetcher {
+    sender: Option<mpsc::Sender<Job>>,
+}
+
+impl TitleFetcher {
+    pub fn spawn(db: AnyPool, config: &Config) -> anyhow::Result<Self> {
+        if !config.fetch_titles {
+            return Ok(Self { sender: None });
+        }
+
+        let client = validation::public_only(reqwest::Client::builder(), config.allow_private_destinations)
+            .timeout(Duration::from_secs(config.title_fetch_timeout_secs))
+            .redirect(validation::redirect_policy(config.max_url_length, config.allow_private_destinations, MAX_REDIRECTS))
+            .user_agent(USER_AGENT)
+            .build()?;
+
+        let (sender, mut receiver) = mpsc::channel::<Job>(QUEUE_SIZE);
+        tokio::spawn(async move {
+            futures::stream::poll_fn(move |cx| receiver.poll_recv(cx))
+                .for_each_concurrent(MAX_CONCURRENT_FETCHES, |job| {
+                    let client = client.clone();
+                    let db = db.clone();
+                    async move {
+                        let Some(card) = fetch(&client, &job.destination).await else {
+                            return;
+                        };
+                        // Somebody may have set a title in the meantime, theirs wins
+                        let saved = sqlx::query(
+                            r#"
+                            UPDATE urls SET title = COALESCE(title, $1), description = COALESCE(description, $2),
+                                image_url = COALESCE(image_url, $3), updated_at = $4
+                            WHERE id = $5
+                            "#,
+                        )
+                        .bind(&card.title)
+                        .bind(&card.description)
+                        .bind(&card.image_url)
+                        .bind(storage::ts(storage::now()))
+                        .bind(&job.url_id)
+                        .execute(&db)
+                        .await;
+                        if let Err(e) = saved {
+                            eprintln!("⚠️  Failed to save fetched title for {}: {}", job.url_id, e);
+                        }
+                    }
+                })
+                .await;
+        });
+
+        Ok(Self { sender: Some(sender) })
+    }
+
+    // Only web pages have a title, mailto: and other allowed_schemes links are skipped
+    pub fn enqueue(&self, url_id: &str, destination: &str) {
+        let web = destination.starts_with("http://") || destination.starts_with("https://");
+        if let Some(sender) = self.sender.as_ref().filter(|_| web) {
+            let job = Job { url_id: url_id.to_string(), destination: destination.to_string() };
+            let _ = sender.try_send(job);
+        }
+    }
+}
+
+async fn fetch(client: &reqwest::Client, destination: &str) -> Option<Card> {
+    let mut response = client
+        .get(destination)
+        .header(reqwest::header::ACCEPT, "text/html,application/xhtml+xml")
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("html"));
+    if !is_html {
+        return None;
+    }
+
+    let mut page = Vec::new();
+    while page.len() < MAX_PAGE_BYTES {
+        match response.chunk().await.ok()? {
+            Some(chunk) => page.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+    page.truncate(MAX_PAGE_BYTES);
+    let card = extract_card(&String::from_utf8_lossy(&page), response.url());
+    (card != Card::default()).then_some(card)
+}
+
+// og:title and twitter:title are usually cleaner than <title>, which often carries the site name.
+// Relative image URLs are resolved against the page they were found on.
+fn extract_card(html: &str, page_url: &url::Url) -> Card {
+    // ASCII lowercasing keeps byte offsets, so positions found in `lower` index into `html`
+    let lower = html.to_ascii_lowercase();
+    let mut meta = Vec::new();
+    let mut rest = 0;
+    while let Some(start) = lower[rest..].find("<meta").map(|i| rest + i) {
+        let end = lower[start..].find('>').map_or(lower.len(), |i| start + i);
+        let tag = &html[start..end];
+        let key = attribute(tag, "property").or_else(|| attribute(tag, "name")).map(str::to_ascii_lowercase);
+        if let (Some(key), Some(content)) = (key, attribute(tag, "content")) {
+            meta.push((key, content));
+        }
+        rest = end;
+    }
+
+    let find = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|wanted| meta.iter().find(|(key, _)| key == wanted).map(|(_, content)| *content))
+    };
+    let from_title = || {
+        let open = lower.find("<title")?;
+        let start = open + lower[open..].find('>')? + 1;
+        let end = start + lower[start..].find("</title")?;
+        Some(&html[start..end])
+    };
+    let title = [find(&["og:title", "twitter:title"]), from_title()]
+        .into_iter()
+        .flatten()
+        .map(|title| clean(title, MAX_TITLE_LENGTH))
+        .find(|title| !title.is_empty());
+    let description = find(&["og:description", "twitter:description", "description"])
+        .map(|description| clean(description, MAX_DESCRIPTION_LENGTH))
+        .filter(|description| !description.is_empty());
+    let image_url = find(&["og:image", "og:image:url", "twitter:image"])
+        .and_then(|image| page_url.join(&clean(image, usize::MAX)).ok())
+        .filter(|image| matches!(image.scheme(), "http" | "https"))
+        .map(String::from);
+    Card { title, description, image_url }
+}
+
+// Value of a quoted or bare attribute inside a single tag
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let lower = tag.to_ascii_lowercase();
+    let mut rest = 0;
+    while let Some(found) = lower[rest..].find(name).map(|i| rest + i) {
+        rest = found + name.len();
+        let preceded = lower[..found].ends_with(|c: char| c.is_ascii_whitespace());
+        let value = lower[rest..].trim_start();
+        if !preceded || !value.starts_with('=') {
+            continue;
+        }
+        let start = tag.len() - value[1..].trim_start().len();
+        let value = &tag[start..];
+        return Some(match value.chars().next()? {
+            quote @ ('"' | '\'') => value[1..].split(quote).next().unwrap_or_default(),
+            _ => value.split(|c: char| c.is_ascii_whitespace() || c == '/').next().unwrap_or_default(),
+        });
+    }
+    None
+}
+
+// Decodes the common entities, collapses whitespace and caps the length
+fn clean(raw: &str, max_length: usize) -> String {
+    let mut decoded = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest[1..].find(';').filter(|end| *end <= 10).map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let code = entity.strip_prefix('#')?;
+                let code = match code.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => code.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(max_length).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract_title(html: &str) -> Option<String> {
+        extract_card(html, &url::Url::parse("https://example.com/blog/post").unwrap()).title
+    }
+
+    #[test]
+    fn test_extract_title() {
+        let page = r#"<html><head>
+            <TITLE> Launch &amp; Learn |
+              Example Blog </TITLE>
+            <meta name="description" content="Not this">
+            <meta property='og:title' content='Launch &#x26; learn&nbsp;&#8212; part 1' />
+        </head>"#;
+        assert_eq!(extract_title(page).as_deref(), Some("Launch & learn — part 1"));
+
+        let page = "<head><meta name=twitter:title content=Short><title>Long | Site</title></head>";
+        assert_eq!(extract_title(page).as_deref(), Some("Short"));
+        assert_eq!(extract_title("<title>Fish &chips &bogus;</title>").as_deref(), Some("Fish &chips &bogus;"));
+        assert_eq!(extract_title("<title>   </title><p>no title</p>"), None);
+        assert_eq!(extract_title(&format!("<title>{}</title>", "a".repeat(500))).map(|title| title.len()), Some(MAX_TITLE_LENGTH));
+    }
+
+    #[test]
+    fn test_extract_card() {
+        let page_url = url::Url::parse("https://example.com/blog/post").unwrap();
+        let page = r#"<head>
+            <meta name="description" content="Fallback">
+            <meta property="og:description" content="Launch   &amp; learn,
+              part 1">
+            <meta property="og:image" content="/img/cover.png?a=1&amp;b=2">
+        </head>"#;
+        let card = extract_card(page, &page_url);
+        assert_eq!(card.description.as_deref(), Some("Launch & learn, part 1"));
+        assert_eq!(card.image_url.as_deref(), Some("https://example.com/img/cover.png?a=1&b=2"));
+
+        let page = r#"<meta name="description" content="Plain"><meta name="twitter:image" content="javascript:alert(1)">"#;
+        let card = extract_card(page, &page_url);
+        assert_eq!(card.description.as_deref(), Some("Plain"));
+        assert_eq!(card.image_url, None);
+        assert_eq!(extract_card("<p>nothing</p>", &page_url), Card::default());
+    }
+}
@@ -0,0 +1,434 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use sqlx::{AnyPool, Row};
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+
+use crate::auth::{Caller, Role};
+use crate::base_url::BaseUrl;
+use crate::models::{LoginRequest, LoginResponse, RegisterRequest, SetRoleRequest, UserResponse};
+use crate::orgs;
+use crate::storage;
+use crate::{load_tags, url_info_from_row, AppError, AppState};
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    iat: i64,
+    exp: i64,
+}
+
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>())
+        .map_err(|e| AppError::InternalError(e.to_string()))?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::InternalError(e.to_string()))
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+pub fn issue_token(secret: &[u8], user_id: &str, expires_at: DateTime<Utc>) -> Result<String, AppError> {
+    let claims = Claims {
+        sub: user_id.to_string(),
+        iat: Utc::now().timestamp(),
+        exp: expires_at.timestamp(),
+    };
+    jsonwebtoken::encode(&Header::default(), &claims, &EncodingKey::from_secret(secret))
+        .map_err(|e| AppError::InternalError(e.to_string()))
+}
+
+// Returns the user id of a valid, unexpired token
+pub fn verify_token(secret: &[u8], token: &str) -> Option<String> {
+    jsonwebtoken::decode::<Claims>(token, &DecodingKey::from_secret(secret), &Validation::default())
+        .ok()
+        .map(|data| data.claims.sub)
+}
+
+fn normalize_email(email: &str) -> Result<String, AppError> {
+    let email = email.trim().to_lowercase();
+    match email.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && domain.contains('.') => Ok(email),
+        _ => Err(AppError::BadRequest("A valid email address is required".into())),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/register",
+    tag = "users",
+    request_body = RegisterRequest,
+    responses(
+        (status = 201, description = "Account created", body = UserResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 409, description = "Email already registered", body = ErrorResponse),
+    )
+)]
+pub async fn register(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<RegisterRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let email = normalize_email(&payload.email)?;
+    if payload.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let created_at = storage::now();
+    let password_hash = hash_password(&payload.password)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO users (id, email, password_hash, created_at)
+        VALUES ($1, $2, $3, $4)
+        "#
+    )
+    .bind(&id)
+    .bind(&email)
+    .bind(&password_hash)
+    .bind(storage::ts(created_at))
+    .execute(&state.db)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+            AppError::Conflict("Email already registered".into())
+        }
+        _ => AppError::DatabaseError(e.to_string()),
+    })?;
+
+    Ok((StatusCode::CREATED, Json(UserResponse { id, email, role: Role::Editor, created_at })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "users",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Bearer token for the account", body = LoginResponse),
+        (status = 401, description = "Wrong email or password", body = ErrorResponse),
+    )
+)]
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<LoginRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let invalid = || AppError::Unauthorized("Invalid email or password".into());
+    let email = normalize_email(&payload.email).map_err(|_| invalid())?;
+
+    let row = sqlx::query("SELECT id, password_hash FROM users WHERE email = $1")
+        .bind(&email)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or_else(invalid)?;
+
+    let user_id: String = row.get("id");
+    let password_hash: String = row.get("password_hash");
+    if !verify_password(&payload.password, &password_hash) {
+        return Err(invalid());
+    }
+
+    let expires_at = Utc::now() + chrono::Duration::hours(state.config.jwt_ttl_hours);
+    let token = issue_token(&state.jwt_secret, &user_id, expires_at)?;
+
+    Ok(Json(LoginResponse {
+        token,
+        token_type: "Bearer".into(),
+        expires_at,
+    }))
+}
+
+fn account_id(caller: &Caller) -> Result<&str, AppError> {
+    caller
+        .user_id()
+        .ok_or_else(|| AppError::Forbidden("Only user accounts have personal data to export or erase".into()))
+}
+
+fn zip_error(e: impl std::fmt::Display) -> AppError {
+    AppError::InternalError(format!("Failed to build export: {}", e))
+}
+
+// Everything stored about an account: account.json, its links (deleted ones included) as
+// links.ndjson and their clicks as clicks.ndjson. Clicks leave out visitor hashes, those are
+// about the visitors rather than the account. The archive is built in memory, one account's
+// data compresses well enough for that.
+async fn build_export(db: &AnyPool, base: &BaseUrl, user_id: &str) -> Result<Vec<u8>, AppError> {
+    let database_error = |e: sqlx::Error| AppError::DatabaseError(e.to_string());
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+
+    let account = sqlx::query("SELECT id, email, role, created_at FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(|| AppError::Unauthorized("Account no longer exists".into()))?;
+    let account = UserResponse {
+        id: account.get("id"),
+        email: account.get("email"),
+        role: Role::parse(&account.get::<String, _>("role")).unwrap_or(Role::ReadOnly),
+        created_at: storage::get_ts(&account, "created_at"),
+    };
+    archive.start_file("account.json", options).map_err(zip_error)?;
+    serde_json::to_writer_pretty(&mut archive, &account).map_err(zip_error)?;
+
+    let rows = sqlx::query("SELECT * FROM urls WHERE user_id = $1 ORDER BY created_at, id")
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+        .map_err(database_error)?;
+    let mut links: Vec<_> = rows.iter().map(|row| url_info_from_row(base, row)).collect();
+    load_tags(db, &mut links).await?;
+    archive.start_file("links.ndjson", options).map_err(zip_error)?;
+    for link in &links {
+        serde_json::to_writer(&mut archive, link).map_err(zip_error)?;
+        archive.write_all(b"\n").map_err(zip_error)?;
+    }
+
+    archive.start_file("clicks.ndjson", options).map_err(zip_error)?;
+    let mut clicks = sqlx::query(
+        r#"
+        SELECT urls.token, clicks.clicked_at, clicks.referrer, clicks.user_agent, clicks.country,
+            clicks.city, clicks.variant
+        FROM clicks JOIN urls ON urls.id = clicks.url_id
+        WHERE urls.user_id = $1
+        ORDER BY clicks.id
+        "#
+    )
+    .bind(user_id)
+    .fetch(db);
+    while let Some(row) = clicks.try_next().await.map_err(database_error)? {
+        let click = serde_json::json!({
+            "token": row.get::<String, _>("token"),
+            "clicked_at": storage::get_ts(&row, "clicked_at"),
+            "referrer": row.get::<Option<String>, _>("referrer"),
+            "user_agent": row.get::<Option<String>, _>("user_agent"),
+            "country": row.get::<Option<String>, _>("country"),
+            "city": row.get::<Option<String>, _>("city"),
+            "variant": row.get::<Option<String>, _>("variant"),
+        });
+        serde_json::to_writer(&mut archive, &click).map_err(zip_error)?;
+        archive.write_all(b"\n").map_err(zip_error)?;
+    }
+
+    Ok(archive.finish().map_err(zip_error)?.into_inner())
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/me/export",
+    tag = "users",
+    responses(
+        (status = 200, description = "Zip of account.json, links.ndjson and clicks.ndjson", content_type = "application/zip"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 403, description = "Not a user account", body = ErrorResponse),
+    ),
+    security(("user_token" = []))
+)]
+pub async fn export_account(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    base: BaseUrl,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = account_id(&caller)?;
+    let archive = build_export(&state.db, &base, user_id).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"quickurl-account.zip\""),
+        ],
+        archive,
+    ))
+}
+
+// Removes the account in one transaction. Links, their clicks, tags, rules, variants, reports
+// and webhooks go with it through the foreign keys; history and archived links have none and
+// are deleted first. Returns the tokens of the removed links, None when there was no account.
+pub async fn erase_user(db: &AnyPool, user_id: &str) -> Result<Option<Vec<String>>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let tokens = sqlx::query("SELECT token FROM urls WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(|row| row.get("token"))
+        .collect();
+
+    sqlx::query("DELETE FROM audit_log WHERE url_id IN (SELECT id FROM urls WHERE user_id = $1) OR actor = $2")
+        .bind(user_id)
+        .bind(Caller::User(user_id.to_string()).actor())
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM archived_urls WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    let removed = sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if removed == 0 {
+        return Ok(None);
+    }
+
+    tx.commit().await?;
+    Ok(Some(tokens))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/me",
+    tag = "users",
+    responses(
+        (status = 204, description = "Account, links and clicks erased"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 403, description = "Not a user account", body = ErrorResponse),
+        (status = 409, description = "The only admin of an organization with other members", body = ErrorResponse),
+    ),
+    security(("user_token" = []))
+)]
+pub async fn delete_account(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = account_id(&caller)?;
+    orgs::ensure_handed_over(&state.db, user_id).await?;
+    let tokens = erase_user(&state.db, user_id)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| AppError::Unauthorized("Account no longer exists".into()))?;
+
+    // The links are gone for good, cached copies must not keep redirecting
+    for token in &tokens {
+        state.links.invalidate(token).await;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/users/{id}/role",
+    tag = "admin",
+    params(("id" = String, Path, description = "User id")),
+    request_body = SetRoleRequest,
+    responses(
+        (status = 200, description = "Role changed, it applies to the account's existing tokens", body = UserResponse),
+        (status = 403, description = "Admin role required", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+    ),
+    security(("admin_key" = []))
+)]
+pub async fn set_user_role(
+    Path(user_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<SetRoleRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let database_error = |e: sqlx::Error| AppError::DatabaseError(e.to_string());
+    let updated = sqlx::query("UPDATE users SET role = $1 WHERE id = $2")
+        .bind(payload.role.as_str())
+        .bind(&user_id)
+        .execute(&state.db)
+        .await
+        .map_err(database_error)?
+        .rows_affected();
+    if updated == 0 {
+        return Err(AppError::NotFound("User not found".into()));
+    }
+
+    let account = sqlx::query("SELECT id, email, created_at FROM users WHERE id = $1")
+        .bind(&user_id)
+        .fetch_one(&state.db)
+        .await
+        .map_err(database_error)?;
+    Ok(Json(UserResponse {
+        id: account.get("id"),
+        email: account.get("email"),
+        role: payload.role,
+        created_at: storage::get_ts(&account, "created_at"),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_and_token_roundtrip() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong horse", &hash));
+
+        let token = issue_token(b"secret", "user-1", Utc::now() + chrono::Duration::hours(1)).unwrap();
+        assert_eq!(verify_token(b"secret", &token).as_deref(), Some("user-1"));
+        assert_eq!(verify_token(b"other", &token), None);
+
+        let expired = issue_token(b"secret", "user-1", Utc::now() - chrono::Duration::hours(1)).unwrap();
+        assert_eq!(verify_token(b"secret", &expired), None);
+    }
+
+    #[tokio::test]
+    async fn test_erase_user_removes_links_and_clicks() {
+        for (db, _) in storage::test_pools().await.unwrap() {
+            let now = storage::ts(Utc::now());
+            for id in ["gone", "kept"] {
+                sqlx::query("INSERT INTO users (id, email, password_hash, created_at) VALUES ($1, $1, 'x', $2)")
+                    .bind(id)
+                    .bind(&now)
+                    .execute(&db)
+                    .await
+                    .unwrap();
+                sqlx::query(
+                    r#"
+                    INSERT INTO urls (id, token, original_url, created_at, expires_at, click_count, user_id)
+                    VALUES ($1, $1, 'https://example.com', $2, NULL, 1, $1)
+                    "#
+                )
+                .bind(id)
+                .bind(&now)
+                .execute(&db)
+                .await
+                .unwrap();
+                sqlx::query("INSERT INTO clicks (url_id, clicked_at) VALUES ($1, $2)")
+                    .bind(id)
+                    .bind(&now)
+                    .execute(&db)
+                    .await
+                    .unwrap();
+            }
+
+            assert_eq!(erase_user(&db, "gone").await.unwrap(), Some(vec!["gone".to_string()]));
+            assert_eq!(erase_user(&db, "gone").await.unwrap(), None);
+
+            let row = sqlx::query("SELECT (SELECT COUNT(*) FROM urls) AS urls, (SELECT COUNT(*) FROM clicks) AS clicks")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+            assert_eq!(row.get::<i64, _>("urls"), 1);
+            assert_eq!(row.get::<i64, _>("clicks"), 1);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+pub use quickurl_core::validation::{is_forbidden_scheme, validate_destination};
+
+use quickurl_core::validation::is_private_ip;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::config::Config;
+
+// Link destinations may use the configured allowed_schemes, everything the server fetches
+// itself (webhooks, titles, health checks) stays on http and https
+pub fn validate_link_destination(config: &Config, raw: &str) -> Result<url::Url, String> {
+    quickurl_core::validation::validate_destination_with_schemes(
+        raw,
+        config.max_url_length,
+        config.allow_private_destinations,
+        &config.allowed_schemes,
+    )
+}
+
+// For fetching destinations ourselves: every hop is checked like a new destination, so a public
+// page cannot redirect us onto an internal host
+pub fn redirect_policy(max_length: usize, allow_private: bool, max_redirects: usize) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        let allowed = validate_destination(attempt.url().as_str(), max_length, allow_private).is_ok();
+        if allowed && attempt.previous().len() < max_redirects {
+            attempt.follow()
+        } else {
+            attempt.stop()
+        }
+    })
+}
+
+// Host names are checked again once resolved, a public looking name can point at a private
+// address. Connections only go to the public addresses it resolves to, at every hop.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+                .await?
+                .filter(|addr| !is_private_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} does not resolve to a public address", host).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+// For the clients that fetch destinations and webhook targets, unless allow_private_destinations
+pub fn public_only(builder: reqwest::ClientBuilder, allow_private: bool) -> reqwest::ClientBuilder {
+    if allow_private {
+        return builder;
+    }
+    builder.dns_resolver(Arc::new(PublicResolver))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_resolver_drops_private_addresses() {
+        assert!(PublicResolver.resolve(Name::from_str("localhost").unwrap()).await.is_err());
+
+        // Names are never fetched once they only resolve to private addresses
+        let client = public_only(reqwest::Client::builder(), false).build().unwrap();
+        let error = client.get("http://localhost:9/").send().await.unwrap_err();
+        assert!(error.is_connect(), "{:?}", error);
+    }
+}
@@ -0,0 +1,234 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Json},
+};
+use sha2::{Digest, Sha256};
+use sqlx::{AnyConnection, Row};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::audit::{self, AuditAction};
+use crate::auth::Caller;
+use crate::models::{SplitVariant, SplitVariantsRequest};
+use crate::storage::SqlBuilder;
+use crate::{find_url_id, push_owner_filter, validate_url, Access, AppError, AppState};
+
+const MAX_VARIANTS: usize = 10;
+const MAX_WEIGHT: i64 = 1000;
+const MAX_NAME_LENGTH: usize = 50;
+
+// Weighted choice between variants. A sticky key (the visitor's IP hash) always lands on the
+// same variant as long as the variants do not change, otherwise every request rolls again.
+pub fn pick<'a>(variants: &'a [SplitVariant], sticky_key: Option<&str>) -> Option<&'a SplitVariant> {
+    let total: i64 = variants.iter().map(|variant| variant.weight).sum();
+    if total <= 0 {
+        return None;
+    }
+
+    let roll = match sticky_key {
+        Some(key) => {
+            let digest = Sha256::digest(key.as_bytes());
+            u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"))
+        }
+        None => rand::random::<u64>(),
+    };
+    let mut point = (roll % total as u64) as i64;
+    variants.iter().find(|variant| {
+        point -= variant.weight;
+        point < 0
+    })
+}
+
+fn validate_variants(state: &AppState, variants: &[SplitVariant]) -> Result<(), AppError> {
+    if variants.len() == 1 || variants.len() > MAX_VARIANTS {
+        return Err(AppError::BadRequest(format!(
+            "A split needs between 2 and {} variants, or none to turn it off",
+            MAX_VARIANTS
+        )));
+    }
+
+    let mut names = HashSet::new();
+    for variant in variants {
+        let valid_chars = variant
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if variant.name.is_empty() || variant.name.len() > MAX_NAME_LENGTH || !valid_chars {
+            return Err(AppError::BadRequest(format!(
+                "Invalid variant name {:?}: use up to {} letters, digits, '-' or '_'",
+                variant.name, MAX_NAME_LENGTH
+            )));
+        }
+        if !names.insert(variant.name.as_str()) {
+            return Err(AppError::BadRequest(format!("Duplicate variant name {:?}", variant.name)));
+        }
+        if !(1..=MAX_WEIGHT).contains(&variant.weight) {
+            return Err(AppError::BadRequest(format!("weight must be between 1 and {}", MAX_WEIGHT)));
+        }
+        validate_url(state, &variant.url)?;
+    }
+    Ok(())
+}
+
+pub async fn load(db: impl sqlx::Executor<'_, Database = sqlx::Any>, url_id: &str) -> Result<Vec<SplitVariant>, AppError> {
+    let rows = sqlx::query("SELECT name, destination, weight FROM url_variants WHERE url_id = $1 ORDER BY position")
+        .bind(url_id)
+        .fetch_all(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| SplitVariant {
+            name: row.get("name"),
+            url: row.get("destination"),
+            weight: row.get("weight"),
+        })
+        .collect())
+}
+
+async fn load_split(conn: &mut AnyConnection, url_id: &str) -> Result<SplitVariantsRequest, AppError> {
+    let sticky: i64 = sqlx::query("SELECT sticky_variants FROM urls WHERE id = $1")
+        .bind(url_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .get("sticky_variants");
+
+    Ok(SplitVariantsRequest {
+        sticky: sticky != 0,
+        variants: load(&mut *conn, url_id).await?,
+    })
+}
+
+async fn replace(conn: &mut AnyConnection, url_id: &str, split: &SplitVariantsRequest) -> Result<(), AppError> {
+    sqlx::query("UPDATE urls SET sticky_variants = $1 WHERE id = $2")
+        .bind(split.sticky as i64)
+        .bind(url_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    sqlx::query("DELETE FROM url_variants WHERE url_id = $1")
+        .bind(url_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    for (position, variant) in split.variants.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO url_variants (url_id, position, name, destination, weight) VALUES ($1, $2, $3, $4, $5)"
+        )
+            .bind(url_id)
+            .bind(position as i64)
+            .bind(&variant.name)
+            .bind(&variant.url)
+            .bind(variant.weight)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/urls/{token}/variants",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    responses(
+        (status = 200, description = "A/B split of the link, empty when it has none", body = SplitVariantsRequest),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_variants(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let url_id = find_url_id(&state.db, &token).await?;
+    let mut conn = state
+        .db
+        .acquire()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(Json(load_split(&mut conn, &url_id).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/urls/{token}/variants",
+    tag = "urls",
+    params(("token" = String, Path, description = "Short URL token")),
+    request_body = SplitVariantsRequest,
+    responses(
+        (status = 200, description = "Split replaced", body = SplitVariantsRequest),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn put_variants(
+    Path(token): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<SplitVariantsRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_variants(&state, &payload.variants)?;
+
+    let mut lookup = SqlBuilder::new("SELECT id FROM urls WHERE token = ");
+    lookup.push_bind(token.clone()).push(" AND deleted_at IS NULL");
+    push_owner_filter(&mut lookup, &caller, Access::Write);
+
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let url_id: String = lookup
+        .build()
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .map(|row| row.get("id"))
+        .ok_or(AppError::UrlNotFound)?;
+
+    let old = load_split(&mut tx, &url_id).await?;
+    replace(&mut tx, &url_id, &payload).await?;
+    audit::record(&mut tx, &url_id, &token, AuditAction::Update, &caller, audit::diff(&old, &payload)).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    state.links.invalidate(&token).await;
+
+    Ok(Json(payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_respects_weights_and_stickiness() {
+        let variant = |name: &str, weight| SplitVariant {
+            name: name.into(),
+            url: format!("https://example.com/{}", name),
+            weight,
+        };
+        let variants = vec![variant("a", 3), variant("b", 1)];
+
+        let mut a = 0;
+        for i in 0..1000 {
+            if pick(&variants, Some(&format!("visitor-{}", i))).unwrap().name == "a" {
+                a += 1;
+            }
+        }
+        assert!((650..=850).contains(&a), "a was picked {} times", a);
+
+        let first = pick(&variants, Some("visitor")).unwrap().name.clone();
+        assert!((0..20).all(|_| pick(&variants, Some("visitor")).unwrap().name == first));
+        assert!(pick(&[], None).is_none());
+    }
+}
@@ -0,0 +1,37 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+// JSON endpoints live under this prefix, the next incompatible version gets a new one
+pub const API_PREFIX: &str = "/api/v1";
+
+// Documented paths that stay at the root: redirects, probes and browser sign-in
+pub const ROOT_PATHS: &[&str] = &[
+    "/{token}",
+    "/p/{token}",
+    "/healthz",
+    "/readyz",
+    "/metrics",
+    "/auth/oidc/login",
+    "/auth/oidc/callback",
+];
+
+// Unversioned paths keep working and point clients at their successor (RFC 8594 style)
+pub async fn deprecated(request: Request, next: Next) -> Response {
+    let successor = request
+        .uri()
+        .path_and_query()
+        .map(|path| format!("<{}{}>; rel=\"successor-version\"", API_PREFIX, path))
+        .and_then(|link| HeaderValue::from_str(&link).ok());
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(HeaderName::from_static("deprecation"), HeaderValue::from_static("true"));
+    if let Some(successor) = successor {
+        headers.append(axum::http::header::LINK, successor);
+    }
+    response
+}
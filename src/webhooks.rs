@@ -0,0 +1,483 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sqlx::{AnyConnection, AnyPool, Row};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::auth::Caller;
+use crate::config::Config;
+use crate::models::{CreateWebhookRequest, CreateWebhookResponse, Webhook, WebhookDelivery};
+use crate::storage::{self, SqlBuilder};
+use crate::token::TokenGenerator;
+use crate::{outbox, push_owner_filter, telemetry, validation, Access, AppError, AppState};
+
+const SECRET_PREFIX: &str = "whsec_";
+const SECRET_LENGTH: usize = 32;
+// Attempt n waits RETRY_BASE_DELAY * 2^(n-1) before the next one
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+// Deliveries sent at once, the next ones are claimed when all of them are done
+const MAX_CLAIMED: usize = 50;
+const LEASE_MARGIN: Duration = Duration::from_secs(60);
+const MAX_LISTED_DELIVERIES: i64 = 100;
+
+pub const EVENT_HEADER: &str = "X-QuickURL-Event";
+pub const DELIVERY_HEADER: &str = "X-QuickURL-Delivery";
+pub const SIGNATURE_HEADER: &str = "X-QuickURL-Signature";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum WebhookEvent {
+    #[serde(rename = "link.created")]
+    LinkCreated,
+    #[serde(rename = "link.deleted")]
+    LinkDeleted,
+    #[serde(rename = "link.expired")]
+    LinkExpired,
+    #[serde(rename = "click.recorded")]
+    ClickRecorded,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::LinkCreated,
+        WebhookEvent::LinkDeleted,
+        WebhookEvent::LinkExpired,
+        WebhookEvent::ClickRecorded,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookEvent::LinkCreated => "link.created",
+            WebhookEvent::LinkDeleted => "link.deleted",
+            WebhookEvent::LinkExpired => "link.expired",
+            WebhookEvent::ClickRecorded => "click.recorded",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|event| event.as_str() == value)
+    }
+}
+
+// Stored comma separated in webhooks.events
+fn parse_events(value: &str) -> Vec<WebhookEvent> {
+    value.split(',').filter_map(WebhookEvent::parse).collect()
+}
+
+// Value of the signature header, receivers recompute it over the raw body with their secret
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+// Queued in the outbox with everything needed to send it, a link scoped webhook is removed
+// together with its link while the final link.expired event still has to go out
+#[derive(Serialize, Deserialize)]
+struct Delivery {
+    id: String,
+    webhook_id: String,
+    target: String,
+    secret: String,
+    event: WebhookEvent,
+    payload: String,
+}
+
+// Queues one delivery per subscribed webhook in the caller's transaction, so an event goes out
+// exactly when the change it announces is committed. Matching needs the link's owner, so this
+// has to run while the link still exists.
+pub async fn enqueue(conn: &mut AnyConnection, event: WebhookEvent, url_id: &str, data: &Value) -> Result<(), sqlx::Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, url, secret, events FROM webhooks
+        WHERE url_id = $1
+           OR (url_id IS NULL AND (user_id IS NULL OR user_id = (SELECT user_id FROM urls WHERE id = $1)))
+        "#
+    )
+    .bind(url_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let created_at = storage::now();
+    for row in rows.iter().filter(|row| parse_events(&row.get::<String, _>("events")).contains(&event)) {
+        let id = Uuid::new_v4().to_string();
+        let payload = json!({ "id": id, "event": event, "created_at": created_at, "data": data });
+        let delivery = Delivery {
+            id,
+            webhook_id: row.get("id"),
+            target: row.get("url"),
+            secret: row.get("secret"),
+            event,
+            payload: payload.to_string(),
+        };
+        sqlx::query("INSERT INTO webhook_deliveries (id, webhook_id, event, payload, created_at) VALUES ($1, $2, $3, $4, $5)")
+            .bind(&delivery.id)
+            .bind(&delivery.webhook_id)
+            .bind(event.as_str())
+            .bind(&delivery.payload)
+            .bind(storage::ts(created_at))
+            .execute(&mut *conn)
+            .await?;
+        outbox::push(conn, outbox::WEBHOOKS, &delivery.id, &serde_json::to_string(&delivery).unwrap_or_default()).await?;
+    }
+    Ok(())
+}
+
+// Sends queued deliveries from a background task, so neither a slow receiver nor its retries
+// ever hold up the request that caused the event. Instances sharing the database split the
+// queue between them, and deliveries left unsent by a crash are picked up after their lease.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    db: AnyPool,
+    client: reqwest::Client,
+    max_attempts: u32,
+    click_sample_percent: u32,
+    poll_interval: Duration,
+    // Outlasts the receiver's timeout, so a delivery is not sent twice while waiting for it
+    lease: Duration,
+}
+
+impl WebhookDispatcher {
+    pub fn new(db: AnyPool, config: &Config) -> anyhow::Result<Self> {
+        let timeout = Duration::from_secs(config.webhook_timeout_secs);
+        let client = validation::public_only(reqwest::Client::builder(), config.allow_private_destinations)
+            .timeout(timeout)
+            // The target was validated on creation, a redirect could point anywhere
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        Ok(Self {
+            db,
+            client,
+            max_attempts: config.webhook_max_attempts,
+            click_sample_percent: config.webhook_click_sample_percent,
+            poll_interval: Duration::from_millis(config.outbox_poll_interval_ms),
+            lease: timeout + LEASE_MARGIN,
+        })
+    }
+
+    pub fn sample_click(&self) -> bool {
+        rand::random::<u32>() % 100 < self.click_sample_percent
+    }
+
+    pub fn spawn(&self) {
+        let dispatcher = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match outbox::claim(&dispatcher.db, outbox::WEBHOOKS, MAX_CLAIMED, dispatcher.lease).await {
+                    Ok(entries) if !entries.is_empty() => {
+                        let mut sending = JoinSet::new();
+                        for entry in entries {
+                            let dispatcher = dispatcher.clone();
+                            sending.spawn(async move { dispatcher.deliver(entry).await });
+                        }
+                        while sending.join_next().await.is_some() {}
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("⚠️  Failed to claim queued webhook deliveries: {}", e),
+                }
+                tokio::time::sleep(dispatcher.poll_interval).await;
+            }
+        });
+    }
+
+    async fn deliver(&self, entry: outbox::Entry) {
+        let delivery: Delivery = match serde_json::from_str(&entry.payload) {
+            Ok(delivery) => delivery,
+            Err(e) => {
+                eprintln!("⚠️  Dropping unreadable webhook delivery {}: {}", entry.id, e);
+                if let Err(e) = outbox::finish(&self.db, std::slice::from_ref(&entry.id)).await {
+                    eprintln!("⚠️  Failed to drop webhook delivery {}: {}", entry.id, e);
+                }
+                return;
+            }
+        };
+
+        let response = self
+            .client
+            .post(&delivery.target)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event.as_str())
+            .header(DELIVERY_HEADER, &delivery.id)
+            .header(SIGNATURE_HEADER, sign(&delivery.secret, delivery.payload.as_bytes()))
+            .body(delivery.payload.clone())
+            .send()
+            .await;
+        let (status_code, error) = match response {
+            Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+            Ok(response) => (Some(response.status().as_u16()), Some(format!("Receiver answered {}", response.status()))),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        let delivered = error.is_none();
+        self.log_attempt(&delivery.id, entry.attempts, delivered, status_code, error).await;
+
+        let outcome = if delivered { "delivered" } else { "failed" };
+        metrics::counter!(telemetry::WEBHOOK_DELIVERIES_TOTAL, "outcome" => outcome).increment(1);
+        let ids = [entry.id];
+        let updated = if delivered || entry.attempts >= self.max_attempts {
+            outbox::finish(&self.db, &ids).await
+        } else {
+            outbox::postpone(&self.db, &ids, RETRY_BASE_DELAY * 2u32.pow(entry.attempts - 1)).await
+        };
+        if let Err(e) = updated {
+            eprintln!("⚠️  Failed to update queued webhook delivery {}: {}", delivery.id, e);
+        }
+    }
+
+    // Matches nothing once a link scoped webhook went away with its link
+    async fn log_attempt(&self, id: &str, attempt: u32, delivered: bool, status_code: Option<u16>, error: Option<String>) {
+        let updated = sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET attempts = $1, delivered = $2, status_code = $3, error = $4, last_attempt_at = $5
+            WHERE id = $6
+            "#
+        )
+        .bind(attempt as i64)
+        .bind(delivered as i64)
+        .bind(status_code.map(i64::from))
+        .bind(error)
+        .bind(storage::ts(storage::now()))
+        .bind(id)
+        .execute(&self.db)
+        .await;
+        if let Err(e) = updated {
+            eprintln!("⚠️  Failed to log webhook delivery {}: {}", id, e);
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    tag = "webhooks",
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 201, description = "Webhook created, the secret is only shown once", body = CreateWebhookResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "URL not found", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<CreateWebhookRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    // Receivers are not link destinations, the block and allow lists do not apply to them
+    validation::validate_destination(&payload.url, state.config.max_url_length, state.config.allow_private_destinations)
+        .map_err(AppError::BadRequest)?;
+    let events: Vec<WebhookEvent> = if payload.events.is_empty() {
+        WebhookEvent::ALL.to_vec()
+    } else {
+        WebhookEvent::ALL
+            .into_iter()
+            .filter(|event| payload.events.contains(event))
+            .collect()
+    };
+
+    let url_id: Option<String> = match &payload.token {
+        Some(token) => {
+            let mut lookup = SqlBuilder::new("SELECT id FROM urls WHERE token = ");
+            lookup.push_bind(token.clone()).push(" AND deleted_at IS NULL");
+            push_owner_filter(&mut lookup, &caller, Access::Write);
+            let id = lookup
+                .build()
+                .fetch_optional(&state.db)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?
+                .map(|row| row.get("id"))
+                .ok_or(AppError::UrlNotFound)?;
+            Some(id)
+        }
+        None => None,
+    };
+
+    let id = Uuid::new_v4().to_string();
+    let secret = format!("{}{}", SECRET_PREFIX, TokenGenerator::with_length(SECRET_LENGTH).generate());
+    let created_at = storage::now();
+    let stored_events = events.iter().map(|event| event.as_str()).collect::<Vec<_>>().join(",");
+
+    sqlx::query(
+        r#"
+        INSERT INTO webhooks (id, url, secret, events, owner, user_id, url_id, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#
+    )
+    .bind(&id)
+    .bind(&payload.url)
+    .bind(&secret)
+    .bind(stored_events)
+    .bind(caller.actor())
+    .bind(caller.user_id())
+    .bind(url_id)
+    .bind(storage::ts(created_at))
+    .execute(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let response = CreateWebhookResponse {
+        id,
+        url: payload.url,
+        events,
+        token: payload.token,
+        secret,
+        created_at,
+    };
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Webhooks created by the caller", body = [Webhook]),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    // Webhooks belong to the exact key or account that created them, not even the admin sees others
+    let webhooks = sqlx::query(
+        r#"
+        SELECT webhooks.id, webhooks.url, webhooks.events, webhooks.created_at, urls.token
+        FROM webhooks LEFT JOIN urls ON urls.id = webhooks.url_id
+        WHERE webhooks.owner = $1
+        ORDER BY webhooks.created_at
+        "#
+    )
+    .bind(caller.actor())
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .iter()
+    .map(|row| Webhook {
+        id: row.get("id"),
+        url: row.get("url"),
+        events: parse_events(&row.get::<String, _>("events")),
+        token: row.get("token"),
+        created_at: storage::get_ts(row, "created_at"),
+    })
+    .collect::<Vec<_>>();
+
+    Ok(Json(webhooks))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = String, Path, description = "Webhook id")),
+    responses(
+        (status = 204, description = "Webhook and its delivery log deleted"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "Webhook not found", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn delete_webhook(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    let deleted = sqlx::query("DELETE FROM webhooks WHERE id = $1 AND owner = $2")
+        .bind(&id)
+        .bind(caller.actor())
+        .execute(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .rows_affected();
+
+    if deleted == 0 {
+        return Err(AppError::NotFound("Webhook not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}/deliveries",
+    tag = "webhooks",
+    params(("id" = String, Path, description = "Webhook id")),
+    responses(
+        (status = 200, description = "Most recent deliveries, newest first", body = [WebhookDelivery]),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "Webhook not found", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("user_token" = []))
+)]
+pub async fn list_deliveries(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<impl IntoResponse, AppError> {
+    sqlx::query("SELECT id FROM webhooks WHERE id = $1 AND owner = $2")
+        .bind(&id)
+        .bind(caller.actor())
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("Webhook not found".into()))?;
+
+    let deliveries = sqlx::query(
+        "SELECT * FROM webhook_deliveries WHERE webhook_id = $1 ORDER BY created_at DESC LIMIT $2"
+    )
+    .bind(&id)
+    .bind(MAX_LISTED_DELIVERIES)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .iter()
+    .map(|row| WebhookDelivery {
+        id: row.get("id"),
+        event: row.get("event"),
+        attempts: row.get("attempts"),
+        delivered: row.get::<i64, _>("delivered") != 0,
+        status_code: row.get("status_code"),
+        error: row.get("error"),
+        created_at: storage::get_ts(row, "created_at"),
+        last_attempt_at: storage::get_opt_ts(row, "last_attempt_at"),
+    })
+    .collect::<Vec<_>>();
+
+    Ok(Json(deliveries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_parse_events() {
+        // Reference value from `printf '{}' | openssl dgst -sha256 -hmac whsec_test`
+        assert_eq!(
+            sign("whsec_test", b"{}"),
+            "sha256=46bdc2a2e83964bd2695acc6a1f97162d8bafc135a071aa5cd5c99aae5481440"
+        );
+        assert_ne!(sign("whsec_test", b"{}"), sign("whsec_other", b"{}"));
+
+        assert_eq!(
+            parse_events("link.created,click.recorded,bogus"),
+            [WebhookEvent::LinkCreated, WebhookEvent::ClickRecorded]
+        );
+        assert_eq!(serde_json::to_value(WebhookEvent::LinkExpired).unwrap(), "link.expired");
+    }
+}
@@ -0,0 +1,214 @@
+// Talks to the regular JSON API, the page itself holds no data until a credential is entered
+const PER_PAGE = 20;
+const STORAGE_KEY = "quickurl.credential";
+
+const state = { page: 1, totalPages: 1, q: "" };
+const $ = (id) => document.getElementById(id);
+
+function el(tag, props = {}, children = []) {
+  const node = document.createElement(tag);
+  Object.assign(node, props);
+  node.append(...children);
+  return node;
+}
+
+function showError(message) {
+  $("error").textContent = message;
+  $("error").hidden = !message;
+}
+
+async function api(method, path, body) {
+  const headers = { Authorization: `Bearer ${localStorage.getItem(STORAGE_KEY)}` };
+  if (body !== undefined) headers["Content-Type"] = "application/json";
+  const response = await fetch(`/api/v1${path}`, { method, headers, body: body === undefined ? undefined : JSON.stringify(body) });
+  if (response.status === 401) signOut();
+  if (!response.ok) {
+    const problem = await response.json().catch(() => ({ detail: response.statusText }));
+    throw new Error(problem.detail);
+  }
+  return response.status === 204 ? null : response.json();
+}
+
+// datetime-local inputs work in local time without a zone, the API wants RFC 3339
+const toInput = (iso) => {
+  const date = new Date(iso);
+  return new Date(date.getTime() - date.getTimezoneOffset() * 60000).toISOString().slice(0, 16);
+};
+const fromInput = (value) => (value ? new Date(value).toISOString() : undefined);
+
+const SEARCH_LIMIT = 100;
+
+async function loadLinks() {
+  if (state.q) return searchLinks();
+  const params = new URLSearchParams({ page: state.page, per_page: PER_PAGE });
+  try {
+    const result = await api("GET", `/urls?${params}`);
+    state.totalPages = Math.max(result.total_pages, 1);
+    $("links").replaceChildren(...result.urls.map(row));
+    $("page").textContent = `Page ${result.page} of ${state.totalPages} (${result.total} links)`;
+    $("prev").disabled = state.page <= 1;
+    $("next").disabled = state.page >= state.totalPages;
+    showError("");
+  } catch (error) {
+    showError(error.message);
+  }
+}
+
+// Search results come ranked in one page, their highlights are escaped HTML from the server
+async function searchLinks() {
+  const params = new URLSearchParams({ q: state.q, limit: SEARCH_LIMIT });
+  try {
+    const { results } = await api("GET", `/urls/search?${params}`);
+    $("links").replaceChildren(...results.map(row));
+    $("page").textContent = `${results.length} best matches`;
+    $("prev").disabled = true;
+    $("next").disabled = true;
+    showError("");
+  } catch (error) {
+    showError(error.message);
+  }
+}
+
+function row(link) {
+  const action = (label, handler) => el("button", { type: "button", textContent: label, onclick: () => handler(link) });
+  return el("tr", {}, [
+    el("td", {}, [el("a", { href: link.short_url, textContent: link.short_url, target: "_blank", rel: "noopener" })]),
+    link.url_highlight
+      ? el("td", { className: "destination", innerHTML: link.url_highlight })
+      : el("td", { className: "destination", textContent: link.original_url }),
+    link.title_highlight ? el("td", { innerHTML: link.title_highlight }) : el("td", { textContent: link.title ?? "" }),
+    el("td", {
+      textContent: link.max_clicks ? `${link.click_count} / ${link.max_clicks}` : link.click_count,
+      title: `${link.unique_clicks} unique, ${link.bot_clicks} more from bots`,
+    }),
+    el("td", { textContent: link.expires_at ? new Date(link.expires_at).toLocaleString() : "Never" }),
+    el("td", { className: "actions" }, [action("Edit", openEdit), " ", action("Stats", openStats), " ", action("Delete", remove)]),
+  ]);
+}
+
+function openEdit(link) {
+  const form = $("edit-form");
+  form.dataset.token = link.token;
+  $("edit-token").textContent = link.token;
+  form.url.value = link.original_url;
+  form.title.value = link.title ?? "";
+  form.expires_at.value = link.expires_at ? toInput(link.expires_at) : "";
+  $("edit").showModal();
+}
+
+$("edit").addEventListener("close", async () => {
+  if ($("edit").returnValue !== "save") return;
+  const form = $("edit-form");
+  try {
+    await api("PATCH", `/urls/${encodeURIComponent(form.dataset.token)}`, {
+      url: form.url.value,
+      title: form.title.value,
+      // Clearing the field makes the link permanent
+      expires_at: fromInput(form.expires_at.value) ?? null,
+    });
+    loadLinks();
+  } catch (error) {
+    showError(error.message);
+  }
+});
+
+async function openStats(link) {
+  $("stats-token").textContent = link.token;
+  $("stats-body").replaceChildren("Loading...");
+  $("stats").showModal();
+  try {
+    const stats = await api("GET", `/urls/${encodeURIComponent(link.token)}/stats`);
+    const list = (title, entries) =>
+      el("div", {}, [
+        el("h3", { textContent: title }),
+        entries.length ? el("ul", {}, entries.map((entry) => el("li", { textContent: entry }))) : "none",
+      ]);
+    $("stats-body").replaceChildren(
+      el("p", { textContent: `${stats.total_clicks} clicks from ${stats.unique_visitors} visitors` }),
+      list("Daily", stats.daily.map((day) => `${day.date}: ${day.clicks}`)),
+      list("Referrers", stats.top_referrers.map((entry) => `${entry.value}: ${entry.count}`)),
+      list("Variants", stats.variants.map((entry) => `${entry.value}: ${entry.count}`)),
+    );
+  } catch (error) {
+    $("stats-body").replaceChildren(error.message);
+  }
+}
+
+async function remove(link) {
+  if (!confirm(`Delete ${link.short_url}? It can be restored until purged.`)) return;
+  try {
+    await api("DELETE", `/urls/${encodeURIComponent(link.token)}`);
+    loadLinks();
+  } catch (error) {
+    showError(error.message);
+  }
+}
+
+$("create").addEventListener("submit", async (event) => {
+  event.preventDefault();
+  const form = event.target;
+  const body = { url: form.url.value };
+  if (form.custom_alias.value) body.custom_alias = form.custom_alias.value;
+  if (form.title.value) body.title = form.title.value;
+  if (form.expires_at.value) body.expires_at = fromInput(form.expires_at.value);
+  try {
+    await api("POST", "/shorten", body);
+    form.reset();
+    state.page = 1;
+    loadLinks();
+  } catch (error) {
+    showError(error.message);
+  }
+});
+
+$("search").addEventListener("submit", (event) => {
+  event.preventDefault();
+  state.q = event.target.q.value.trim();
+  state.page = 1;
+  loadLinks();
+});
+
+$("prev").addEventListener("click", () => {
+  state.page -= 1;
+  loadLinks();
+});
+$("next").addEventListener("click", () => {
+  state.page += 1;
+  loadLinks();
+});
+
+function signIn(credential) {
+  localStorage.setItem(STORAGE_KEY, credential);
+  $("credential").hidden = true;
+  $("login").querySelector("[type=submit]").hidden = true;
+  $("sso").dataset.shown = String(!$("sso").hidden);
+  $("sso").hidden = true;
+  $("logout").hidden = false;
+  $("app").hidden = false;
+  loadLinks();
+}
+
+function signOut() {
+  localStorage.removeItem(STORAGE_KEY);
+  $("credential").hidden = false;
+  $("login").querySelector("[type=submit]").hidden = false;
+  $("logout").hidden = true;
+  $("sso").hidden = $("sso").dataset.shown === "false";
+  $("app").hidden = true;
+}
+
+$("login").addEventListener("submit", (event) => {
+  event.preventDefault();
+  const credential = $("credential").value.trim();
+  if (credential) signIn(credential);
+});
+$("logout").addEventListener("click", signOut);
+
+// Single sign-on comes back with the token in the fragment, which is dropped from the address bar
+const fromProvider = new URLSearchParams(location.hash.slice(1)).get("token");
+if (fromProvider) {
+  history.replaceState(null, "", location.pathname);
+  signIn(fromProvider);
+} else if (localStorage.getItem(STORAGE_KEY)) {
+  signIn(localStorage.getItem(STORAGE_KEY));
+}